@@ -1,19 +1,32 @@
 use std::sync::Arc;
 
 use kinematics::{
+    forward::algorithms::compute_arm_vertices,
     inverse::solvers::KinematicSolver,
     model::{KinematicParameters, KinematicState},
 };
+use motion::{
+    linear::LinearMotion,
+    player::{validate_motion, Configuration},
+};
+use nalgebra::{Matrix3, Vector3};
+
+use crate::error::Error;
 
 pub mod motion;
+pub mod registry;
 
 pub struct Arm {
     kinematic_parameters: KinematicParameters,
     kinematic_state: KinematicState,
     kinematic_solver: Arc<dyn KinematicSolver>,
+    max_speed: f64, // The maximum speed of the end-effector (in meters/second).
 }
 
 impl Arm {
+    /// The time step used to dry-run planned motions (in seconds).
+    pub const PLAN_DELTA_TIME: f64 = 0.01_f64;
+
     pub fn new(
         kinematic_parameters: KinematicParameters,
         kinematic_state: KinematicState,
@@ -23,9 +36,19 @@ impl Arm {
             kinematic_parameters,
             kinematic_state,
             kinematic_solver,
+            max_speed: LinearMotion::DEFAULT_MAX_SPEED,
         }
     }
 
+    /// Limit the speed of the end-effector in the motions planned for the arm (in meters/second).
+    pub fn with_max_speed(mut self, max_speed: f64) -> Self {
+        assert!(max_speed > 0_f64);
+
+        self.max_speed = max_speed;
+
+        self
+    }
+
     #[inline]
     pub fn kinematic_parameters(&self) -> &KinematicParameters {
         &self.kinematic_parameters
@@ -40,4 +63,214 @@ impl Arm {
     pub fn kinematic_solver(&self) -> &Arc<dyn KinematicSolver> {
         &self.kinematic_solver
     }
+
+    #[inline]
+    pub fn max_speed(&self) -> f64 {
+        self.max_speed
+    }
+
+    /// Get the position and orientation of the end-effector in the current state.
+    pub fn end_effector_pose(&self) -> (Vector3<f64>, Matrix3<f64>) {
+        self.end_effector_pose_at(&self.kinematic_state)
+    }
+
+    /// Get the position and orientation of the end-effector in the given state.
+    pub fn end_effector_pose_at(&self, state: &KinematicState) -> (Vector3<f64>, Matrix3<f64>) {
+        let forward_algorithm = self.kinematic_solver.forward_algorithm();
+
+        (
+            forward_algorithm.limb4_position_vector(&self.kinematic_parameters, state),
+            forward_algorithm.limb4_orientation_matrix(&self.kinematic_parameters, state),
+        )
+    }
+
+    /// Get the vertices of the arm (from the base up to the end-effector) in the current state.
+    pub fn vertices(&self) -> [Vector3<f64>; 6] {
+        self.vertices_at(&self.kinematic_state)
+    }
+
+    /// Get the vertices of the arm (from the base up to the end-effector) in the given state.
+    pub fn vertices_at(&self, state: &KinematicState) -> [Vector3<f64>; 6] {
+        compute_arm_vertices(
+            self.kinematic_solver.forward_algorithm(),
+            &self.kinematic_parameters,
+            state,
+        )
+    }
+
+    /// Get the vertices of the arm in each of the given states, which traces the path of a
+    ///  trajectory in a single call.
+    pub fn trajectory_vertices(&self, states: &[KinematicState]) -> Vec<[Vector3<f64>; 6]> {
+        states.iter().map(|x| self.vertices_at(x)).collect()
+    }
+
+    /// Plan a linear motion of the end-effector from its current position to the given target
+    ///  position (in meters) at the given speed (in meters/second), which may not exceed the
+    ///  maximum speed of the arm.
+    ///
+    /// The target position is solved first, so an unreachable target fails before any motion is
+    ///  constructed. The motion is then dry-run the way the player would run it, and the error
+    ///  of the first sample that can't be solved is returned.
+    pub(crate) fn plan_linear(
+        &self,
+        target_position: &Vector3<f64>,
+        speed: f64,
+    ) -> Result<LinearMotion, Error> {
+        self.kinematic_solver
+            .translate_limb4_end_effector(
+                &self.kinematic_parameters,
+                &self.kinematic_state,
+                target_position,
+            )?
+            .into_result()?;
+
+        let (original_position, _) = self.end_effector_pose();
+        let motion = LinearMotion::new_with_max_speed(
+            original_position,
+            *target_position,
+            speed,
+            self.max_speed,
+        )?;
+
+        let report = validate_motion(self, &Configuration::new(Self::PLAN_DELTA_TIME), &motion);
+        if let Some(failure) = report.failures.into_iter().next() {
+            return Err(failure.error);
+        }
+
+        Ok(motion)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Arc;
+
+    use kinematics::{
+        error::KinematicError,
+        forward::algorithms::{analytical::AnalyticalFKAlgorithm, compute_arm_vertices},
+        inverse::{
+            algorithms::heuristic::HeuristicIKAlgorithm,
+            solvers::{heuristic::HeuristicSolver, IKSolverResult},
+        },
+        model::{KinematicParameters, KinematicState},
+    };
+    use nalgebra::{Matrix3, Vector3};
+
+    use crate::{
+        arm::{
+            motion::{Motion, MotionError},
+            Arm,
+        },
+        error::Error,
+    };
+
+    fn arm() -> Arm {
+        Arm::new(
+            KinematicParameters::default(),
+            KinematicState::default(),
+            Arc::new(
+                HeuristicSolver::builder(
+                    Arc::new(HeuristicIKAlgorithm::default()),
+                    Arc::new(AnalyticalFKAlgorithm::default()),
+                )
+                .build(),
+            ),
+        )
+    }
+
+    #[test]
+    pub fn pose_and_vertices_of_default_state() {
+        let arm = arm();
+        let forward_algorithm = arm.kinematic_solver().forward_algorithm();
+
+        let (position, orientation) = arm.end_effector_pose();
+        assert_eq!(
+            position,
+            forward_algorithm
+                .limb4_position_vector(arm.kinematic_parameters(), arm.kinematic_state())
+        );
+        assert!(
+            (orientation * orientation.transpose() - Matrix3::<f64>::identity()).norm() < 1e-9_f64
+        );
+
+        // The vertices run from the base up to the end-effector.
+        let vertices = arm.vertices();
+        assert_eq!(vertices[0_usize], Vector3::<f64>::zeros());
+        assert_eq!(vertices[5_usize], position);
+        assert_eq!(vertices, arm.vertices_at(&KinematicState::default()));
+    }
+
+    #[test]
+    pub fn solver_result_is_kinematics_type() {
+        let arm = arm();
+
+        // Target the current position, which is always reachable within the joint limits.
+        let target_position: Vector3<f64> = arm
+            .kinematic_solver()
+            .forward_algorithm()
+            .limb4_position_vector(arm.kinematic_parameters(), arm.kinematic_state());
+
+        // The arm only knows about the solver of the kinematics crate, so this only compiles
+        //  as long as there is a single result type.
+        let result: IKSolverResult = arm
+            .kinematic_solver()
+            .translate_limb4_end_effector(
+                arm.kinematic_parameters(),
+                arm.kinematic_state(),
+                &target_position,
+            )
+            .unwrap();
+
+        let _ = result.is_reached();
+    }
+
+    #[test]
+    pub fn plan_linear_checks_endpoint() {
+        let arm = arm();
+        let (position, _) = arm.end_effector_pose();
+
+        // The links are only 50 meters long together, so this is far out of reach.
+        let result = arm.plan_linear(&Vector3::<f64>::new(100_f64, 0_f64, 0_f64), 0.5_f64);
+        assert!(matches!(
+            result,
+            Err(Error::KinematicError(KinematicError::Unreachable))
+        ));
+
+        // A short move from the current position is planned, ending at the target.
+        let target_position = position + Vector3::<f64>::new(0_f64, 0_f64, 0.1_f64);
+        let motion = arm.plan_linear(&target_position, 0.5_f64).unwrap();
+        assert!(
+            (motion.interpolate(motion.duration()).unwrap() - target_position).norm() < 1e-9_f64
+        );
+
+        // Unless it's faster than the arm may move.
+        assert!(matches!(
+            arm.with_max_speed(0.25_f64)
+                .plan_linear(&target_position, 0.5_f64),
+            Err(Error::MotionError(MotionError::InvalidSpeed { .. }))
+        ));
+    }
+
+    #[test]
+    pub fn trajectory_vertices_batched() {
+        let arm = arm();
+        let states: Vec<KinematicState> = (0_usize..100_usize)
+            .map(|i| KinematicState {
+                theta_0: i as f64 * 0.01_f64,
+                ..KinematicState::default()
+            })
+            .collect();
+
+        let vertices = arm.trajectory_vertices(&states);
+        assert_eq!(vertices.len(), 100_usize);
+        assert_eq!(
+            vertices[0_usize],
+            compute_arm_vertices(
+                arm.kinematic_solver().forward_algorithm(),
+                arm.kinematic_parameters(),
+                &states[0_usize]
+            )
+        );
+        assert_eq!(vertices[99_usize], arm.vertices_at(&states[99_usize]));
+    }
 }
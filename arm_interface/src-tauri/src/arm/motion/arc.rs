@@ -0,0 +1,146 @@
+use std::f64::consts::PI;
+
+use nalgebra::{Rotation3, Unit, Vector3};
+
+use super::Motion;
+
+/// This struct represents a motion along an arc around a center, from the original position to
+///  the target position.
+///
+/// The arc lies in the plane perpendicular to the normal, any offset between the original and
+///  target position along the normal is covered linearly (resulting in a helix).
+pub(crate) struct ArcMotion {
+    center_position: Vector3<f64>, // The center of the arc (in meters).
+    radius_vector: Vector3<f64>, // The vector from the center to the start of the arc (in meters).
+    normal: Unit<Vector3<f64>>,  // The normal of the plane the arc lies in.
+    sweep: f64,                  // The angle to sweep around the normal (in radians).
+    normal_offset: f64,          // The offset to cover along the normal (in meters).
+    speed: f64,                  // The speed (in meters/second).
+}
+
+impl ArcMotion {
+    /// Create a new arc motion, the arc goes clockwise around the normal if `clockwise` is set,
+    ///  and counter clockwise otherwise. An arc ending where it started makes a full circle.
+    pub fn new(
+        center_position: Vector3<f64>,
+        original_position: Vector3<f64>,
+        target_position: Vector3<f64>,
+        normal: Vector3<f64>,
+        clockwise: bool,
+        speed: f64,
+    ) -> Self {
+        let normal = Unit::new_normalize(normal);
+
+        // Project the start and end of the arc onto the plane of the arc.
+        let original_offset = original_position - center_position;
+        let target_offset = target_position - center_position;
+        let radius_vector = original_offset - normal.into_inner() * normal.dot(&original_offset);
+        let target_radius_vector = target_offset - normal.into_inner() * normal.dot(&target_offset);
+
+        // Compute the counter clockwise angle from the start to the end, in the range [0, 2pi).
+        let mut angle = normal
+            .dot(&radius_vector.cross(&target_radius_vector))
+            .atan2(radius_vector.dot(&target_radius_vector));
+        if angle < 0_f64 {
+            angle += 2_f64 * PI;
+        }
+
+        // Turn the angle into the sweep in the requested direction, where no angle at all means
+        //  that a full circle should be made.
+        let sweep = match (clockwise, angle.abs() < f64::EPSILON) {
+            (false, false) => angle,
+            (false, true) => 2_f64 * PI,
+            (true, false) => angle - 2_f64 * PI,
+            (true, true) => -2_f64 * PI,
+        };
+
+        Self {
+            center_position,
+            radius_vector,
+            normal,
+            sweep,
+            normal_offset: normal.dot(&(target_offset - original_offset)),
+            speed,
+        }
+    }
+}
+
+impl Motion for ArcMotion {
+    fn interpolate(&self, t: f64) -> Option<Vector3<f64>> {
+        assert!(t >= 0_f64);
+
+        let duration = self.duration();
+
+        if t > duration {
+            return None;
+        }
+
+        // The fraction of the arc that has been covered.
+        let fraction = if duration == 0_f64 {
+            1_f64
+        } else {
+            t / duration
+        };
+
+        let rotation = Rotation3::from_axis_angle(&self.normal, self.sweep * fraction);
+
+        Some(
+            self.center_position
+                + rotation * self.radius_vector
+                + self.normal.into_inner() * (self.normal_offset * fraction),
+        )
+    }
+
+    fn duration(&self) -> f64 {
+        let arc_length = self.radius_vector.magnitude() * self.sweep.abs();
+
+        arc_length.hypot(self.normal_offset) / self.speed
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use nalgebra::Vector3;
+
+    use crate::arm::motion::{arc::ArcMotion, Motion};
+
+    fn half_circle(clockwise: bool) -> ArcMotion {
+        ArcMotion::new(
+            Vector3::<f64>::new(0_f64, 0_f64, 0_f64),
+            Vector3::<f64>::new(-1_f64, 0_f64, 0_f64),
+            Vector3::<f64>::new(1_f64, 0_f64, 0_f64),
+            Vector3::<f64>::new(0_f64, 1_f64, 0_f64),
+            clockwise,
+            1_f64,
+        )
+    }
+
+    #[test]
+    pub fn half_circle_direction() {
+        // Rotating clockwise around the y axis moves from -x through -z, and counter clockwise
+        //  through +z.
+        let clockwise = half_circle(true);
+        let counter_clockwise = half_circle(false);
+
+        assert!((clockwise.duration() - std::f64::consts::PI).abs() < 1e-9_f64);
+
+        let t = clockwise.duration() / 2_f64;
+        assert!(
+            (clockwise.interpolate(t).unwrap() - Vector3::<f64>::new(0_f64, 0_f64, -1_f64))
+                .magnitude()
+                < 1e-9_f64
+        );
+        assert!(
+            (counter_clockwise.interpolate(t).unwrap() - Vector3::<f64>::new(0_f64, 0_f64, 1_f64))
+                .magnitude()
+                < 1e-9_f64
+        );
+
+        // The arc should end at the target.
+        let end = clockwise.interpolate(clockwise.duration()).unwrap();
+        assert!((end - Vector3::<f64>::new(1_f64, 0_f64, 0_f64)).magnitude() < 1e-9_f64);
+        assert!(clockwise
+            .interpolate(clockwise.duration() + 0.1_f64)
+            .is_none());
+    }
+}
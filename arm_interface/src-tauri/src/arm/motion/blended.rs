@@ -0,0 +1,207 @@
+use nalgebra::Vector3;
+
+use super::{arc::ArcMotion, linear::LinearMotion, sequence::SequenceMotion, Motion, MotionError};
+
+/// This struct represents linear motions through a list of waypoints, where the corners between
+///  adjacent segments are rounded off with arcs so the tool keeps moving instead of stopping.
+///
+/// The blend radius is the distance from the corner at which the blend starts and ends, it's
+///  clamped to half the length of the adjacent segments so neighbouring blends never overlap.
+pub(crate) struct BlendedSequenceMotion {
+    sequence: SequenceMotion,
+}
+
+impl BlendedSequenceMotion {
+    /// Create a new blended motion through the given waypoints at the given speed (in
+    ///  meters/second), rounding off the corners within the given blend radius (in meters). The
+    ///  speed must be positive and may not exceed the given maximum speed (in meters/second).
+    pub fn new(
+        waypoints: Vec<Vector3<f64>>,
+        speed: f64,
+        blend_radius: f64,
+        max_speed: f64,
+    ) -> Result<Self, MotionError> {
+        if !(speed > 0_f64 && speed <= max_speed) {
+            return Err(MotionError::InvalidSpeed { speed, max_speed });
+        }
+
+        // Repeated waypoints don't make a segment, and would leave the corner without a direction.
+        let mut points: Vec<Vector3<f64>> = Vec::with_capacity(waypoints.len());
+        for waypoint in waypoints {
+            if !points
+                .last()
+                .is_some_and(|last| (waypoint - last).magnitude() <= f64::EPSILON)
+            {
+                points.push(waypoint);
+            }
+        }
+
+        let mut motions: Vec<Box<dyn Motion>> = Vec::new();
+        let Some(first) = points.first() else {
+            return Ok(Self {
+                sequence: SequenceMotion::new(motions),
+            });
+        };
+
+        // The position the next linear segment starts from.
+        let mut position = *first;
+
+        for i in 1_usize..points.len() - 1_usize {
+            let (previous, corner, next) = (points[i - 1_usize], points[i], points[i + 1_usize]);
+
+            let incoming = corner - previous;
+            let outgoing = next - corner;
+            let incoming_direction = incoming.normalize();
+            let outgoing_direction = outgoing.normalize();
+
+            // Collinear segments (including reversals) have no corner that can be rounded off.
+            let normal = incoming_direction.cross(&outgoing_direction);
+            if normal.magnitude() < 1e-9_f64 || blend_radius <= 0_f64 {
+                motions.push(Box::new(LinearMotion::new_with_max_speed(
+                    position, corner, speed, max_speed,
+                )?));
+                position = corner;
+                continue;
+            }
+
+            let distance = blend_radius
+                .min(incoming.magnitude() / 2_f64)
+                .min(outgoing.magnitude() / 2_f64);
+
+            // The blend is tangent to both segments, at the given distance from the corner.
+            let blend_start = corner - incoming_direction * distance;
+            let blend_end = corner + outgoing_direction * distance;
+
+            // The center lies perpendicular to the incoming segment, on the inside of the corner.
+            let turn = incoming_direction
+                .dot(&outgoing_direction)
+                .clamp(-1_f64, 1_f64)
+                .acos();
+            let radius = distance / (turn / 2_f64).tan();
+            let inward = (outgoing_direction
+                - incoming_direction * incoming_direction.dot(&outgoing_direction))
+            .normalize();
+            let center = blend_start + inward * radius;
+
+            motions.push(Box::new(LinearMotion::new_with_max_speed(
+                position,
+                blend_start,
+                speed,
+                max_speed,
+            )?));
+            motions.push(Box::new(ArcMotion::new(
+                center,
+                blend_start,
+                blend_end,
+                normal,
+                false,
+                speed,
+            )));
+            position = blend_end;
+        }
+
+        if let Some(last) = points.last() {
+            motions.push(Box::new(LinearMotion::new_with_max_speed(
+                position, *last, speed, max_speed,
+            )?));
+        }
+
+        Ok(Self {
+            sequence: SequenceMotion::new(motions),
+        })
+    }
+}
+
+impl Motion for BlendedSequenceMotion {
+    fn interpolate(&self, t: f64) -> Option<Vector3<f64>> {
+        self.sequence.interpolate(t)
+    }
+
+    fn duration(&self) -> f64 {
+        self.sequence.duration()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use nalgebra::Vector3;
+
+    use crate::arm::motion::{blended::BlendedSequenceMotion, Motion};
+
+    #[test]
+    pub fn velocity_kept_at_interior_corner() {
+        let corner = Vector3::<f64>::new(1_f64, 0_f64, 0_f64);
+        let motion = BlendedSequenceMotion::new(
+            vec![
+                Vector3::<f64>::new(0_f64, 0_f64, 0_f64),
+                corner,
+                Vector3::<f64>::new(1_f64, 1_f64, 0_f64),
+            ],
+            0.5_f64,
+            0.1_f64,
+            1_f64,
+        )
+        .unwrap();
+
+        // The arc is shorter than the corner it replaces.
+        assert!(motion.duration() < 4_f64);
+
+        // The speed stays the same all along the motion, so it never drops to zero at the corner.
+        let dt = 1e-3_f64;
+        let mut closest = f64::MAX;
+        for i in 0_usize..(motion.duration() / dt) as usize - 1_usize {
+            let t = i as f64 * dt;
+            let a = motion.interpolate(t).unwrap();
+            let b = motion.interpolate(t + dt).unwrap();
+
+            assert!(((b - a).magnitude() / dt - 0.5_f64).abs() < 1e-3_f64);
+
+            closest = closest.min((a - corner).magnitude());
+        }
+
+        // The corner itself is cut, but within the blend radius.
+        assert!(closest > 0.01_f64 && closest < 0.1_f64);
+
+        let end = motion.interpolate(motion.duration()).unwrap();
+        assert!((end - Vector3::<f64>::new(1_f64, 1_f64, 0_f64)).magnitude() < 1e-9_f64);
+    }
+
+    #[test]
+    pub fn collinear_and_clamped_blends() {
+        // Collinear segments aren't blended, so the path is as long as the original one.
+        let collinear = BlendedSequenceMotion::new(
+            vec![
+                Vector3::<f64>::new(0_f64, 0_f64, 0_f64),
+                Vector3::<f64>::new(1_f64, 0_f64, 0_f64),
+                Vector3::<f64>::new(2_f64, 0_f64, 0_f64),
+            ],
+            1_f64,
+            0.5_f64,
+            1_f64,
+        )
+        .unwrap();
+        assert!((collinear.duration() - 2_f64).abs() < 1e-9_f64);
+
+        // A blend radius larger than the segments is clamped to half of the shortest one.
+        let clamped = BlendedSequenceMotion::new(
+            vec![
+                Vector3::<f64>::new(0_f64, 0_f64, 0_f64),
+                Vector3::<f64>::new(0.2_f64, 0_f64, 0_f64),
+                Vector3::<f64>::new(0.2_f64, 1_f64, 0_f64),
+            ],
+            1_f64,
+            10_f64,
+            1_f64,
+        )
+        .unwrap();
+
+        // The blend goes from (0.1, 0, 0) to (0.2, 0.1, 0), a quarter circle with a radius of 0.1.
+        let expected = 0.1_f64 + std::f64::consts::FRAC_PI_2 * 0.1_f64 + 0.9_f64;
+        assert!((clamped.duration() - expected).abs() < 1e-9_f64);
+        assert!(
+            (clamped.interpolate(0.1_f64).unwrap() - Vector3::<f64>::new(0.1_f64, 0_f64, 0_f64))
+                .magnitude()
+                < 1e-9_f64
+        );
+    }
+}
@@ -16,4 +16,8 @@ impl Motion for CircleMotion {
     fn interpolate(&self, t: f64) -> Option<nalgebra::Vector3<f64>> {
         None
     }
+
+    fn duration(&self) -> f64 {
+        self.laps * 2_f64 * std::f64::consts::PI / self.angular_velocity
+    }
 }
@@ -0,0 +1,355 @@
+use nalgebra::Vector3;
+use thiserror::Error;
+
+use super::{arc::ArcMotion, linear::LinearMotion, Motion, MotionError};
+
+/// This error represents a G-code line that could not be turned into a motion.
+#[derive(Debug, Error)]
+pub(crate) enum GCodeError {
+    #[error("Invalid word {word:?} on line {line}")]
+    InvalidWord { line: usize, word: String },
+    #[error("Feed move without a feed rate on line {line}")]
+    MissingFeedRate { line: usize },
+    #[error("Arc without a center offset on line {line}")]
+    MissingArcCenter { line: usize },
+    #[error("Invalid move on line {line}: {source}")]
+    InvalidMotion { line: usize, source: MotionError },
+}
+
+/// This struct represents the result of parsing a G-code program.
+pub(crate) struct GCodeProgram {
+    pub motions: Vec<Box<dyn Motion>>, // The motions, in the order they should be executed.
+    pub warnings: Vec<String>,         // The warnings about the codes that have been skipped.
+}
+
+/// The motion modes of G-code, these are modal so they apply until another one is given.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MotionMode {
+    Rapid,
+    Linear,
+    ClockwiseArc,
+    CounterClockwiseArc,
+}
+
+/// This struct holds the modal state while parsing a G-code program.
+///
+/// G-code coordinates are in millimeters (or inches) with the $z$ axis pointing up, while the
+///  kinematic model uses meters with the $y$ axis pointing up. The coordinates are therefore
+///  converted by mapping $(x, y, z)$ onto $(x, z, -y)$, which keeps the direction of arcs intact.
+struct GCodeParser {
+    position: Vector3<f64>, // The current position (in G-code coordinates and millimeters).
+    motion_mode: Option<MotionMode>, // The current motion mode.
+    absolute: bool,         // Whether the coordinates are absolute (G90) or relative (G91).
+    unit_scale: f64,        // The number of millimeters per program unit.
+    feed_rate: Option<f64>, // The feed rate (in millimeters/minute).
+    rapid_speed: f64,       // The speed of rapid moves (in meters/second).
+    max_speed: f64,         // The maximum speed of linear moves (in meters/second).
+    motions: Vec<Box<dyn Motion>>,
+    warnings: Vec<String>,
+}
+
+/// Parse the G0/G1 (linear) and G2/G3 (arc in the XY plane) moves of the given G-code program into
+///  motions, starting at the given position (in meters).
+///
+/// Rapid moves are executed at the given rapid speed (in meters/second), linear moves may not
+///  exceed the given maximum speed (in meters/second), unsupported codes are skipped with a
+///  warning.
+pub(crate) fn parse_gcode(
+    program: &str,
+    start_position: Vector3<f64>,
+    rapid_speed: f64,
+    max_speed: f64,
+) -> Result<GCodeProgram, GCodeError> {
+    let mut parser = GCodeParser {
+        position: GCodeParser::from_kinematic(&start_position),
+        motion_mode: None,
+        absolute: true,
+        unit_scale: 1_f64,
+        feed_rate: None,
+        rapid_speed,
+        max_speed,
+        motions: Vec::new(),
+        warnings: Vec::new(),
+    };
+
+    for (i, line) in program.lines().enumerate() {
+        parser.parse_line(i + 1_usize, line)?;
+    }
+
+    Ok(GCodeProgram {
+        motions: parser.motions,
+        warnings: parser.warnings,
+    })
+}
+
+impl GCodeParser {
+    /// Convert a position in G-code coordinates (in millimeters) to the kinematic model.
+    fn to_kinematic(position: &Vector3<f64>) -> Vector3<f64> {
+        Vector3::<f64>::new(position.x, position.z, -position.y) / 1000_f64
+    }
+
+    /// Convert a position in the kinematic model to G-code coordinates (in millimeters).
+    fn from_kinematic(position: &Vector3<f64>) -> Vector3<f64> {
+        Vector3::<f64>::new(position.x, -position.z, position.y) * 1000_f64
+    }
+
+    /// Split the line into its words (letter and number pairs), stripping the comments.
+    fn words(line_number: usize, line: &str) -> Result<Vec<(char, f64)>, GCodeError> {
+        let mut words: Vec<(char, f64)> = Vec::new();
+        let mut chars = line.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                ';' => break,
+                '(' => {
+                    // Skip everything up to the end of the comment.
+                    for c in chars.by_ref() {
+                        if c == ')' {
+                            break;
+                        }
+                    }
+                }
+                c if c.is_whitespace() => {}
+                c if c.is_ascii_alphabetic() => {
+                    let mut number = String::new();
+                    while let Some(&c) = chars.peek() {
+                        if !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+') {
+                            break;
+                        }
+
+                        number.push(c);
+                        chars.next();
+                    }
+
+                    let value = number.parse::<f64>().map_err(|_| GCodeError::InvalidWord {
+                        line: line_number,
+                        word: format!("{}{}", c, number),
+                    })?;
+
+                    words.push((c.to_ascii_uppercase(), value));
+                }
+                c => {
+                    return Err(GCodeError::InvalidWord {
+                        line: line_number,
+                        word: c.to_string(),
+                    })
+                }
+            }
+        }
+
+        Ok(words)
+    }
+
+    /// Apply the given G code to the modal state.
+    fn parse_g_code(&mut self, line_number: usize, code: f64) {
+        // Sub-codes (such as G90.1) are not supported.
+        let supported_code = match code.fract() == 0_f64 {
+            true => Some(code as u32),
+            false => None,
+        };
+
+        match supported_code {
+            Some(0) => self.motion_mode = Some(MotionMode::Rapid),
+            Some(1) => self.motion_mode = Some(MotionMode::Linear),
+            Some(2) => self.motion_mode = Some(MotionMode::ClockwiseArc),
+            Some(3) => self.motion_mode = Some(MotionMode::CounterClockwiseArc),
+            Some(20) => self.unit_scale = 25.4_f64,
+            Some(21) => self.unit_scale = 1_f64,
+            Some(90) => self.absolute = true,
+            Some(91) => self.absolute = false,
+            _ => self.warnings.push(format!(
+                "Unsupported code G{} on line {}, skipped",
+                code, line_number
+            )),
+        }
+    }
+
+    fn parse_line(&mut self, line_number: usize, line: &str) -> Result<(), GCodeError> {
+        let mut target: [Option<f64>; 3] = [None; 3];
+        let mut center_offset: [Option<f64>; 2] = [None; 2];
+
+        for (letter, value) in Self::words(line_number, line)? {
+            match (letter, value) {
+                ('G', x) => self.parse_g_code(line_number, x),
+                ('X', x) => target[0] = Some(x * self.unit_scale),
+                ('Y', x) => target[1] = Some(x * self.unit_scale),
+                ('Z', x) => target[2] = Some(x * self.unit_scale),
+                ('I', x) => center_offset[0] = Some(x * self.unit_scale),
+                ('J', x) => center_offset[1] = Some(x * self.unit_scale),
+                ('F', x) => self.feed_rate = Some(x * self.unit_scale),
+                ('N', _) => {}
+                (letter, value) => self.warnings.push(format!(
+                    "Unsupported code {}{} on line {}, skipped",
+                    letter, value, line_number
+                )),
+            }
+        }
+
+        // Only lines with coordinates result in a move.
+        let Some(motion_mode) = self.motion_mode else {
+            return Ok(());
+        };
+        if target
+            .iter()
+            .chain(center_offset.iter())
+            .all(Option::is_none)
+        {
+            return Ok(());
+        }
+
+        // Compute the target position, the coordinates that are not given stay the same.
+        let mut target_position = self.position;
+        for (axis, value) in target.iter().enumerate() {
+            if let Some(value) = value {
+                target_position[axis] = match self.absolute {
+                    true => *value,
+                    false => self.position[axis] + value,
+                };
+            }
+        }
+
+        let original_position = Self::to_kinematic(&self.position);
+        let kinematic_target_position = Self::to_kinematic(&target_position);
+
+        // The feed rate is in millimeters/minute, so convert it to meters/second.
+        let feed_speed = self
+            .feed_rate
+            .map(|feed_rate| feed_rate / 1000_f64 / 60_f64);
+
+        let motion: Box<dyn Motion> = match motion_mode {
+            MotionMode::Rapid => Box::new(
+                LinearMotion::new_with_max_speed(
+                    original_position,
+                    kinematic_target_position,
+                    self.rapid_speed,
+                    self.max_speed,
+                )
+                .map_err(|source| GCodeError::InvalidMotion {
+                    line: line_number,
+                    source,
+                })?,
+            ),
+            MotionMode::Linear => Box::new(
+                LinearMotion::new_with_max_speed(
+                    original_position,
+                    kinematic_target_position,
+                    feed_speed.ok_or(GCodeError::MissingFeedRate { line: line_number })?,
+                    self.max_speed,
+                )
+                .map_err(|source| GCodeError::InvalidMotion {
+                    line: line_number,
+                    source,
+                })?,
+            ),
+            MotionMode::ClockwiseArc | MotionMode::CounterClockwiseArc => {
+                if center_offset.iter().all(Option::is_none) {
+                    return Err(GCodeError::MissingArcCenter { line: line_number });
+                }
+
+                // The center offset is always relative to the current position.
+                let center_position = self.position
+                    + Vector3::<f64>::new(
+                        center_offset[0].unwrap_or(0_f64),
+                        center_offset[1].unwrap_or(0_f64),
+                        0_f64,
+                    );
+
+                Box::new(ArcMotion::new(
+                    Self::to_kinematic(&center_position),
+                    original_position,
+                    kinematic_target_position,
+                    Vector3::<f64>::y(),
+                    motion_mode == MotionMode::ClockwiseArc,
+                    feed_speed.ok_or(GCodeError::MissingFeedRate { line: line_number })?,
+                ))
+            }
+        };
+
+        self.motions.push(motion);
+        self.position = target_position;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use nalgebra::Vector3;
+
+    use crate::arm::motion::{gcode::parse_gcode, sequence::SequenceMotion, Motion};
+
+    fn assert_near(a: Vector3<f64>, b: Vector3<f64>) {
+        assert!((a - b).magnitude() < 1e-9_f64, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    pub fn rapid_feed_and_clockwise_arc() {
+        let program = "G21 G90 (millimeters, absolute)\n\
+                       G0 X10 Y0 Z5\n\
+                       G1 X20 F600 ; 10 mm/s\n\
+                       G2 X30 Y0 I5 J0\n\
+                       M3 S1000\n";
+
+        let program = parse_gcode(program, Vector3::<f64>::zeros(), 0.1_f64, 1_f64).unwrap();
+
+        assert_eq!(program.motions.len(), 3_usize);
+        assert_eq!(program.warnings.len(), 2_usize);
+
+        // The rapid ends 5 millimeters up, which is along the y axis of the kinematic model.
+        let rapid = &program.motions[0];
+        assert_near(
+            rapid.interpolate(rapid.duration()).unwrap(),
+            Vector3::<f64>::new(0.01_f64, 0.005_f64, 0_f64),
+        );
+
+        // The feed move covers 10 millimeters at 10 millimeters/second.
+        let feed = &program.motions[1];
+        assert!((feed.duration() - 1_f64).abs() < 1e-9_f64);
+
+        // The clockwise arc passes over the top (positive y in G-code) of its center.
+        let arc = &program.motions[2];
+        assert_near(
+            arc.interpolate(arc.duration() / 2_f64).unwrap(),
+            Vector3::<f64>::new(0.025_f64, 0.005_f64, -0.005_f64),
+        );
+        assert_near(
+            arc.interpolate(arc.duration()).unwrap(),
+            Vector3::<f64>::new(0.03_f64, 0.005_f64, 0_f64),
+        );
+
+        // The motions can be run as a single sequence.
+        let sequence = SequenceMotion::new(program.motions);
+        assert_near(
+            sequence.interpolate(sequence.duration()).unwrap(),
+            Vector3::<f64>::new(0.03_f64, 0.005_f64, 0_f64),
+        );
+    }
+
+    #[test]
+    pub fn relative_moves() {
+        let program = "G91\nG1 X10 F600\nX10 Y10\n";
+
+        let program = parse_gcode(program, Vector3::<f64>::zeros(), 0.1_f64, 1_f64).unwrap();
+
+        assert_eq!(program.motions.len(), 2_usize);
+        assert_near(
+            program.motions[1]
+                .interpolate(program.motions[1].duration())
+                .unwrap(),
+            Vector3::<f64>::new(0.02_f64, 0_f64, -0.01_f64),
+        );
+    }
+
+    #[test]
+    pub fn feed_move_without_feed_rate() {
+        assert!(parse_gcode("G1 X10\n", Vector3::<f64>::zeros(), 0.1_f64, 1_f64).is_err());
+    }
+
+    #[test]
+    pub fn rapid_faster_than_max_speed() {
+        let program = "G0 X10\n";
+
+        assert!(parse_gcode(program, Vector3::<f64>::zeros(), 0.5_f64, 1_f64).is_ok());
+        assert!(parse_gcode(program, Vector3::<f64>::zeros(), 0.5_f64, 0.25_f64).is_err());
+    }
+}
@@ -1,6 +1,6 @@
 use nalgebra::Vector3;
 
-use super::Motion;
+use super::{Motion, MotionError};
 
 /// This struct represents a linear motion from the original position to the target position.
 pub(crate) struct LinearMotion {
@@ -9,6 +9,45 @@ pub(crate) struct LinearMotion {
     speed: f64,                      // The speed (in meters/second).
 }
 
+impl LinearMotion {
+    /// The maximum speed used when none is configured (in meters/second).
+    pub const DEFAULT_MAX_SPEED: f64 = 1_f64;
+
+    /// Create a new linear motion, the speed must be positive and may not exceed the default
+    ///  maximum speed.
+    pub fn new(
+        original_position: Vector3<f64>,
+        target_position: Vector3<f64>,
+        speed: f64,
+    ) -> Result<Self, MotionError> {
+        Self::new_with_max_speed(
+            original_position,
+            target_position,
+            speed,
+            Self::DEFAULT_MAX_SPEED,
+        )
+    }
+
+    /// Create a new linear motion, the speed must be positive and may not exceed the given
+    ///  maximum speed (in meters/second).
+    pub fn new_with_max_speed(
+        original_position: Vector3<f64>,
+        target_position: Vector3<f64>,
+        speed: f64,
+        max_speed: f64,
+    ) -> Result<Self, MotionError> {
+        if !(speed > 0_f64 && speed <= max_speed) {
+            return Err(MotionError::InvalidSpeed { speed, max_speed });
+        }
+
+        Ok(Self {
+            target_position,
+            original_position,
+            speed,
+        })
+    }
+}
+
 impl Motion for LinearMotion {
     /// Interpolates the position at a given time.
     ///
@@ -19,25 +58,99 @@ impl Motion for LinearMotion {
     /// # Returns
     ///
     /// * `Some(Vector3<f64>)` - The interpolated position if `t` is within the motion duration.
-    /// * `None` - If `t` is greater than the motion duration.
+    /// * `None` - If `t` is negative or greater than the motion duration.
     fn interpolate(&self, t: f64) -> Option<nalgebra::Vector3<f64>> {
-        assert!(t >= 0_f64);
+        // There's no position before the motion started.
+        if t.is_nan() || t < 0_f64 {
+            return None;
+        }
 
         // Calculate the change in position from the original position to the target position.
-        let delta_position = self.original_position - self.target_position;
+        let delta_position = self.target_position - self.original_position;
 
         // Calculate the duration of the motion based on the magnitude of the delta position and the speed.
-        let duration = delta_position.magnitude() / self.speed;
+        let duration = self.duration();
 
         // If the given time is greater than the duration of the motion, return None.
         if t > duration {
             return None;
         }
 
+        // A motion without any distance to cover is at its target right away.
+        if duration == 0_f64 {
+            return Some(self.target_position);
+        }
+
         // Calculate the delta change in position per unit of time.
         let delta = delta_position / duration;
 
         // Calculate the interpolated position at the given time.
-        Some(delta * t)
+        Some(self.original_position + delta * t)
+    }
+
+    fn duration(&self) -> f64 {
+        (self.target_position - self.original_position).magnitude() / self.speed
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use nalgebra::Vector3;
+
+    use crate::arm::motion::{linear::LinearMotion, Motion, MotionError, MAX_SAMPLES};
+
+    #[test]
+    pub fn zero_speed_rejected() {
+        let result = LinearMotion::new(Vector3::<f64>::zeros(), Vector3::<f64>::x(), 0_f64);
+        assert!(matches!(
+            result,
+            Err(MotionError::InvalidSpeed { speed, .. }) if speed == 0_f64
+        ));
+
+        // Neither may the speed exceed the maximum.
+        let result = LinearMotion::new_with_max_speed(
+            Vector3::<f64>::zeros(),
+            Vector3::<f64>::x(),
+            2_f64,
+            1_f64,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    pub fn negative_time() {
+        let motion =
+            LinearMotion::new(Vector3::<f64>::zeros(), Vector3::<f64>::x(), 1_f64).unwrap();
+
+        assert_eq!(motion.interpolate(-0.1_f64), None);
+        assert_eq!(motion.interpolate(0_f64), Some(Vector3::<f64>::zeros()));
+    }
+
+    #[test]
+    pub fn sample_all_includes_end() {
+        let target = Vector3::<f64>::new(1_f64, 0_f64, 0_f64);
+        let motion = LinearMotion::new(Vector3::<f64>::zeros(), target, 1_f64).unwrap();
+
+        // The time steps don't divide the duration, so the end is sampled separately.
+        let samples = motion.sample_all(0.3_f64);
+        assert_eq!(samples.len(), 5_usize);
+        assert_eq!(samples.first(), Some(&Vector3::<f64>::zeros()));
+        assert_eq!(samples.last(), Some(&target));
+
+        // When they do, the end isn't sampled twice.
+        let samples = motion.sample_all(0.5_f64);
+        assert_eq!(samples.len(), 3_usize);
+        assert_eq!(samples.first(), Some(&Vector3::<f64>::zeros()));
+        assert_eq!(samples.last(), Some(&target));
+    }
+
+    #[test]
+    pub fn sample_all_capped() {
+        let motion =
+            LinearMotion::new(Vector3::<f64>::zeros(), Vector3::<f64>::x(), 1_f64).unwrap();
+
+        let samples = motion.sample_all(1e-6_f64);
+        assert_eq!(samples.len(), MAX_SAMPLES);
+        assert_eq!(samples.first(), Some(&Vector3::<f64>::zeros()));
     }
 }
@@ -0,0 +1,78 @@
+use kinematics::model::KinematicState;
+use nalgebra::Vector5;
+
+use super::{JointMotion, MotionError};
+
+/// This struct represents a motion in joint space that moves all the joints at a constant
+///  velocity from the original state to the target state.
+pub(crate) struct LinearJointMotion {
+    original_angles: Vector5<f64>, // The original joint angles (in radians).
+    target_angles: Vector5<f64>,   // The target joint angles (in radians).
+    duration: f64,                 // The duration (in seconds).
+}
+
+impl LinearJointMotion {
+    /// Create a new linear joint motion, the duration must be positive and finite.
+    pub fn new(
+        original_state: &KinematicState,
+        target_state: &KinematicState,
+        duration: f64,
+    ) -> Result<Self, MotionError> {
+        if !(duration > 0_f64 && duration.is_finite()) {
+            return Err(MotionError::DurationOutOfRange { duration });
+        }
+
+        Ok(Self {
+            original_angles: Vector5::<f64>::from(original_state),
+            target_angles: Vector5::<f64>::from(target_state),
+            duration,
+        })
+    }
+}
+
+impl JointMotion for LinearJointMotion {
+    fn interpolate(&self, t: f64) -> Option<KinematicState> {
+        if t.is_nan() || t < 0_f64 || t > self.duration {
+            return None;
+        }
+
+        Some(KinematicState::from(
+            self.original_angles
+                + (self.target_angles - self.original_angles) * (t / self.duration),
+        ))
+    }
+
+    fn duration(&self) -> f64 {
+        self.duration
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use kinematics::model::KinematicState;
+
+    use crate::arm::motion::{linear_joint::LinearJointMotion, JointMotion, MotionError};
+
+    #[test]
+    pub fn constant_velocity() {
+        let original_state = KinematicState::from_angles([0_f64; 5]);
+        let target_state = KinematicState::from_angles([1_f64, -1_f64, 0.5_f64, 0_f64, 2_f64]);
+
+        let motion = LinearJointMotion::new(&original_state, &target_state, 2_f64).unwrap();
+
+        assert_eq!(motion.interpolate(0_f64), Some(original_state));
+        assert_eq!(
+            motion.interpolate(0.5_f64),
+            Some(KinematicState::from_angles([
+                0.25_f64, -0.25_f64, 0.125_f64, 0_f64, 0.5_f64
+            ]))
+        );
+        assert_eq!(motion.interpolate(2_f64), Some(target_state.clone()));
+        assert_eq!(motion.interpolate(2.5_f64), None);
+
+        assert!(matches!(
+            LinearJointMotion::new(&target_state, &target_state, 0_f64),
+            Err(MotionError::DurationOutOfRange { .. })
+        ));
+    }
+}
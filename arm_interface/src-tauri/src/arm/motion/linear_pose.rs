@@ -0,0 +1,131 @@
+use super::{MotionError, Pose, PoseMotion};
+
+/// This struct represents a motion that moves the end-effector in a straight line from the
+///  original pose to the target pose, turning the approach angle and the wrist roll along the way
+///  so they reach the target at the same time.
+pub(crate) struct LinearPoseMotion {
+    original_pose: Pose, // The original pose.
+    target_pose: Pose,   // The target pose.
+    duration: f64,       // The duration (in seconds).
+}
+
+impl LinearPoseMotion {
+    /// The speed at which the approach angle and the wrist roll turn at most (in radians/second),
+    ///  which sets the duration of a motion that mostly rotates the end-effector.
+    pub const MAX_ANGULAR_SPEED: f64 = 1_f64;
+
+    /// Create a new linear pose motion, the speed (in meters/second) must be positive and may not
+    ///  exceed the given maximum speed.
+    pub fn new_with_max_speed(
+        original_pose: Pose,
+        target_pose: Pose,
+        speed: f64,
+        max_speed: f64,
+    ) -> Result<Self, MotionError> {
+        if !(speed > 0_f64 && speed <= max_speed) {
+            return Err(MotionError::InvalidSpeed { speed, max_speed });
+        }
+
+        let distance = (target_pose.position - original_pose.position).magnitude();
+        let rotation = (target_pose.approach - original_pose.approach)
+            .abs()
+            .max((target_pose.roll - original_pose.roll).abs());
+
+        Ok(Self {
+            duration: (distance / speed).max(rotation / Self::MAX_ANGULAR_SPEED),
+            original_pose,
+            target_pose,
+        })
+    }
+}
+
+impl PoseMotion for LinearPoseMotion {
+    fn interpolate(&self, t: f64) -> Option<Pose> {
+        if t.is_nan() || t < 0_f64 || t > self.duration {
+            return None;
+        }
+
+        // A motion without anything to cover is at its target right away.
+        if self.duration == 0_f64 {
+            return Some(self.target_pose.clone());
+        }
+
+        let f = t / self.duration;
+
+        Some(Pose {
+            position: self.original_pose.position
+                + (self.target_pose.position - self.original_pose.position) * f,
+            approach: self.original_pose.approach
+                + (self.target_pose.approach - self.original_pose.approach) * f,
+            roll: self.original_pose.roll + (self.target_pose.roll - self.original_pose.roll) * f,
+        })
+    }
+
+    fn duration(&self) -> f64 {
+        self.duration
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use nalgebra::Vector3;
+
+    use crate::arm::motion::{linear_pose::LinearPoseMotion, MotionError, Pose, PoseMotion};
+
+    #[test]
+    pub fn position_and_orientation_arrive_together() {
+        let original_pose = Pose {
+            position: Vector3::<f64>::zeros(),
+            approach: 0_f64,
+            roll: 0_f64,
+        };
+        let target_pose = Pose {
+            position: Vector3::<f64>::new(0.5_f64, 0_f64, 0_f64),
+            approach: 0.5_f64,
+            roll: -0.25_f64,
+        };
+
+        // Moving takes longer than turning, so the speed sets the duration.
+        let motion = LinearPoseMotion::new_with_max_speed(
+            original_pose.clone(),
+            target_pose.clone(),
+            0.25_f64,
+            1_f64,
+        )
+        .unwrap();
+        assert_eq!(motion.duration(), 2_f64);
+
+        assert_eq!(motion.interpolate(0_f64), Some(original_pose.clone()));
+        assert_eq!(
+            motion.interpolate(1_f64),
+            Some(Pose {
+                position: Vector3::<f64>::new(0.25_f64, 0_f64, 0_f64),
+                approach: 0.25_f64,
+                roll: -0.125_f64,
+            })
+        );
+        assert_eq!(motion.interpolate(2_f64), Some(target_pose.clone()));
+        assert_eq!(motion.interpolate(2.5_f64), None);
+
+        // Only turning the wrist still takes time.
+        let motion = LinearPoseMotion::new_with_max_speed(
+            original_pose.clone(),
+            Pose {
+                roll: 2_f64,
+                ..original_pose.clone()
+            },
+            0.25_f64,
+            1_f64,
+        )
+        .unwrap();
+        assert_eq!(
+            motion.duration(),
+            2_f64 / LinearPoseMotion::MAX_ANGULAR_SPEED
+        );
+
+        assert!(matches!(
+            LinearPoseMotion::new_with_max_speed(original_pose, target_pose, 2_f64, 1_f64),
+            Err(MotionError::InvalidSpeed { .. })
+        ));
+    }
+}
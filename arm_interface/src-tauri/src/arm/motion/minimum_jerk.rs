@@ -0,0 +1,109 @@
+use kinematics::model::KinematicState;
+use nalgebra::Vector5;
+
+use super::{JointMotion, MotionError};
+
+/// This struct represents a point-to-point motion in joint space following the minimum jerk
+///  profile, which is the fifth order polynomial that starts and ends with zero velocity and
+///  acceleration.
+pub(crate) struct MinimumJerkJointMotion {
+    original_angles: Vector5<f64>, // The original joint angles (in radians).
+    target_angles: Vector5<f64>,   // The target joint angles (in radians).
+    duration: f64,                 // The duration (in seconds).
+}
+
+impl MinimumJerkJointMotion {
+    /// Create a new minimum jerk motion, the duration must be positive and finite.
+    pub fn new(
+        original_state: &KinematicState,
+        target_state: &KinematicState,
+        duration: f64,
+    ) -> Result<Self, MotionError> {
+        if !(duration > 0_f64 && duration.is_finite()) {
+            return Err(MotionError::DurationOutOfRange { duration });
+        }
+
+        Ok(Self {
+            original_angles: Vector5::<f64>::from(original_state),
+            target_angles: Vector5::<f64>::from(target_state),
+            duration,
+        })
+    }
+
+    /// Get the normalized time (between zero and one) of the given timestamp, or None if it's
+    ///  outside of the motion.
+    fn normalized_time(&self, t: f64) -> Option<f64> {
+        if t.is_nan() || t < 0_f64 || t > self.duration {
+            return None;
+        }
+
+        Some(t / self.duration)
+    }
+
+    /// Get the joint velocities at the given timestamp (in radians/second), or None if it's
+    ///  outside of the motion.
+    pub fn velocity(&self, t: f64) -> Option<Vector5<f64>> {
+        let tau = self.normalized_time(t)?;
+
+        // The derivative of the position profile, scaled back from the normalized time.
+        let rate = 30_f64 * tau.powi(2) - 60_f64 * tau.powi(3) + 30_f64 * tau.powi(4);
+
+        Some((self.target_angles - self.original_angles) * (rate / self.duration))
+    }
+}
+
+impl JointMotion for MinimumJerkJointMotion {
+    fn interpolate(&self, t: f64) -> Option<KinematicState> {
+        let tau = self.normalized_time(t)?;
+
+        // The fraction of the way covered, which rises from zero to one.
+        let s = 10_f64 * tau.powi(3) - 15_f64 * tau.powi(4) + 6_f64 * tau.powi(5);
+
+        Some(KinematicState::from(
+            self.original_angles + (self.target_angles - self.original_angles) * s,
+        ))
+    }
+
+    fn duration(&self) -> f64 {
+        self.duration
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use kinematics::model::KinematicState;
+    use nalgebra::Vector5;
+
+    use crate::arm::motion::{minimum_jerk::MinimumJerkJointMotion, JointMotion};
+
+    #[test]
+    pub fn endpoints_and_symmetry() {
+        let original_state = KinematicState::from(Vector5::<f64>::zeros());
+        let target_state = KinematicState::from(Vector5::<f64>::new(
+            1_f64, -0.5_f64, 0.25_f64, 2_f64, -1_f64,
+        ));
+
+        let motion = MinimumJerkJointMotion::new(&original_state, &target_state, 2_f64).unwrap();
+        let angles = |t: f64| Vector5::<f64>::from(&motion.interpolate(t).unwrap());
+
+        // The motion starts and ends at rest at the given states.
+        assert!((angles(0_f64) - Vector5::<f64>::from(&original_state)).norm() < 1e-12_f64);
+        assert!((angles(2_f64) - Vector5::<f64>::from(&target_state)).norm() < 1e-12_f64);
+        assert!(motion.velocity(0_f64).unwrap().norm() < 1e-12_f64);
+        assert!(motion.velocity(2_f64).unwrap().norm() < 1e-12_f64);
+        assert!(motion.interpolate(2.1_f64).is_none());
+
+        // The midpoint is halfway, and the profile is point symmetric around it.
+        let halfway = Vector5::<f64>::from(&target_state) / 2_f64;
+        assert!((angles(1_f64) - halfway).norm() < 1e-12_f64);
+        for t in [0.1_f64, 0.5_f64, 0.8_f64] {
+            assert!((angles(t) + angles(2_f64 - t) - 2_f64 * halfway).norm() < 1e-12_f64);
+        }
+
+        // The velocity peaks at the center.
+        let peak = motion.velocity(1_f64).unwrap().norm();
+        for i in 0_usize..=20_usize {
+            assert!(motion.velocity(i as f64 * 0.1_f64).unwrap().norm() <= peak + 1e-12_f64);
+        }
+    }
+}
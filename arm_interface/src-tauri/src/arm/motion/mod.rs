@@ -1,11 +1,101 @@
+use kinematics::model::KinematicState;
 use nalgebra::Vector3;
+use thiserror::Error;
 
-pub(crate) mod linear;
+pub(crate) mod arc;
+pub(crate) mod blended;
 pub(crate) mod circle;
+pub(crate) mod gcode;
+pub(crate) mod linear;
+pub(crate) mod linear_joint;
+pub(crate) mod linear_pose;
+pub(crate) mod minimum_jerk;
 pub(crate) mod player;
+pub(crate) mod reversed;
+pub(crate) mod safety;
+pub(crate) mod sequence;
+pub(crate) mod time_scaled;
+
+/// The maximum number of samples taken by `Motion::sample_all`, so motions that never end (or
+///  take extremely long) don't sample forever.
+pub(crate) const MAX_SAMPLES: usize = 100_000_usize;
 
-pub(crate) trait Motion: Send {
+pub(crate) trait Motion: Send + Sync {
     /// Interpolate the motion at the given timestamp, return the new end-effector position
     ///  or None if the motion is finished.
     fn interpolate(&self, t: f64) -> Option<Vector3<f64>>;
+
+    /// Get the duration of the motion (in seconds).
+    fn duration(&self) -> f64;
+
+    /// Sample the end-effector positions of the whole motion every `dt` seconds, and at its end
+    ///  (even if the time steps don't evenly divide its duration). At most `MAX_SAMPLES` samples
+    ///  are taken.
+    fn sample_all(&self, dt: f64) -> Vec<Vector3<f64>> {
+        assert!(dt > 0_f64);
+
+        let mut samples: Vec<Vector3<f64>> = Vec::new();
+        while samples.len() < MAX_SAMPLES {
+            match self.interpolate(samples.len() as f64 * dt) {
+                Some(position) => samples.push(position),
+                None => break,
+            }
+        }
+
+        // The last time step went past the end of the motion, so take one more sample at the end
+        //  (unless that's where the last one was taken).
+        let duration = self.duration();
+        let last_t = (samples.len() as f64 - 1_f64) * dt;
+        if !samples.is_empty()
+            && samples.len() < MAX_SAMPLES
+            && duration.is_finite()
+            && last_t < duration
+        {
+            samples.extend(self.interpolate(duration));
+        }
+
+        samples
+    }
+}
+
+/// This trait means that the thing implementing it is a motion in joint space, so it moves the
+///  joints directly instead of the end-effector.
+pub(crate) trait JointMotion: Send {
+    /// Interpolate the motion at the given timestamp, return the new joint angles or None if the
+    ///  motion is finished.
+    fn interpolate(&self, t: f64) -> Option<KinematicState>;
+
+    /// Get the duration of the motion (in seconds).
+    fn duration(&self) -> f64;
+}
+
+/// This struct represents a pose of the end-effector the way this arm can reach it, so the
+///  orientation is given by the approach angle of the last links and the wrist roll.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Pose {
+    pub position: Vector3<f64>, // The position of the end-effector (in meters).
+    pub approach: f64,          // The angle of the last links from the vertical (in radians).
+    pub roll: f64,              // The wrist roll (in radians).
+}
+
+/// This trait means that the thing implementing it is a motion of the whole pose of the
+///  end-effector, so it moves and rotates the end-effector at the same time.
+pub(crate) trait PoseMotion: Send + Sync {
+    /// Interpolate the motion at the given timestamp, return the new end-effector pose or None if
+    ///  the motion is finished.
+    fn interpolate(&self, t: f64) -> Option<Pose>;
+
+    /// Get the duration of the motion (in seconds).
+    fn duration(&self) -> f64;
+}
+
+/// This error represents a motion that can't be created with the given parameters.
+#[derive(Debug, Error)]
+pub(crate) enum MotionError {
+    #[error("Speed must be positive and at most {max_speed} m/s, got {speed} m/s")]
+    InvalidSpeed { speed: f64, max_speed: f64 },
+    #[error("Time scale must be positive, got {scale}")]
+    InvalidScale { scale: f64 },
+    #[error("Duration must be positive and finite, got {duration} s")]
+    DurationOutOfRange { duration: f64 },
 }
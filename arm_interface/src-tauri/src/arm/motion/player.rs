@@ -1,27 +1,565 @@
-use std::sync::Arc;
+use std::{future::Future, pin::Pin, sync::Arc};
 
-use tokio::sync::mpsc;
+use kinematics::{
+    error::KinematicError,
+    forward::algorithms::{analytical::AnalyticalFKAlgorithm, ForwardKinematicAlgorithm},
+    inverse::{
+        algorithms::analytical::{AnalyticalIKAlgorithm, ElbowConfiguration},
+        solvers::select_solution,
+    },
+    model::{Joint, JointLimitWarning, KinematicParameters, KinematicState},
+};
+use nalgebra::{Vector3, Vector5};
+use tokio::{
+    select,
+    sync::{broadcast, mpsc, oneshot, watch},
+    time::{self, Duration, Instant},
+};
 use tokio_util::sync::CancellationToken;
 
-use kinematics::inverse::solvers::{IKSolverResult, KinematicSolver};
+use crate::{
+    arm::Arm,
+    error::Error,
+    servo_com::{self, events::PoseChangedEvent},
+};
 
-use crate::{arm::Arm, error::Error, servo_com::Handle};
+use super::{
+    linear_joint::LinearJointMotion,
+    linear_pose::LinearPoseMotion,
+    safety::{check_motion_safety, WorkspaceBounds},
+    JointMotion, Motion, Pose, PoseMotion,
+};
 
-use super::Motion;
+/// This struct configures how the time step adapts to the path, so sharp curves get more samples
+///  and straight sections fewer.
+#[derive(Debug, Clone)]
+pub(crate) struct AdaptiveTimeStep {
+    pub tolerance: f64,      // The largest turn of the path per step (in radians).
+    pub min_delta_time: f64, // The smallest time step (in seconds).
+    pub max_delta_time: f64, // The largest time step (in seconds).
+}
+
+impl AdaptiveTimeStep {
+    pub fn new(tolerance: f64, min_delta_time: f64, max_delta_time: f64) -> Self {
+        Self {
+            tolerance,
+            min_delta_time,
+            max_delta_time,
+        }
+    }
+
+    /// The angle between two successive steps along the motion after `t`, which is about the
+    ///  distance covered in a step times the curvature of the path. `None` if the motion ends
+    ///  before the second step does.
+    fn step_error(motion: &dyn Motion, t: f64, delta_time: f64) -> Option<f64> {
+        let p_0 = motion.interpolate(t)?;
+        let p_1 = motion.interpolate(t + delta_time)?;
+        let p_2 = motion.interpolate(t + 2_f64 * delta_time)?;
+
+        let (a, b) = (p_1 - p_0, p_2 - p_1);
+        if a.magnitude() == 0_f64 || b.magnitude() == 0_f64 {
+            return Some(0_f64);
+        }
+
+        Some(a.angle(&b))
+    }
+
+    /// Get the time step to take after `t`, given the previous one. The step is halved while the
+    ///  path turns too much within it, and doubled when it turns well within the tolerance.
+    pub fn next_delta_time(&self, motion: &dyn Motion, t: f64, delta_time: f64) -> f64 {
+        let mut delta_time = delta_time.clamp(self.min_delta_time, self.max_delta_time);
+
+        while delta_time > self.min_delta_time
+            && Self::step_error(motion, t, delta_time).is_some_and(|x| x > self.tolerance)
+        {
+            delta_time = (delta_time / 2_f64).max(self.min_delta_time);
+        }
+
+        // Only grow if the path turns well within the tolerance, even over the doubled step.
+        let grown = (delta_time * 2_f64).min(self.max_delta_time);
+        if Self::step_error(motion, t, grown).is_some_and(|x| x < self.tolerance / 4_f64) {
+            return grown;
+        }
+
+        delta_time
+    }
+}
 
 pub(crate) struct Configuration {
     delta_time: f64,
+    workspace_bounds: WorkspaceBounds,
+    adaptive_time_step: Option<AdaptiveTimeStep>, // Fixed time steps are used if not set.
+    max_motion_duration: f64, // The time after which a motion is aborted (in seconds).
+    warm_start: bool, // Whether the solver starts from the predicted state of the next sample.
+    prefer_gravity_assisted: bool, // Whether equally close solutions lowering the arm are preferred.
 }
 
 impl Configuration {
+    /// The maximum motion duration used when none is configured (in seconds).
+    pub const DEFAULT_MAX_MOTION_DURATION: f64 = 600_f64;
+
     pub fn new(delta_time: f64) -> Self {
-        Self { delta_time }
+        Self {
+            delta_time,
+            workspace_bounds: WorkspaceBounds::default(),
+            adaptive_time_step: None,
+            max_motion_duration: Self::DEFAULT_MAX_MOTION_DURATION,
+            warm_start: false,
+            prefer_gravity_assisted: false,
+        }
+    }
+
+    pub fn with_workspace_bounds(mut self, workspace_bounds: WorkspaceBounds) -> Self {
+        self.workspace_bounds = workspace_bounds;
+
+        self
+    }
+
+    /// Adapt the time step to the path, `delta_time` is then only used as the first step.
+    pub fn with_adaptive_time_step(mut self, adaptive_time_step: AdaptiveTimeStep) -> Self {
+        self.adaptive_time_step = Some(adaptive_time_step);
+
+        self
+    }
+
+    /// Abort motions that are still running after the given time (in seconds), this protects
+    ///  against motions that are extremely slow or never end.
+    pub fn with_max_motion_duration(mut self, max_motion_duration: f64) -> Self {
+        assert!(max_motion_duration > 0_f64);
+
+        self.max_motion_duration = max_motion_duration;
+
+        self
+    }
+
+    /// Start solving every sample from the state predicted by continuing the joint motion of the
+    ///  previous time step, instead of from the previous state. The previous step was solved
+    ///  through the jacobian, so for the tiny steps of a dense trajectory this reuses it as a first
+    ///  guess and the solver needs fewer iterations.
+    pub fn with_warm_start(mut self, warm_start: bool) -> Self {
+        self.warm_start = warm_start;
+
+        self
+    }
+
+    /// Among the solutions that are equally close to the current state, pick the one with the
+    ///  lowest center of mass, so the shoulder moves with gravity rather than against it. This only
+    ///  matters for solvers that find more than one solution.
+    pub fn with_prefer_gravity_assisted(mut self, prefer_gravity_assisted: bool) -> Self {
+        self.prefer_gravity_assisted = prefer_gravity_assisted;
+
+        self
+    }
+
+    /// Get the error for a motion that's still running after the maximum motion duration.
+    pub(self) fn max_motion_duration_exceeded() -> Error {
+        Error::Generic("motion exceeded max duration".into())
+    }
+
+    /// Check that the given motion ends before the maximum motion duration, so a motion that's
+    ///  too long is rejected before it's started instead of aborted halfway.
+    pub(self) fn check_motion_duration(&self, motion: &dyn Motion) -> Result<(), Error> {
+        let duration = motion.duration();
+
+        if duration.is_nan() || duration > self.max_motion_duration {
+            return Err(Self::max_motion_duration_exceeded());
+        }
+
+        Ok(())
+    }
+
+    /// Get the time step to take after `t`, given the previous one.
+    pub(self) fn next_delta_time(&self, motion: &dyn Motion, t: f64, delta_time: f64) -> f64 {
+        match &self.adaptive_time_step {
+            Some(adaptive_time_step) => adaptive_time_step.next_delta_time(motion, t, delta_time),
+            None => self.delta_time,
+        }
+    }
+}
+
+/// This struct represents how the joints moved during a time step, which is continued into the
+///  next time step to predict where the arm will be.
+#[derive(Debug, Clone)]
+struct JointStep {
+    delta: Vector5<f64>, // The change of the joint angles (in radians).
+    delta_time: f64,     // The duration of the time step (in seconds).
+}
+
+impl JointStep {
+    /// Get the joint step from the first to the second state, which took the given time.
+    fn between(from: &KinematicState, to: &KinematicState, delta_time: f64) -> Self {
+        Self {
+            delta: Vector5::<f64>::from(to) - Vector5::<f64>::from(from),
+            delta_time,
+        }
+    }
+
+    /// Predict the state after the given time step by moving on from the given state at the same
+    ///  joint velocities.
+    fn predict(&self, state: &KinematicState, delta_time: f64) -> KinematicState {
+        KinematicState::from(
+            Vector5::<f64>::from(state) + self.delta * (delta_time / self.delta_time),
+        )
+    }
+}
+
+/// This struct represents the states solved for a single sample of a motion, each one with the
+///  duration it's commanded with (in seconds).
+struct MotionSample {
+    t: f64,                                            // The time of the sample (in seconds).
+    states: Result<Vec<(KinematicState, f64)>, Error>, // More than one if the time step got subdivided.
+}
+
+/// This struct steps through a motion the way the player runs it, solving (and validating) the
+///  states of every sample. Running and validating a motion both use it, so a motion that
+///  passes validation is sampled and solved the same way when it's run.
+///
+/// The last sample is always taken at the end of the motion, even if the time steps don't
+///  evenly divide its duration, so the arm ends up exactly at the target. A motion that's still
+///  going on after the maximum motion duration gets a failing sample, after which sampling stops.
+///
+/// After a failing sample, solving continues from the last state that was solved.
+struct MotionSampler<'a> {
+    arm: &'a Arm,
+    configuration: &'a Configuration,
+    motion: &'a dyn Motion,
+    t: f64,
+    delta_time: f64,
+    sampled_t: Option<f64>, // The time of the previous sample, even if it failed.
+    previous_t: Option<f64>, // The time of the previous sample, if it was solved.
+    kinematic_state: KinematicState, // The last solved state.
+    previous_step: Option<JointStep>,
+    finished: bool,
+}
+
+impl<'a> MotionSampler<'a> {
+    fn new(
+        arm: &'a Arm,
+        configuration: &'a Configuration,
+        motion: &'a dyn Motion,
+        start_state: KinematicState,
+    ) -> Self {
+        Self {
+            arm,
+            configuration,
+            motion,
+            t: 0_f64,
+            delta_time: configuration.delta_time,
+            sampled_t: None,
+            previous_t: None,
+            kinematic_state: start_state,
+            previous_step: None,
+            finished: false,
+        }
+    }
+
+    /// The last state that was solved, or the start state if none was.
+    fn kinematic_state(&self) -> &KinematicState {
+        &self.kinematic_state
+    }
+
+    /// Solve the states that move the end-effector to the given position at the current time,
+    ///  and validate them against the kinematic parameters of the arm. The given step is the time
+    ///  since the previous sample, which is what the state is commanded with if that sample failed.
+    fn solve(
+        &self,
+        target_position: &Vector3<f64>,
+        step: f64,
+    ) -> Result<Vec<(KinematicState, f64)>, Error> {
+        let states = match (self.previous_t, self.previous_step.as_ref()) {
+            // There's no earlier sample of the motion to subdivide towards.
+            (None, _) => vec![(
+                self.solve_sample(&self.kinematic_state, target_position)?,
+                step,
+            )],
+            (Some(previous_t), Some(previous_step)) if self.configuration.warm_start => self
+                .solve_warm_time_step(
+                    &self.kinematic_state,
+                    &previous_step.predict(&self.kinematic_state, self.t - previous_t),
+                    previous_t,
+                    self.t,
+                )?,
+            (Some(previous_t), _) => {
+                self.solve_time_step(&self.kinematic_state, previous_t, self.t, 0_usize)?
+            }
+        };
+
+        for (state, _) in states.iter() {
+            state.validate(self.arm.kinematic_parameters())?;
+        }
+
+        Ok(states)
+    }
+
+    /// Solve the kinematic state that moves the end-effector to the given position, starting from
+    ///  the given state. If the solver finds more than one solution, the one closest to the given
+    ///  state is picked (preferring the gravity assisted one, if configured).
+    fn solve_sample(
+        &self,
+        state: &KinematicState,
+        target_position: &Vector3<f64>,
+    ) -> Result<KinematicState, Error> {
+        let solver = self.arm.kinematic_solver();
+        let params = self.arm.kinematic_parameters();
+
+        let solutions =
+            solver.translate_limb4_end_effector_solutions(params, state, target_position)?;
+
+        Ok(select_solution(
+            solver.forward_algorithm(),
+            params,
+            state,
+            &solutions,
+            self.configuration.prefer_gravity_assisted,
+        )
+        .cloned()
+        .ok_or(KinematicError::Unreachable)?)
+    }
+
+    /// Solve the kinematic states for the part of the motion from `t_0` (at which the arm is in
+    ///  the given state) up to `t_1`.
+    ///
+    /// Every returned state comes with the duration of the (part of the) time step it ends. If a
+    ///  joint would move faster than its velocity limit the time step is subdivided, so a solver
+    ///  that picked another branch of the solution from the given state can get to it through
+    ///  states in between. If it still can't, the joint velocity limit is exceeded.
+    fn solve_time_step(
+        &self,
+        state: &KinematicState,
+        t_0: f64,
+        t_1: f64,
+        depth: usize,
+    ) -> Result<Vec<(KinematicState, f64)>, Error> {
+        let Some(target_position) = self.motion.interpolate(t_1) else {
+            return Ok(Vec::new());
+        };
+
+        let new_state = self.solve_sample(state, &target_position)?;
+
+        let Some((joint, velocity)) = Worker::exceeding_joint_velocity(
+            self.arm.kinematic_parameters(),
+            state,
+            &new_state,
+            t_1 - t_0,
+        ) else {
+            return Ok(vec![(new_state, t_1 - t_0)]);
+        };
+
+        if depth >= Worker::MAX_SUBDIVISION_DEPTH {
+            return Err(Error::JointVelocityExceeded {
+                joint: joint.index(),
+                velocity,
+            });
+        }
+
+        // Split the time step in two, solving the second half from the end of the first one.
+        let t_m = (t_0 + t_1) / 2_f64;
+
+        let mut states = self.solve_time_step(state, t_0, t_m, depth + 1_usize)?;
+        let middle_state = states
+            .last()
+            .map(|(middle_state, _)| middle_state.clone())
+            .unwrap_or_else(|| state.clone());
+
+        states.extend(self.solve_time_step(&middle_state, t_m, t_1, depth + 1_usize)?);
+
+        Ok(states)
+    }
+
+    /// Solve the kinematic states for the part of the motion from `t_0` up to `t_1` like
+    ///  `solve_time_step` does, but start the solver from the given seed instead of from the
+    ///  state the arm is in at `t_0`. If the time step has to be subdivided, the seed is dropped
+    ///  and it's solved from the state instead.
+    fn solve_warm_time_step(
+        &self,
+        state: &KinematicState,
+        seed: &KinematicState,
+        t_0: f64,
+        t_1: f64,
+    ) -> Result<Vec<(KinematicState, f64)>, Error> {
+        let Some(target_position) = self.motion.interpolate(t_1) else {
+            return Ok(Vec::new());
+        };
+
+        let new_state = self.solve_sample(seed, &target_position)?;
+
+        if Worker::exceeding_joint_velocity(
+            self.arm.kinematic_parameters(),
+            state,
+            &new_state,
+            t_1 - t_0,
+        )
+        .is_none()
+        {
+            return Ok(vec![(new_state, t_1 - t_0)]);
+        }
+
+        self.solve_time_step(state, t_0, t_1, 0_usize)
+    }
+}
+
+impl Iterator for MotionSampler<'_> {
+    type Item = MotionSample;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let duration = self.motion.duration();
+
+        let target_position = match self.motion.interpolate(self.t) {
+            Some(target_position) => target_position,
+            // The time step went past the end of the motion, so take one more sample at the end
+            //  (unless that's where the previous one was taken).
+            None if duration.is_finite() && self.sampled_t.is_some_and(|x| x < duration) => {
+                self.t = duration;
+                self.motion.interpolate(self.t)?
+            }
+            None => return None,
+        };
+
+        let t = self.t;
+        let delta_time = self.delta_time;
+
+        // The last sample is closer to the previous one than a time step if it got moved to the end.
+        let step = self.sampled_t.map_or(delta_time, |sampled_t| t - sampled_t);
+        self.sampled_t = Some(t);
+
+        // Protect against motions that never end (or take unreasonably long).
+        if t > self.configuration.max_motion_duration {
+            self.finished = true;
+
+            return Some(MotionSample {
+                t,
+                states: Err(Configuration::max_motion_duration_exceeded()),
+            });
+        }
+
+        let states = self.solve(&target_position, step);
+
+        match &states {
+            Ok(states) => {
+                if let Some((last, _)) = states.last() {
+                    self.previous_step = self.previous_t.map(|previous_t| {
+                        JointStep::between(&self.kinematic_state, last, t - previous_t)
+                    });
+                    self.kinematic_state = last.clone();
+                }
+
+                self.previous_t = Some(t);
+            }
+            Err(_) => {
+                self.previous_step = None;
+
+                // The last state is outdated, so don't subdivide towards the next sample.
+                self.previous_t = None;
+            }
+        }
+
+        self.delta_time = self
+            .configuration
+            .next_delta_time(self.motion, t, delta_time);
+        self.t += self.delta_time;
+
+        Some(MotionSample { t, states })
+    }
+}
+
+/// This struct represents a sample of a motion that can't be run, found while validating it.
+#[derive(Debug)]
+pub(crate) struct SampleFailure {
+    pub t: f64,       // The time of the sample (in seconds).
+    pub error: Error, // The reason the sample can't be run.
+}
+
+/// This struct represents the outcome of validating a motion without running it.
+#[derive(Debug, Default)]
+pub(crate) struct MotionReport {
+    pub failures: Vec<SampleFailure>, // The failing samples, ordered by time.
+}
+
+impl MotionReport {
+    /// Check if the motion can be run, meaning no sample failed.
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// This struct represents the outcome of moving a single joint during the self-test.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct JointSelfTest {
+    pub joint: Joint,
+    pub commanded: f64,        // The angle the joint was moved to (in radians).
+    pub reported: Option<f64>, // The last angle the servo reported, if any (in radians).
+    pub passed: bool,          // Whether the reported angle is close to the commanded one.
+    pub error: Option<String>, // Why the joint couldn't be moved, if it couldn't.
+}
+
+/// This struct represents the outcome of the self-test, with a result for every joint.
+#[derive(Debug, Default)]
+pub(crate) struct SelfTestReport {
+    pub joints: Vec<JointSelfTest>, // The results, ordered like `Joint::ALL`.
+}
+
+impl SelfTestReport {
+    /// Check if every joint moved as commanded.
+    pub fn is_ok(&self) -> bool {
+        self.joints.iter().all(|x| x.passed)
+    }
+}
+
+/// Validate the given motion the way the player would run it, so stepping through the
+///  interpolation with the same `MotionSampler`, but without commanding the servo (nor waiting
+///  for the clock).
+///
+/// Instead of stopping at the first failure all the failing samples are collected, after a
+///  failure solving continues from the last state that was solved.
+pub(crate) fn validate_motion(
+    arm: &Arm,
+    configuration: &Configuration,
+    motion: &dyn Motion,
+) -> MotionReport {
+    let mut report = MotionReport::default();
+
+    // The player wouldn't start the motion at all, so there's nothing to sample.
+    if let Err(error) = configuration.check_motion_duration(motion) {
+        report.failures.push(SampleFailure {
+            t: configuration.max_motion_duration,
+            error,
+        });
+
+        return report;
     }
+
+    if let Err(violation) = check_motion_safety(
+        motion,
+        &configuration.workspace_bounds,
+        configuration.delta_time,
+        configuration.max_motion_duration,
+    ) {
+        report.failures.push(SampleFailure {
+            t: violation.t,
+            error: violation.into(),
+        });
+    }
+
+    let sampler = MotionSampler::new(arm, configuration, motion, arm.kinematic_state().clone());
+
+    for sample in sampler {
+        if let Err(error) = sample.states {
+            report.failures.push(SampleFailure { t: sample.t, error });
+        }
+    }
+
+    report
 }
 
 pub(crate) enum Instructon {
     Start(Box<dyn Motion>),
+    MoveToPose { target: Pose, speed: f64 },
     Stop,
+    SelfTest(oneshot::Sender<Result<SelfTestReport, Error>>),
 }
 
 pub(crate) struct Player;
@@ -30,86 +568,2438 @@ impl Player {
     pub const CHANNEL_CAPACITY: usize = 64_usize;
 
     pub fn new(
-        handle: Handle,
+        handle: servo_com::Handle,
         configuration: Configuration,
         arm: Arc<Arm>,
     ) -> (Worker, Handle) {
         let (instruction_sender, instruction_receiver) = mpsc::channel(Self::CHANNEL_CAPACITY);
+        let (joint_limit_warnings, _) = broadcast::channel(Self::CHANNEL_CAPACITY);
+        let (motion_failures, _) = broadcast::channel(Self::CHANNEL_CAPACITY);
+        let (kinematic_state, _) = watch::channel(arm.kinematic_state().clone());
 
-        let worker = Worker::new(handle, instruction_receiver, configuration, arm);
-        let handle = Handle::new(instruction_sender);
+        let worker = Worker::new(
+            handle,
+            instruction_receiver,
+            joint_limit_warnings.clone(),
+            motion_failures.clone(),
+            kinematic_state.clone(),
+            configuration,
+            arm,
+        );
+        let handle = Handle::new(
+            instruction_sender,
+            joint_limit_warnings,
+            motion_failures,
+            kinematic_state,
+        );
 
         (worker, handle)
     }
 }
 
 pub(crate) struct Worker {
-    handle: Handle,
+    handle: servo_com::Handle,
     instruction_receiver: mpsc::Receiver<Instructon>,
+    joint_limit_warnings: broadcast::Sender<JointLimitWarning>,
+    motion_failures: broadcast::Sender<Arc<Error>>,
+    kinematic_state: watch::Sender<KinematicState>, // The state the last motion ended in.
     configuration: Configuration,
     arm: Arc<Arm>,
 }
 
 impl Worker {
+    /// The maximum number of times a time step is split in two to respect the joint velocity
+    ///  limits.
+    pub const MAX_SUBDIVISION_DEPTH: usize = 8_usize;
+
+    /// The angle every joint is moved by during the self-test (in radians).
+    pub const SELF_TEST_AMPLITUDE: f64 = 0.05_f64;
+
+    /// The duration of every move during the self-test (in seconds).
+    pub const SELF_TEST_DURATION: f64 = 0.5_f64;
+
+    /// The largest difference between the commanded and reported angle for a joint to pass the
+    ///  self-test (in radians).
+    pub const SELF_TEST_TOLERANCE: f64 = 0.01_f64;
+
+    /// The time to wait for the servo to report the commanded angle during the self-test.
+    pub const SELF_TEST_TIMEOUT: Duration = Duration::from_secs(2_u64);
+
     pub fn new(
-        handle: Handle,
+        handle: servo_com::Handle,
         instruction_receiver: mpsc::Receiver<Instructon>,
+        joint_limit_warnings: broadcast::Sender<JointLimitWarning>,
+        motion_failures: broadcast::Sender<Arc<Error>>,
+        kinematic_state: watch::Sender<KinematicState>,
         configuration: Configuration,
         arm: Arc<Arm>,
     ) -> Self {
         Self {
             handle,
             instruction_receiver,
+            joint_limit_warnings,
+            motion_failures,
+            kinematic_state,
             configuration,
             arm,
         }
     }
 
+    /// Run the given motion starting from the given state, pushing the poses into the pose buffer
+    ///  of the servo.
+    ///
+    /// This takes the fields of the worker separately, so the instruction receiver can still be
+    ///  polled while the motion runs.
+    ///
+    /// The states are streamed into the pose buffer while the next ones are being solved, the
+    ///  stream waits for the servo to make space whenever the pose buffer is full. Returns the last
+    ///  state that was pushed.
+    ///
+    /// Pushing the last state doesn't mean that the arm got there, so this only returns once the
+    ///  servo reported its pose buffer empty (or the motion got cancelled).
     async fn run_motion(
-        &mut self,
+        handle: &mut servo_com::Handle,
+        joint_limit_warnings: &broadcast::Sender<JointLimitWarning>,
+        configuration: &Configuration,
+        arm: &Arm,
+        start_state: KinematicState,
+        motion: Box<dyn Motion>,
+        cancellation_token: &CancellationToken,
+    ) -> Result<KinematicState, Error> {
+        // Make sure that the motion ends in time and stays within the workspace before commanding
+        //  anything.
+        configuration.check_motion_duration(motion.as_ref())?;
+        check_motion_safety(
+            motion.as_ref(),
+            &configuration.workspace_bounds,
+            configuration.delta_time,
+            configuration.max_motion_duration,
+        )?;
+
+        handle.clear_pose_buffer(cancellation_token).await?;
+
+        let (state_sender, mut state_receiver) = mpsc::channel(1_usize);
+        let states = futures_util::stream::poll_fn(|cx| state_receiver.poll_recv(cx));
+
+        let (last_state, _) = tokio::try_join!(
+            Self::produce_states(
+                joint_limit_warnings,
+                configuration,
+                arm,
+                start_state,
+                motion,
+                state_sender,
+                cancellation_token,
+            ),
+            handle.stream_kinematic_states(states, arm.kinematic_parameters(), cancellation_token),
+        )?;
+
+        // Wait for the servo to have executed all the poses.
+        handle.wait_for_buffer_empty(cancellation_token).await?;
+
+        Ok(last_state)
+    }
+
+    /// Solve the states of the given motion starting from the given state, sending every state
+    ///  (along with the time it takes to move to it) to the given sender. Returns the last state.
+    ///
+    /// The motion is sampled by a `MotionSampler`, so the states are validated before they're
+    ///  sent and the first failing sample aborts the motion.
+    ///
+    /// States that enter the band between the soft and hard joint limits are broadcast as warnings,
+    ///  but don't stop the motion.
+    ///
+    /// Every state is sent after the time step it was solved for (of wall-clock time) passed,
+    ///  which is the rate at which the servo executes them. Without an adaptive time step that's
+    ///  a fixed rate of one per `delta_time`.
+    async fn produce_states(
+        joint_limit_warnings: &broadcast::Sender<JointLimitWarning>,
+        configuration: &Configuration,
+        arm: &Arm,
+        start_state: KinematicState,
         motion: Box<dyn Motion>,
-        cancellation_token: CancellationToken,
+        state_sender: mpsc::Sender<(KinematicState, f64)>,
+        cancellation_token: &CancellationToken,
+    ) -> Result<KinematicState, Error> {
+        let mut sampler = MotionSampler::new(arm, configuration, motion.as_ref(), start_state);
+
+        // The deadline that paces the states, the first one is sent right away.
+        let mut deadline = Instant::now();
+
+        loop {
+            // The solver can't be interrupted, so a stop is checked for before every sample
+            //  instead of only while waiting for the clock.
+            Self::check_cancelled(cancellation_token)?;
+
+            let Some(sample) = sampler.next() else {
+                break;
+            };
+
+            for (kinematic_state, delta_time) in sample.states? {
+                Self::wait_for_deadline(&mut deadline, delta_time, cancellation_token).await?;
+
+                state_sender
+                    .send((kinematic_state.clone(), delta_time))
+                    .await
+                    .map_err(|_| Error::Generic("Pose stream closed".into()))?;
+
+                // Nobody might be listening for warnings, which is fine.
+                for warning in kinematic_state.soft_limit_warnings(arm.kinematic_parameters()) {
+                    let _ = joint_limit_warnings.send(warning);
+                }
+            }
+        }
+
+        Ok(sampler.kinematic_state().clone())
+    }
+
+    /// Return an error if the given cancellation token has been cancelled.
+    fn check_cancelled(cancellation_token: &CancellationToken) -> Result<(), Error> {
+        if cancellation_token.is_cancelled() {
+            return Err(com::error::Error::Cancelled.into());
+        }
+
+        Ok(())
+    }
+
+    /// Wait for the given deadline, unless cancelled, and move it on by the given duration (in
+    ///  seconds). If the deadline was missed (because solving took too long) it's moved on from
+    ///  now, so the next states are delayed rather than produced in a burst.
+    ///
+    /// The cancellation is checked first, since the deadline has usually passed already when
+    ///  solving is falling behind.
+    async fn wait_for_deadline(
+        deadline: &mut Instant,
+        duration: f64,
+        cancellation_token: &CancellationToken,
     ) -> Result<(), Error> {
-        self.handle.clear_pose_buffer(&cancellation_token).await?;
+        select! {
+            biased;
+
+            _ = cancellation_token.cancelled() => {
+                return Err(com::error::Error::Cancelled.into());
+            }
+            _ = time::sleep_until(*deadline) => {}
+        }
+
+        *deadline = (*deadline).max(Instant::now()) + Duration::from_secs_f64(duration);
+
+        Ok(())
+    }
+
+    /// Get the first joint (and its velocity) that would exceed its velocity limit when moving
+    ///  from the original state to the new state in the given time.
+    fn exceeding_joint_velocity(
+        params: &KinematicParameters,
+        original_state: &KinematicState,
+        new_state: &KinematicState,
+        delta_time: f64,
+    ) -> Option<(Joint, f64)> {
+        Joint::ALL
+            .into_iter()
+            .map(|joint| {
+                let velocity = (new_state[joint] - original_state[joint]).abs() / delta_time;
+
+                (joint, velocity)
+            })
+            .find(|(joint, velocity)| *velocity > params.joint_velocity_limits[joint.index()])
+    }
+
+    /// Sample the given motion in joint space every time step (and at the end), getting the states
+    ///  to push for it.
+    fn sample_joint_motion(
+        configuration: &Configuration,
+        motion: &dyn JointMotion,
+    ) -> Vec<(KinematicState, f64)> {
+        let duration = motion.duration();
+
+        let mut states: Vec<(KinematicState, f64)> = Vec::new();
+        let mut t = 0_f64;
+
+        // The last sample is taken at the end, even if the time steps don't evenly divide it.
+        //  That sample is commanded with the part of the time step that's left.
+        while let Some(state) = motion.interpolate(t.min(duration)) {
+            states.push((state, configuration.delta_time - (t - duration).max(0_f64)));
+
+            if t >= duration {
+                break;
+            }
+
+            t += configuration.delta_time;
+        }
+
+        states
+    }
+
+    /// Get the pose the end-effector is in for the given state, the way the analytical algorithm
+    ///  solves it.
+    fn pose_of(params: &KinematicParameters, state: &KinematicState) -> Pose {
+        Pose {
+            position: AnalyticalFKAlgorithm::default().limb4_position_vector(params, state),
+            approach: state.theta_1 + state.theta_2 + state.theta_3,
+            roll: state.theta_4,
+        }
+    }
 
-        let mut available = self.handle.get_buffer_capacity(&cancellation_token).await?;
+    /// Sample the given pose motion every time step (and at the end), solving the state for every
+    ///  sample with the analytical algorithm. The base keeps facing the way it faced in the
+    ///  previous state, and the elbow stays in the configuration of the start state, so the arm
+    ///  doesn't flip over halfway.
+    ///
+    /// Every state is validated and checked against the joint velocity limits, the error of the
+    ///  first one that fails is returned.
+    fn sample_pose_motion(
+        configuration: &Configuration,
+        params: &KinematicParameters,
+        motion: &dyn PoseMotion,
+        start_state: &KinematicState,
+    ) -> Result<Vec<(KinematicState, f64)>, Error> {
+        let algorithm = AnalyticalIKAlgorithm::default();
+        let elbow = ElbowConfiguration::of(start_state);
+        let duration = motion.duration();
 
+        let mut states: Vec<(KinematicState, f64)> = Vec::new();
+        let mut previous_state = start_state.clone();
         let mut t = 0_f64;
 
-        let mut new_kinematic_state = self.arm.kinematic_state().clone();
-        let kinematic_params = self.arm.kinematic_parameters();
-
-        while let Some(target_position) = motion.interpolate(t) {
-            new_kinematic_state = match self.arm.kinematic_solver().translate_limb4_end_effector(
-                kinematic_params,
-                &new_kinematic_state,
-                &target_position,
-            )? {
-                IKSolverResult::Reached { new_state, .. } => new_state,
-                IKSolverResult::Unreachable => {
-                    return Err(Error::Generic("Could not reach target".into()))
+        // The last sample is taken at the end, even if the time steps don't evenly divide it.
+        //  That sample is commanded with the part of the time step that's left.
+        while let Some(pose) = motion.interpolate(t.min(duration)) {
+            let delta_time = configuration.delta_time - (t - duration).max(0_f64);
+
+            let state = algorithm.solve(
+                params,
+                &pose.position,
+                pose.approach,
+                pose.roll,
+                elbow,
+                previous_state.theta_0,
+            )?;
+            state.validate(params)?;
+
+            if let Some((joint, velocity)) =
+                Self::exceeding_joint_velocity(params, &previous_state, &state, delta_time)
+            {
+                return Err(Error::JointVelocityExceeded {
+                    joint: joint.index(),
+                    velocity,
+                });
+            }
+
+            states.push((state.clone(), delta_time));
+            previous_state = state;
+
+            if t >= duration {
+                break;
+            }
+
+            t += configuration.delta_time;
+        }
+
+        Ok(states)
+    }
+
+    /// Run the given pose motion starting from the given state, and return the state the arm ends
+    ///  up in. The whole motion is solved before anything is pushed, so a pose motion that can't
+    ///  be run doesn't move the arm at all.
+    async fn run_pose_motion(
+        handle: &mut servo_com::Handle,
+        joint_limit_warnings: &broadcast::Sender<JointLimitWarning>,
+        configuration: &Configuration,
+        arm: &Arm,
+        start_state: KinematicState,
+        motion: Box<dyn PoseMotion>,
+        cancellation_token: &CancellationToken,
+    ) -> Result<KinematicState, Error> {
+        let states = Self::sample_pose_motion(
+            configuration,
+            arm.kinematic_parameters(),
+            motion.as_ref(),
+            &start_state,
+        )?;
+
+        // Nobody might be listening for warnings, which is fine.
+        for (state, _) in states.iter() {
+            for warning in state.soft_limit_warnings(arm.kinematic_parameters()) {
+                let _ = joint_limit_warnings.send(warning);
+            }
+        }
+
+        let last_state = states
+            .last()
+            .map(|(state, _)| state.clone())
+            .unwrap_or(start_state);
+
+        Self::run_states(handle, arm, states, cancellation_token).await?;
+
+        Ok(last_state)
+    }
+
+    /// Push the given states into the pose buffer and wait until the servo emptied it, the states
+    ///  are validated before they're pushed.
+    async fn run_states(
+        handle: &mut servo_com::Handle,
+        arm: &Arm,
+        states: Vec<(KinematicState, f64)>,
+        cancellation_token: &CancellationToken,
+    ) -> Result<(), Error> {
+        handle
+            .stream_kinematic_states(
+                futures_util::stream::iter(states),
+                arm.kinematic_parameters(),
+                cancellation_token,
+            )
+            .await?;
+        handle.wait_for_buffer_empty(cancellation_token).await
+    }
+
+    /// Wait for the servo to report the given joint within tolerance of the commanded angle, or
+    ///  for the self-test timeout. Returns the last angle reported for the joint (if any).
+    async fn wait_for_reported_angle(
+        pose_changed: &mut broadcast::Receiver<PoseChangedEvent>,
+        joint: Joint,
+        commanded: f64,
+        cancellation_token: &CancellationToken,
+    ) -> Result<Option<f64>, Error> {
+        let timeout = time::sleep(Self::SELF_TEST_TIMEOUT);
+        tokio::pin!(timeout);
+
+        let mut reported: Option<f64> = None;
+
+        loop {
+            select! {
+                x = pose_changed.recv() => match x {
+                    Ok(PoseChangedEvent { angles }) => {
+                        let angle = angles[joint.index()];
+                        reported = Some(angle);
+
+                        if (angle - commanded).abs() <= Self::SELF_TEST_TOLERANCE {
+                            return Ok(reported);
+                        }
+                    }
+                    // Only the most recent pose matters, so missing older ones is fine.
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => return Ok(reported),
+                },
+                _ = &mut timeout => return Ok(reported),
+                _ = cancellation_token.cancelled() => {
+                    return Err(com::error::Error::Cancelled.into());
                 }
-            };
+            }
+        }
+    }
 
-            available -= 1;
+    /// Move every joint in turn a small amount away from the given state and back, checking
+    ///  that the servo reports the commanded angle. Meant for commissioning a new arm, a joint
+    ///  that's not connected (or wired to the wrong servo) fails instead of the whole test.
+    ///
+    /// Every joint is moved towards the side that has room within its joint limits, a joint that
+    ///  can't be moved at all (or fails to) gets its error in the report, and the self-test goes
+    ///  on with the next joint. Only a stop ends the self-test early.
+    async fn run_self_test(
+        handle: &mut servo_com::Handle,
+        configuration: &Configuration,
+        arm: &Arm,
+        original_state: KinematicState,
+        cancellation_token: &CancellationToken,
+    ) -> Result<SelfTestReport, Error> {
+        let mut report = SelfTestReport::default();
 
-            t += self.configuration.delta_time;
+        handle.clear_pose_buffer(cancellation_token).await?;
+
+        for joint in Joint::ALL {
+            let (commanded, result) =
+                match Self::self_test_angle(arm.kinematic_parameters(), &original_state, joint) {
+                    Some(commanded) => (
+                        commanded,
+                        Self::self_test_joint(
+                            handle,
+                            configuration,
+                            arm,
+                            &original_state,
+                            joint,
+                            commanded,
+                            cancellation_token,
+                        )
+                        .await,
+                    ),
+                    None => (
+                        original_state[joint],
+                        Err(Error::Generic(
+                            "Joint has no room to move within its limits".into(),
+                        )),
+                    ),
+                };
+
+            report.joints.push(match result {
+                Ok(reported) => JointSelfTest {
+                    joint,
+                    commanded,
+                    reported,
+                    passed: reported
+                        .is_some_and(|x| (x - commanded).abs() <= Self::SELF_TEST_TOLERANCE),
+                    error: None,
+                },
+                Err(_) if cancellation_token.is_cancelled() => {
+                    return Err(com::error::Error::Cancelled.into());
+                }
+                Err(error) => JointSelfTest {
+                    joint,
+                    commanded,
+                    reported: None,
+                    passed: false,
+                    error: Some(error.to_string()),
+                },
+            });
         }
 
-        Ok(())
+        Ok(report)
     }
 
-    pub(crate) async fn run(&mut self, cancellation_token: CancellationToken) -> Result<(), Error> {
-        todo!()
+    /// Get the angle to move the given joint to during the self-test, which is away from the
+    ///  given state towards the side that has room within its joint limits. `None` if neither
+    ///  side has room.
+    fn self_test_angle(
+        params: &KinematicParameters,
+        state: &KinematicState,
+        joint: Joint,
+    ) -> Option<f64> {
+        let [min, max] = params.joint_limits[joint.index()];
+
+        [
+            state[joint] + Self::SELF_TEST_AMPLITUDE,
+            state[joint] - Self::SELF_TEST_AMPLITUDE,
+        ]
+        .into_iter()
+        .find(|x| (min..=max).contains(x))
     }
-}
 
-pub(crate) struct Handle {
-    instruction_sender: mpsc::Sender<Instructon>,
-}
+    /// Move the given joint from the given state to the commanded angle and back, returning the
+    ///  last angle the servo reported for it while at the commanded angle (if any).
+    async fn self_test_joint(
+        handle: &mut servo_com::Handle,
+        configuration: &Configuration,
+        arm: &Arm,
+        original_state: &KinematicState,
+        joint: Joint,
+        commanded: f64,
+        cancellation_token: &CancellationToken,
+    ) -> Result<Option<f64>, Error> {
+        let mut perturbed_state = original_state.clone();
+        perturbed_state[joint] = commanded;
 
-impl Handle {
-    pub fn new(instruction_sender: mpsc::Sender<Instructon>) -> Self {
-        Self { instruction_sender }
+        // Subscribe before moving, so the reports during the move aren't missed.
+        let mut pose_changed = handle.broadcasts().pose_changed().subscribe();
+
+        let motion =
+            LinearJointMotion::new(original_state, &perturbed_state, Self::SELF_TEST_DURATION)?;
+        let states = Self::sample_joint_motion(configuration, &motion);
+        Self::run_states(handle, arm, states, cancellation_token).await?;
+
+        let reported =
+            Self::wait_for_reported_angle(&mut pose_changed, joint, commanded, cancellation_token)
+                .await?;
+
+        let motion =
+            LinearJointMotion::new(&perturbed_state, original_state, Self::SELF_TEST_DURATION)?;
+        let states = Self::sample_joint_motion(configuration, &motion);
+        Self::run_states(handle, arm, states, cancellation_token).await?;
+
+        Ok(reported)
+    }
+
+    /// Stop the motion by clearing the pose buffer of the servo, so that the poses which have
+    ///  already been pushed won't be executed.
+    ///
+    /// The local interpolation has already been stopped by the time this is called, so clearing
+    ///  the buffer is all that's left, any error doing so is returned to the caller.
+    async fn stop_motion(
+        handle: &mut servo_com::Handle,
+        cancellation_token: &CancellationToken,
+    ) -> Result<(), Error> {
+        handle.clear_pose_buffer(cancellation_token).await
+    }
+
+    /// Report a failure to the subscribers, the worker keeps running so the next instruction
+    ///  still gets handled.
+    fn report_failure(&self, error: Error) {
+        // Nobody might be listening, which is fine.
+        let _ = self.motion_failures.send(Arc::new(error));
+    }
+
+    /// Stop the servo, reporting the failure to do so instead of returning it.
+    async fn stop_or_report(&mut self, cancellation_token: &CancellationToken) {
+        if let Err(error) = Self::stop_motion(&mut self.handle, cancellation_token).await {
+            self.report_failure(error);
+        }
+    }
+
+    /// Run the worker until it's cancelled or all the handles are dropped.
+    ///
+    /// A motion that fails doesn't stop the worker, the failure is reported to the subscribers of
+    ///  the motion failures and the servo is told to stop instead.
+    pub(crate) async fn run(&mut self, cancellation_token: CancellationToken) -> Result<(), Error> {
+        // The instruction that interrupted the previous motion (if any).
+        let mut next_instruction: Option<Instructon> = None;
+
+        loop {
+            // Take the interrupting instruction, or wait for the next one.
+            let instruction = match next_instruction.take() {
+                Some(instruction) => instruction,
+                None => select! {
+                    _ = cancellation_token.cancelled() => return Ok(()),
+                    instruction = self.instruction_receiver.recv() => match instruction {
+                        Some(instruction) => instruction,
+                        None => return Ok(()),
+                    },
+                },
+            };
+
+            let start_state = self.kinematic_state.borrow().clone();
+
+            let run: Pin<Box<dyn Future<Output = Result<KinematicState, Error>> + Send + '_>> =
+                match instruction {
+                    Instructon::Start(motion) => Box::pin(Self::run_motion(
+                        &mut self.handle,
+                        &self.joint_limit_warnings,
+                        &self.configuration,
+                        &self.arm,
+                        start_state,
+                        motion,
+                        &cancellation_token,
+                    )),
+                    Instructon::MoveToPose { target, speed } => {
+                        let motion = match LinearPoseMotion::new_with_max_speed(
+                            Self::pose_of(self.arm.kinematic_parameters(), &start_state),
+                            target,
+                            speed,
+                            self.arm.max_speed(),
+                        ) {
+                            Ok(motion) => motion,
+                            Err(error) => {
+                                // Nothing has been pushed, so there's nothing to stop.
+                                self.report_failure(error.into());
+                                continue;
+                            }
+                        };
+
+                        Box::pin(Self::run_pose_motion(
+                            &mut self.handle,
+                            &self.joint_limit_warnings,
+                            &self.configuration,
+                            &self.arm,
+                            start_state,
+                            Box::new(motion),
+                            &cancellation_token,
+                        ))
+                    }
+                    Instructon::Stop => {
+                        self.stop_or_report(&cancellation_token).await;
+                        continue;
+                    }
+                    Instructon::SelfTest(sender) => {
+                        let result = Self::run_self_test(
+                            &mut self.handle,
+                            &self.configuration,
+                            &self.arm,
+                            start_state,
+                            &cancellation_token,
+                        )
+                        .await;
+
+                        // The caller might have stopped waiting for the report, which is fine.
+                        let _ = sender.send(result);
+                        continue;
+                    }
+                };
+
+            // Run the motion while listening for instructions, any instruction received while
+            //  running interrupts the motion (dropping its future).
+            let interruption = select! {
+                result = run => match result {
+                    Ok(state) => {
+                        // The next motion starts where this one ended.
+                        self.kinematic_state.send_replace(state);
+                        continue;
+                    }
+                    Err(error) => Err(error),
+                },
+                instruction = self.instruction_receiver.recv() => Ok(instruction),
+            };
+
+            match interruption {
+                Ok(instruction) => {
+                    // The motion got interrupted, so make sure the servo stops as well.
+                    self.stop_or_report(&cancellation_token).await;
+
+                    match instruction {
+                        Some(Instructon::Stop) => {}
+                        Some(instruction) => next_instruction = Some(instruction),
+                        None => return Ok(()),
+                    }
+                }
+                Err(_) if cancellation_token.is_cancelled() => return Ok(()),
+                Err(error) => {
+                    // The motion failed halfway, so make sure the servo stops as well.
+                    self.report_failure(error);
+                    self.stop_or_report(&cancellation_token).await;
+                }
+            }
+        }
+    }
+}
+
+pub(crate) struct Handle {
+    instruction_sender: mpsc::Sender<Instructon>,
+    joint_limit_warnings: broadcast::Sender<JointLimitWarning>,
+    motion_failures: broadcast::Sender<Arc<Error>>,
+    kinematic_state: watch::Sender<KinematicState>,
+}
+
+impl Handle {
+    pub fn new(
+        instruction_sender: mpsc::Sender<Instructon>,
+        joint_limit_warnings: broadcast::Sender<JointLimitWarning>,
+        motion_failures: broadcast::Sender<Arc<Error>>,
+        kinematic_state: watch::Sender<KinematicState>,
+    ) -> Self {
+        Self {
+            instruction_sender,
+            joint_limit_warnings,
+            motion_failures,
+            kinematic_state,
+        }
+    }
+
+    /// The state the arm ended up in after the last motion, which the next one starts from.
+    pub fn kinematic_state(&self) -> &watch::Sender<KinematicState> {
+        &self.kinematic_state
+    }
+
+    /// Subscribe to the warnings about joints entering the band between their soft and hard
+    ///  limits during a motion.
+    pub fn subscribe_joint_limit_warnings(&self) -> broadcast::Receiver<JointLimitWarning> {
+        self.joint_limit_warnings.subscribe()
+    }
+
+    /// Subscribe to the errors of the motions that failed, the worker keeps running after those.
+    pub fn subscribe_motion_failures(&self) -> broadcast::Receiver<Arc<Error>> {
+        self.motion_failures.subscribe()
+    }
+
+    /// Start the given motion, interrupting the current one (if any).
+    pub async fn start_motion(&self, motion: Box<dyn Motion>) -> Result<(), Error> {
+        self.send_instruction(Instructon::Start(motion)).await
+    }
+
+    /// Move the end-effector in a straight line to the given pose at the given speed (in
+    ///  meters/second), interrupting the current motion (if any).
+    pub async fn move_to_pose(&self, target: Pose, speed: f64) -> Result<(), Error> {
+        self.send_instruction(Instructon::MoveToPose { target, speed })
+            .await
+    }
+
+    /// Stop the current motion, this stops the interpolation and clears the pose buffer of the
+    ///  servo so that the arm halts promptly.
+    pub async fn stop_motion(&self) -> Result<(), Error> {
+        self.send_instruction(Instructon::Stop).await
+    }
+
+    /// Run the self-test, which moves every joint a small amount and back, and wait for the
+    ///  report. The current motion (if any) is interrupted.
+    pub async fn run_self_test(&self) -> Result<SelfTestReport, Error> {
+        let (sender, receiver) = oneshot::channel();
+
+        self.send_instruction(Instructon::SelfTest(sender)).await?;
+
+        receiver
+            .await
+            .map_err(|_| Error::Generic("Player worker is not running".into()))?
+    }
+
+    async fn send_instruction(&self, instruction: Instructon) -> Result<(), Error> {
+        self.instruction_sender
+            .send(instruction)
+            .await
+            .map_err(|_| Error::Generic("Player worker is not running".into()))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use com::client::{self, Command};
+    use kinematics::{
+        error::KinematicError,
+        forward::algorithms::{analytical::AnalyticalFKAlgorithm, ForwardKinematicAlgorithm},
+        inverse::{
+            algorithms::{heuristic::HeuristicIKAlgorithm, InverseKinematicAlgorithm},
+            solvers::{
+                heuristic::HeuristicSolver, IKSolverResult, KinematicSolver, SolverCapabilities,
+            },
+        },
+        model::{Joint, KinematicParameters, KinematicState},
+    };
+    use nalgebra::Vector3;
+    use tokio::{
+        sync::{broadcast, mpsc},
+        task::JoinHandle,
+        time::{self, Duration, Instant},
+    };
+    use tokio_util::sync::CancellationToken;
+
+    use crate::arm::motion::blended::BlendedSequenceMotion;
+    use crate::arm::motion::linear::LinearMotion;
+    use crate::arm::motion::player::{
+        validate_motion, AdaptiveTimeStep, Configuration, MotionSampler, Player, SelfTestReport,
+        Worker,
+    };
+    use crate::arm::motion::{Motion, Pose};
+    use crate::arm::Arm;
+    use crate::error::Error;
+    use crate::servo_com::{
+        self,
+        calibration::ServoCalibration,
+        commands::{
+            ClearPoseBufferCommand, GetPoseBufferAvailableSpaceCommand,
+            GetPoseBufferCapacityCommand, PushIntoPoseBufferCommand,
+        },
+        events::{PoseBufferDrainEvent, PoseBufferEmptyEvent, PoseChangedEvent},
+        mock::MockServo,
+        replies::{
+            ClearPoseBufferReply, GetPoseBufferAvailableSpaceReply, GetPoseBufferCapacityReply,
+            PushIntoPoseBufferReply,
+        },
+        ServoCom,
+    };
+
+    /// Solver that only turns the base towards the target, which makes the base spin around
+    ///  quickly when the target passes close to the base axis (like the real arm does). Targets
+    ///  further away than the arm is long are unreachable.
+    struct BaseSolver {
+        inverse_algorithm: Arc<dyn InverseKinematicAlgorithm>,
+        forward_algorithm: Arc<dyn ForwardKinematicAlgorithm>,
+    }
+
+    impl KinematicSolver for BaseSolver {
+        fn translate_limb4_end_effector(
+            &self,
+            params: &KinematicParameters,
+            state: &KinematicState,
+            target_position: &Vector3<f64>,
+        ) -> Result<IKSolverResult, KinematicError> {
+            if target_position.magnitude() > params.sum_of_link_lengths() {
+                return Ok(IKSolverResult::Unreachable);
+            }
+
+            let mut new_state = state.clone();
+            new_state.set_base(target_position.z.atan2(target_position.x));
+
+            Ok(IKSolverResult::Reached {
+                iterations: 0_usize,
+                delta_position_magnitude: 0_f64,
+                new_state,
+            })
+        }
+
+        fn rotate_limb4_end_effector(
+            &self,
+            _params: &KinematicParameters,
+            _state: &KinematicState,
+            _target_position: &Vector3<f64>,
+        ) -> Result<IKSolverResult, KinematicError> {
+            Ok(IKSolverResult::Unreachable)
+        }
+
+        fn inverse_algorithm(&self) -> &Arc<dyn InverseKinematicAlgorithm> {
+            &self.inverse_algorithm
+        }
+
+        fn forward_algorithm(&self) -> &Arc<dyn ForwardKinematicAlgorithm> {
+            &self.forward_algorithm
+        }
+
+        fn capabilities(&self) -> SolverCapabilities {
+            SolverCapabilities {
+                is_exact: true,
+                ..SolverCapabilities::default()
+            }
+        }
+    }
+
+    /// Solver that takes the given time for every solve, like one that converges slowly.
+    struct SlowSolver {
+        inner: BaseSolver,
+        delay: std::time::Duration,
+    }
+
+    impl KinematicSolver for SlowSolver {
+        fn translate_limb4_end_effector(
+            &self,
+            params: &KinematicParameters,
+            state: &KinematicState,
+            target_position: &Vector3<f64>,
+        ) -> Result<IKSolverResult, KinematicError> {
+            std::thread::sleep(self.delay);
+
+            self.inner
+                .translate_limb4_end_effector(params, state, target_position)
+        }
+
+        fn rotate_limb4_end_effector(
+            &self,
+            params: &KinematicParameters,
+            state: &KinematicState,
+            target_position: &Vector3<f64>,
+        ) -> Result<IKSolverResult, KinematicError> {
+            self.inner
+                .rotate_limb4_end_effector(params, state, target_position)
+        }
+
+        fn inverse_algorithm(&self) -> &Arc<dyn InverseKinematicAlgorithm> {
+            self.inner.inverse_algorithm()
+        }
+
+        fn forward_algorithm(&self) -> &Arc<dyn ForwardKinematicAlgorithm> {
+            self.inner.forward_algorithm()
+        }
+
+        fn capabilities(&self) -> SolverCapabilities {
+            self.inner.capabilities()
+        }
+    }
+
+    /// Solver that turns the base towards the target the way that's closest to the given state.
+    ///  When the target is behind the base it reaches over the top instead, with the shoulder
+    ///  bent far backward.
+    struct OverTheTopSolver {
+        inner: BaseSolver,
+    }
+
+    impl OverTheTopSolver {
+        const OVER_THE_TOP_SHOULDER: f64 = 3_f64;
+    }
+
+    impl KinematicSolver for OverTheTopSolver {
+        fn translate_limb4_end_effector(
+            &self,
+            params: &KinematicParameters,
+            state: &KinematicState,
+            target_position: &Vector3<f64>,
+        ) -> Result<IKSolverResult, KinematicError> {
+            if target_position.magnitude() > params.sum_of_link_lengths() {
+                return Ok(IKSolverResult::Unreachable);
+            }
+
+            let facing = target_position.z.atan2(target_position.x);
+            let behind = if facing > 0_f64 {
+                facing - std::f64::consts::PI
+            } else {
+                facing + std::f64::consts::PI
+            };
+
+            let mut new_state = state.clone();
+            if (facing - state.base()).abs() <= (behind - state.base()).abs() {
+                new_state.set_base(facing);
+                new_state.set_shoulder(0_f64);
+            } else {
+                new_state.set_base(behind);
+                new_state.set_shoulder(Self::OVER_THE_TOP_SHOULDER);
+            }
+
+            Ok(IKSolverResult::Reached {
+                iterations: 0_usize,
+                delta_position_magnitude: 0_f64,
+                new_state,
+            })
+        }
+
+        fn rotate_limb4_end_effector(
+            &self,
+            params: &KinematicParameters,
+            state: &KinematicState,
+            target_position: &Vector3<f64>,
+        ) -> Result<IKSolverResult, KinematicError> {
+            self.inner
+                .rotate_limb4_end_effector(params, state, target_position)
+        }
+
+        fn inverse_algorithm(&self) -> &Arc<dyn InverseKinematicAlgorithm> {
+            self.inner.inverse_algorithm()
+        }
+
+        fn forward_algorithm(&self) -> &Arc<dyn ForwardKinematicAlgorithm> {
+            self.inner.forward_algorithm()
+        }
+
+        fn capabilities(&self) -> SolverCapabilities {
+            self.inner.capabilities()
+        }
+    }
+
+    /// Solver that adds up the iterations of every solve that reached its target.
+    struct CountingSolver {
+        inner: HeuristicSolver,
+        iterations: AtomicUsize,
+    }
+
+    impl KinematicSolver for CountingSolver {
+        fn translate_limb4_end_effector(
+            &self,
+            params: &KinematicParameters,
+            state: &KinematicState,
+            target_position: &Vector3<f64>,
+        ) -> Result<IKSolverResult, KinematicError> {
+            let result = self
+                .inner
+                .translate_limb4_end_effector(params, state, target_position)?;
+
+            if let IKSolverResult::Reached { iterations, .. } = result {
+                self.iterations.fetch_add(iterations, Ordering::SeqCst);
+            }
+
+            Ok(result)
+        }
+
+        fn rotate_limb4_end_effector(
+            &self,
+            params: &KinematicParameters,
+            state: &KinematicState,
+            target_position: &Vector3<f64>,
+        ) -> Result<IKSolverResult, KinematicError> {
+            self.inner
+                .rotate_limb4_end_effector(params, state, target_position)
+        }
+
+        fn inverse_algorithm(&self) -> &Arc<dyn InverseKinematicAlgorithm> {
+            self.inner.inverse_algorithm()
+        }
+
+        fn forward_algorithm(&self) -> &Arc<dyn ForwardKinematicAlgorithm> {
+            self.inner.forward_algorithm()
+        }
+
+        fn capabilities(&self) -> SolverCapabilities {
+            self.inner.capabilities()
+        }
+    }
+
+    /// Solver that finds two solutions for every target, equally far away from the given state.
+    ///  One leans the wrist and the other the shoulder, which lowers the arm a lot more.
+    struct TwoSolutionSolver {
+        inner: BaseSolver,
+    }
+
+    impl KinematicSolver for TwoSolutionSolver {
+        fn translate_limb4_end_effector(
+            &self,
+            params: &KinematicParameters,
+            state: &KinematicState,
+            target_position: &Vector3<f64>,
+        ) -> Result<IKSolverResult, KinematicError> {
+            self.inner
+                .translate_limb4_end_effector(params, state, target_position)
+        }
+
+        fn translate_limb4_end_effector_solutions(
+            &self,
+            _params: &KinematicParameters,
+            state: &KinematicState,
+            _target_position: &Vector3<f64>,
+        ) -> Result<Vec<KinematicState>, KinematicError> {
+            let mut wrist = state.clone();
+            wrist.set_wrist_pitch(state.wrist_pitch() + 0.5_f64);
+
+            let mut shoulder = state.clone();
+            shoulder.set_shoulder(state.shoulder() + 0.5_f64);
+
+            Ok(vec![wrist, shoulder])
+        }
+
+        fn rotate_limb4_end_effector(
+            &self,
+            params: &KinematicParameters,
+            state: &KinematicState,
+            target_position: &Vector3<f64>,
+        ) -> Result<IKSolverResult, KinematicError> {
+            self.inner
+                .rotate_limb4_end_effector(params, state, target_position)
+        }
+
+        fn inverse_algorithm(&self) -> &Arc<dyn InverseKinematicAlgorithm> {
+            self.inner.inverse_algorithm()
+        }
+
+        fn forward_algorithm(&self) -> &Arc<dyn ForwardKinematicAlgorithm> {
+            self.inner.forward_algorithm()
+        }
+
+        fn capabilities(&self) -> SolverCapabilities {
+            SolverCapabilities {
+                supports_multiple_solutions: true,
+                ..self.inner.capabilities()
+            }
+        }
+    }
+
+    /// Run the servo communication worker on top of the given client, returning its handle once
+    ///  the worker handles the events of the servo (so none of them gets lost).
+    async fn start_servo_com(
+        servo: &mut MockServo,
+        client_handle: client::Handle,
+        cancellation_token: &CancellationToken,
+    ) -> servo_com::Handle {
+        start_calibrated_servo_com(
+            servo,
+            client_handle,
+            ServoCalibration::default(),
+            cancellation_token,
+        )
+        .await
+    }
+
+    /// Run the servo communication worker with the given calibration like `start_servo_com` does.
+    async fn start_calibrated_servo_com(
+        servo: &mut MockServo,
+        client_handle: client::Handle,
+        calibration: ServoCalibration,
+        cancellation_token: &CancellationToken,
+    ) -> servo_com::Handle {
+        let (mut servo_com_worker, servo_com_handle) =
+            ServoCom::with_calibration(client_handle, calibration);
+        tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move { servo_com_worker.run(cancellation_token).await }
+        });
+
+        // Keep firing the empty event until the worker is subscribed to it.
+        let mut empty = servo_com_handle.notifiers().empty().subscribe();
+        time::timeout(Duration::from_secs(5_u64), async {
+            loop {
+                servo
+                    .write_event(PoseBufferEmptyEvent::CODE, &PoseBufferEmptyEvent {})
+                    .await;
+
+                if time::timeout(Duration::from_millis(10_u64), empty.wait_for(|x| *x))
+                    .await
+                    .is_ok()
+                {
+                    break;
+                }
+            }
+        })
+        .await
+        .expect("Worker did not subscribe");
+
+        servo_com_handle
+    }
+
+    /// Answer the commands the player sends while running a motion, like a servo that executes
+    ///  every pose as soon as it's pushed (so it reports its pose buffer empty right after). The
+    ///  pushed poses are sent to the returned receiver, in order.
+    fn answer_motion(
+        mut servo: MockServo,
+    ) -> (JoinHandle<()>, mpsc::UnboundedReceiver<([f64; 5], f64)>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        let servo_task = tokio::spawn(async move {
+            loop {
+                let (code, tag, value) = servo.read_command().await;
+
+                if code == ClearPoseBufferCommand::CODE {
+                    servo.write_reply(tag, &ClearPoseBufferReply {}).await;
+                } else if code == GetPoseBufferCapacityCommand::CODE {
+                    let reply = GetPoseBufferCapacityReply {
+                        capacity: 1024_usize,
+                    };
+                    servo.write_reply(tag, &reply).await;
+                } else if code == GetPoseBufferAvailableSpaceCommand::CODE {
+                    let reply = GetPoseBufferAvailableSpaceReply {
+                        available: 1024_usize,
+                    };
+                    servo.write_reply(tag, &reply).await;
+                } else if code == PushIntoPoseBufferCommand::CODE {
+                    let _ = sender.send(rmp_serde::from_slice(&value).unwrap());
+
+                    let reply = PushIntoPoseBufferReply {
+                        accepted: true,
+                        index: 0_usize,
+                        remaining_capacity: 1024_usize,
+                    };
+                    servo.write_reply(tag, &reply).await;
+                    servo
+                        .write_event(PoseBufferEmptyEvent::CODE, &PoseBufferEmptyEvent {})
+                        .await;
+                } else {
+                    panic!("Unexpected command {:?}", code);
+                }
+            }
+        });
+
+        (servo_task, receiver)
+    }
+
+    /// Take all the poses that have been pushed so far.
+    fn pushed_poses(
+        receiver: &mut mpsc::UnboundedReceiver<([f64; 5], f64)>,
+    ) -> Vec<([f64; 5], f64)> {
+        std::iter::from_fn(|| receiver.try_recv().ok()).collect()
+    }
+
+    #[test]
+    pub fn time_step_subdivided_near_singularity() {
+        let arm = Arm::new(
+            KinematicParameters {
+                joint_velocity_limits: [4_f64; 5_usize],
+                ..KinematicParameters::default()
+            },
+            KinematicState::default(),
+            Arc::new(OverTheTopSolver {
+                inner: BaseSolver {
+                    inverse_algorithm: Arc::new(HeuristicIKAlgorithm::default()),
+                    forward_algorithm: Arc::new(AnalyticalFKAlgorithm::default()),
+                },
+            }),
+        );
+        let configuration = Configuration::new(0.5_f64);
+
+        // Move past the base axis at a distance of ten centimeters.
+        let motion = LinearMotion::new(
+            Vector3::<f64>::new(1_f64, 0_f64, 0.1_f64),
+            Vector3::<f64>::new(-1_f64, 0_f64, 0.1_f64),
+            0.5_f64,
+        )
+        .unwrap();
+
+        let solve = |t_0: f64, t_1: f64| {
+            let state = KinematicState {
+                theta_0: 0.1_f64.atan2(motion.interpolate(t_0).unwrap().x),
+                ..KinematicState::default()
+            };
+
+            let states = MotionSampler::new(&arm, &configuration, &motion, state.clone())
+                .solve_time_step(&state, t_0, t_1, 0_usize)
+                .unwrap();
+
+            (state, states)
+        };
+
+        // Far away from the axis, the time step can be used as-is.
+        let (_, states) = solve(0_f64, 0.5_f64);
+        assert_eq!(states.len(), 1_usize);
+        assert_eq!(states[0_usize].1, 0.5_f64);
+
+        // Over the axis the target ends up behind the base, so the solver would reach over the
+        //  top. Halving the time step lets the base turn along with the target instead.
+        let (state, states) = solve(1.75_f64, 2.25_f64);
+        assert_eq!(states.len(), 2_usize);
+
+        let limits = arm.kinematic_parameters().joint_velocity_limits;
+        for (a, (b, delta_time)) in std::iter::once(&state)
+            .chain(states.iter().map(|(state, _)| state))
+            .zip(states.iter())
+        {
+            // Every state is checked against (and commanded with) its half of the time step.
+            assert_eq!(*delta_time, 0.25_f64);
+            assert_eq!(b.shoulder(), 0_f64);
+
+            for joint in Joint::ALL {
+                assert!((b[joint] - a[joint]).abs() / delta_time <= limits[joint.index()]);
+            }
+        }
+    }
+
+    #[test]
+    pub fn joint_velocity_exceeded_near_axis() {
+        let arm = Arm::new(
+            KinematicParameters::default(),
+            KinematicState::default(),
+            Arc::new(BaseSolver {
+                inverse_algorithm: Arc::new(HeuristicIKAlgorithm::default()),
+                forward_algorithm: Arc::new(AnalyticalFKAlgorithm::default()),
+            }),
+        );
+        let configuration = Configuration::new(0.05_f64);
+
+        // Move past the base axis at a distance of one centimeter, which turns the base faster
+        //  than it can, however much the time step is subdivided.
+        let motion = LinearMotion::new(
+            Vector3::<f64>::new(1_f64, 0_f64, 0.01_f64),
+            Vector3::<f64>::new(-1_f64, 0_f64, 0.01_f64),
+            1_f64,
+        )
+        .unwrap();
+
+        let state = KinematicState {
+            theta_0: 0.01_f64.atan2(motion.interpolate(0.95_f64).unwrap().x),
+            ..KinematicState::default()
+        };
+
+        let result = MotionSampler::new(&arm, &configuration, &motion, state.clone())
+            .solve_time_step(&state, 0.95_f64, 1_f64, 0_usize);
+
+        assert!(matches!(
+            result,
+            Err(Error::JointVelocityExceeded { joint, .. }) if joint == Joint::Base.index()
+        ));
+    }
+
+    #[tokio::test]
+    pub async fn stop_clears_pose_buffer() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let cancellation_token = CancellationToken::new();
+
+        let (_servo_com_worker, servo_com_handle) = ServoCom::from_client(client_handle);
+
+        let arm = Arc::new(Arm::new(
+            KinematicParameters::default(),
+            KinematicState::default(),
+            Arc::new(
+                HeuristicSolver::builder(
+                    Arc::new(HeuristicIKAlgorithm::default()),
+                    Arc::new(AnalyticalFKAlgorithm::default()),
+                )
+                .build(),
+            ),
+        ));
+
+        // Create and run the player worker.
+        let (mut worker, handle) = Player::new(servo_com_handle, Configuration::new(0.01_f64), arm);
+        tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move { worker.run(cancellation_token).await }
+        });
+
+        handle.stop_motion().await.unwrap();
+
+        // The servo should be told to clear its pose buffer.
+        let (code, tag, _) = time::timeout(Duration::from_secs(5_u64), servo.read_command())
+            .await
+            .expect("No command was sent");
+        assert_eq!(code, ClearPoseBufferCommand::new().code());
+
+        servo.write_reply(tag, &ClearPoseBufferReply {}).await;
+
+        cancellation_token.cancel();
+    }
+
+    #[tokio::test]
+    pub async fn soft_limit_warning_does_not_stop_motion() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let cancellation_token = CancellationToken::new();
+
+        let mut servo_com_handle =
+            start_servo_com(&mut servo, client_handle, &cancellation_token).await;
+
+        let arm = Arm::new(
+            KinematicParameters {
+                joint_limits: [[-std::f64::consts::PI, std::f64::consts::PI]; 5],
+                soft_joint_limits: Some([[-2.5_f64, 2.5_f64]; 5]),
+                ..KinematicParameters::default()
+            },
+            KinematicState::default(),
+            Arc::new(BaseSolver {
+                inverse_algorithm: Arc::new(HeuristicIKAlgorithm::default()),
+                forward_algorithm: Arc::new(AnalyticalFKAlgorithm::default()),
+            }),
+        );
+        let configuration = Configuration::new(0.05_f64);
+        let (joint_limit_warnings, mut warnings) = broadcast::channel(Player::CHANNEL_CAPACITY);
+
+        // Swing the base from the front to the back, ending up beyond its soft limit.
+        let motion = Box::new(
+            LinearMotion::new(
+                Vector3::<f64>::new(1_f64, 0.1_f64, 0.5_f64),
+                Vector3::<f64>::new(-1_f64, 0.1_f64, 0.5_f64),
+                1_f64,
+            )
+            .unwrap(),
+        );
+
+        let (servo_task, mut pushes) = answer_motion(servo);
+
+        // The motion should run to the end, even though the base entered the warning band.
+        Worker::run_motion(
+            &mut servo_com_handle,
+            &joint_limit_warnings,
+            &configuration,
+            &arm,
+            arm.kinematic_state().clone(),
+            motion,
+            &cancellation_token,
+        )
+        .await
+        .unwrap();
+
+        let warning = warnings.try_recv().unwrap();
+        assert_eq!(warning.joint, 0_usize);
+        assert!(warning.value > 2.5_f64);
+        assert!(warning.margin < std::f64::consts::PI - 2.5_f64);
+
+        // The states beyond the soft limit have been pushed all the same.
+        let poses = pushed_poses(&mut pushes);
+        assert!(poses.last().unwrap().0[0_usize] > 2.5_f64);
+
+        servo_task.abort();
+        cancellation_token.cancel();
+    }
+
+    #[tokio::test]
+    pub async fn motion_runs_in_real_time() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let cancellation_token = CancellationToken::new();
+
+        let mut servo_com_handle =
+            start_servo_com(&mut servo, client_handle, &cancellation_token).await;
+
+        let arm = Arm::new(
+            KinematicParameters::default(),
+            KinematicState::default(),
+            Arc::new(BaseSolver {
+                inverse_algorithm: Arc::new(HeuristicIKAlgorithm::default()),
+                forward_algorithm: Arc::new(AnalyticalFKAlgorithm::default()),
+            }),
+        );
+        let configuration = Configuration::new(0.05_f64);
+        let (joint_limit_warnings, _) = broadcast::channel(Player::CHANNEL_CAPACITY);
+
+        // A motion that takes one second.
+        let motion = Box::new(
+            LinearMotion::new(
+                Vector3::<f64>::new(0.5_f64, 0.1_f64, 1_f64),
+                Vector3::<f64>::new(-0.5_f64, 0.1_f64, 1_f64),
+                1_f64,
+            )
+            .unwrap(),
+        );
+
+        let (servo_task, mut pushes) = answer_motion(servo);
+
+        let start: Instant = Instant::now();
+
+        Worker::run_motion(
+            &mut servo_com_handle,
+            &joint_limit_warnings,
+            &configuration,
+            &arm,
+            arm.kinematic_state().clone(),
+            motion,
+            &cancellation_token,
+        )
+        .await
+        .unwrap();
+
+        let elapsed: Duration = start.elapsed();
+
+        assert!(elapsed >= Duration::from_millis(900_u64));
+        assert!(elapsed <= Duration::from_millis(1100_u64));
+
+        // A pose was pushed for every time step, to be executed in that time step.
+        let poses = pushed_poses(&mut pushes);
+        assert_eq!(poses.len(), 21_usize);
+        assert!(poses
+            .iter()
+            .all(|(_, duration)| (*duration - 0.05_f64).abs() < 1e-12_f64));
+
+        servo_task.abort();
+        cancellation_token.cancel();
+    }
+
+    #[tokio::test]
+    pub async fn last_sample_at_end_of_motion() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let cancellation_token = CancellationToken::new();
+
+        let mut servo_com_handle =
+            start_servo_com(&mut servo, client_handle, &cancellation_token).await;
+
+        let arm = Arm::new(
+            KinematicParameters::default(),
+            KinematicState::default(),
+            Arc::new(BaseSolver {
+                inverse_algorithm: Arc::new(HeuristicIKAlgorithm::default()),
+                forward_algorithm: Arc::new(AnalyticalFKAlgorithm::default()),
+            }),
+        );
+
+        // The time step doesn't evenly divide the (about 1.1 seconds) duration of the motion.
+        let configuration = Configuration::new(0.3_f64);
+        let (joint_limit_warnings, _) = broadcast::channel(Player::CHANNEL_CAPACITY);
+
+        let target = Vector3::<f64>::new(0.5_f64, 0.1_f64, 1_f64);
+        let motion = Box::new(
+            LinearMotion::new(Vector3::<f64>::new(1_f64, 0.1_f64, 0_f64), target, 1_f64).unwrap(),
+        );
+        let motion_duration = motion.duration();
+
+        let (servo_task, mut pushes) = answer_motion(servo);
+
+        let last_state = Worker::run_motion(
+            &mut servo_com_handle,
+            &joint_limit_warnings,
+            &configuration,
+            &arm,
+            arm.kinematic_state().clone(),
+            motion,
+            &cancellation_token,
+        )
+        .await
+        .unwrap();
+
+        // The base faces the target itself, not the sample taken at 0.9 seconds.
+        assert!((last_state.base() - target.z.atan2(target.x)).abs() < 1e-12_f64);
+
+        // Which is the last pose that has been pushed, with what's left of the time step.
+        let (angles, duration) = *pushed_poses(&mut pushes).last().unwrap();
+        assert_eq!(angles, last_state.angles());
+        assert!((duration - (motion_duration - 0.9_f64)).abs() < 1e-12_f64);
+
+        servo_task.abort();
+        cancellation_token.cancel();
+    }
+
+    #[tokio::test]
+    pub async fn motion_pushes_calibrated_angles() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let cancellation_token = CancellationToken::new();
+
+        // The base servo is mounted backwards and its zero is off by 0.1 radians.
+        let calibration = ServoCalibration::new(
+            [0.1_f64, 0_f64, 0_f64, 0_f64, 0_f64],
+            [-1_f64, 1_f64, 1_f64, 1_f64, 1_f64],
+        );
+        let mut servo_com_handle =
+            start_calibrated_servo_com(&mut servo, client_handle, calibration, &cancellation_token)
+                .await;
+
+        let arm = Arm::new(
+            KinematicParameters::default(),
+            KinematicState::default(),
+            Arc::new(BaseSolver {
+                inverse_algorithm: Arc::new(HeuristicIKAlgorithm::default()),
+                forward_algorithm: Arc::new(AnalyticalFKAlgorithm::default()),
+            }),
+        );
+        let configuration = Configuration::new(0.05_f64);
+        let (joint_limit_warnings, _) = broadcast::channel(Player::CHANNEL_CAPACITY);
+
+        let motion = Box::new(
+            LinearMotion::new(
+                Vector3::<f64>::new(0.05_f64, 0.1_f64, 1_f64),
+                Vector3::<f64>::new(-0.05_f64, 0.1_f64, 1_f64),
+                1_f64,
+            )
+            .unwrap(),
+        );
+
+        let (servo_task, mut pushes) = answer_motion(servo);
+
+        let last_state = Worker::run_motion(
+            &mut servo_com_handle,
+            &joint_limit_warnings,
+            &configuration,
+            &arm,
+            arm.kinematic_state().clone(),
+            motion,
+            &cancellation_token,
+        )
+        .await
+        .unwrap();
+
+        // The servo got the raw angles, while the state is kept in model angles.
+        let (angles, _) = *pushed_poses(&mut pushes).last().unwrap();
+        assert!((angles[0_usize] - (0.1_f64 - last_state.base())).abs() < 1e-12_f64);
+        assert_eq!(angles[1_usize], last_state.theta_1);
+
+        servo_task.abort();
+        cancellation_token.cancel();
+    }
+
+    /// Motion that stays at the same position forever, like a buggy one that never ends.
+    struct EndlessMotion;
+
+    impl Motion for EndlessMotion {
+        fn interpolate(&self, _t: f64) -> Option<Vector3<f64>> {
+            Some(Vector3::<f64>::new(0.5_f64, 0.1_f64, 1_f64))
+        }
+
+        fn duration(&self) -> f64 {
+            f64::INFINITY
+        }
+    }
+
+    #[tokio::test]
+    pub async fn endless_motion_aborted() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let cancellation_token = CancellationToken::new();
+
+        let mut servo_com_handle =
+            start_servo_com(&mut servo, client_handle, &cancellation_token).await;
+
+        let arm = Arm::new(
+            KinematicParameters::default(),
+            KinematicState::default(),
+            Arc::new(BaseSolver {
+                inverse_algorithm: Arc::new(HeuristicIKAlgorithm::default()),
+                forward_algorithm: Arc::new(AnalyticalFKAlgorithm::default()),
+            }),
+        );
+        let configuration = Configuration::new(0.05_f64).with_max_motion_duration(1_f64);
+        let (joint_limit_warnings, _) = broadcast::channel(Player::CHANNEL_CAPACITY);
+
+        let result = Worker::run_motion(
+            &mut servo_com_handle,
+            &joint_limit_warnings,
+            &configuration,
+            &arm,
+            arm.kinematic_state().clone(),
+            Box::new(EndlessMotion),
+            &cancellation_token,
+        )
+        .await;
+        assert!(
+            matches!(result, Err(Error::Generic(message)) if message == "motion exceeded max duration")
+        );
+
+        // The motion is rejected before anything is commanded, not even clearing the pose buffer.
+        assert!(
+            time::timeout(Duration::from_millis(100_u64), servo.read_command())
+                .await
+                .is_err()
+        );
+
+        // Validating the motion rejects it as well, instead of stepping through it forever.
+        let report = validate_motion(&arm, &configuration, &EndlessMotion);
+        assert_eq!(report.failures.len(), 1_usize);
+        assert_eq!(report.failures[0_usize].t, 1_f64);
+
+        cancellation_token.cancel();
+    }
+
+    #[tokio::test]
+    pub async fn motion_longer_than_pose_buffer() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let cancellation_token = CancellationToken::new();
+
+        let mut servo_com_handle =
+            start_servo_com(&mut servo, client_handle, &cancellation_token).await;
+
+        let arm = Arm::new(
+            KinematicParameters::default(),
+            KinematicState::default(),
+            Arc::new(BaseSolver {
+                inverse_algorithm: Arc::new(HeuristicIKAlgorithm::default()),
+                forward_algorithm: Arc::new(AnalyticalFKAlgorithm::default()),
+            }),
+        );
+        let configuration = Configuration::new(0.05_f64);
+        let (joint_limit_warnings, _) = broadcast::channel(Player::CHANNEL_CAPACITY);
+
+        // A motion of about twenty samples, while the pose buffer only holds two.
+        let target = Vector3::<f64>::new(-0.5_f64, 0.1_f64, 1_f64);
+        let motion = Box::new(
+            LinearMotion::new(Vector3::<f64>::new(0.5_f64, 0.1_f64, 1_f64), target, 1_f64).unwrap(),
+        );
+
+        // Once its pose buffer is full (or the last pose got pushed) the servo executes the poses
+        //  and reports the buffer drained. The player can't know that before the report though,
+        //  so until it asks for the space again the buffer counts as full.
+        const CAPACITY: usize = 2_usize;
+
+        let servo_task = tokio::spawn(async move {
+            let (_, tag, _) = servo.read_command().await;
+            servo.write_reply(tag, &ClearPoseBufferReply {}).await;
+
+            let mut queued = 0_usize;
+            let mut drains = 0_usize;
+            let mut overflows = 0_usize;
+
+            loop {
+                let (code, tag, value) = servo.read_command().await;
+
+                if code == GetPoseBufferAvailableSpaceCommand::CODE {
+                    if queued == CAPACITY {
+                        queued = 0_usize;
+                    }
+
+                    let reply = GetPoseBufferAvailableSpaceReply {
+                        available: CAPACITY - queued,
+                    };
+                    servo.write_reply(tag, &reply).await;
+
+                    continue;
+                }
+
+                assert_eq!(code, PushIntoPoseBufferCommand::CODE);
+                let (angles, _): ([f64; 5], f64) = rmp_serde::from_slice(&value).unwrap();
+
+                if queued == CAPACITY {
+                    overflows += 1_usize;
+                } else {
+                    queued += 1_usize;
+                }
+
+                let reply = PushIntoPoseBufferReply {
+                    accepted: true,
+                    index: queued - 1_usize,
+                    remaining_capacity: CAPACITY - queued,
+                };
+                servo.write_reply(tag, &reply).await;
+
+                let last = (angles[0_usize] - target.z.atan2(target.x)).abs() < 1e-12_f64;
+
+                if queued == CAPACITY || last {
+                    drains += 1_usize;
+
+                    let event = PoseBufferDrainEvent {
+                        available: CAPACITY,
+                    };
+                    servo.write_event(PoseBufferDrainEvent::CODE, &event).await;
+                    servo
+                        .write_event(PoseBufferEmptyEvent::CODE, &PoseBufferEmptyEvent {})
+                        .await;
+                }
+
+                if last {
+                    break (servo, drains, overflows);
+                }
+            }
+        });
+
+        Worker::run_motion(
+            &mut servo_com_handle,
+            &joint_limit_warnings,
+            &configuration,
+            &arm,
+            arm.kinematic_state().clone(),
+            motion,
+            &cancellation_token,
+        )
+        .await
+        .unwrap();
+
+        // No more than two poses were pushed before every drain.
+        let (_servo, drains, overflows) = servo_task.await.unwrap();
+        assert_eq!(overflows, 0_usize);
+        assert!(drains >= 10_usize);
+
+        cancellation_token.cancel();
+    }
+
+    #[tokio::test]
+    pub async fn motion_finishes_once_buffer_empty() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let cancellation_token = CancellationToken::new();
+
+        // The servo communication worker must run to receive the empty event.
+        let mut servo_com_handle =
+            start_servo_com(&mut servo, client_handle, &cancellation_token).await;
+
+        let arm = Arm::new(
+            KinematicParameters::default(),
+            KinematicState::default(),
+            Arc::new(BaseSolver {
+                inverse_algorithm: Arc::new(HeuristicIKAlgorithm::default()),
+                forward_algorithm: Arc::new(AnalyticalFKAlgorithm::default()),
+            }),
+        );
+        let configuration = Configuration::new(0.05_f64);
+        let (joint_limit_warnings, _) = broadcast::channel(Player::CHANNEL_CAPACITY);
+
+        // A motion that takes a tenth of a second.
+        let target = Vector3::<f64>::new(-0.05_f64, 0.1_f64, 1_f64);
+        let motion = Box::new(
+            LinearMotion::new(Vector3::<f64>::new(0.05_f64, 0.1_f64, 1_f64), target, 1_f64)
+                .unwrap(),
+        );
+
+        // The servo accepts every pose, but doesn't execute them, so it never reports its pose
+        //  buffer empty by itself. It's handed back once the last pose of the motion got pushed.
+        let servo_task = tokio::spawn(async move {
+            let (_, tag, _) = servo.read_command().await;
+            servo.write_reply(tag, &ClearPoseBufferReply {}).await;
+
+            let (_, tag, _) = servo.read_command().await;
+            let reply = GetPoseBufferAvailableSpaceReply {
+                available: 1024_usize,
+            };
+            servo.write_reply(tag, &reply).await;
+
+            let mut pushed = 0_usize;
+
+            loop {
+                let (code, tag, value) = servo.read_command().await;
+                assert_eq!(code, PushIntoPoseBufferCommand::CODE);
+
+                let (angles, _): ([f64; 5], f64) = rmp_serde::from_slice(&value).unwrap();
+                pushed += 1_usize;
+
+                let reply = PushIntoPoseBufferReply {
+                    accepted: true,
+                    index: pushed - 1_usize,
+                    remaining_capacity: 1024_usize - pushed,
+                };
+                servo.write_reply(tag, &reply).await;
+
+                if (angles[0_usize] - target.z.atan2(target.x)).abs() < 1e-12_f64 {
+                    break (servo, pushed);
+                }
+            }
+        });
+
+        let motion_run = Worker::run_motion(
+            &mut servo_com_handle,
+            &joint_limit_warnings,
+            &configuration,
+            &arm,
+            arm.kinematic_state().clone(),
+            motion,
+            &cancellation_token,
+        );
+        tokio::pin!(motion_run);
+
+        // All the poses have been pushed long before, but the servo didn't execute them yet.
+        assert!(time::timeout(Duration::from_secs(1_u64), &mut motion_run)
+            .await
+            .is_err());
+
+        let (mut servo, pushed) = servo_task.await.unwrap();
+        assert_eq!(pushed, 3_usize);
+
+        // Once the servo reports its pose buffer empty, the motion finishes.
+        servo
+            .write_event(PoseBufferEmptyEvent::CODE, &PoseBufferEmptyEvent {})
+            .await;
+
+        let result = time::timeout(Duration::from_secs(5_u64), &mut motion_run)
+            .await
+            .expect("Motion did not finish");
+        assert!(result.is_ok());
+
+        cancellation_token.cancel();
+    }
+
+    #[tokio::test]
+    pub async fn move_to_pose_reaches_pose() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let cancellation_token = CancellationToken::new();
+
+        let servo_com_handle =
+            start_servo_com(&mut servo, client_handle, &cancellation_token).await;
+        let (servo_task, mut pushes) = answer_motion(servo);
+
+        let params = KinematicParameters::default();
+        let arm = Arm::new(
+            params.clone(),
+            KinematicState::default(),
+            Arc::new(BaseSolver {
+                inverse_algorithm: Arc::new(HeuristicIKAlgorithm::default()),
+                forward_algorithm: Arc::new(AnalyticalFKAlgorithm::default()),
+            }),
+        );
+        let (mut worker, handle) = Player::new(
+            servo_com_handle,
+            Configuration::new(0.05_f64),
+            Arc::new(arm),
+        );
+        tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move { worker.run(cancellation_token).await }
+        });
+
+        let start_state =
+            KinematicState::from_angles([0.2_f64, 0.3_f64, 0.6_f64, 0.4_f64, 0.1_f64]);
+        handle.kinematic_state().send_replace(start_state);
+        let mut kinematic_state = handle.kinematic_state().subscribe();
+        let mut motion_failures = handle.subscribe_motion_failures();
+
+        // A pose the arm can reach without flipping its elbow.
+        let target = Worker::pose_of(
+            &params,
+            &KinematicState::from_angles([0.4_f64, 0.2_f64, 0.8_f64, 0.3_f64, -0.2_f64]),
+        );
+        handle.move_to_pose(target.clone(), 1_f64).await.unwrap();
+
+        time::timeout(Duration::from_secs(5_u64), kinematic_state.changed())
+            .await
+            .expect("Motion did not finish")
+            .unwrap();
+
+        // The arm ended up in the pose, which is the last one that has been pushed.
+        let state = kinematic_state.borrow().clone();
+        let pose = Worker::pose_of(&params, &state);
+        assert!((pose.position - target.position).magnitude() < 1e-9_f64);
+        assert!((pose.approach - target.approach).abs() < 1e-9_f64);
+        assert!((pose.roll - target.roll).abs() < 1e-9_f64);
+
+        let poses = pushed_poses(&mut pushes);
+        assert!(poses.len() > 1_usize);
+        assert_eq!(poses.last().unwrap().0, state.angles());
+
+        // A pose out of reach is reported, without pushing anything.
+        let unreachable = Pose {
+            position: Vector3::<f64>::new(0_f64, 100_f64, 0_f64),
+            ..target
+        };
+        handle.move_to_pose(unreachable, 1_f64).await.unwrap();
+
+        let failure = time::timeout(Duration::from_secs(5_u64), motion_failures.recv())
+            .await
+            .expect("Failure was not reported")
+            .unwrap();
+        assert!(matches!(
+            &*failure,
+            Error::KinematicError(KinematicError::Unreachable)
+        ));
+        assert!(pushed_poses(&mut pushes).is_empty());
+
+        servo_task.abort();
+        cancellation_token.cancel();
+    }
+
+    #[tokio::test]
+    pub async fn next_motion_starts_where_last_ended() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let cancellation_token = CancellationToken::new();
+
+        let servo_com_handle =
+            start_servo_com(&mut servo, client_handle, &cancellation_token).await;
+        let (servo_task, mut pushes) = answer_motion(servo);
+
+        let arm = Arm::new(
+            KinematicParameters::default(),
+            KinematicState::default(),
+            Arc::new(BaseSolver {
+                inverse_algorithm: Arc::new(HeuristicIKAlgorithm::default()),
+                forward_algorithm: Arc::new(AnalyticalFKAlgorithm::default()),
+            }),
+        );
+        let (mut worker, handle) = Player::new(
+            servo_com_handle,
+            Configuration::new(0.05_f64),
+            Arc::new(arm),
+        );
+        tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move { worker.run(cancellation_token).await }
+        });
+
+        // Lift the shoulder, like an earlier motion would have.
+        handle.kinematic_state().send_replace(KinematicState {
+            theta_1: 0.3_f64,
+            ..KinematicState::default()
+        });
+        let mut kinematic_state = handle.kinematic_state().subscribe();
+
+        let target = Vector3::<f64>::new(-0.05_f64, 0.1_f64, 1_f64);
+        let motion = Box::new(
+            LinearMotion::new(Vector3::<f64>::new(0.05_f64, 0.1_f64, 1_f64), target, 1_f64)
+                .unwrap(),
+        );
+        handle.start_motion(motion).await.unwrap();
+
+        time::timeout(Duration::from_secs(5_u64), kinematic_state.changed())
+            .await
+            .expect("Motion did not finish")
+            .unwrap();
+
+        // The motion started from the stored state, and its end got stored in turn.
+        let poses = pushed_poses(&mut pushes);
+        assert!(!poses.is_empty());
+        assert!(poses.iter().all(|(angles, _)| angles[1_usize] == 0.3_f64));
+
+        let state = kinematic_state.borrow().clone();
+        assert!((state.base() - target.z.atan2(target.x)).abs() < 1e-12_f64);
+        assert_eq!(state.theta_1, 0.3_f64);
+
+        servo_task.abort();
+        cancellation_token.cancel();
+    }
+
+    #[tokio::test]
+    pub async fn failed_motion_keeps_worker_running() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let cancellation_token = CancellationToken::new();
+
+        let servo_com_handle =
+            start_servo_com(&mut servo, client_handle, &cancellation_token).await;
+        let (servo_task, _pushes) = answer_motion(servo);
+
+        let arm = Arm::new(
+            KinematicParameters::default(),
+            KinematicState::default(),
+            Arc::new(BaseSolver {
+                inverse_algorithm: Arc::new(HeuristicIKAlgorithm::default()),
+                forward_algorithm: Arc::new(AnalyticalFKAlgorithm::default()),
+            }),
+        );
+        let (mut worker, handle) = Player::new(
+            servo_com_handle,
+            Configuration::new(0.05_f64).with_max_motion_duration(1_f64),
+            Arc::new(arm),
+        );
+        let worker_task = tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move { worker.run(cancellation_token).await }
+        });
+
+        let mut failures = handle.subscribe_motion_failures();
+        let mut kinematic_state = handle.kinematic_state().subscribe();
+
+        // The endless motion fails, which gets reported.
+        handle.start_motion(Box::new(EndlessMotion)).await.unwrap();
+
+        let failure = time::timeout(Duration::from_secs(5_u64), failures.recv())
+            .await
+            .expect("Failure was not reported")
+            .unwrap();
+        assert!(
+            matches!(&*failure, Error::Generic(message) if message == "motion exceeded max duration")
+        );
+
+        // The worker still runs the next motion.
+        let motion = Box::new(
+            LinearMotion::new(
+                Vector3::<f64>::new(0.05_f64, 0.1_f64, 1_f64),
+                Vector3::<f64>::new(-0.05_f64, 0.1_f64, 1_f64),
+                1_f64,
+            )
+            .unwrap(),
+        );
+        handle.start_motion(motion).await.unwrap();
+
+        time::timeout(Duration::from_secs(5_u64), kinematic_state.changed())
+            .await
+            .expect("Motion did not finish")
+            .unwrap();
+        assert!(!worker_task.is_finished());
+
+        servo_task.abort();
+        cancellation_token.cancel();
+        assert!(worker_task.await.unwrap().is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn cancelled_motion_returns_promptly() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let cancellation_token = CancellationToken::new();
+
+        let mut servo_com_handle =
+            start_servo_com(&mut servo, client_handle, &cancellation_token).await;
+
+        // Every solve takes longer than a time step, so the clock is always ready.
+        let arm = Arm::new(
+            KinematicParameters::default(),
+            KinematicState::default(),
+            Arc::new(SlowSolver {
+                inner: BaseSolver {
+                    inverse_algorithm: Arc::new(HeuristicIKAlgorithm::default()),
+                    forward_algorithm: Arc::new(AnalyticalFKAlgorithm::default()),
+                },
+                delay: std::time::Duration::from_millis(20_u64),
+            }),
+        );
+        let configuration = Configuration::new(0.01_f64);
+        let (joint_limit_warnings, _) = broadcast::channel(Player::CHANNEL_CAPACITY);
+
+        // A motion that takes ten seconds.
+        let motion = Box::new(
+            LinearMotion::new(
+                Vector3::<f64>::new(0.5_f64, 0.1_f64, 1_f64),
+                Vector3::<f64>::new(-0.5_f64, 0.1_f64, 1_f64),
+                0.1_f64,
+            )
+            .unwrap(),
+        );
+
+        let (servo_task, _pushes) = answer_motion(servo);
+
+        // Stop the motion shortly after it started.
+        tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+
+            async move {
+                time::sleep(Duration::from_millis(100_u64)).await;
+                cancellation_token.cancel();
+            }
+        });
+
+        let start: Instant = Instant::now();
+
+        let result = Worker::run_motion(
+            &mut servo_com_handle,
+            &joint_limit_warnings,
+            &configuration,
+            &arm,
+            arm.kinematic_state().clone(),
+            motion,
+            &cancellation_token,
+        )
+        .await;
+
+        let elapsed: Duration = start.elapsed();
+
+        assert!(matches!(
+            result,
+            Err(Error::ComError(com::error::Error::Cancelled))
+        ));
+
+        // At most the solve that was running when cancelled may finish.
+        assert!(elapsed <= Duration::from_millis(200_u64));
+
+        servo_task.abort();
+    }
+
+    #[test]
+    pub fn adaptive_time_step_follows_curvature() {
+        // Two straight sections joined by a quarter circle with a radius of ten centimeters,
+        //  which starts after about 0.9 seconds.
+        let motion = BlendedSequenceMotion::new(
+            vec![
+                Vector3::<f64>::new(0_f64, 0_f64, 0_f64),
+                Vector3::<f64>::new(1_f64, 0_f64, 0_f64),
+                Vector3::<f64>::new(1_f64, 1_f64, 0_f64),
+            ],
+            1_f64,
+            0.1_f64,
+            1_f64,
+        )
+        .unwrap();
+        let configuration = Configuration::new(0.05_f64)
+            .with_adaptive_time_step(AdaptiveTimeStep::new(0.05_f64, 0.005_f64, 0.2_f64));
+
+        let mut steps: Vec<(f64, f64)> = Vec::new();
+        let mut t = 0_f64;
+        let mut delta_time = 0.05_f64;
+        while motion.interpolate(t).is_some() {
+            delta_time = configuration.next_delta_time(&motion, t, delta_time);
+            steps.push((t, delta_time));
+            t += delta_time;
+        }
+
+        // The steps grow on the first straight section.
+        let straight = steps.iter().filter(|(t, _)| *t < 0.5_f64);
+        assert!(straight.clone().any(|(_, x)| *x == 0.2_f64));
+
+        // They're as small as allowed along the curve.
+        let curved: Vec<f64> = steps
+            .iter()
+            .filter(|(t, _)| *t > 0.92_f64 && *t < 1.03_f64)
+            .map(|(_, x)| *x)
+            .collect();
+        assert!(!curved.is_empty());
+        assert!(curved.iter().all(|x| *x <= 0.01_f64));
+
+        // And grow again on the second straight section.
+        assert!(steps.iter().any(|(t, x)| *t > 1.2_f64 && *x > 0.05_f64));
+    }
+
+    #[test]
+    pub fn warm_start_saves_iterations() {
+        let params = KinematicParameters::default();
+        let start = AnalyticalFKAlgorithm::default()
+            .limb4_position_vector(&params, &KinematicState::ready());
+
+        // A fine trajectory through a well-conditioned part of the workspace.
+        let motion = LinearMotion::new(
+            start,
+            start + Vector3::<f64>::new(2_f64, 0_f64, 1_f64),
+            1_f64,
+        )
+        .unwrap();
+
+        let total_iterations = |configuration: &Configuration| {
+            let solver = Arc::new(CountingSolver {
+                inner: HeuristicSolver::builder(
+                    Arc::new(HeuristicIKAlgorithm::default()),
+                    Arc::new(AnalyticalFKAlgorithm::default()),
+                )
+                .with_threshold(1e-4_f64)
+                .build(),
+                iterations: AtomicUsize::new(0_usize),
+            });
+            let arm = Arm::new(params.clone(), KinematicState::ready(), solver.clone());
+
+            assert!(validate_motion(&arm, configuration, &motion).is_ok());
+
+            solver.iterations.load(Ordering::SeqCst)
+        };
+
+        let cold = total_iterations(&Configuration::new(0.05_f64));
+        let warm = total_iterations(&Configuration::new(0.05_f64).with_warm_start(true));
+
+        assert!(warm < cold);
+    }
+
+    #[test]
+    pub fn prefer_gravity_assisted_solution() {
+        let arm = Arm::new(
+            KinematicParameters::default(),
+            KinematicState::default(),
+            Arc::new(TwoSolutionSolver {
+                inner: BaseSolver {
+                    inverse_algorithm: Arc::new(HeuristicIKAlgorithm::default()),
+                    forward_algorithm: Arc::new(AnalyticalFKAlgorithm::default()),
+                },
+            }),
+        );
+        let motion = LinearMotion::new(
+            Vector3::<f64>::new(0_f64, 40_f64, 0_f64),
+            Vector3::<f64>::new(0_f64, 30_f64, 0_f64),
+            1_f64,
+        )
+        .unwrap();
+
+        // Solve the first sample with all links pointing straight up.
+        let first_state = |configuration: &Configuration| {
+            let straight_up = KinematicState::from_angles([0_f64; 5_usize]);
+            let sample = MotionSampler::new(&arm, configuration, &motion, straight_up)
+                .next()
+                .unwrap();
+
+            sample.states.unwrap()[0_usize].0.clone()
+        };
+
+        // Both solutions are equally close, so without the preference the first one is picked.
+        let state = first_state(&Configuration::new(0.05_f64));
+        assert_eq!(state.wrist_pitch(), 0.5_f64);
+        assert_eq!(state.shoulder(), 0_f64);
+
+        // Leaning the shoulder lowers the center of mass the most.
+        let state = first_state(&Configuration::new(0.05_f64).with_prefer_gravity_assisted(true));
+        assert_eq!(state.shoulder(), 0.5_f64);
+        assert_eq!(state.wrist_pitch(), 0_f64);
+    }
+
+    #[test]
+    pub fn validate_motion_reports_failing_samples() {
+        let arm = Arm::new(
+            KinematicParameters::default(),
+            KinematicState::default(),
+            Arc::new(BaseSolver {
+                inverse_algorithm: Arc::new(HeuristicIKAlgorithm::default()),
+                forward_algorithm: Arc::new(AnalyticalFKAlgorithm::default()),
+            }),
+        );
+        let configuration = Configuration::new(0.1_f64);
+
+        // A motion that stays within reach.
+        let motion = LinearMotion::new(
+            Vector3::<f64>::new(0.5_f64, 0.1_f64, 1_f64),
+            Vector3::<f64>::new(-0.5_f64, 0.1_f64, 1_f64),
+            1_f64,
+        )
+        .unwrap();
+
+        assert!(validate_motion(&arm, &configuration, &motion).is_ok());
+
+        // A motion that leaves the reach of the arm (50 meters) after about one second.
+        let motion = LinearMotion::new(
+            Vector3::<f64>::new(49_f64, 0.1_f64, 1_f64),
+            Vector3::<f64>::new(51_f64, 0.1_f64, 1_f64),
+            1_f64,
+        )
+        .unwrap();
+
+        let report = validate_motion(&arm, &configuration, &motion);
+        assert!(!report.is_ok());
+
+        let failure = report.failures.first().unwrap();
+        assert!(failure.t > 0.95_f64 && failure.t < 1.05_f64);
+        assert!(matches!(
+            failure.error,
+            Error::KinematicError(KinematicError::Unreachable)
+        ));
+
+        // Every sample after that is unreachable as well.
+        assert!(report
+            .failures
+            .windows(2)
+            .all(|x| (x[1].t - x[0].t - 0.1_f64).abs() < 1e-9_f64));
+    }
+
+    #[test]
+    pub fn validate_motion_checks_solved_states() {
+        // The base can only turn half a radian either way.
+        let params = KinematicParameters {
+            joint_limits: [
+                [-0.5_f64, 0.5_f64],
+                [-3_f64, 3_f64],
+                [-3_f64, 3_f64],
+                [-3_f64, 3_f64],
+                [-3_f64, 3_f64],
+            ],
+            ..KinematicParameters::default()
+        };
+        let arm = Arm::new(
+            params,
+            KinematicState::default(),
+            Arc::new(BaseSolver {
+                inverse_algorithm: Arc::new(HeuristicIKAlgorithm::default()),
+                forward_algorithm: Arc::new(AnalyticalFKAlgorithm::default()),
+            }),
+        );
+        let configuration = Configuration::new(0.1_f64);
+
+        // The solver reaches every sample, but turns the base past its limit after a while.
+        let motion = LinearMotion::new(
+            Vector3::<f64>::new(0.5_f64, 0.1_f64, 0_f64),
+            Vector3::<f64>::new(0.5_f64, 0.1_f64, 1_f64),
+            1_f64,
+        )
+        .unwrap();
+
+        let report = validate_motion(&arm, &configuration, &motion);
+
+        let first = report.failures.first().unwrap();
+        assert!(first.t > 0.2_f64 && first.t < 0.35_f64);
+        assert!(matches!(
+            first.error,
+            Error::KinematicError(KinematicError::JointLimitViolated { joint: 0_usize, .. })
+        ));
+
+        // The end of the motion is checked as well, like it's pushed when running it.
+        assert_eq!(report.failures.last().unwrap().t, 1_f64);
+    }
+
+    /// Run the self-test of the given arm against a servo that follows every pushed pose.
+    async fn run_self_test_on(arm: Arm) -> SelfTestReport {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let cancellation_token = CancellationToken::new();
+
+        // The servo communication worker must run to receive the pose changed events.
+        let (mut servo_com_worker, servo_com_handle) = ServoCom::from_client(client_handle);
+        tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move { servo_com_worker.run(cancellation_token).await }
+        });
+
+        // Keep firing the pose changed event until the worker is subscribed to it.
+        let mut pose_changed = servo_com_handle.broadcasts().pose_changed().subscribe();
+        time::timeout(Duration::from_secs(5_u64), async {
+            loop {
+                let event = PoseChangedEvent { angles: [0_f64; 5] };
+                servo.write_event(PoseChangedEvent::CODE, &event).await;
+
+                if time::timeout(Duration::from_millis(10_u64), pose_changed.recv())
+                    .await
+                    .is_ok()
+                {
+                    break;
+                }
+            }
+        })
+        .await
+        .expect("Worker did not subscribe");
+
+        // The servo moves to every pushed pose right away and reports it, after which its buffer
+        //  is empty again.
+        let servo_task = tokio::spawn(async move {
+            loop {
+                let (code, tag, value) = servo.read_command().await;
+
+                if code == ClearPoseBufferCommand::CODE {
+                    servo.write_reply(tag, &ClearPoseBufferReply {}).await;
+                } else if code == GetPoseBufferAvailableSpaceCommand::CODE {
+                    let reply = GetPoseBufferAvailableSpaceReply {
+                        available: 1024_usize,
+                    };
+                    servo.write_reply(tag, &reply).await;
+                } else if code == PushIntoPoseBufferCommand::CODE {
+                    let (angles, _): ([f64; 5], f64) = rmp_serde::from_slice(&value).unwrap();
+
+                    let reply = PushIntoPoseBufferReply {
+                        accepted: true,
+                        index: 0_usize,
+                        remaining_capacity: 1024_usize,
+                    };
+                    servo.write_reply(tag, &reply).await;
+                    servo
+                        .write_event(PoseChangedEvent::CODE, &PoseChangedEvent { angles })
+                        .await;
+                    servo
+                        .write_event(PoseBufferEmptyEvent::CODE, &PoseBufferEmptyEvent {})
+                        .await;
+                } else {
+                    panic!("Unexpected command {:?}", code);
+                }
+            }
+        });
+
+        let (mut player_worker, player_handle) = Player::new(
+            servo_com_handle,
+            Configuration::new(0.25_f64),
+            Arc::new(arm),
+        );
+        tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move { player_worker.run(cancellation_token).await }
+        });
+
+        let report = time::timeout(Duration::from_secs(10_u64), player_handle.run_self_test())
+            .await
+            .expect("Self-test did not finish")
+            .unwrap();
+
+        servo_task.abort();
+        cancellation_token.cancel();
+
+        report
+    }
+
+    #[tokio::test]
+    pub async fn self_test_passes_when_servo_follows() {
+        let arm = Arm::new(
+            KinematicParameters::default(),
+            KinematicState::default(),
+            Arc::new(BaseSolver {
+                inverse_algorithm: Arc::new(HeuristicIKAlgorithm::default()),
+                forward_algorithm: Arc::new(AnalyticalFKAlgorithm::default()),
+            }),
+        );
+
+        let report = run_self_test_on(arm).await;
+
+        assert_eq!(report.joints.len(), 5_usize);
+        assert!(report.is_ok());
+        for (result, joint) in report.joints.iter().zip(Joint::ALL) {
+            assert_eq!(result.joint, joint);
+            assert_eq!(result.reported, Some(result.commanded));
+        }
+    }
+
+    #[tokio::test]
+    pub async fn self_test_respects_joint_limits() {
+        // The base has no room to move either way, and the shoulder is at its upper limit.
+        let mut params = KinematicParameters::default();
+        params.joint_limits[0_usize] = [0.18_f64, 0.22_f64];
+        params.joint_limits[1_usize] = [-3_f64, 0.2_f64];
+
+        let arm = Arm::new(
+            params,
+            KinematicState::default(),
+            Arc::new(BaseSolver {
+                inverse_algorithm: Arc::new(HeuristicIKAlgorithm::default()),
+                forward_algorithm: Arc::new(AnalyticalFKAlgorithm::default()),
+            }),
+        );
+
+        let report = run_self_test_on(arm).await;
+
+        // The base fails, but every other joint is still tested.
+        assert_eq!(report.joints.len(), 5_usize);
+        assert!(!report.joints[0_usize].passed);
+        assert!(report.joints[0_usize].error.is_some());
+
+        // The shoulder is moved down instead of past its limit.
+        let shoulder = &report.joints[1_usize];
+        assert!(shoulder.passed);
+        assert_eq!(shoulder.commanded, 0.2_f64 - Worker::SELF_TEST_AMPLITUDE);
+
+        assert!(report.joints[2_usize..].iter().all(|x| x.passed));
     }
 }
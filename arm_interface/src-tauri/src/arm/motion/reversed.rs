@@ -0,0 +1,55 @@
+use nalgebra::Vector3;
+
+use super::Motion;
+
+/// This struct represents a motion that runs the wrapped motion backward, so it starts where the
+///  wrapped motion ends (e.g. to return to the start of a motion).
+pub(crate) struct ReversedMotion {
+    motion: Box<dyn Motion>,
+}
+
+impl ReversedMotion {
+    pub fn new(motion: Box<dyn Motion>) -> Self {
+        Self { motion }
+    }
+}
+
+impl Motion for ReversedMotion {
+    fn interpolate(&self, t: f64) -> Option<Vector3<f64>> {
+        let duration = self.motion.duration();
+
+        // There's no position before the motion started, or after the original one began.
+        if t.is_nan() || t < 0_f64 || t > duration {
+            return None;
+        }
+
+        self.motion.interpolate(duration - t)
+    }
+
+    fn duration(&self) -> f64 {
+        self.motion.duration()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use nalgebra::Vector3;
+
+    use crate::arm::motion::{linear::LinearMotion, reversed::ReversedMotion, Motion};
+
+    #[test]
+    pub fn reversed_linear_motion() {
+        let original = Vector3::<f64>::new(0_f64, 0_f64, 0_f64);
+        let target = Vector3::<f64>::new(1_f64, 2_f64, 0_f64);
+
+        let motion = ReversedMotion::new(Box::new(
+            LinearMotion::new(original, target, 1_f64).unwrap(),
+        ));
+
+        assert_eq!(motion.duration(), 5_f64.sqrt());
+        assert_eq!(motion.interpolate(0_f64), Some(target));
+        assert_eq!(motion.interpolate(motion.duration()), Some(original));
+        assert_eq!(motion.interpolate(motion.duration() + 0.1_f64), None);
+        assert_eq!(motion.interpolate(-0.1_f64), None);
+    }
+}
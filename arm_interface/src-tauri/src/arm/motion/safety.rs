@@ -0,0 +1,142 @@
+use nalgebra::Vector3;
+use thiserror::Error;
+
+use super::Motion;
+
+/// This struct represents the box the end-effector is allowed to move in.
+///
+/// The coordinates follow the kinematic model, meaning that the $y$ axis points up, so the
+///  minimum $y$ coordinate represents the table (floor) the arm is mounted on.
+#[derive(Debug, Clone)]
+pub(crate) struct WorkspaceBounds {
+    min: Vector3<f64>, // The minimum corner of the box (in meters).
+    max: Vector3<f64>, // The maximum corner of the box (in meters).
+}
+
+impl WorkspaceBounds {
+    pub fn new(min: Vector3<f64>, max: Vector3<f64>) -> Self {
+        Self { min, max }
+    }
+
+    /// Check if the given position lies within the bounds.
+    pub fn contains(&self, position: &Vector3<f64>) -> bool {
+        position
+            .iter()
+            .zip(self.min.iter().zip(self.max.iter()))
+            .all(|(x, (min, max))| x >= min && x <= max)
+    }
+}
+
+impl Default for WorkspaceBounds {
+    /// The default bounds only prevent the end-effector from going below the table.
+    fn default() -> Self {
+        Self {
+            min: Vector3::<f64>::new(f64::NEG_INFINITY, 0_f64, f64::NEG_INFINITY),
+            max: Vector3::<f64>::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+        }
+    }
+}
+
+/// This error represents the first sample of a motion that violates the workspace bounds.
+#[derive(Debug, Error)]
+#[error("Motion leaves the workspace at t = {t}s, position: {position:?}")]
+pub(crate) struct SafetyViolation {
+    pub t: f64,                 // The time of the violating sample (in seconds).
+    pub position: Vector3<f64>, // The violating position (in meters).
+}
+
+/// Sample the given motion every `dt` seconds and check that all the samples lie within the
+///  given bounds, returning the first violating sample if any. Sampling stops after `max_t`
+///  seconds, so a motion that never ends can't keep the check going forever.
+pub(crate) fn check_motion_safety(
+    motion: &dyn Motion,
+    bounds: &WorkspaceBounds,
+    dt: f64,
+    max_t: f64,
+) -> Result<(), SafetyViolation> {
+    assert!(dt > 0_f64);
+
+    let mut t = 0_f64;
+
+    while let Some(position) = motion.interpolate(t).filter(|_| t <= max_t) {
+        if !bounds.contains(&position) {
+            return Err(SafetyViolation { t, position });
+        }
+
+        t += dt;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use nalgebra::Vector3;
+
+    use crate::arm::motion::safety::{check_motion_safety, WorkspaceBounds};
+    use crate::arm::motion::Motion;
+
+    /// Motion that moves down along the $y$ axis at 1 meter/second for one second.
+    struct DescendingMotion {
+        start_position: Vector3<f64>,
+    }
+
+    impl Motion for DescendingMotion {
+        fn interpolate(&self, t: f64) -> Option<Vector3<f64>> {
+            if t > 1_f64 {
+                return None;
+            }
+
+            Some(self.start_position - Vector3::<f64>::new(0_f64, t, 0_f64))
+        }
+
+        fn duration(&self) -> f64 {
+            1_f64
+        }
+    }
+
+    #[test]
+    pub fn motion_dipping_below_floor() {
+        let motion = DescendingMotion {
+            start_position: Vector3::<f64>::new(0_f64, 0.5_f64, 0_f64),
+        };
+
+        // The motion reaches the floor half-way, so the violation should be right after that.
+        let violation =
+            check_motion_safety(&motion, &WorkspaceBounds::default(), 0.1_f64, f64::INFINITY)
+                .expect_err("Motion should violate the bounds");
+
+        assert!(violation.t > 0.5_f64 && violation.t < 0.7_f64);
+        assert!(violation.position.y < 0_f64);
+    }
+
+    #[test]
+    pub fn motion_staying_safe() {
+        let motion = DescendingMotion {
+            start_position: Vector3::<f64>::new(0_f64, 2_f64, 0_f64),
+        };
+
+        assert!(
+            check_motion_safety(&motion, &WorkspaceBounds::default(), 0.1_f64, f64::INFINITY)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    pub fn motion_leaving_box() {
+        let motion = DescendingMotion {
+            start_position: Vector3::<f64>::new(0_f64, 2_f64, 0_f64),
+        };
+
+        // The box is too narrow for the given x coordinate.
+        let bounds = WorkspaceBounds::new(
+            Vector3::<f64>::new(1_f64, 0_f64, -1_f64),
+            Vector3::<f64>::new(2_f64, 3_f64, 1_f64),
+        );
+
+        let violation = check_motion_safety(&motion, &bounds, 0.1_f64, f64::INFINITY)
+            .expect_err("Motion should violate the bounds");
+
+        assert_eq!(violation.t, 0_f64);
+    }
+}
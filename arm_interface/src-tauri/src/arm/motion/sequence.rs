@@ -0,0 +1,76 @@
+use nalgebra::Vector3;
+
+use super::Motion;
+
+/// This struct represents a sequence of motions that are executed one after the other.
+pub(crate) struct SequenceMotion {
+    motions: Vec<Box<dyn Motion>>,
+}
+
+impl SequenceMotion {
+    pub fn new(motions: Vec<Box<dyn Motion>>) -> Self {
+        Self { motions }
+    }
+
+    /// Get the motions in the sequence.
+    #[inline]
+    pub fn motions(&self) -> &[Box<dyn Motion>] {
+        &self.motions
+    }
+}
+
+impl Motion for SequenceMotion {
+    fn interpolate(&self, t: f64) -> Option<Vector3<f64>> {
+        // Find the motion the time falls in, making the time relative to the start of it.
+        let mut start = 0_f64;
+
+        for motion in self.motions.iter() {
+            let duration = motion.duration();
+
+            // The start times are summed in the same order as the total duration, so the end of
+            //  the sequence is never lost to rounding.
+            if t <= start + duration {
+                return motion.interpolate((t - start).min(duration));
+            }
+
+            start += duration;
+        }
+
+        None
+    }
+
+    fn duration(&self) -> f64 {
+        self.motions.iter().map(|motion| motion.duration()).sum()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use nalgebra::Vector3;
+
+    use crate::arm::motion::{linear::LinearMotion, sequence::SequenceMotion, Motion};
+
+    #[test]
+    pub fn motions_run_one_after_the_other() {
+        let a = Vector3::<f64>::new(0_f64, 0_f64, 0_f64);
+        let b = Vector3::<f64>::new(1_f64, 0_f64, 0_f64);
+        let c = Vector3::<f64>::new(1_f64, 2_f64, 0_f64);
+
+        let sequence = SequenceMotion::new(vec![
+            Box::new(LinearMotion::new(a, b, 1_f64).unwrap()),
+            Box::new(LinearMotion::new(b, c, 1_f64).unwrap()),
+        ]);
+
+        assert_eq!(sequence.duration(), 3_f64);
+        assert_eq!(
+            sequence.interpolate(0.5_f64),
+            Some(Vector3::<f64>::new(0.5_f64, 0_f64, 0_f64))
+        );
+        assert_eq!(
+            sequence.interpolate(2_f64),
+            Some(Vector3::<f64>::new(1_f64, 1_f64, 0_f64))
+        );
+        assert_eq!(sequence.interpolate(3_f64), Some(c));
+        assert_eq!(sequence.interpolate(3.5_f64), None);
+    }
+}
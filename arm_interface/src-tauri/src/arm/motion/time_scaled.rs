@@ -0,0 +1,69 @@
+use nalgebra::Vector3;
+
+use super::{Motion, MotionError};
+
+/// This struct represents a motion that runs the wrapped motion faster or slower, a scale of 2
+///  follows the same path in half the time.
+pub(crate) struct TimeScaledMotion {
+    motion: Box<dyn Motion>,
+    scale: f64, // The factor the time of the wrapped motion runs faster with.
+}
+
+impl TimeScaledMotion {
+    /// Create a new time scaled motion, the scale must be positive and finite.
+    pub fn new(motion: Box<dyn Motion>, scale: f64) -> Result<Self, MotionError> {
+        if !(scale > 0_f64 && scale.is_finite()) {
+            return Err(MotionError::InvalidScale { scale });
+        }
+
+        Ok(Self { motion, scale })
+    }
+}
+
+impl Motion for TimeScaledMotion {
+    fn interpolate(&self, t: f64) -> Option<Vector3<f64>> {
+        self.motion.interpolate(t * self.scale)
+    }
+
+    fn duration(&self) -> f64 {
+        self.motion.duration() / self.scale
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use nalgebra::Vector3;
+
+    use crate::arm::motion::{
+        linear::LinearMotion, time_scaled::TimeScaledMotion, Motion, MotionError,
+    };
+
+    #[test]
+    pub fn double_speed_halves_duration() {
+        let original = Vector3::<f64>::new(0_f64, 0_f64, 0_f64);
+        let target = Vector3::<f64>::new(1_f64, 0_f64, 0_f64);
+
+        let motion = TimeScaledMotion::new(
+            Box::new(LinearMotion::new(original, target, 0.5_f64).unwrap()),
+            2_f64,
+        )
+        .unwrap();
+
+        // The same path is followed, in half the time.
+        assert_eq!(motion.duration(), 1_f64);
+        assert_eq!(
+            motion.interpolate(0.5_f64),
+            Some(Vector3::<f64>::new(0.5_f64, 0_f64, 0_f64))
+        );
+        assert_eq!(motion.interpolate(1_f64), Some(target));
+        assert_eq!(motion.interpolate(1.5_f64), None);
+
+        assert!(matches!(
+            TimeScaledMotion::new(
+                Box::new(LinearMotion::new(original, target, 0.5_f64).unwrap()),
+                0_f64,
+            ),
+            Err(MotionError::InvalidScale { .. })
+        ));
+    }
+}
@@ -0,0 +1,332 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    sync::Arc,
+};
+
+use kinematics::{inverse::solvers::IKSolverResult, model::KinematicState};
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::{arm::Arm, error::Error};
+
+use super::motion::player;
+
+/// This struct represents the id of an arm.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(transparent)]
+pub struct ArmId(u32);
+
+impl ArmId {
+    /// Create a new arm id.
+    #[inline(always)]
+    pub const fn new(inner: u32) -> Self {
+        Self(inner)
+    }
+
+    /// Get the inner value of the arm id.
+    #[inline(always)]
+    pub fn inner(&self) -> u32 {
+        self.0
+    }
+
+    /// Get the name of the given frontend event namespaced by this arm, like
+    ///  `arm:{id}:state-changed`.
+    pub fn event_name(&self, event: &str) -> String {
+        format!("arm:{}:{}", self.0, event)
+    }
+}
+
+impl Display for ArmId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// This struct holds everything that belongs to a single arm.
+pub(crate) struct ArmEntry {
+    arm: Arc<Arm>,
+    kinematic_state: watch::Sender<KinematicState>,
+    player_handle: player::Handle,
+}
+
+impl ArmEntry {
+    /// Create a new entry, the kinematic state is shared with the player so that it follows the
+    ///  motions it runs.
+    pub fn new(arm: Arc<Arm>, player_handle: player::Handle) -> Self {
+        let kinematic_state = player_handle.kinematic_state().clone();
+
+        Self {
+            arm,
+            kinematic_state,
+            player_handle,
+        }
+    }
+
+    #[inline]
+    pub fn arm(&self) -> &Arc<Arm> {
+        &self.arm
+    }
+
+    /// The current kinematic state of the arm, subscribe to it to get notified of changes.
+    #[inline]
+    pub fn kinematic_state(&self) -> &watch::Sender<KinematicState> {
+        &self.kinematic_state
+    }
+
+    #[inline]
+    pub fn player_handle(&self) -> &player::Handle {
+        &self.player_handle
+    }
+
+    /// Set the kinematic state directly, after making sure the arm can be in it.
+    pub fn update_kinematic_state(&self, new_state: &KinematicState) -> Result<(), Error> {
+        new_state.validate(self.arm.kinematic_parameters())?;
+        self.kinematic_state.send_replace(new_state.clone());
+
+        Ok(())
+    }
+
+    /// Move the end-effector to the given position, updating the kinematic state if the target
+    ///  has been reached.
+    pub fn move_end_effector(
+        &self,
+        target_position: &Vector3<f64>,
+    ) -> Result<IKSolverResult, Error> {
+        let state: KinematicState = self.kinematic_state.borrow().clone();
+
+        let solver_result: IKSolverResult =
+            self.arm.kinematic_solver().translate_limb4_end_effector(
+                self.arm.kinematic_parameters(),
+                &state,
+                target_position,
+            )?;
+
+        if let Some(new_state) = solver_result.new_state() {
+            new_state.validate(self.arm.kinematic_parameters())?;
+            self.kinematic_state.send_replace(new_state.clone());
+        }
+
+        Ok(solver_result)
+    }
+
+    /// Rotate the end-effector to the given orientation (as euler angles), updating the kinematic
+    ///  state if the target has been reached.
+    pub fn rotate_end_effector(
+        &self,
+        target_orientation: &Vector3<f64>,
+    ) -> Result<IKSolverResult, Error> {
+        let state: KinematicState = self.kinematic_state.borrow().clone();
+
+        let solver_result: IKSolverResult = self.arm.kinematic_solver().rotate_limb4_end_effector(
+            self.arm.kinematic_parameters(),
+            &state,
+            target_orientation,
+        )?;
+
+        if let Some(new_state) = solver_result.new_state() {
+            new_state.validate(self.arm.kinematic_parameters())?;
+            self.kinematic_state.send_replace(new_state.clone());
+        }
+
+        Ok(solver_result)
+    }
+}
+
+/// This struct holds all the arms managed by the app, keyed by their id.
+#[derive(Default)]
+pub(crate) struct ArmRegistry {
+    arms: BTreeMap<ArmId, ArmEntry>,
+}
+
+impl ArmRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the given arm, returning an error if an arm with the same id already exists.
+    pub fn register(&mut self, arm_id: ArmId, entry: ArmEntry) -> Result<(), Error> {
+        if self.arms.contains_key(&arm_id) {
+            return Err(Error::DuplicateArm(arm_id));
+        }
+
+        self.arms.insert(arm_id, entry);
+
+        Ok(())
+    }
+
+    /// Get the arm with the given id.
+    pub fn get(&self, arm_id: ArmId) -> Result<&ArmEntry, Error> {
+        self.arms.get(&arm_id).ok_or(Error::UnknownArm(arm_id))
+    }
+
+    /// Iterate over all the arms, ordered by their id.
+    pub fn iter(&self) -> impl Iterator<Item = (ArmId, &ArmEntry)> {
+        self.arms.iter().map(|(arm_id, entry)| (*arm_id, entry))
+    }
+
+    /// Get the ids of all the arms, in order.
+    pub fn ids(&self) -> Vec<ArmId> {
+        self.arms.keys().copied().collect()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Arc;
+
+    use kinematics::{
+        error::KinematicError,
+        forward::algorithms::analytical::AnalyticalFKAlgorithm,
+        inverse::{
+            algorithms::heuristic::HeuristicIKAlgorithm, solvers::heuristic::HeuristicSolver,
+        },
+        model::{KinematicParameters, KinematicState},
+    };
+    use nalgebra::Vector3;
+    use tokio::sync::{broadcast, mpsc, watch};
+
+    use crate::arm::motion::player;
+    use crate::arm::registry::{ArmEntry, ArmId, ArmRegistry};
+    use crate::arm::Arm;
+    use crate::error::Error;
+
+    fn entry() -> ArmEntry {
+        let arm = Arm::new(
+            KinematicParameters::default(),
+            KinematicState::default(),
+            Arc::new(
+                HeuristicSolver::builder(
+                    Arc::new(HeuristicIKAlgorithm::default()),
+                    Arc::new(AnalyticalFKAlgorithm::default()),
+                )
+                .build(),
+            ),
+        );
+
+        // The player worker isn't needed to move the end-effector.
+        let (instruction_sender, _) = mpsc::channel(1_usize);
+        let (joint_limit_warnings, _) = broadcast::channel(1_usize);
+        let (motion_failures, _) = broadcast::channel(1_usize);
+        let (kinematic_state, _) = watch::channel(arm.kinematic_state().clone());
+
+        ArmEntry::new(
+            Arc::new(arm),
+            player::Handle::new(
+                instruction_sender,
+                joint_limit_warnings,
+                motion_failures,
+                kinematic_state,
+            ),
+        )
+    }
+
+    fn end_effector_position(entry: &ArmEntry) -> Vector3<f64> {
+        entry
+            .arm()
+            .kinematic_solver()
+            .forward_algorithm()
+            .limb4_position_vector(
+                entry.arm().kinematic_parameters(),
+                &entry.kinematic_state().borrow(),
+            )
+    }
+
+    #[test]
+    pub fn arms_move_independently() {
+        let left = ArmId::new(0_u32);
+        let right = ArmId::new(1_u32);
+
+        let mut registry = ArmRegistry::new();
+        registry.register(left, entry()).unwrap();
+        registry.register(right, entry()).unwrap();
+
+        assert!(matches!(
+            registry.register(left, entry()),
+            Err(Error::DuplicateArm(arm_id)) if arm_id == left
+        ));
+        assert!(matches!(
+            registry.get(ArmId::new(2_u32)),
+            Err(Error::UnknownArm(_))
+        ));
+        assert_eq!(registry.ids(), vec![left, right]);
+
+        // Move the left arm, the right one should stay where it is.
+        let start = end_effector_position(registry.get(left).unwrap());
+        let left_target = start + Vector3::<f64>::new(0.5_f64, 0_f64, 0_f64);
+        let right_target = start - Vector3::<f64>::new(0_f64, 0.5_f64, 0_f64);
+
+        let result = registry
+            .get(left)
+            .unwrap()
+            .move_end_effector(&left_target)
+            .unwrap();
+        assert!(result.is_reached());
+        assert!(
+            (end_effector_position(registry.get(left).unwrap()) - left_target).magnitude()
+                < 0.01_f64
+        );
+        assert!(
+            (end_effector_position(registry.get(right).unwrap()) - start).magnitude() < 1e-9_f64
+        );
+
+        // Move the right arm, the left one should stay where it moved to.
+        let result = registry
+            .get(right)
+            .unwrap()
+            .move_end_effector(&right_target)
+            .unwrap();
+        assert!(result.is_reached());
+        assert!(
+            (end_effector_position(registry.get(right).unwrap()) - right_target).magnitude()
+                < 0.01_f64
+        );
+        assert!(
+            (end_effector_position(registry.get(left).unwrap()) - left_target).magnitude()
+                < 0.01_f64
+        );
+
+        assert_eq!(left.event_name("state-changed"), "arm:0:state-changed");
+    }
+
+    #[test]
+    pub fn update_kinematic_state_validated() {
+        let entry = entry();
+
+        let new_state = KinematicState {
+            theta_1: 0.5_f64,
+            ..KinematicState::default()
+        };
+        entry.update_kinematic_state(&new_state).unwrap();
+        assert_eq!(*entry.kinematic_state().borrow(), new_state);
+
+        // A state with a non-finite angle is rejected, keeping the previous state.
+        let result = entry.update_kinematic_state(&KinematicState {
+            theta_3: f64::INFINITY,
+            ..KinematicState::default()
+        });
+        assert!(matches!(
+            result,
+            Err(Error::KinematicError(KinematicError::NonFiniteAngle {
+                joint: 3_usize,
+                ..
+            }))
+        ));
+        assert_eq!(*entry.kinematic_state().borrow(), new_state);
+    }
+
+    #[test]
+    pub fn rotate_end_effector_unreachable() {
+        let entry = entry();
+        let state = entry.kinematic_state().borrow().clone();
+
+        // The heuristic solver can't rotate yet, so the state should stay as it is.
+        let result = entry
+            .rotate_end_effector(&Vector3::<f64>::new(0_f64, 0.5_f64, 0_f64))
+            .unwrap();
+
+        assert!(!result.is_reached());
+        assert!(entry.kinematic_state().borrow().approx_eq(&state, 0_f64));
+    }
+}
@@ -3,6 +3,11 @@ use std::borrow::Cow;
 use kinematics::error::KinematicError;
 use thiserror::Error;
 
+use crate::arm::{
+    motion::{gcode::GCodeError, safety::SafetyViolation, MotionError},
+    registry::ArmId,
+};
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("Communication error: {0}")]
@@ -10,5 +15,25 @@ pub enum Error {
     #[error("{0}")]
     Generic(Cow<'static, str>),
     #[error("Kinematic error: {0}")]
-    KinematicError(#[from] KinematicError)
+    KinematicError(#[from] KinematicError),
+    #[error("Protocol mismatch, expected version {expected} but servo uses {actual}")]
+    ProtocolMismatch { expected: u32, actual: u32 },
+    #[error("Watchdog timed out waiting for the keep alive reply")]
+    WatchdogTimeout,
+    #[error("Safety violation: {0}")]
+    SafetyViolation(#[from] SafetyViolation),
+    #[error("Pose duration must be positive and finite, got {duration} s")]
+    InvalidPoseDuration { duration: f64 },
+    #[error("Pose rejected by the servo {attempts} times in a row")]
+    PoseRejected { attempts: usize },
+    #[error("Joint {joint} would move at {velocity} rad/s, exceeding its velocity limit")]
+    JointVelocityExceeded { joint: usize, velocity: f64 },
+    #[error("G-code error: {0}")]
+    GCodeError(#[from] GCodeError),
+    #[error("Motion error: {0}")]
+    MotionError(#[from] MotionError),
+    #[error("No arm with id {0}")]
+    UnknownArm(ArmId),
+    #[error("An arm with id {0} has already been registered")]
+    DuplicateArm(ArmId),
 }
@@ -1,7 +1,24 @@
 use nalgebra::Vector3;
 use serde::{Deserialize, Serialize};
 
-use kinematics::model::{KinematicParameters, KinematicState};
+use kinematics::{
+    error::KinematicError,
+    inverse::solvers::IKSolverResult,
+    model::{KinematicParameters, KinematicState},
+};
+
+use crate::{
+    arm::{
+        motion::{
+            arc::ArcMotion,
+            linear::LinearMotion,
+            player::{JointSelfTest, SelfTestReport},
+            Motion, MotionError,
+        },
+        Arm,
+    },
+    error::Error,
+};
 
 /// This response contains the current kinematic state.
 #[derive(Serialize)]
@@ -31,17 +48,97 @@ pub struct MoveEndEffectorCommand {
     pub target_position: Vector3<f64>,
 }
 
-/// This is the
+/// This response contains the result of moving or rotating the end effector.
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
-pub enum MoveEndEffectorResponse {
+pub enum EndEffectorResponse {
     Unreachable,
+    NotConverged {
+        delta_position_magnitude: f64,
+        iterations: usize,
+    },
     Reached {
         delta_position_magnitude: f64,
         iterations: usize,
     },
 }
 
+impl From<&IKSolverResult> for EndEffectorResponse {
+    fn from(value: &IKSolverResult) -> Self {
+        match *value {
+            IKSolverResult::Unreachable => Self::Unreachable,
+            IKSolverResult::NotConverged {
+                iterations,
+                delta_position_magnitude,
+            } => Self::NotConverged {
+                delta_position_magnitude,
+                iterations,
+            },
+            IKSolverResult::Reached {
+                iterations,
+                delta_position_magnitude,
+                ..
+            } => Self::Reached {
+                delta_position_magnitude,
+                iterations,
+            },
+        }
+    }
+}
+
+/// This error is returned when moving or rotating the end effector failed, it keeps the details of
+///  the kinematic error so the frontend can show which joint is the problem.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "reason", rename_all = "camelCase")]
+pub enum MoveEndEffectorError {
+    JointLimitViolated { joint: usize, value: f64 },
+    NonFiniteAngle { joint: usize, value: f64 },
+    NearSingularity,
+    MaxIterationsExceeded { iterations: usize },
+    Other { message: String },
+}
+
+impl From<Error> for MoveEndEffectorError {
+    fn from(value: Error) -> Self {
+        match value {
+            Error::KinematicError(KinematicError::JointLimitViolated { joint, value }) => {
+                Self::JointLimitViolated { joint, value }
+            }
+            Error::KinematicError(KinematicError::NonFiniteAngle { joint, value }) => {
+                Self::NonFiniteAngle { joint, value }
+            }
+            Error::KinematicError(KinematicError::NearSingularity) => Self::NearSingularity,
+            Error::KinematicError(KinematicError::MaxIterationsExceeded { iterations }) => {
+                Self::MaxIterationsExceeded { iterations }
+            }
+            error => Self::Other {
+                message: error.to_string(),
+            },
+        }
+    }
+}
+
+/// This command will rotate the end effector.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotateEndEffectorCommand {
+    pub target_orientation: Vector3<f64>, // The target orientation (as euler angles in radians).
+}
+
+/// This command will compute the vertices of the arm along a trajectory.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComputeTrajectoryPathCommand {
+    pub states: Vec<KinematicState>,
+}
+
+/// This response contains the vertices of the arm for each state of the trajectory, in order.
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ComputeTrajectoryPathResponse {
+    pub vertices: Vec<[Vector3<f64>; 6]>,
+}
+
 /// This command contains the response to the get vertices command.
 #[derive(Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -49,3 +146,381 @@ pub struct GetVerticesResponse {
     pub vertices: [Vector3<f64>; 6],
 }
 
+/// This command will start a motion of the end effector from where it is now, interrupting the
+///  current motion (if any).
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum StartMotionCommand {
+    #[serde(rename_all = "camelCase")]
+    Linear {
+        target_position: Vector3<f64>,
+        speed: f64, // The speed (in meters/second).
+    },
+    #[serde(rename_all = "camelCase")]
+    Arc {
+        center_position: Vector3<f64>,
+        target_position: Vector3<f64>,
+        normal: Vector3<f64>, // The normal of the plane the arc lies in.
+        clockwise: bool,
+        speed: f64, // The speed (in meters/second).
+    },
+}
+
+impl StartMotionCommand {
+    /// Create the motion starting at the given position, the speed may not exceed the given
+    ///  maximum speed (in meters/second).
+    pub fn into_motion(
+        self,
+        original_position: Vector3<f64>,
+        max_speed: f64,
+    ) -> Result<Box<dyn Motion>, Error> {
+        match self {
+            Self::Linear {
+                target_position,
+                speed,
+            } => Ok(Box::new(LinearMotion::new_with_max_speed(
+                original_position,
+                target_position,
+                speed,
+                max_speed,
+            )?)),
+            Self::Arc {
+                center_position,
+                target_position,
+                normal,
+                clockwise,
+                speed,
+            } => {
+                if !(speed > 0_f64 && speed <= max_speed) {
+                    return Err(MotionError::InvalidSpeed { speed, max_speed }.into());
+                }
+
+                Ok(Box::new(ArcMotion::new(
+                    center_position,
+                    original_position,
+                    target_position,
+                    normal,
+                    clockwise,
+                    speed,
+                )))
+            }
+        }
+    }
+}
+
+/// This command will move the end effector in a straight line to a pose, turning it along the
+///  way, interrupting the current motion (if any).
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MoveToPoseCommand {
+    pub target_position: Vector3<f64>,
+    pub approach: f64, // The angle of the last links from the vertical (in radians).
+    pub roll: f64,     // The wrist roll (in radians).
+    pub speed: f64,    // The speed (in meters/second).
+}
+
+/// This response contains the end effector positions along a motion, sampled at a fixed interval
+///  (and at its end), so the frontend can draw the path before starting it.
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewMotionResponse {
+    pub path: Vec<Vector3<f64>>,
+}
+
+/// This response contains the outcome of moving a single joint during the self-test.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JointSelfTestResponse {
+    pub joint: usize,
+    pub commanded: f64,
+    pub reported: Option<f64>,
+    pub passed: bool,
+    pub error: Option<String>,
+}
+
+impl From<&JointSelfTest> for JointSelfTestResponse {
+    fn from(value: &JointSelfTest) -> Self {
+        Self {
+            joint: value.joint.index(),
+            commanded: value.commanded,
+            reported: value.reported,
+            passed: value.passed,
+            error: value.error.clone(),
+        }
+    }
+}
+
+/// This response contains the outcome of the self-test, with a result for every joint.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RunSelfTestResponse {
+    pub passed: bool, // Whether every joint moved as commanded.
+    pub joints: Vec<JointSelfTestResponse>,
+}
+
+impl From<&SelfTestReport> for RunSelfTestResponse {
+    fn from(value: &SelfTestReport) -> Self {
+        Self {
+            passed: value.is_ok(),
+            joints: value
+                .joints
+                .iter()
+                .map(JointSelfTestResponse::from)
+                .collect(),
+        }
+    }
+}
+
+/// This enum represents a kind of motion the arm can run.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum MotionKind {
+    Linear,
+    Arc,
+    Circle,
+    MinimumJerkJoint,
+}
+
+/// This response contains the operations the arm supports, so the frontend can adapt to the
+///  solver that's configured instead of assuming every operation exists.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    pub translation: bool,        // Whether the end-effector can be moved.
+    pub orientation: bool,        // Whether the end-effector can be rotated.
+    pub multiple_solutions: bool, // Whether all solutions for a target are found.
+    pub exact: bool,              // Whether the solutions are exact, not iterated.
+    pub motions: Vec<MotionKind>, // The motions that can be run.
+}
+
+impl From<&Arm> for Capabilities {
+    fn from(value: &Arm) -> Self {
+        let solver_capabilities = value.kinematic_solver().capabilities();
+
+        Self {
+            // Every solver can at least move the end-effector.
+            translation: true,
+            orientation: solver_capabilities.supports_orientation,
+            multiple_solutions: solver_capabilities.supports_multiple_solutions,
+            exact: solver_capabilities.is_exact,
+            // The motions are all solved by translating the end-effector, or run in joint space.
+            motions: vec![
+                MotionKind::Linear,
+                MotionKind::Arc,
+                MotionKind::Circle,
+                MotionKind::MinimumJerkJoint,
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Arc;
+
+    use kinematics::{
+        forward::algorithms::{analytical::AnalyticalFKAlgorithm, ForwardKinematicAlgorithm},
+        inverse::{
+            algorithms::heuristic::HeuristicIKAlgorithm,
+            solvers::{heuristic::HeuristicSolver, IKSolverResult},
+        },
+        model::{Joint, KinematicParameters, KinematicState},
+    };
+    use nalgebra::Vector3;
+    use tokio::sync::{broadcast, mpsc, watch};
+
+    use crate::arm::{
+        motion::{
+            player::{self, JointSelfTest, SelfTestReport},
+            MotionError,
+        },
+        registry::ArmEntry,
+        Arm,
+    };
+    use crate::error::Error;
+    use crate::frontend::commands::arm::{
+        Capabilities, EndEffectorResponse, MotionKind, MoveEndEffectorError, RunSelfTestResponse,
+        StartMotionCommand,
+    };
+
+    #[test]
+    pub fn joint_limit_violation_is_structured() {
+        let params = KinematicParameters {
+            joint_limits: [
+                [-0.5_f64, 0.5_f64],
+                [-3_f64, 3_f64],
+                [-3_f64, 3_f64],
+                [-3_f64, 3_f64],
+                [-3_f64, 3_f64],
+            ],
+            ..KinematicParameters::default()
+        };
+
+        // A target that can only be reached by turning the base far beyond its limits.
+        let target_position = AnalyticalFKAlgorithm::default().limb4_position_vector(
+            &params,
+            &KinematicState {
+                theta_0: 2_f64,
+                ..KinematicState::default()
+            },
+        );
+
+        let arm = Arm::new(
+            params,
+            KinematicState::default(),
+            Arc::new(
+                HeuristicSolver::builder(
+                    Arc::new(HeuristicIKAlgorithm::default()),
+                    Arc::new(AnalyticalFKAlgorithm::default()),
+                )
+                .build(),
+            ),
+        );
+        let (instruction_sender, _) = mpsc::channel(1_usize);
+        let (joint_limit_warnings, _) = broadcast::channel(1_usize);
+        let (motion_failures, _) = broadcast::channel(1_usize);
+        let (kinematic_state, _) = watch::channel(arm.kinematic_state().clone());
+        let entry = ArmEntry::new(
+            Arc::new(arm),
+            player::Handle::new(
+                instruction_sender,
+                joint_limit_warnings,
+                motion_failures,
+                kinematic_state,
+            ),
+        );
+
+        let Err(error) = entry.move_end_effector(&target_position) else {
+            panic!("Expected the target to violate the joint limits");
+        };
+
+        let error: MoveEndEffectorError = error.into();
+        assert!(matches!(
+            error,
+            MoveEndEffectorError::JointLimitViolated { joint: 0_usize, value } if value.abs() > 0.5_f64
+        ));
+
+        // The frontend gets the reason along with the details.
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(json["reason"], "jointLimitViolated");
+        assert_eq!(json["joint"], 0_usize);
+    }
+
+    #[test]
+    pub fn solver_result_into_response() {
+        let reached = IKSolverResult::Reached {
+            iterations: 3_usize,
+            delta_position_magnitude: 0.001_f64,
+            new_state: KinematicState::default(),
+        };
+        assert!(matches!(
+            EndEffectorResponse::from(&reached),
+            EndEffectorResponse::Reached { iterations: 3_usize, delta_position_magnitude }
+                if delta_position_magnitude == 0.001_f64
+        ));
+
+        let not_converged = IKSolverResult::NotConverged {
+            iterations: 200_usize,
+            delta_position_magnitude: 0.5_f64,
+        };
+        assert!(matches!(
+            EndEffectorResponse::from(&not_converged),
+            EndEffectorResponse::NotConverged {
+                iterations: 200_usize,
+                ..
+            }
+        ));
+
+        assert!(matches!(
+            EndEffectorResponse::from(&IKSolverResult::Unreachable),
+            EndEffectorResponse::Unreachable
+        ));
+    }
+
+    #[test]
+    pub fn default_arm_translates_only() {
+        let arm = Arm::new(
+            KinematicParameters::default(),
+            KinematicState::default(),
+            Arc::new(
+                HeuristicSolver::builder(
+                    Arc::new(HeuristicIKAlgorithm::default()),
+                    Arc::new(AnalyticalFKAlgorithm::default()),
+                )
+                .build(),
+            ),
+        );
+
+        let capabilities = Capabilities::from(&arm);
+        assert!(capabilities.translation);
+        assert!(!capabilities.orientation);
+        assert!(capabilities.motions.contains(&MotionKind::Linear));
+
+        let json = serde_json::to_value(&capabilities).unwrap();
+        assert_eq!(json["multipleSolutions"], false);
+        assert_eq!(json["motions"][0_usize], "linear");
+    }
+
+    #[test]
+    pub fn start_motion_command_into_motion() {
+        let original_position = Vector3::<f64>::new(0.1_f64, 0.2_f64, 0_f64);
+
+        let command: StartMotionCommand = serde_json::from_value(serde_json::json!({
+            "kind": "linear",
+            "targetPosition": [0.1_f64, 0.2_f64, 0.5_f64],
+            "speed": 0.25_f64,
+        }))
+        .unwrap();
+
+        // The motion starts where the end effector is now.
+        let motion = command.into_motion(original_position, 1_f64).unwrap();
+        assert_eq!(motion.interpolate(0_f64).unwrap(), original_position);
+        assert!((motion.duration() - 2_f64).abs() < 1e-12_f64);
+
+        let command: StartMotionCommand = serde_json::from_value(serde_json::json!({
+            "kind": "arc",
+            "centerPosition": [0_f64, 0.2_f64, 0_f64],
+            "targetPosition": [-0.1_f64, 0.2_f64, 0_f64],
+            "normal": [0_f64, 1_f64, 0_f64],
+            "clockwise": false,
+            "speed": 2_f64,
+        }))
+        .unwrap();
+
+        // Arcs may not go faster than the arm either.
+        assert!(matches!(
+            command.into_motion(original_position, 1_f64),
+            Err(Error::MotionError(MotionError::InvalidSpeed { .. }))
+        ));
+    }
+
+    #[test]
+    pub fn self_test_report_into_response() {
+        let report = SelfTestReport {
+            joints: vec![
+                JointSelfTest {
+                    joint: Joint::Base,
+                    commanded: 0.1_f64,
+                    reported: Some(0.1_f64),
+                    passed: true,
+                    error: None,
+                },
+                JointSelfTest {
+                    joint: Joint::Shoulder,
+                    commanded: 0.1_f64,
+                    reported: None,
+                    passed: false,
+                    error: Some("No pose reported".into()),
+                },
+            ],
+        };
+
+        let response = RunSelfTestResponse::from(&report);
+        assert!(!response.passed);
+        assert_eq!(response.joints[1_usize].joint, Joint::Shoulder.index());
+
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["joints"][0_usize]["passed"], true);
+        assert_eq!(json["joints"][1_usize]["error"], "No pose reported");
+    }
+}
@@ -1,7 +1,9 @@
 use nalgebra::Vector3;
 use serde::Serialize;
 
-use kinematics::model::KinematicState;
+use kinematics::model::{JointLimitWarning, KinematicState};
+
+use crate::error::Error;
 
 #[derive(Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -10,3 +12,36 @@ pub struct ArmStateChangedEvent {
     pub vertices: [Vector3<f64>; 6],
 }
 
+/// This event is published when a joint enters the band between its soft and hard limits.
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct JointLimitWarningEvent {
+    pub joint: usize,
+    pub value: f64,
+    pub margin: f64,
+}
+
+impl From<JointLimitWarning> for JointLimitWarningEvent {
+    fn from(value: JointLimitWarning) -> Self {
+        Self {
+            joint: value.joint,
+            value: value.value,
+            margin: value.margin,
+        }
+    }
+}
+
+/// This event is published when a motion fails, the arm stops but still accepts new motions.
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MotionFailedEvent {
+    pub message: String,
+}
+
+impl From<&Error> for MotionFailedEvent {
+    fn from(value: &Error) -> Self {
+        Self {
+            message: value.to_string(),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use tokio::{
+    sync::watch::{self, error::RecvError},
+    time::{self, Duration, MissedTickBehavior},
+};
+
+/// Call the given closure with the latest value of the watch channel whenever it changes, but no
+///  more often than once per the given interval (if any). The values that change within the same
+///  interval are coalesced, and the latest one is always emitted once the interval has passed, so
+///  the final state after a burst of changes never gets lost.
+pub async fn emit_throttled<T, E>(
+    mut receiver: watch::Receiver<T>,
+    min_interval: Option<Duration>,
+    mut emit: impl FnMut(T) -> Result<(), E>,
+) -> Result<(), E>
+where
+    T: Clone,
+    E: From<RecvError>,
+{
+    let mut interval = min_interval.map(|min_interval| {
+        let mut interval = time::interval(min_interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        interval
+    });
+
+    loop {
+        // Wait for the value to be changed.
+        receiver.changed().await?;
+
+        // Wait for the interval to pass, the value may change a few more times meanwhile.
+        if let Some(interval) = &mut interval {
+            interval.tick().await;
+        }
+
+        // Emit the latest value, marking all the changes up to now as seen.
+        let value = receiver.borrow_and_update().clone();
+        emit(value)?;
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use tokio::{
+        sync::watch::{self, error::RecvError},
+        time::{self, Duration},
+    };
+
+    use crate::frontend::events::throttle::emit_throttled;
+
+    #[tokio::test(start_paused = true)]
+    pub async fn rapid_changes_coalesced() {
+        let (sender, receiver) = watch::channel(0_usize);
+        let mut emitted: Vec<usize> = Vec::new();
+
+        // Change the value every millisecond for a second, while emitting at most at 30 Hz.
+        let (result, _) = tokio::join!(
+            emit_throttled(receiver, Some(Duration::from_millis(33_u64)), |x| {
+                emitted.push(x);
+                Ok::<(), RecvError>(())
+            }),
+            async move {
+                for i in 1_usize..=1000_usize {
+                    sender.send_replace(i);
+                    time::sleep(Duration::from_millis(1_u64)).await;
+                }
+            }
+        );
+        assert!(result.is_err());
+
+        // About one emit per interval, and the final state is always among them.
+        assert!(emitted.len() <= 1000_usize / 33_usize + 2_usize);
+        assert_eq!(emitted.last(), Some(&1000_usize));
+    }
+}
@@ -1,24 +1,32 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use std::{error::Error, sync::Arc};
+use std::{error::Error, sync::Arc, time::Duration};
 
 use arm::{
-    motion::player::{self, Player},
+    motion::{
+        player::{self, Player},
+        Pose,
+    },
+    registry::{ArmEntry, ArmId, ArmRegistry},
     Arm,
 };
 use com::client::Client;
 use frontend::{
     commands::arm::{
-        GetKinematicParametersResponse, GetKinematicStateResponse, GetVerticesResponse,
-        MoveEndEffectorCommand, MoveEndEffectorResponse,
+        Capabilities, ComputeTrajectoryPathCommand, ComputeTrajectoryPathResponse,
+        EndEffectorResponse, GetKinematicParametersResponse, GetKinematicStateResponse,
+        GetVerticesResponse, MoveEndEffectorCommand, MoveEndEffectorError, MoveToPoseCommand,
+        PreviewMotionResponse, RotateEndEffectorCommand, RunSelfTestResponse, StartMotionCommand,
+        UpdateKinematicStateCommand,
+    },
+    events::{
+        arm::{ArmStateChangedEvent, JointLimitWarningEvent, MotionFailedEvent},
+        throttle::emit_throttled,
     },
-    events::arm::ArmStateChangedEvent,
 };
 use kinematics::{
-    forward::algorithms::{
-        analytical::AnalyticalFKAlgorithm, compute_arm_vertices, ForwardKinematicAlgorithm,
-    },
+    forward::algorithms::analytical::AnalyticalFKAlgorithm,
     inverse::{
         algorithms::heuristic::HeuristicIKAlgorithm,
         solvers::{heuristic::HeuristicSolver, IKSolverResult},
@@ -26,9 +34,12 @@ use kinematics::{
     model::{KinematicParameters, KinematicState},
 };
 use nalgebra::Vector3;
-use servo_com::Handle;
+use servo_com::{watchdog::WatchdogConfig, ServoCom};
 use tauri::Manager;
-use tokio::sync::watch::Receiver as WatchReceiver;
+use tokio::{
+    sync::{broadcast::error::RecvError, watch::Receiver as WatchReceiver},
+    task::JoinSet,
+};
 use tokio_util::{sync::CancellationToken, task::TaskTracker};
 
 mod arm;
@@ -36,18 +47,28 @@ mod error;
 mod frontend;
 mod servo_com;
 
+/// The arms managed by the app, along with the address of their servo controller.
+const ARMS: [(ArmId, &str); 1] = [(ArmId::new(0_u32), "127.0.0.1:5000")];
+
+/// The minimum interval between two state changed events of the same arm (about 30 Hz), or None
+///  to publish every single change.
+const STATE_CHANGED_MIN_INTERVAL: Option<Duration> = Some(Duration::from_millis(33_u64));
+
+/// The interval at which the path of a motion is sampled for its preview (in seconds).
+const MOTION_PREVIEW_DELTA_TIME: f64 = 0.05_f64;
+
 struct AppState {
-    player_handle: player::Handle,
+    arms: ArmRegistry,
 }
 
 impl AppState {
-    pub fn new(player_handle: player::Handle) -> Self {
-        Self { player_handle }
+    pub fn new(arms: ArmRegistry) -> Self {
+        Self { arms }
     }
 
     #[inline]
-    pub fn player_handle(&self) -> &player::Handle {
-        &self.player_handle
+    pub fn arms(&self) -> &ArmRegistry {
+        &self.arms
     }
 }
 
@@ -59,101 +80,344 @@ fn greet(name: &str) -> String {
 
 /// This command gets the vertices.
 #[tauri::command]
-fn get_vertices(arm_state: tauri::State<AppState>) -> GetVerticesResponse {
-    todo!()
+fn get_vertices(
+    app_state: tauri::State<AppState>,
+    arm_id: ArmId,
+) -> Result<GetVerticesResponse, String> {
+    let entry: &ArmEntry = app_state.arms().get(arm_id).map_err(|e| e.to_string())?;
+    let state: KinematicState = entry.kinematic_state().borrow().clone();
+
+    let vertices: [Vector3<f64>; 6] = entry.arm().vertices_at(&state);
+
+    Ok(GetVerticesResponse { vertices })
+}
+
+/// This command computes the vertices of the arm for a whole trajectory at once, which saves a
+///  round trip per state.
+#[tauri::command]
+fn compute_trajectory_path(
+    app_state: tauri::State<AppState>,
+    arm_id: ArmId,
+    command: ComputeTrajectoryPathCommand,
+) -> Result<ComputeTrajectoryPathResponse, String> {
+    let entry: &ArmEntry = app_state.arms().get(arm_id).map_err(|e| e.to_string())?;
+
+    let vertices: Vec<[Vector3<f64>; 6]> = entry.arm().trajectory_vertices(&command.states);
+
+    Ok(ComputeTrajectoryPathResponse { vertices })
 }
 
 /// This handler can be used to get the kinematic state.
 #[tauri::command]
-fn get_kinematic_state(arm_state: tauri::State<AppState>) -> GetKinematicStateResponse {
-    let kinematic_state: KinematicState = arm_state.kinematic_state.borrow().clone();
+fn get_kinematic_state(
+    app_state: tauri::State<AppState>,
+    arm_id: ArmId,
+) -> Result<GetKinematicStateResponse, String> {
+    let entry: &ArmEntry = app_state.arms().get(arm_id).map_err(|e| e.to_string())?;
+    let kinematic_state: KinematicState = entry.kinematic_state().borrow().clone();
+
+    Ok(GetKinematicStateResponse { kinematic_state })
+}
 
-    GetKinematicStateResponse { kinematic_state }
+/// This handler can be used to set the kinematic state directly, it's rejected if any of the
+///  angles isn't finite or lies beyond its joint limits.
+#[tauri::command]
+fn update_kinematic_state(
+    app_state: tauri::State<AppState>,
+    arm_id: ArmId,
+    command: UpdateKinematicStateCommand,
+) -> Result<(), String> {
+    let entry: &ArmEntry = app_state.arms().get(arm_id).map_err(|e| e.to_string())?;
+
+    entry
+        .update_kinematic_state(&command.new_kinematic_state)
+        .map_err(|e| e.to_string())
 }
 
 /// This handler can be used to get the kinematic parameters.
 #[tauri::command]
-fn get_kinematic_parameters(arm_state: tauri::State<AppState>) -> GetKinematicParametersResponse {
-    let kinematic_parameters: KinematicParameters = arm_state.kinematic_parameters.clone();
-
-    GetKinematicParametersResponse {
+fn get_kinematic_parameters(
+    app_state: tauri::State<AppState>,
+    arm_id: ArmId,
+) -> Result<GetKinematicParametersResponse, String> {
+    let entry: &ArmEntry = app_state.arms().get(arm_id).map_err(|e| e.to_string())?;
+    let kinematic_parameters: KinematicParameters = entry.arm().kinematic_parameters().clone();
+
+    Ok(GetKinematicParametersResponse {
         kinematic_parameters,
-    }
+    })
+}
+
+/// This handler can be used to get the operations the arm supports.
+#[tauri::command]
+fn get_capabilities(
+    app_state: tauri::State<AppState>,
+    arm_id: ArmId,
+) -> Result<Capabilities, String> {
+    let entry: &ArmEntry = app_state.arms().get(arm_id).map_err(|e| e.to_string())?;
+
+    Ok(Capabilities::from(entry.arm().as_ref()))
 }
 
 #[tauri::command]
 fn move_end_effector(
-    arm_state: tauri::State<AppState>,
+    app_state: tauri::State<AppState>,
+    arm_id: ArmId,
     command: MoveEndEffectorCommand,
-) -> Result<MoveEndEffectorResponse, String> {
-    // Get the kinematic parameters and state.
-    let params: KinematicParameters = arm_state.kinematic_parameters.clone();
-    let state: KinematicState = arm_state.kinematic_state.borrow().clone();
-
-    // Comoute the new kinematic state.
-    let solver_result: IKSolverResult = arm_state
-        .kinematic_solver
-        .translate_limb4_end_effector(&params, &state, &command.target_position)
-        .map_err(|_| "Failed to translate end effector")?;
-
-    match solver_result {
-        IKSolverResult::Reached {
-            iterations,
-            delta_position_magnitude,
-            new_state,
-        } => {
-            // Send the new kinematic state.
-            arm_state
-                .kinematic_state
-                .send(new_state)
-                .map_err(|_| "Failed to send new kinematic state")?;
-
-            // Return that we reached the target position.
-            Ok(MoveEndEffectorResponse::Reached {
-                delta_position_magnitude,
-                iterations,
-            })
-        }
-        IKSolverResult::Unreachable => Ok(MoveEndEffectorResponse::Unreachable),
-    }
+) -> Result<EndEffectorResponse, MoveEndEffectorError> {
+    let entry: &ArmEntry = app_state.arms().get(arm_id)?;
+
+    // Compute the new kinematic state, the arm is moved if the target is reached.
+    let solver_result: IKSolverResult = entry.move_end_effector(&command.target_position)?;
+
+    Ok(EndEffectorResponse::from(&solver_result))
+}
+
+#[tauri::command]
+fn rotate_end_effector(
+    app_state: tauri::State<AppState>,
+    arm_id: ArmId,
+    command: RotateEndEffectorCommand,
+) -> Result<EndEffectorResponse, MoveEndEffectorError> {
+    let entry: &ArmEntry = app_state.arms().get(arm_id)?;
+
+    // Compute the new kinematic state, the arm is rotated if the target is reached.
+    let solver_result: IKSolverResult = entry.rotate_end_effector(&command.target_orientation)?;
+
+    Ok(EndEffectorResponse::from(&solver_result))
+}
+
+/// This handler starts a motion of the end effector from where it is now, interrupting the current
+///  motion (if any). It returns once the player got the motion, not once the motion finished.
+#[tauri::command]
+async fn start_motion(
+    app_state: tauri::State<'_, AppState>,
+    arm_id: ArmId,
+    command: StartMotionCommand,
+) -> Result<(), String> {
+    let entry: &ArmEntry = app_state.arms().get(arm_id).map_err(|e| e.to_string())?;
+
+    let state: KinematicState = entry.kinematic_state().borrow().clone();
+    let (original_position, _) = entry.arm().end_effector_pose_at(&state);
+
+    let motion = command
+        .into_motion(original_position, entry.arm().max_speed())
+        .map_err(|e| e.to_string())?;
+
+    entry
+        .player_handle()
+        .start_motion(motion)
+        .await
+        .map_err(|e| e.to_string())
 }
 
-/// This function will handle arm state changes.
+/// This handler moves the end effector in a straight line to the given pose, interrupting the
+///  current motion (if any). It returns once the player got the pose, not once it's reached.
+#[tauri::command]
+async fn move_to_pose(
+    app_state: tauri::State<'_, AppState>,
+    arm_id: ArmId,
+    command: MoveToPoseCommand,
+) -> Result<(), String> {
+    let entry: &ArmEntry = app_state.arms().get(arm_id).map_err(|e| e.to_string())?;
+
+    let target = Pose {
+        position: command.target_position,
+        approach: command.approach,
+        roll: command.roll,
+    };
+
+    entry
+        .player_handle()
+        .move_to_pose(target, command.speed)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// This handler samples the path the end effector would take for the given motion from where it
+///  is now, without starting it.
+#[tauri::command]
+fn preview_motion(
+    app_state: tauri::State<AppState>,
+    arm_id: ArmId,
+    command: StartMotionCommand,
+) -> Result<PreviewMotionResponse, String> {
+    let entry: &ArmEntry = app_state.arms().get(arm_id).map_err(|e| e.to_string())?;
+
+    let state: KinematicState = entry.kinematic_state().borrow().clone();
+    let (original_position, _) = entry.arm().end_effector_pose_at(&state);
+
+    let motion = command
+        .into_motion(original_position, entry.arm().max_speed())
+        .map_err(|e| e.to_string())?;
+
+    Ok(PreviewMotionResponse {
+        path: motion.sample_all(MOTION_PREVIEW_DELTA_TIME),
+    })
+}
+
+/// This handler stops the current motion, the poses the servo hasn't executed yet are dropped so
+///  the arm halts promptly.
+#[tauri::command]
+async fn stop_motion(app_state: tauri::State<'_, AppState>, arm_id: ArmId) -> Result<(), String> {
+    let entry: &ArmEntry = app_state.arms().get(arm_id).map_err(|e| e.to_string())?;
+
+    entry
+        .player_handle()
+        .stop_motion()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// This handler wiggles every joint a small amount and back, and reports whether the servo
+///  followed. The current motion (if any) is interrupted.
+#[tauri::command]
+async fn run_self_test(
+    app_state: tauri::State<'_, AppState>,
+    arm_id: ArmId,
+) -> Result<RunSelfTestResponse, String> {
+    let entry: &ArmEntry = app_state.arms().get(arm_id).map_err(|e| e.to_string())?;
+
+    let report = entry
+        .player_handle()
+        .run_self_test()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(RunSelfTestResponse::from(&report))
+}
+
+/// This function will handle the state changes of all the arms.
 async fn handle_arm_state_changes(app_handle: tauri::AppHandle) -> Result<(), Box<dyn Error>> {
-    let arm_state = app_handle.state::<AppState>();
+    let mut tasks: JoinSet<Result<(), String>> = JoinSet::new();
+
+    for arm_id in app_handle.state::<AppState>().arms().ids() {
+        let app_handle = app_handle.clone();
+
+        tasks.spawn({
+            let app_handle = app_handle.clone();
+
+            async move {
+                handle_arm_state_change(app_handle, arm_id)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+        });
+
+        tasks.spawn({
+            let app_handle = app_handle.clone();
+
+            async move {
+                handle_joint_limit_warnings(app_handle, arm_id)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+        });
+
+        tasks.spawn(async move {
+            handle_motion_failures(app_handle, arm_id)
+                .await
+                .map_err(|e| e.to_string())
+        });
+    }
 
-    let mut receiver: WatchReceiver<KinematicState> = arm_state.kinematic_state.subscribe();
+    // Stop as soon as one of the arms fails.
+    while let Some(result) = tasks.join_next().await {
+        result??;
+    }
 
-    loop {
-        // Wait for the kinematic state to be changed.
-        receiver.changed().await?;
+    Ok(())
+}
+
+/// This function will handle the state changes of the arm with the given id.
+async fn handle_arm_state_change(
+    app_handle: tauri::AppHandle,
+    arm_id: ArmId,
+) -> Result<(), Box<dyn Error>> {
+    let app_state = app_handle.state::<AppState>();
+    let entry: &ArmEntry = app_state.arms().get(arm_id)?;
 
-        // Get the kinematic parameters and the kinematic state.
-        let params: KinematicParameters = arm_state.kinematic_parameters.clone();
-        let state: KinematicState = receiver.borrow().clone();
+    let receiver: WatchReceiver<KinematicState> = entry.kinematic_state().subscribe();
 
+    // Publish the latest state whenever it changes, coalescing the changes that happen faster
+    //  than the frontend can render them.
+    emit_throttled(receiver, STATE_CHANGED_MIN_INTERVAL, |state| {
         // Compute all the vertices.
-        let forward_algorithm: &Arc<dyn ForwardKinematicAlgorithm> =
-            arm_state.kinematic_solver.forward_algorithm();
-        let vertices: [Vector3<f64>; 6] = compute_arm_vertices(forward_algorithm, &params, &state);
+        let vertices: [Vector3<f64>; 6] = entry.arm().vertices_at(&state);
 
         // Publish the event.
         app_handle.emit_all(
-            "arm:state-changed",
+            &arm_id.event_name("state-changed"),
             ArmStateChangedEvent {
                 kinematic_state: state,
                 vertices,
             },
         )?;
+
+        Ok::<(), Box<dyn Error>>(())
+    })
+    .await
+}
+
+/// This function will forward the joint limit warnings of the arm with the given id.
+async fn handle_joint_limit_warnings(
+    app_handle: tauri::AppHandle,
+    arm_id: ArmId,
+) -> Result<(), Box<dyn Error>> {
+    let app_state = app_handle.state::<AppState>();
+    let entry: &ArmEntry = app_state.arms().get(arm_id)?;
+
+    let mut receiver = entry.player_handle().subscribe_joint_limit_warnings();
+
+    loop {
+        // Wait for the next warning, skipping the ones we couldn't keep up with.
+        let warning = match receiver.recv().await {
+            Ok(warning) => warning,
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => return Ok(()),
+        };
+
+        // Publish the event.
+        app_handle.emit_all(
+            &arm_id.event_name("joint-limit-warning"),
+            JointLimitWarningEvent::from(warning),
+        )?;
     }
 }
 
-#[tokio::main]
-async fn main() {
-    let (client_handle, mut client_worker) = Client::connect("127.0.0.1:5000").await.unwrap();
+/// This function will forward the motion failures of the arm with the given id.
+async fn handle_motion_failures(
+    app_handle: tauri::AppHandle,
+    arm_id: ArmId,
+) -> Result<(), Box<dyn Error>> {
+    let app_state = app_handle.state::<AppState>();
+    let entry: &ArmEntry = app_state.arms().get(arm_id)?;
 
-    let task_tracker = TaskTracker::new();
-    let cancellation_token = CancellationToken::new();
+    let mut receiver = entry.player_handle().subscribe_motion_failures();
+
+    loop {
+        // Wait for the next failure, skipping the ones we couldn't keep up with.
+        let failure = match receiver.recv().await {
+            Ok(failure) => failure,
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => return Ok(()),
+        };
+
+        // Publish the event.
+        app_handle.emit_all(
+            &arm_id.event_name("motion-failed"),
+            MotionFailedEvent::from(&*failure),
+        )?;
+    }
+}
+
+/// Connect to the servo controller at the given address and spawn everything an arm needs.
+async fn spawn_arm(
+    address: &str,
+    task_tracker: &TaskTracker,
+    cancellation_token: &CancellationToken,
+) -> ArmEntry {
+    let (client_handle, mut client_worker) = Client::connect(address).await.unwrap();
 
     // Spawn the client worker.
     task_tracker.spawn({
@@ -164,9 +428,33 @@ async fn main() {
         }
     });
 
+    // Create the servo communication on top of the client.
+    let (mut servo_com_worker, servo_com_handle) = ServoCom::from_client(client_handle);
+
+    // Spawn the servo communication worker.
+    task_tracker.spawn({
+        let cancellation_token = cancellation_token.clone();
+
+        async move {
+            servo_com_worker.run(cancellation_token).await.unwrap();
+        }
+    });
+
+    // Make sure that the servo controller speaks the same protocol before using it.
+    servo_com_handle
+        .get_firmware_info(cancellation_token)
+        .await
+        .unwrap();
+
+    // Start from the actual pose of the arm instead of waiting for it to be reported.
+    let angles = servo_com_handle
+        .get_current_pose(cancellation_token)
+        .await
+        .unwrap();
+
     let arm = Arc::new(Arm::new(
         KinematicParameters::default(),
-        KinematicState::default(),
+        servo_com_handle.calibration().to_model(&angles),
         {
             let ik = Arc::new(HeuristicIKAlgorithm::default());
             let fk = Arc::new(AnalyticalFKAlgorithm::default());
@@ -174,30 +462,69 @@ async fn main() {
         },
     ));
 
-    let player_configuration = player::Configuration::new(0.05_f64);
-    let (player_worker, player_handle) = Player::new(
-        Handle::new(client_handle),
-        player_configuration,
-        arm,
-    );
+    // Spawn the watchdog, which keeps the servo controller alive. If the servo controller stops
+    //  replying the connection is considered dead and everything is cancelled.
+    task_tracker.spawn({
+        let cancellation_token = cancellation_token.clone();
+        let mut watchdog = servo_com_handle.watchdog(WatchdogConfig::default());
+
+        async move {
+            if watchdog.run(cancellation_token.clone()).await.is_err() {
+                cancellation_token.cancel();
+            }
+        }
+    });
+
+    let player_configuration = player::Configuration::new(0.05_f64).with_warm_start(true);
+    let (mut player_worker, player_handle) =
+        Player::new(servo_com_handle, player_configuration, arm.clone());
 
     // Spawn the motion player worker.
-    // task_tracker.spawn({
-    //     let cancellation_token = cancellation_token.clone();
+    task_tracker.spawn({
+        let cancellation_token = cancellation_token.clone();
 
-    //     async move {
-    //         player_worker.run(cancellation_token).await.unwrap();
-    //     }
-    // });
+        async move {
+            player_worker.run(cancellation_token).await.unwrap();
+        }
+    });
+
+    ArmEntry::new(arm, player_handle)
+}
+
+#[tokio::main]
+async fn main() {
+    let task_tracker = TaskTracker::new();
+    let cancellation_token = CancellationToken::new();
+
+    // Make the logs show the names of the servo controller commands and events.
+    ServoCom::register_code_names();
+
+    // Spawn all the arms and register them.
+    let mut arms = ArmRegistry::new();
+
+    for (arm_id, address) in ARMS {
+        let entry = spawn_arm(address, &task_tracker, &cancellation_token).await;
+
+        arms.register(arm_id, entry).unwrap();
+    }
 
     tauri::Builder::default()
-        .manage(AppState::new(player_handle))
+        .manage(AppState::new(arms))
         .invoke_handler(tauri::generate_handler![
             greet,
             get_kinematic_state,
+            update_kinematic_state,
             get_kinematic_parameters,
+            get_capabilities,
             move_end_effector,
-            get_vertices
+            rotate_end_effector,
+            get_vertices,
+            compute_trajectory_path,
+            start_motion,
+            move_to_pose,
+            preview_motion,
+            stop_motion,
+            run_self_test
         ])
         .setup(|app| {
             tauri::async_runtime::spawn({
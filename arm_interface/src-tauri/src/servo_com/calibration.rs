@@ -0,0 +1,100 @@
+use kinematics::model::KinematicState;
+
+/// This struct maps the joint angles of the kinematic model onto the raw angles of the servos,
+///  which may differ by a per-joint offset and sign (e.g. when a gear is mounted backwards).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServoCalibration {
+    pub offsets: [f64; 5], // The raw servo angle at which each joint is at zero (in radians).
+    pub signs: [f64; 5],   // The direction of each servo relative to the model, either 1 or -1.
+}
+
+impl ServoCalibration {
+    pub fn new(offsets: [f64; 5], signs: [f64; 5]) -> Self {
+        let calibration = Self { offsets, signs };
+        assert!(calibration.has_unit_signs());
+
+        calibration
+    }
+
+    /// Check that every sign is either 1 or -1, any other value would scale the joint instead of
+    ///  only flipping its direction.
+    pub fn has_unit_signs(&self) -> bool {
+        self.signs.iter().all(|x| *x == 1_f64 || *x == -1_f64)
+    }
+
+    /// Convert the given kinematic state into the raw angles that are pushed to the servos.
+    pub fn to_servo(&self, state: &KinematicState) -> [f64; 5] {
+        let mut angles = state.angles();
+
+        for (i, angle) in angles.iter_mut().enumerate() {
+            *angle = *angle * self.signs[i] + self.offsets[i];
+        }
+
+        angles
+    }
+
+    /// Convert the given raw servo angles back into the kinematic state of the model.
+    pub fn to_model(&self, angles: &[f64; 5]) -> KinematicState {
+        let mut model_angles = *angles;
+
+        for (i, angle) in model_angles.iter_mut().enumerate() {
+            *angle = (*angle - self.offsets[i]) * self.signs[i];
+        }
+
+        KinematicState {
+            theta_0: model_angles[0_usize],
+            theta_1: model_angles[1_usize],
+            theta_2: model_angles[2_usize],
+            theta_3: model_angles[3_usize],
+            theta_4: model_angles[4_usize],
+        }
+    }
+}
+
+impl Default for ServoCalibration {
+    /// The identity calibration, the servo angles are the model angles.
+    fn default() -> Self {
+        Self::new([0_f64; 5], [1_f64; 5])
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use kinematics::model::KinematicState;
+
+    use crate::servo_com::calibration::ServoCalibration;
+
+    #[test]
+    pub fn inverted_joint_with_offset_round_trips() {
+        let calibration = ServoCalibration::new(
+            [0_f64, 0.1_f64, 0_f64, 0_f64, 0_f64],
+            [1_f64, -1_f64, 1_f64, 1_f64, 1_f64],
+        );
+
+        let state = KinematicState {
+            theta_0: 0.2_f64,
+            theta_1: 0.5_f64,
+            theta_2: -0.3_f64,
+            theta_3: 0.4_f64,
+            theta_4: 0_f64,
+        };
+
+        // The inverted joint is mirrored and then shifted by its offset.
+        let angles = calibration.to_servo(&state);
+        assert!((angles[1_usize] - (-0.4_f64)).abs() < 1e-12_f64);
+        assert_eq!(angles[0_usize], state.theta_0);
+
+        assert!(calibration.to_model(&angles).approx_eq(&state, 1e-12_f64));
+    }
+
+    #[test]
+    pub fn signs_must_be_unit() {
+        assert!(ServoCalibration::default().has_unit_signs());
+
+        let calibration = ServoCalibration {
+            offsets: [0_f64; 5],
+            signs: [1_f64, 0.5_f64, 1_f64, 1_f64, 1_f64],
+        };
+        assert!(!calibration.has_unit_signs());
+    }
+}
@@ -1,5 +1,46 @@
 use com::{client::Command, proto::CommandCode};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+
+/// Command that can be sent to get the firmware information of the servo controller.
+#[derive(Serialize)]
+pub struct GetFirmwareInfoCommand {}
+
+impl GetFirmwareInfoCommand {
+    pub const CODE: CommandCode = CommandCode::const_new(0x00000000_u32);
+
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Command for GetFirmwareInfoCommand {
+    /// Get the command code.
+    fn code(&self) -> CommandCode {
+        Self::CODE
+    }
+}
+
+/// Command that has to be sent periodically to keep the servo controller operating.
+///
+/// The servo controller is expected to stop moving and clear its pose buffer when it doesn't
+/// receive this command within its own timeout, so a crashed client can't leave the arm running.
+#[derive(Serialize)]
+pub struct KeepAliveCommand {}
+
+impl KeepAliveCommand {
+    pub const CODE: CommandCode = CommandCode::const_new(0x00000001_u32);
+
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Command for KeepAliveCommand {
+    /// Get the command code.
+    fn code(&self) -> CommandCode {
+        Self::CODE
+    }
+}
 
 /// Command that can be sent to push a new pose into the pose buffer.
 #[derive(Serialize)]
@@ -9,6 +50,8 @@ pub struct PushIntoPoseBufferCommand {
 }
 
 impl PushIntoPoseBufferCommand {
+    pub const CODE: CommandCode = CommandCode::const_new(0x00000100_u32);
+
     pub fn new(angles: [f64; 5], duration: f64) -> Self {
         Self { angles, duration }
     }
@@ -17,7 +60,7 @@ impl PushIntoPoseBufferCommand {
 impl Command for PushIntoPoseBufferCommand {
     /// Get the command code.
     fn code(&self) -> CommandCode {
-        CommandCode::new(0x00000100_u32)
+        Self::CODE
     }
 }
 
@@ -26,6 +69,8 @@ impl Command for PushIntoPoseBufferCommand {
 pub struct ClearPoseBufferCommand {}
 
 impl ClearPoseBufferCommand {
+    pub const CODE: CommandCode = CommandCode::const_new(0x00000101_u32);
+
     pub fn new() -> Self {
         Self {}
     }
@@ -34,7 +79,7 @@ impl ClearPoseBufferCommand {
 impl Command for ClearPoseBufferCommand {
     /// Get the command code.
     fn code(&self) -> CommandCode {
-        CommandCode::new(0x00000101_u32)
+        Self::CODE
     }
 }
 
@@ -43,6 +88,8 @@ impl Command for ClearPoseBufferCommand {
 pub struct GetPoseBufferCapacityCommand {}
 
 impl GetPoseBufferCapacityCommand {
+    pub const CODE: CommandCode = CommandCode::const_new(0x00000102_u32);
+
     pub fn new() -> Self {
         Self {}
     }
@@ -51,7 +98,7 @@ impl GetPoseBufferCapacityCommand {
 impl Command for GetPoseBufferCapacityCommand {
     /// Get the command code.
     fn code(&self) -> CommandCode {
-        CommandCode::new(0x00000102_u32)
+        Self::CODE
     }
 }
 
@@ -60,6 +107,8 @@ impl Command for GetPoseBufferCapacityCommand {
 pub struct GetPoseBufferAvailableSpaceCommand {}
 
 impl GetPoseBufferAvailableSpaceCommand {
+    pub const CODE: CommandCode = CommandCode::const_new(0x00000103_u32);
+
     pub fn new() -> Self {
         Self {}
     }
@@ -68,6 +117,48 @@ impl GetPoseBufferAvailableSpaceCommand {
 impl Command for GetPoseBufferAvailableSpaceCommand {
     /// Get the command code.
     fn code(&self) -> CommandCode {
-        CommandCode::new(0x00000103_u32)
+        Self::CODE
+    }
+}
+
+/// Command that can be sent to tell the servo controller the angles of the current physical pose,
+///  the angles reported afterwards are relative to this new zero.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct SetZeroOffsetCommand {
+    pub offsets: [f64; 5],
+}
+
+impl SetZeroOffsetCommand {
+    pub const CODE: CommandCode = CommandCode::const_new(0x00000200_u32);
+
+    pub fn new(offsets: [f64; 5]) -> Self {
+        Self { offsets }
+    }
+}
+
+impl Command for SetZeroOffsetCommand {
+    /// Get the command code.
+    fn code(&self) -> CommandCode {
+        Self::CODE
+    }
+}
+
+/// Command that can be sent to get the current angles of the servos, unlike the pose changed
+///  event this is answered right away.
+#[derive(Serialize)]
+pub struct GetCurrentPoseCommand {}
+
+impl GetCurrentPoseCommand {
+    pub const CODE: CommandCode = CommandCode::const_new(0x00000201_u32);
+
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Command for GetCurrentPoseCommand {
+    /// Get the command code.
+    fn code(&self) -> CommandCode {
+        Self::CODE
     }
 }
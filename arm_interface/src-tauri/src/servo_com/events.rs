@@ -1,8 +1,9 @@
 use com::{client::Event, proto::EventCode};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 /// Represents an event that is emitted when the arm pose changes.
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct PoseChangedEvent {
     pub angles: [f64; 5],
 }
@@ -19,7 +20,8 @@ impl Event for PoseChangedEvent {
 }
 
 /// Represents an event that is emitted when the buffer is partially drained.
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct PoseBufferDrainEvent {
     pub available: usize,
 }
@@ -36,7 +38,8 @@ impl Event for PoseBufferDrainEvent {
 }
 
 /// Represents an event that is emitted when the pose buffer is empty.
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct PoseBufferEmptyEvent {}
 
 impl PoseBufferEmptyEvent {
@@ -49,3 +52,23 @@ impl Event for PoseBufferEmptyEvent {
         Self::CODE
     }
 }
+
+/// Represents an event that is periodically emitted with the condition of the servos.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ServoTelemetryEvent {
+    pub per_joint_current: [f64; 5], // The current drawn by each joint (in amperes).
+    pub temperature: [f64; 5],       // The temperature of each joint (in degrees celsius).
+    pub bus_voltage: f64,            // The voltage of the servo bus (in volts).
+}
+
+impl ServoTelemetryEvent {
+    pub const CODE: EventCode = EventCode::const_new(0x00000003_u32);
+}
+
+impl Event for ServoTelemetryEvent {
+    /// Get the event code.
+    fn code(&self) -> EventCode {
+        Self::CODE
+    }
+}
@@ -0,0 +1,47 @@
+use std::collections::VecDeque;
+
+use tokio::time::Duration;
+
+/// This struct keeps the most recent round trip measurements to compute a rolling average, so a
+///  single slow reply doesn't dominate the reported link health.
+#[derive(Debug, Clone)]
+pub struct LatencyStats {
+    samples: VecDeque<Duration>, // The most recent measurements, oldest first.
+}
+
+impl LatencyStats {
+    /// The number of measurements the rolling average is computed over.
+    pub const WINDOW: usize = 16_usize;
+
+    pub fn new() -> Self {
+        Self {
+            samples: VecDeque::with_capacity(Self::WINDOW),
+        }
+    }
+
+    /// Record a new measurement, discarding the oldest one once the window is full.
+    pub fn record(&mut self, latency: Duration) {
+        if self.samples.len() == Self::WINDOW {
+            self.samples.pop_front();
+        }
+
+        self.samples.push_back(latency);
+    }
+
+    /// The average of the recorded measurements, or `None` if nothing has been measured yet.
+    pub fn average(&self) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+
+        let total: Duration = self.samples.iter().sum();
+
+        Some(total / self.samples.len() as u32)
+    }
+}
+
+impl Default for LatencyStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,201 @@
+use std::path::Path;
+
+use com::{
+    client::{self, config::ClientConfig, Client},
+    net::PacketCodec,
+    proto::{CommandCode, EventCode, Packet, Tag},
+};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tokio_util::{
+    bytes::BytesMut,
+    codec::{Encoder, FramedRead},
+    sync::CancellationToken,
+};
+
+/// This struct represents a fake servo controller that tests can use to observe the commands a
+///  client sends, and to reply or publish events to it.
+pub(crate) struct MockServo {
+    stream: TcpStream,
+    cancellation_token: CancellationToken,
+}
+
+impl MockServo {
+    /// Start a mock servo and connect a client to it, the client worker is spawned and keeps
+    ///  running until the mock servo is dropped.
+    pub async fn start() -> (Self, client::Handle) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // Connect the client and accept the connection on the servo side.
+        let (client_handle, mut client_worker) = Client::connect(addr).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+
+        // Every field is written separately, which would otherwise be held back waiting for the
+        //  acknowledgement of the previous one.
+        stream.set_nodelay(true).unwrap();
+
+        // Spawn the client worker.
+        let cancellation_token = CancellationToken::new();
+        tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move { client_worker.run(cancellation_token).await }
+        });
+
+        (
+            Self {
+                stream,
+                cancellation_token,
+            },
+            client_handle,
+        )
+    }
+
+    /// Read the next command sent by the client.
+    pub async fn read_command(&mut self) -> (CommandCode, Tag, Vec<u8>) {
+        let identifier = self.stream.read_u8().await.unwrap();
+        assert_eq!(identifier, Packet::COMMAND_IDENTIFIER);
+
+        let code = CommandCode::new(self.stream.read_u32().await.unwrap());
+        let tag = Tag::new(self.stream.read_u64().await.unwrap());
+        let len = self.stream.read_u32().await.unwrap();
+
+        let mut value = vec![0_u8; len as usize];
+        self.stream.read_exact(&mut value).await.unwrap();
+
+        (code, tag, value)
+    }
+
+    /// Write a reply with the given tag and value to the client.
+    pub async fn write_reply<T>(&mut self, tag: Tag, value: &T)
+    where
+        T: Serialize,
+    {
+        let value = rmp_serde::to_vec(value).unwrap();
+
+        self.stream.write_u8(Packet::REPLY_IDENTIFIER).await.unwrap();
+        self.stream.write_u64(tag.inner()).await.unwrap();
+        self.stream.write_u32(value.len() as u32).await.unwrap();
+        self.stream.write_all(&value).await.unwrap();
+        self.stream.flush().await.unwrap();
+    }
+
+    /// Write an event with the given code and value to the client.
+    pub async fn write_event<T>(&mut self, code: EventCode, value: &T)
+    where
+        T: Serialize,
+    {
+        let value = rmp_serde::to_vec(value).unwrap();
+
+        self.stream.write_u8(Packet::EVENT_IDENTIFIER).await.unwrap();
+        self.stream.write_u32(code.inner()).await.unwrap();
+        self.stream.write_u32(value.len() as u32).await.unwrap();
+        self.stream.write_all(&value).await.unwrap();
+        self.stream.flush().await.unwrap();
+    }
+}
+
+impl Drop for MockServo {
+    fn drop(&mut self) {
+        self.cancellation_token.cancel();
+    }
+}
+
+/// This enum represents a packet recorded from the servo controller, replies are keyed by the
+///  code of the command they answered since the tags differ between sessions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum CapturedPacket {
+    /// An event that's sent as soon as the replay reaches it.
+    Event { event: EventCode, value: Vec<u8> },
+    /// A reply that's sent once the client sent a command with the given code.
+    Reply {
+        command: CommandCode,
+        value: Vec<u8>,
+    },
+}
+
+/// This struct represents a fake servo controller that replays a recorded capture (a JSON list
+///  of `CapturedPacket`s) to a client over an in-memory stream.
+pub(crate) struct MockServer {
+    cancellation_token: CancellationToken,
+}
+
+impl MockServer {
+    /// The size of the in-memory stream between the client and the mock server (in bytes).
+    pub const BUFFER_SIZE: usize = 64_usize * 1024_usize;
+
+    /// Load the capture at the given path and connect a client to it, the client worker and the
+    ///  replay are spawned and keep running until the mock server is dropped.
+    ///
+    /// Commands that no captured reply is waiting for are skipped, so they never get a reply.
+    pub async fn from_capture<P>(path: P) -> (Self, client::Handle)
+    where
+        P: AsRef<Path>,
+    {
+        let capture: Vec<CapturedPacket> =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+
+        let (client_stream, server_stream) = tokio::io::duplex(Self::BUFFER_SIZE);
+        let (reader, writer) = tokio::io::split(client_stream);
+        let (client_handle, mut client_worker) =
+            Client::from_split(reader, writer, &ClientConfig::default());
+
+        let cancellation_token = CancellationToken::new();
+
+        // Spawn the client worker.
+        tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move { client_worker.run(cancellation_token).await }
+        });
+
+        // Spawn the replay, which keeps the stream open once the capture has been replayed.
+        tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            let (reader, mut writer) = tokio::io::split(server_stream);
+            let mut commands = FramedRead::new(reader, PacketCodec::new());
+
+            async move {
+                for captured_packet in capture {
+                    let packet = match captured_packet {
+                        CapturedPacket::Event { event, value } => Packet::Event(event, value),
+                        CapturedPacket::Reply { command, value } => loop {
+                            let packet = tokio::select! {
+                                x = commands.next() => x,
+                                _ = cancellation_token.cancelled() => return,
+                            };
+
+                            match packet {
+                                Some(Ok(Packet::Command(code, tag, _))) if code == command => {
+                                    break Packet::Reply(tag, value);
+                                }
+                                Some(Ok(_)) => continue,
+                                _ => return,
+                            }
+                        },
+                    };
+
+                    let mut buf = BytesMut::new();
+                    PacketCodec::new().encode(packet, &mut buf).unwrap();
+
+                    if writer.write_all(&buf).await.is_err() {
+                        return;
+                    }
+                }
+
+                cancellation_token.cancelled().await;
+            }
+        });
+
+        (Self { cancellation_token }, client_handle)
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.cancellation_token.cancel();
+    }
+}
@@ -1,76 +1,229 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
-use com::client;
-use tokio::sync::{broadcast, Notify};
+use com::{
+    client::{self, receiver::SubscriberId, retry_policy::RetryPolicy},
+    proto::{register_command_names, register_event_names},
+};
+use futures_util::{Stream, StreamExt};
+use kinematics::model::{KinematicParameters, KinematicState};
+use tokio::{
+    select,
+    sync::{broadcast, watch},
+    time::{self, Duration, Instant},
+};
 use tokio_util::sync::CancellationToken;
 
-use crate::{error::Error, servo_com::events::PoseChangedEvent};
+use crate::{
+    error::Error,
+    servo_com::events::{PoseChangedEvent, ServoTelemetryEvent},
+};
 
 use self::{
-    commands::{ClearPoseBufferCommand, PushIntoPoseBufferCommand},
+    calibration::ServoCalibration,
+    commands::{
+        ClearPoseBufferCommand, GetCurrentPoseCommand, GetFirmwareInfoCommand,
+        GetPoseBufferAvailableSpaceCommand, GetPoseBufferCapacityCommand, KeepAliveCommand,
+        PushIntoPoseBufferCommand, SetZeroOffsetCommand,
+    },
     events::{PoseBufferDrainEvent, PoseBufferEmptyEvent},
-    replies::{ClearPoseBufferReply, GetPoseBufferCapacityReply, PushIntoPoseBufferReply},
+    latency::LatencyStats,
+    replies::{
+        ClearPoseBufferReply, FirmwareInfoReply, GetCurrentPoseReply,
+        GetPoseBufferAvailableSpaceReply, GetPoseBufferCapacityReply, KeepAliveReply,
+        PushIntoPoseBufferReply, SetZeroOffsetReply,
+    },
+    watchdog::{Watchdog, WatchdogConfig},
 };
 
+pub mod calibration;
 pub mod commands;
 pub mod events;
+pub mod latency;
 pub mod replies;
+pub mod watchdog;
+
+#[cfg(test)]
+pub(crate) mod mock;
+
+pub struct ServoCom;
+
+impl ServoCom {
+    /// The protocol version this client implements, the servo controller must report the same.
+    pub const PROTOCOL_VERSION: u32 = 1_u32;
+
+    /// Register the names of the commands and events of the servo controller, so the logs show
+    ///  those instead of the raw codes. Meant to be called once at startup.
+    pub fn register_code_names() {
+        register_command_names(&[
+            (GetFirmwareInfoCommand::CODE, "GetFirmwareInfo"),
+            (KeepAliveCommand::CODE, "KeepAlive"),
+            (PushIntoPoseBufferCommand::CODE, "PushIntoPoseBuffer"),
+            (ClearPoseBufferCommand::CODE, "ClearPoseBuffer"),
+            (GetPoseBufferCapacityCommand::CODE, "GetPoseBufferCapacity"),
+            (
+                GetPoseBufferAvailableSpaceCommand::CODE,
+                "GetPoseBufferAvailableSpace",
+            ),
+            (SetZeroOffsetCommand::CODE, "SetZeroOffset"),
+            (GetCurrentPoseCommand::CODE, "GetCurrentPose"),
+        ]);
+
+        register_event_names(&[
+            (PoseChangedEvent::CODE, "PoseChanged"),
+            (PoseBufferDrainEvent::CODE, "PoseBufferDrain"),
+            (PoseBufferEmptyEvent::CODE, "PoseBufferEmpty"),
+            (ServoTelemetryEvent::CODE, "ServoTelemetry"),
+        ]);
+    }
+
+    /// Create the worker and the handle for the servo communication on top of the given client.
+    pub fn from_client(handle: client::Handle) -> (Worker, Handle) {
+        Self::with_calibration(handle, ServoCalibration::default())
+    }
+
+    /// Create the worker and the handle for the servo communication on top of the given client,
+    ///  translating between the model angles and the servo angles using the given calibration.
+    pub fn with_calibration(
+        handle: client::Handle,
+        calibration: ServoCalibration,
+    ) -> (Worker, Handle) {
+        assert!(calibration.has_unit_signs());
+
+        let notifiers = Arc::new(Notifiers::new());
+        let broadcasts = Arc::new(Broadcasts::new());
+        let calibration = Arc::new(calibration);
+        let handle = Arc::new(handle);
+
+        let worker = Worker::new(
+            notifiers.clone(),
+            broadcasts.clone(),
+            calibration.clone(),
+            handle.clone(),
+        );
+        let handle = Handle::new(notifiers, broadcasts, calibration, handle);
+
+        (worker, handle)
+    }
+}
 
 pub struct Broadcasts {
     pose_changed: broadcast::Sender<PoseChangedEvent>,
+    telemetry: broadcast::Sender<ServoTelemetryEvent>,
 }
 
 impl Broadcasts {
     pub fn new() -> Self {
         let (pose_changed, _) = broadcast::channel(1);
+        let (telemetry, _) = broadcast::channel(1);
 
-        Self { pose_changed }
+        Self {
+            pose_changed,
+            telemetry,
+        }
     }
 
     pub fn pose_changed(&self) -> &broadcast::Sender<PoseChangedEvent> {
         &self.pose_changed
     }
+
+    pub fn telemetry(&self) -> &broadcast::Sender<ServoTelemetryEvent> {
+        &self.telemetry
+    }
 }
 
+/// The notifiers hold the last reported state of the pose buffer instead of being edge-triggered,
+///  this way a report that arrives before anyone is waiting for it is never lost.
 pub struct Notifiers {
-    drain: Notify,
-    empty: Notify,
+    drain: watch::Sender<usize>,
+    empty: watch::Sender<bool>,
 }
 
 impl Notifiers {
     pub fn new() -> Self {
-        Self {
-            drain: Notify::new(),
-            empty: Notify::new(),
-        }
+        let (drain, _) = watch::channel(0_usize);
+        let (empty, _) = watch::channel(false);
+
+        Self { drain, empty }
     }
 
-    pub fn drain(&self) -> &Notify {
+    /// The available space last reported by a pose buffer drain event. Subscribe before pushing
+    ///  poses to be sure that no drain after the push is missed.
+    pub fn drain(&self) -> &watch::Sender<usize> {
         &self.drain
     }
 
-    pub fn empty(&self) -> &Notify {
+    /// Whether the pose buffer has been reported empty since the last pose was pushed.
+    pub fn empty(&self) -> &watch::Sender<bool> {
         &self.empty
     }
+
+    /// Store the available space reported by a drain event.
+    pub(self) fn notify_drain(&self, available: usize) {
+        self.drain.send_replace(available);
+    }
+
+    /// Mark the pose buffer as empty.
+    pub(self) fn notify_empty(&self) {
+        self.empty.send_replace(true);
+    }
+
+    /// Mark the pose buffer as no longer empty (because a pose is about to be pushed), the
+    ///  receivers are only notified if it was marked empty before.
+    pub(self) fn reset_empty(&self) {
+        self.empty
+            .send_if_modified(|empty| std::mem::replace(empty, false));
+    }
+}
+
+/// The identifiers of the event subscriptions of the worker, these are only valid for the
+///  connection they've been made on.
+struct Subscriptions {
+    pose_changed: SubscriberId,
+    pose_buffer_drain: SubscriberId,
+    pose_buffer_empty: SubscriberId,
+    servo_telemetry: SubscriberId,
 }
 
 pub struct Worker {
     notifiers: Arc<Notifiers>,
     broadcasts: Arc<Broadcasts>,
-    handle: client::Handle,
+    calibration: Arc<ServoCalibration>,
+    handle: Arc<client::Handle>,
 }
 
 impl Worker {
-    pub(self) async fn run(&mut self, cancellation_token: CancellationToken) -> Result<(), Error> {
+    pub(self) fn new(
+        notifiers: Arc<Notifiers>,
+        broadcasts: Arc<Broadcasts>,
+        calibration: Arc<ServoCalibration>,
+        handle: Arc<client::Handle>,
+    ) -> Self {
+        Self {
+            notifiers,
+            broadcasts,
+            calibration,
+            handle,
+        }
+    }
+
+    /// Subscribe to all the events the servo controller publishes.
+    ///
+    /// The subscriptions belong to the current connection, so this has to be run again (and the
+    ///  returned subscriptions discarded) whenever the connection gets re-established.
+    pub(self) async fn subscribe(&self) -> Result<Subscriptions, Error> {
         // Subscribe to the pose changed event (and handle it).
         let pose_changed_ev_sub = self
             .handle
             .serde_sub_to_ev::<PoseChangedEvent>(PoseChangedEvent::CODE, {
                 let broadcasts = self.broadcasts.clone();
+                let calibration = self.calibration.clone();
 
                 move |x| {
-                    if let Ok(event) = x {
-                        broadcasts.pose_changed.send(event);
+                    if let Ok(PoseChangedEvent { angles }) = x {
+                        // The servo reports raw angles, subscribers expect model angles.
+                        _ = broadcasts.pose_changed.send(PoseChangedEvent {
+                            angles: calibration.to_model(&angles).angles(),
+                        });
                     }
                 }
             })
@@ -83,8 +236,8 @@ impl Worker {
                 let notifiers = self.notifiers.clone();
 
                 move |x| {
-                    if let Ok(_) = x {
-                        notifiers.drain.notify_waiters();
+                    if let Ok(PoseBufferDrainEvent { available }) = x {
+                        notifiers.notify_drain(available);
                     }
                 }
             })
@@ -98,42 +251,117 @@ impl Worker {
 
                 move |x| {
                     if let Ok(_) = x {
-                        notifiers.empty.notify_waiters();
+                        notifiers.notify_empty();
                     }
                 }
             })
             .await?;
 
-        // Wait for the cancellation.
-        cancellation_token.cancelled().await;
+        // Subscribe to the servo telemetry event (and handle it).
+        let servo_telemetry_ev_sub = self
+            .handle
+            .serde_sub_to_ev::<ServoTelemetryEvent>(ServoTelemetryEvent::CODE, {
+                let broadcasts = self.broadcasts.clone();
+
+                move |x| {
+                    if let Ok(event) = x {
+                        _ = broadcasts.telemetry.send(event);
+                    }
+                }
+            })
+            .await?;
+
+        Ok(Subscriptions {
+            pose_changed: pose_changed_ev_sub,
+            pose_buffer_drain: pose_buffer_drain_ev_sub,
+            pose_buffer_empty: pose_buffer_empty_ev_sub,
+            servo_telemetry: servo_telemetry_ev_sub,
+        })
+    }
 
+    /// Unsubscribe from all the events subscribed to by `subscribe`.
+    pub(self) async fn unsubscribe(&self, subscriptions: Subscriptions) -> Result<(), Error> {
         // Unsubscribe from the pose changed event.
         self.handle
-            .unsub_ev(PoseChangedEvent::CODE, pose_changed_ev_sub)
+            .unsub_ev(PoseChangedEvent::CODE, subscriptions.pose_changed)
             .await?;
 
         // Unsubscribe from the pose buffer drain event.
         self.handle
-            .unsub_ev(PoseBufferDrainEvent::CODE, pose_buffer_drain_ev_sub)
+            .unsub_ev(PoseBufferDrainEvent::CODE, subscriptions.pose_buffer_drain)
             .await?;
 
         // Unsubscribe from the pose buffer empty event.
         self.handle
-            .unsub_ev(PoseBufferEmptyEvent::CODE, pose_buffer_empty_ev_sub)
+            .unsub_ev(PoseBufferEmptyEvent::CODE, subscriptions.pose_buffer_empty)
+            .await?;
+
+        // Unsubscribe from the servo telemetry event.
+        self.handle
+            .unsub_ev(ServoTelemetryEvent::CODE, subscriptions.servo_telemetry)
             .await?;
 
         Ok(())
     }
+
+    pub(crate) async fn run(&mut self, cancellation_token: CancellationToken) -> Result<(), Error> {
+        let subscriptions = self.subscribe().await?;
+
+        // Wait for the cancellation.
+        cancellation_token.cancelled().await;
+
+        self.unsubscribe(subscriptions).await
+    }
 }
 
 pub struct Handle {
-    notifiers: Notifiers,
-    handle: client::Handle,
+    notifiers: Arc<Notifiers>,
+    broadcasts: Arc<Broadcasts>,
+    calibration: Arc<ServoCalibration>,
+    handle: Arc<client::Handle>,
+    latency: Mutex<LatencyStats>, // The recent round trip measurements.
+    clear_attempts: usize,        // The number of times clearing the pose buffer is tried.
+    clear_retry_policy: RetryPolicy, // The wait between two attempts to clear the pose buffer.
 }
 
 impl Handle {
-    pub(crate) fn new(notifiers: Notifiers, handle: client::Handle) -> Self {
-        Self { notifiers, handle }
+    /// The number of times clearing the pose buffer is tried when none is configured.
+    pub const DEFAULT_CLEAR_ATTEMPTS: usize = 3_usize;
+
+    /// The number of times a pose is pushed while the servo keeps rejecting it.
+    pub const MAX_PUSH_ATTEMPTS: usize = 3_usize;
+
+    pub(self) fn new(
+        notifiers: Arc<Notifiers>,
+        broadcasts: Arc<Broadcasts>,
+        calibration: Arc<ServoCalibration>,
+        handle: Arc<client::Handle>,
+    ) -> Self {
+        Self {
+            notifiers,
+            broadcasts,
+            calibration,
+            handle,
+            latency: Mutex::new(LatencyStats::new()),
+            clear_attempts: Self::DEFAULT_CLEAR_ATTEMPTS,
+            clear_retry_policy: RetryPolicy::Exponential {
+                base: Duration::from_millis(10_u64),
+                max: Duration::from_millis(100_u64),
+                factor: 2_f64,
+            },
+        }
+    }
+
+    /// Try clearing the pose buffer the given number of times, waiting between the attempts as
+    ///  the retry policy prescribes. Clearing is what stops the arm, so a single transient
+    ///  failure shouldn't leave the buffered poses to be executed.
+    pub fn with_clear_retry(mut self, attempts: usize, retry_policy: RetryPolicy) -> Self {
+        assert!(attempts > 0_usize);
+
+        self.clear_attempts = attempts;
+        self.clear_retry_policy = retry_policy;
+
+        self
     }
 
     #[inline]
@@ -141,20 +369,460 @@ impl Handle {
         &self.notifiers
     }
 
+    #[inline]
+    pub fn broadcasts(&self) -> &Broadcasts {
+        &self.broadcasts
+    }
+
+    #[inline]
+    pub fn calibration(&self) -> &ServoCalibration {
+        &self.calibration
+    }
+
+    /// Create a watchdog that keeps the servo controller alive using the given configuration.
+    pub fn watchdog(&self, config: WatchdogConfig) -> Watchdog {
+        Watchdog::new(config, self.handle.clone())
+    }
+
+    /// Measures the round trip time to the servo controller.
+    ///
+    /// This sends a keep alive command and times how long it takes for the reply to arrive, the
+    /// measurement is also recorded in the rolling average returned by `average_latency`.
+    ///
+    /// # Arguments
+    ///
+    /// * `cancellation_token` - A reference to a `CancellationToken` used for cancellation.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Duration, Error>` - The round trip time if successful, or an `Error` if an error
+    ///   occurs.
+    pub async fn measure_latency(
+        &self,
+        cancellation_token: &CancellationToken,
+    ) -> Result<Duration, Error> {
+        let command = KeepAliveCommand::new();
+
+        let start = Instant::now();
+
+        _ = self
+            .handle
+            .serde_write_cmd_wc::<_, KeepAliveReply>(command, cancellation_token)
+            .await?;
+
+        let latency = start.elapsed();
+
+        self.latency.lock().unwrap().record(latency);
+
+        Ok(latency)
+    }
+
+    /// The rolling average of the recent latency measurements, `None` if nothing has been
+    ///  measured yet.
+    pub fn average_latency(&self) -> Option<Duration> {
+        self.latency.lock().unwrap().average()
+    }
+
+    /// Waits until the servo reports that the pose buffer is empty.
+    ///
+    /// The empty state is remembered until the next pose is pushed, so this resolves immediately
+    /// if the servo already reported the buffer empty before this method got called.
+    ///
+    /// # Arguments
+    ///
+    /// * `cancellation_token` - A reference to a `CancellationToken` used for cancellation.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Error>` - `Ok(())` once the buffer is empty, or an `Error` if cancelled.
+    pub(crate) async fn wait_for_buffer_empty(
+        &self,
+        cancellation_token: &CancellationToken,
+    ) -> Result<(), Error> {
+        let mut receiver = self.notifiers.empty().subscribe();
+
+        select! {
+            x = receiver.wait_for(|empty| *empty) => x
+                .map(|_| ())
+                .map_err(|_| Error::Generic("Empty notifier closed".into())),
+            _ = cancellation_token.cancelled() => Err(com::error::Error::Cancelled.into()),
+        }
+    }
+
+    /// Retrieves the firmware information of the servo controller.
+    ///
+    /// This function should be called before trusting any of the other commands, since it makes
+    /// sure that the servo controller speaks the same protocol version as this client.
+    ///
+    /// # Arguments
+    ///
+    /// * `cancellation_token` - A reference to a `CancellationToken` used for cancellation.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<FirmwareInfoReply, Error>` - The firmware information if successful, or
+    ///   `Error::ProtocolMismatch` if the servo controller uses another protocol version.
+    pub(crate) async fn get_firmware_info(
+        &self,
+        cancellation_token: &CancellationToken,
+    ) -> Result<FirmwareInfoReply, Error> {
+        let command = GetFirmwareInfoCommand::new();
+
+        // Send the command and wait for the response containing the firmware info.
+        let firmware_info: FirmwareInfoReply = self
+            .handle
+            .serde_write_cmd_wc(command, cancellation_token)
+            .await?;
+
+        // Refuse to operate if the protocol version doesn't match.
+        if firmware_info.protocol != ServoCom::PROTOCOL_VERSION {
+            return Err(Error::ProtocolMismatch {
+                expected: ServoCom::PROTOCOL_VERSION,
+                actual: firmware_info.protocol,
+            });
+        }
+
+        Ok(firmware_info)
+    }
+
+    /// Sets the zero offsets of the servo encoders.
+    ///
+    /// This tells the servo controller that the current physical pose has the given angles, the
+    /// `PoseChangedEvent`s that follow are reported relative to this new zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `offsets` - The angles of the current physical pose (in radians).
+    /// * `cancellation_token` - A reference to a `CancellationToken` used for cancellation.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Error>` - `Ok(())` if successful, or an `Error` if an error occurs.
+    pub(crate) async fn set_zero_offsets(
+        &self,
+        offsets: [f64; 5],
+        cancellation_token: &CancellationToken,
+    ) -> Result<(), Error> {
+        let command = SetZeroOffsetCommand::new(offsets);
+
+        _ = self
+            .handle
+            .serde_write_cmd_wc::<_, SetZeroOffsetReply>(command, cancellation_token)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves the current angles of the servos.
+    ///
+    /// Unlike waiting for a `PoseChangedEvent`, this can be used right after connecting to
+    /// initialize the kinematic state with the actual pose of the arm.
+    ///
+    /// # Arguments
+    ///
+    /// * `cancellation_token` - A reference to a `CancellationToken` used for cancellation.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<[f64; 5], Error>` - The angles (in radians) if successful, or an `Error` if an
+    ///   error occurs.
+    pub(crate) async fn get_current_pose(
+        &self,
+        cancellation_token: &CancellationToken,
+    ) -> Result<[f64; 5], Error> {
+        let command = GetCurrentPoseCommand::new();
+
+        // Send the command and wait for the response containing the angles.
+        let GetCurrentPoseReply { angles } = self
+            .handle
+            .serde_write_cmd_wc(command, cancellation_token)
+            .await?;
+
+        Ok(angles)
+    }
+
+    /// Pushes a single pose into the pose buffer.
+    ///
+    /// The duration must be positive and finite, otherwise `Error::InvalidPoseDuration` is returned
+    /// without sending anything, since the servo would move to the pose instantly (or not at all).
+    ///
+    /// # Arguments
+    ///
+    /// * `angles` - The angles (in radians) of the pose.
+    /// * `duration` - The duration (in seconds) it should take to move to the pose.
+    /// * `cancellation_token` - A reference to a `CancellationToken` used for cancellation.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<PushIntoPoseBufferReply, Error>` - Whether the pose was accepted, where it was
+    ///   stored and how much space is left if successful, or an `Error` if an error occurs.
     pub(crate) async fn push_into_pose_buffer(
         &mut self,
         angles: [f64; 5],
         duration: f64,
         cancellation_token: &CancellationToken,
-    ) -> Result<(), Error> {
+    ) -> Result<PushIntoPoseBufferReply, Error> {
+        if !(duration > 0_f64 && duration.is_finite()) {
+            return Err(Error::InvalidPoseDuration { duration });
+        }
+
         let command = PushIntoPoseBufferCommand::new(angles, duration);
 
-        _ = self
+        // The buffer won't be empty anymore once the pose is pushed.
+        self.notifiers.reset_empty();
+
+        let reply = self
             .handle
-            .serde_write_cmd_wc::<_, PushIntoPoseBufferReply>(command, cancellation_token)
+            .serde_write_cmd_wc(command, cancellation_token)
             .await?;
 
-        Ok(())
+        Ok(reply)
+    }
+
+    /// Pushes the given kinematic state into the pose buffer.
+    ///
+    /// The state is validated against the given parameters first, so a non-finite angle or one
+    /// beyond the joint limits is never sent to the servo. The joint angles of the model are then
+    /// translated into the servo angles using the calibration before they're pushed.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The kinematic state to move to.
+    /// * `params` - The kinematic parameters to validate the state against.
+    /// * `duration` - The duration (in seconds) it should take to move to the pose.
+    /// * `cancellation_token` - A reference to a `CancellationToken` used for cancellation.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<PushIntoPoseBufferReply, Error>` - The reply of the servo if successful, or an
+    ///   `Error` if the state is invalid or another error occurs.
+    pub(crate) async fn push_kinematic_state(
+        &mut self,
+        state: &KinematicState,
+        params: &KinematicParameters,
+        duration: f64,
+        cancellation_token: &CancellationToken,
+    ) -> Result<PushIntoPoseBufferReply, Error> {
+        let angles = Self::servo_angles(&self.calibration, state, params)?;
+
+        self.push_into_pose_buffer(angles, duration, cancellation_token)
+            .await
+    }
+
+    /// Translates the given kinematic state into the servo angles using the given calibration,
+    /// after validating it against the given parameters.
+    fn servo_angles(
+        calibration: &ServoCalibration,
+        state: &KinematicState,
+        params: &KinematicParameters,
+    ) -> Result<[f64; 5], Error> {
+        state.validate(params)?;
+
+        Ok(calibration.to_servo(state))
+    }
+
+    /// Pushes all the poses of the given stream into the pose buffer, in order.
+    ///
+    /// Poses are only pushed while the pose buffer has space left, once it's full this waits for
+    /// the servo to report that the buffer drained (or emptied) and asks how much space is
+    /// available again, so the caller doesn't have to deal with the backpressure. A pose rejected
+    /// by the servo is pushed again once the buffer drained, up to `MAX_PUSH_ATTEMPTS` times.
+    ///
+    /// # Arguments
+    ///
+    /// * `poses` - The stream of poses, being the angles (in radians) and the duration (in
+    ///   seconds) of each pose.
+    /// * `cancellation_token` - A reference to a `CancellationToken` used for cancellation.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<usize, Error>` - The number of pushed poses if successful, or an `Error` if an
+    ///   error occurs.
+    pub(crate) async fn stream_poses<S>(
+        &mut self,
+        poses: S,
+        cancellation_token: &CancellationToken,
+    ) -> Result<usize, Error>
+    where
+        S: Stream<Item = ([f64; 5], f64)>,
+    {
+        self.stream_pose_results(poses.map(Ok), cancellation_token)
+            .await
+    }
+
+    /// Pushes all the poses of the given stream into the pose buffer like `stream_poses` does,
+    /// but stops at the first error in the stream (which is returned).
+    async fn stream_pose_results<S>(
+        &mut self,
+        poses: S,
+        cancellation_token: &CancellationToken,
+    ) -> Result<usize, Error>
+    where
+        S: Stream<Item = Result<([f64; 5], f64), Error>>,
+    {
+        // Subscribe before asking for the available space, so no report after it can be missed.
+        let mut drain = self.notifiers.drain().subscribe();
+        let mut empty = self.notifiers.empty().subscribe();
+
+        let mut available = self.get_buffer_available_space(cancellation_token).await?;
+        let mut pushed = 0_usize;
+
+        tokio::pin!(poses);
+
+        loop {
+            let pose = select! {
+                x = poses.next() => x,
+                _ = cancellation_token.cancelled() => {
+                    return Err(com::error::Error::Cancelled.into());
+                }
+            };
+            let Some(pose) = pose else {
+                return Ok(pushed);
+            };
+            let (angles, duration) = pose?;
+
+            let mut attempts = 0_usize;
+            loop {
+                // The available space is reported again by the reply to the push.
+                if available == 0_usize {
+                    self.wait_for_space(&mut drain, &mut empty, cancellation_token)
+                        .await?;
+                }
+
+                // The drains reported before this push are already accounted for by its reply.
+                drain.borrow_and_update();
+
+                let reply = self
+                    .push_into_pose_buffer(angles, duration, cancellation_token)
+                    .await?;
+
+                available = reply.remaining_capacity;
+                if reply.accepted {
+                    break;
+                }
+
+                attempts += 1_usize;
+                if attempts == Self::MAX_PUSH_ATTEMPTS {
+                    return Err(Error::PoseRejected { attempts });
+                }
+
+                // The servo rejected the pose even though it reports space, pushing it again
+                //  right away would most likely be rejected too, so wait until the buffer moves.
+                if available > 0_usize {
+                    self.wait_for_buffer_change(&mut drain, &mut empty, cancellation_token)
+                        .await?;
+                }
+            }
+
+            pushed += 1_usize;
+        }
+    }
+
+    /// Pushes all the kinematic states of the given stream into the pose buffer, in order, waiting
+    /// for space in the pose buffer like `stream_poses` does.
+    ///
+    /// Every state is validated and translated into the servo angles like `push_kinematic_state`
+    /// does, streaming stops at the first invalid state.
+    ///
+    /// # Arguments
+    ///
+    /// * `states` - The stream of kinematic states, along with the duration (in seconds) it should
+    ///   take to move to each of them.
+    /// * `params` - The kinematic parameters to validate the states against.
+    /// * `cancellation_token` - A reference to a `CancellationToken` used for cancellation.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<usize, Error>` - The number of pushed states if successful, or an `Error` if a
+    ///   state is invalid or another error occurs.
+    pub(crate) async fn stream_kinematic_states<S>(
+        &mut self,
+        states: S,
+        params: &KinematicParameters,
+        cancellation_token: &CancellationToken,
+    ) -> Result<usize, Error>
+    where
+        S: Stream<Item = (KinematicState, f64)>,
+    {
+        let calibration = self.calibration.clone();
+
+        self.stream_pose_results(
+            states.map(move |(state, duration)| {
+                Ok((Self::servo_angles(&calibration, &state, params)?, duration))
+            }),
+            cancellation_token,
+        )
+        .await
+    }
+
+    /// Waits for the servo to report that the pose buffer drained (or emptied) until it has space
+    ///  again, the receivers must have been subscribed before the buffer was found to be full.
+    ///
+    /// A report might be outdated by the time it's received, so the available space is always
+    ///  asked for afterwards.
+    async fn wait_for_space(
+        &self,
+        drain: &mut watch::Receiver<usize>,
+        empty: &mut watch::Receiver<bool>,
+        cancellation_token: &CancellationToken,
+    ) -> Result<usize, Error> {
+        loop {
+            self.wait_for_buffer_change(drain, empty, cancellation_token)
+                .await?;
+
+            let available = self.get_buffer_available_space(cancellation_token).await?;
+            if available > 0_usize {
+                return Ok(available);
+            }
+        }
+    }
+
+    /// Waits for the servo to report that the pose buffer drained (or emptied), without asking
+    ///  for the available space.
+    ///
+    /// The pose buffer is only reset to not empty when a pose is pushed, which isn't a change the
+    ///  caller waits for, so the empty receiver waits for the buffer to be empty instead.
+    async fn wait_for_buffer_change(
+        &self,
+        drain: &mut watch::Receiver<usize>,
+        empty: &mut watch::Receiver<bool>,
+        cancellation_token: &CancellationToken,
+    ) -> Result<(), Error> {
+        select! {
+            x = drain.changed() => x
+                .map_err(|_| Error::Generic("Drain notifier closed".into())),
+            x = empty.wait_for(|empty| *empty) => x
+                .map(|_| ())
+                .map_err(|_| Error::Generic("Empty notifier closed".into())),
+            _ = cancellation_token.cancelled() => {
+                Err(com::error::Error::Cancelled.into())
+            }
+        }
+    }
+
+    /// Retrieves the space that's currently available in the pose buffer.
+    ///
+    /// # Arguments
+    ///
+    /// * `cancellation_token` - A reference to a `CancellationToken` used for cancellation.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<usize, Error>` - The number of poses that can still be pushed if successful, or
+    ///   an `Error` if an error occurs.
+    pub(crate) async fn get_buffer_available_space(
+        &self,
+        cancellation_token: &CancellationToken,
+    ) -> Result<usize, Error> {
+        let command = GetPoseBufferAvailableSpaceCommand::new();
+
+        // Send the command and wait for the response containing the available space.
+        let GetPoseBufferAvailableSpaceReply { available } = self
+            .handle
+            .serde_write_cmd_wc(command, cancellation_token)
+            .await?;
+
+        Ok(available)
     }
 
     /// Retrieves the buffer capacity for the task.
@@ -174,7 +842,7 @@ impl Handle {
         &mut self,
         cancellation_token: &CancellationToken,
     ) -> Result<usize, Error> {
-        let command = ClearPoseBufferCommand::new();
+        let command = GetPoseBufferCapacityCommand::new();
 
         // Send the command and wait for the response containing the capacity.
         let GetPoseBufferCapacityReply { capacity } = self
@@ -189,7 +857,9 @@ impl Handle {
     /// Clears the pose buffer.
     ///
     /// This function sends a command to the client to clear the pose buffer. It returns `Ok(())` if
-    /// successful, or an `Error` if an error occurs during the process.
+    /// successful, after a failure the command is sent again (up to the configured number of
+    /// attempts) and the error of the last attempt is returned if none succeeded. Cancellation
+    /// isn't retried.
     ///
     /// # Arguments
     ///
@@ -202,13 +872,881 @@ impl Handle {
         &mut self,
         cancellation_token: &CancellationToken,
     ) -> Result<(), Error> {
-        let command = ClearPoseBufferCommand::new();
+        let mut attempts = 0_usize;
 
-        _ = self
-            .handle
-            .serde_write_cmd_wc::<_, ClearPoseBufferReply>(command, cancellation_token)
-            .await?;
+        loop {
+            let command = ClearPoseBufferCommand::new();
 
-        Ok(())
+            let error = match self
+                .handle
+                .serde_write_cmd_wc::<_, ClearPoseBufferReply>(command, cancellation_token)
+                .await
+            {
+                Ok(_) => return Ok(()),
+                Err(com::error::Error::Cancelled) => {
+                    return Err(com::error::Error::Cancelled.into())
+                }
+                Err(error) => error,
+            };
+
+            attempts += 1_usize;
+
+            if attempts >= self.clear_attempts {
+                return Err(error.into());
+            }
+
+            let Some(delay) = self.clear_retry_policy.delay(attempts - 1_usize) else {
+                return Err(error.into());
+            };
+
+            select! {
+                _ = time::sleep(delay) => {}
+                _ = cancellation_token.cancelled() => return Err(com::error::Error::Cancelled.into()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use com::client::{retry_policy::RetryPolicy, Command};
+    use com::proto::{CommandCode, EventCode};
+    use kinematics::{
+        error::KinematicError,
+        model::{KinematicParameters, KinematicState},
+    };
+    use nalgebra::Vector5;
+    use tokio::time::{self, Duration};
+    use tokio_util::sync::CancellationToken;
+
+    use crate::error::Error;
+    use crate::servo_com::{
+        calibration::ServoCalibration,
+        commands::{
+            ClearPoseBufferCommand, GetCurrentPoseCommand, GetFirmwareInfoCommand,
+            GetPoseBufferAvailableSpaceCommand, KeepAliveCommand, PushIntoPoseBufferCommand,
+            SetZeroOffsetCommand,
+        },
+        events::{
+            PoseBufferDrainEvent, PoseBufferEmptyEvent, PoseChangedEvent, ServoTelemetryEvent,
+        },
+        mock::{CapturedPacket, MockServer, MockServo},
+        replies::{
+            ClearPoseBufferReply, FirmwareInfoReply, GetCurrentPoseReply,
+            GetPoseBufferAvailableSpaceReply, KeepAliveReply, PushIntoPoseBufferReply,
+            SetZeroOffsetReply,
+        },
+        ServoCom,
+    };
+
+    #[tokio::test]
+    pub async fn wait_for_buffer_empty() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let cancellation_token = CancellationToken::new();
+
+        // Create and run the servo communication worker.
+        let (mut worker, handle) = ServoCom::from_client(client_handle);
+        tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move { worker.run(cancellation_token).await }
+        });
+
+        // Start waiting before the servo reports the empty buffer.
+        let waiter = handle.wait_for_buffer_empty(&cancellation_token);
+        tokio::pin!(waiter);
+
+        // Keep firing the empty event (the worker might not be subscribed yet) until the waiter
+        //  resolves.
+        let result = time::timeout(Duration::from_secs(5_u64), async {
+            loop {
+                servo
+                    .write_event(PoseBufferEmptyEvent::CODE, &PoseBufferEmptyEvent {})
+                    .await;
+
+                if let Ok(result) = time::timeout(Duration::from_millis(10_u64), &mut waiter).await {
+                    break result;
+                }
+            }
+        })
+        .await
+        .expect("Waiter did not resolve");
+
+        assert!(result.is_ok());
+
+        cancellation_token.cancel();
+    }
+
+    #[tokio::test]
+    pub async fn buffer_empty_before_waiting() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let cancellation_token = CancellationToken::new();
+
+        let (mut worker, handle) = ServoCom::from_client(client_handle);
+        tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move { worker.run(cancellation_token).await }
+        });
+
+        // Fire the empty event until it has been processed, without anyone waiting for it.
+        time::timeout(Duration::from_secs(5_u64), async {
+            while !*handle.notifiers().empty().borrow() {
+                servo
+                    .write_event(PoseBufferEmptyEvent::CODE, &PoseBufferEmptyEvent {})
+                    .await;
+
+                time::sleep(Duration::from_millis(10_u64)).await;
+            }
+        })
+        .await
+        .expect("Empty event was not processed");
+
+        // The waiter should still wake up even though it started after the event.
+        let result = time::timeout(
+            Duration::from_secs(1_u64),
+            handle.wait_for_buffer_empty(&cancellation_token),
+        )
+        .await
+        .expect("Waiter did not resolve");
+
+        assert!(result.is_ok());
+
+        cancellation_token.cancel();
+    }
+
+    #[tokio::test]
+    pub async fn get_firmware_info() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let (_worker, handle) = ServoCom::from_client(client_handle);
+        let cancellation_token = CancellationToken::new();
+
+        let firmware_info = FirmwareInfoReply {
+            version: "1.2.3".into(),
+            protocol: ServoCom::PROTOCOL_VERSION,
+            capabilities: vec!["pose-buffer".into()],
+        };
+
+        // Reply to the command from the mock servo.
+        let servo_task = tokio::spawn({
+            let firmware_info = firmware_info.clone();
+
+            async move {
+                let (code, tag, _) = servo.read_command().await;
+                assert_eq!(code, GetFirmwareInfoCommand::new().code());
+
+                servo.write_reply(tag, &firmware_info).await;
+                servo
+            }
+        });
+
+        assert_eq!(
+            handle.get_firmware_info(&cancellation_token).await.unwrap(),
+            firmware_info
+        );
+
+        servo_task.await.unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn get_firmware_info_protocol_mismatch() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let (_worker, handle) = ServoCom::from_client(client_handle);
+        let cancellation_token = CancellationToken::new();
+
+        // Reply with another protocol version.
+        let servo_task = tokio::spawn(async move {
+            let (_, tag, _) = servo.read_command().await;
+
+            servo
+                .write_reply(
+                    tag,
+                    &FirmwareInfoReply {
+                        version: "0.0.1".into(),
+                        protocol: ServoCom::PROTOCOL_VERSION + 1_u32,
+                        capabilities: Vec::new(),
+                    },
+                )
+                .await;
+            servo
+        });
+
+        match handle.get_firmware_info(&cancellation_token).await {
+            Err(Error::ProtocolMismatch { expected, actual }) => {
+                assert_eq!(expected, ServoCom::PROTOCOL_VERSION);
+                assert_eq!(actual, ServoCom::PROTOCOL_VERSION + 1_u32);
+            }
+            _ => panic!("Expected protocol mismatch"),
+        }
+
+        servo_task.await.unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn telemetry_rebroadcast() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let cancellation_token = CancellationToken::new();
+
+        let (mut worker, handle) = ServoCom::from_client(client_handle);
+        tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move { worker.run(cancellation_token).await }
+        });
+
+        let telemetry = ServoTelemetryEvent {
+            per_joint_current: [0.1_f64, 0.2_f64, 0.3_f64, 0.4_f64, 0.5_f64],
+            temperature: [30_f64, 31_f64, 32_f64, 33_f64, 34_f64],
+            bus_voltage: 12_f64,
+        };
+
+        // Subscribe to the rebroadcast telemetry.
+        let mut receiver = handle.broadcasts().telemetry().subscribe();
+
+        // Keep publishing the telemetry (the worker might not be subscribed yet) until it's
+        //  rebroadcast.
+        let received = time::timeout(Duration::from_secs(5_u64), async {
+            loop {
+                servo.write_event(ServoTelemetryEvent::CODE, &telemetry).await;
+
+                if let Ok(x) = time::timeout(Duration::from_millis(10_u64), receiver.recv()).await {
+                    break x.unwrap();
+                }
+            }
+        })
+        .await
+        .expect("Telemetry was not rebroadcast");
+
+        assert_eq!(received, telemetry);
+
+        cancellation_token.cancel();
+    }
+
+    #[tokio::test]
+    pub async fn wait_for_buffer_empty_cancelled() {
+        let (_servo, client_handle) = MockServo::start().await;
+        let (_worker, handle) = ServoCom::from_client(client_handle);
+
+        // Cancel the token, the waiter should resolve with an error.
+        let cancellation_token = CancellationToken::new();
+        cancellation_token.cancel();
+
+        assert!(handle.wait_for_buffer_empty(&cancellation_token).await.is_err());
+    }
+
+    #[tokio::test]
+    pub async fn clear_pose_buffer_retried() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let (_worker, handle) = ServoCom::from_client(client_handle);
+        let mut handle =
+            handle.with_clear_retry(3_usize, RetryPolicy::Fixed(Duration::from_millis(10_u64)));
+        let cancellation_token = CancellationToken::new();
+
+        // Fail the first attempt with a reply that can't be decoded, and acknowledge the second.
+        let servo_task = tokio::spawn(async move {
+            let (code, tag, _) = servo.read_command().await;
+            assert_eq!(code, ClearPoseBufferCommand::new().code());
+            servo.write_reply(tag, &"not a reply").await;
+
+            let (code, tag, _) = servo.read_command().await;
+            assert_eq!(code, ClearPoseBufferCommand::new().code());
+            servo.write_reply(tag, &ClearPoseBufferReply {}).await;
+            servo
+        });
+
+        handle.clear_pose_buffer(&cancellation_token).await.unwrap();
+
+        servo_task.await.unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn set_zero_offsets() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let (_worker, handle) = ServoCom::from_client(client_handle);
+        let cancellation_token = CancellationToken::new();
+
+        let offsets = [0.1_f64, -0.2_f64, 0.3_f64, -0.4_f64, 0.5_f64];
+
+        // Check the command on the mock servo side and acknowledge it.
+        let servo_task = tokio::spawn(async move {
+            let (code, tag, value) = servo.read_command().await;
+            assert_eq!(code, SetZeroOffsetCommand::new(offsets).code());
+            assert_eq!(
+                rmp_serde::from_slice::<SetZeroOffsetCommand>(&value).unwrap(),
+                SetZeroOffsetCommand::new(offsets)
+            );
+
+            servo.write_reply(tag, &SetZeroOffsetReply {}).await;
+            servo
+        });
+
+        handle
+            .set_zero_offsets(offsets, &cancellation_token)
+            .await
+            .unwrap();
+
+        servo_task.await.unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn measure_latency() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let (_worker, handle) = ServoCom::from_client(client_handle);
+        let cancellation_token = CancellationToken::new();
+
+        assert_eq!(handle.average_latency(), None);
+
+        // Echo the ping after a short delay.
+        let servo_task = tokio::spawn(async move {
+            let (code, tag, _) = servo.read_command().await;
+            assert_eq!(code, KeepAliveCommand::new().code());
+
+            time::sleep(Duration::from_millis(10_u64)).await;
+
+            servo.write_reply(tag, &KeepAliveReply {}).await;
+            servo
+        });
+
+        let latency = handle.measure_latency(&cancellation_token).await.unwrap();
+        assert!(latency >= Duration::from_millis(10_u64));
+        assert_eq!(handle.average_latency(), Some(latency));
+
+        servo_task.await.unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn get_current_pose() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let (_worker, handle) = ServoCom::from_client(client_handle);
+        let cancellation_token = CancellationToken::new();
+
+        let angles = [0.5_f64, -0.4_f64, 0.3_f64, -0.2_f64, 0.1_f64];
+
+        // Reply with the current pose from the mock servo.
+        let servo_task = tokio::spawn(async move {
+            let (code, tag, _) = servo.read_command().await;
+            assert_eq!(code, GetCurrentPoseCommand::new().code());
+
+            servo
+                .write_reply(tag, &GetCurrentPoseReply { angles })
+                .await;
+            servo
+        });
+
+        assert_eq!(
+            handle.get_current_pose(&cancellation_token).await.unwrap(),
+            angles
+        );
+
+        servo_task.await.unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn calibrated_pose_round_trip() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let cancellation_token = CancellationToken::new();
+
+        // The shoulder servo is mounted backwards and its zero is off by 0.1 radians.
+        let calibration = ServoCalibration::new(
+            [0_f64, 0.1_f64, 0_f64, 0_f64, 0_f64],
+            [1_f64, -1_f64, 1_f64, 1_f64, 1_f64],
+        );
+
+        let (mut worker, mut handle) = ServoCom::with_calibration(client_handle, calibration);
+        tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move { worker.run(cancellation_token).await }
+        });
+
+        let state = KinematicState {
+            theta_0: 0.2_f64,
+            theta_1: 0.5_f64,
+            theta_2: -0.3_f64,
+            theta_3: 0.4_f64,
+            theta_4: 0_f64,
+        };
+
+        // Accept the pose and hand the raw angles back to the test.
+        let servo_task = tokio::spawn(async move {
+            let (_, tag, value) = servo.read_command().await;
+            let (angles, _): ([f64; 5], f64) = rmp_serde::from_slice(&value).unwrap();
+
+            servo
+                .write_reply(
+                    tag,
+                    &PushIntoPoseBufferReply {
+                        accepted: true,
+                        index: 0_usize,
+                        remaining_capacity: 1_usize,
+                    },
+                )
+                .await;
+
+            (servo, angles)
+        });
+
+        handle
+            .push_kinematic_state(
+                &state,
+                &KinematicParameters::default(),
+                0.1_f64,
+                &cancellation_token,
+            )
+            .await
+            .unwrap();
+
+        let (mut servo, angles) = servo_task.await.unwrap();
+        assert!((angles[1_usize] - (-0.4_f64)).abs() < 1e-12_f64);
+
+        // Report the raw angles as the new pose until the worker translated it back.
+        let mut receiver = handle.broadcasts().pose_changed().subscribe();
+        let event = time::timeout(Duration::from_secs(5_u64), async {
+            loop {
+                servo
+                    .write_event(PoseChangedEvent::CODE, &PoseChangedEvent { angles })
+                    .await;
+
+                if let Ok(Ok(event)) =
+                    time::timeout(Duration::from_millis(10_u64), receiver.recv()).await
+                {
+                    break event;
+                }
+            }
+        })
+        .await
+        .expect("Pose changed event was not processed");
+
+        let reported = KinematicState::from(Vector5::<f64>::from(event.angles));
+        assert!(reported.approx_eq(&state, 1e-12_f64));
+
+        cancellation_token.cancel();
+    }
+
+    #[tokio::test]
+    pub async fn replayed_capture() {
+        let angles = [0.5_f64, -0.4_f64, 0.3_f64, -0.2_f64, 0.1_f64];
+
+        // A capture of the servo answering a single current pose request.
+        let capture = vec![CapturedPacket::Reply {
+            command: GetCurrentPoseCommand::new().code(),
+            value: rmp_serde::to_vec(&GetCurrentPoseReply { angles }).unwrap(),
+        }];
+        let path = std::env::temp_dir().join(format!("capture-{}.json", std::process::id()));
+        std::fs::write(&path, serde_json::to_string(&capture).unwrap()).unwrap();
+
+        let (_server, client_handle) = MockServer::from_capture(&path).await;
+        let (_worker, handle) = ServoCom::from_client(client_handle);
+        let cancellation_token = CancellationToken::new();
+
+        let result = time::timeout(
+            Duration::from_secs(5_u64),
+            handle.get_current_pose(&cancellation_token),
+        )
+        .await
+        .expect("No reply was replayed");
+        assert_eq!(result.unwrap(), angles);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn push_into_pose_buffer_reply() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let (_worker, mut handle) = ServoCom::from_client(client_handle);
+        let cancellation_token = CancellationToken::new();
+
+        let replies = [
+            PushIntoPoseBufferReply {
+                accepted: true,
+                index: 4_usize,
+                remaining_capacity: 0_usize,
+            },
+            PushIntoPoseBufferReply {
+                accepted: false,
+                index: 0_usize,
+                remaining_capacity: 0_usize,
+            },
+        ];
+
+        // Accept the first pose into the last slot, and reject the second one.
+        let servo_task = tokio::spawn({
+            let replies = replies.clone();
+
+            async move {
+                for reply in replies {
+                    let (code, tag, _) = servo.read_command().await;
+                    assert_eq!(
+                        code,
+                        PushIntoPoseBufferCommand::new([0_f64; 5], 0_f64).code()
+                    );
+
+                    servo.write_reply(tag, &reply).await;
+                }
+
+                servo
+            }
+        });
+
+        for reply in replies {
+            assert_eq!(
+                handle
+                    .push_into_pose_buffer([0_f64; 5], 0.1_f64, &cancellation_token)
+                    .await
+                    .unwrap(),
+                reply
+            );
+        }
+
+        servo_task.await.unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn push_with_invalid_duration_rejected() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let (_worker, mut handle) = ServoCom::from_client(client_handle);
+        let cancellation_token = CancellationToken::new();
+
+        for duration in [0_f64, -0.1_f64, f64::NAN] {
+            let result = handle
+                .push_into_pose_buffer([0_f64; 5], duration, &cancellation_token)
+                .await;
+
+            assert!(matches!(
+                result,
+                Err(Error::InvalidPoseDuration { duration: x }) if x.to_bits() == duration.to_bits()
+            ));
+        }
+
+        // Nothing has been sent for the rejected poses, so the next command is a valid push.
+        let servo_task = tokio::spawn(async move {
+            let (_, tag, value) = servo.read_command().await;
+            assert_eq!(
+                value,
+                rmp_serde::to_vec(&PushIntoPoseBufferCommand::new([0_f64; 5], 0.1_f64)).unwrap()
+            );
+
+            let reply = PushIntoPoseBufferReply {
+                accepted: true,
+                index: 0_usize,
+                remaining_capacity: 1_usize,
+            };
+            servo.write_reply(tag, &reply).await;
+            servo
+        });
+
+        handle
+            .push_into_pose_buffer([0_f64; 5], 0.1_f64, &cancellation_token)
+            .await
+            .unwrap();
+
+        servo_task.await.unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn invalid_kinematic_state_not_pushed() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let (_worker, mut handle) = ServoCom::from_client(client_handle);
+        let cancellation_token = CancellationToken::new();
+
+        let params = KinematicParameters {
+            joint_limits: [[-3_f64, 3_f64]; 5],
+            ..KinematicParameters::default()
+        };
+        let valid = KinematicState::default();
+        let non_finite = KinematicState {
+            theta_1: f64::NAN,
+            ..KinematicState::default()
+        };
+        let out_of_range = KinematicState {
+            theta_2: 10_f64,
+            ..KinematicState::default()
+        };
+
+        let result = handle
+            .push_kinematic_state(&non_finite, &params, 0.1_f64, &cancellation_token)
+            .await;
+        assert!(matches!(
+            result,
+            Err(Error::KinematicError(KinematicError::NonFiniteAngle {
+                joint: 1_usize,
+                ..
+            }))
+        ));
+
+        let result = handle
+            .push_kinematic_state(&out_of_range, &params, 0.1_f64, &cancellation_token)
+            .await;
+        assert!(matches!(
+            result,
+            Err(Error::KinematicError(KinematicError::JointLimitViolated {
+                joint: 2_usize,
+                ..
+            }))
+        ));
+
+        // Nothing has been sent for the invalid states, and streaming stops at the first one.
+        let servo_task = tokio::spawn(async move {
+            let (_, tag, _) = servo.read_command().await;
+            let reply = GetPoseBufferAvailableSpaceReply { available: 8_usize };
+            servo.write_reply(tag, &reply).await;
+
+            let (code, tag, _) = servo.read_command().await;
+            assert_eq!(code, PushIntoPoseBufferCommand::CODE);
+
+            let reply = PushIntoPoseBufferReply {
+                accepted: true,
+                index: 0_usize,
+                remaining_capacity: 7_usize,
+            };
+            servo.write_reply(tag, &reply).await;
+            servo
+        });
+
+        let states = vec![
+            (valid.clone(), 0.1_f64),
+            (non_finite, 0.1_f64),
+            (valid, 0.1_f64),
+        ];
+        let result = handle
+            .stream_kinematic_states(
+                futures_util::stream::iter(states),
+                &params,
+                &cancellation_token,
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(Error::KinematicError(KinematicError::NonFiniteAngle {
+                joint: 1_usize,
+                ..
+            }))
+        ));
+
+        servo_task.await.unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn stream_poses_in_batches() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let cancellation_token = CancellationToken::new();
+
+        let (mut worker, mut handle) = ServoCom::from_client(client_handle);
+        tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move { worker.run(cancellation_token).await }
+        });
+
+        // Make sure the worker handles drain events before the poses get streamed.
+        time::timeout(Duration::from_secs(5_u64), async {
+            while *handle.notifiers().drain().borrow() != 99_usize {
+                servo
+                    .write_event(
+                        PoseBufferDrainEvent::CODE,
+                        &PoseBufferDrainEvent {
+                            available: 99_usize,
+                        },
+                    )
+                    .await;
+
+                time::sleep(Duration::from_millis(10_u64)).await;
+            }
+        })
+        .await
+        .expect("Drain event was not processed");
+
+        // The mock servo has room for three poses, and drains all of them once the buffer is full.
+        const CAPACITY: usize = 3_usize;
+
+        let servo_task = tokio::spawn(async move {
+            let mut available = CAPACITY;
+            let mut received: Vec<[f64; 5]> = Vec::new();
+
+            while received.len() < 10_usize {
+                let (code, tag, value) = servo.read_command().await;
+
+                if code == GetPoseBufferAvailableSpaceCommand::new().code() {
+                    servo
+                        .write_reply(tag, &GetPoseBufferAvailableSpaceReply { available })
+                        .await;
+                    continue;
+                }
+
+                assert_eq!(
+                    code,
+                    PushIntoPoseBufferCommand::new([0_f64; 5], 0_f64).code()
+                );
+                assert!(available > 0_usize, "Pose pushed into a full buffer");
+
+                let (angles, _): ([f64; 5], f64) = rmp_serde::from_slice(&value).unwrap();
+                received.push(angles);
+                available -= 1_usize;
+
+                servo
+                    .write_reply(
+                        tag,
+                        &PushIntoPoseBufferReply {
+                            accepted: true,
+                            index: CAPACITY - available - 1_usize,
+                            remaining_capacity: available,
+                        },
+                    )
+                    .await;
+
+                if available == 0_usize {
+                    available = CAPACITY;
+                    servo
+                        .write_event(
+                            PoseBufferDrainEvent::CODE,
+                            &PoseBufferDrainEvent { available },
+                        )
+                        .await;
+                }
+            }
+
+            (servo, received)
+        });
+
+        let poses: Vec<([f64; 5], f64)> =
+            (0..10_usize).map(|i| ([i as f64; 5], 0.05_f64)).collect();
+
+        let pushed = time::timeout(
+            Duration::from_secs(5_u64),
+            handle.stream_poses(
+                futures_util::stream::iter(poses.clone()),
+                &cancellation_token,
+            ),
+        )
+        .await
+        .expect("Streaming the poses did not finish")
+        .unwrap();
+
+        let (_servo, received) = servo_task.await.unwrap();
+
+        assert_eq!(pushed, 10_usize);
+        assert_eq!(
+            received,
+            poses.iter().map(|(angles, _)| *angles).collect::<Vec<_>>()
+        );
+
+        cancellation_token.cancel();
+    }
+
+    #[tokio::test]
+    pub async fn rejected_pose_waits_for_buffer() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let cancellation_token = CancellationToken::new();
+
+        let (mut worker, mut handle) = ServoCom::from_client(client_handle);
+        tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move { worker.run(cancellation_token).await }
+        });
+
+        // Have the buffer reported empty before streaming, so the stream starts with an empty
+        //  buffer that's no longer empty once the pose gets pushed.
+        time::timeout(Duration::from_secs(5_u64), async {
+            while !*handle.notifiers().empty().borrow() {
+                servo
+                    .write_event(PoseBufferEmptyEvent::CODE, &PoseBufferEmptyEvent {})
+                    .await;
+
+                time::sleep(Duration::from_millis(10_u64)).await;
+            }
+        })
+        .await
+        .expect("Empty event was not processed");
+
+        let stream_task = tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move {
+                handle
+                    .stream_poses(
+                        futures_util::stream::iter([([0_f64; 5], 0.05_f64)]),
+                        &cancellation_token,
+                    )
+                    .await
+            }
+        });
+
+        // The stream asks for the available space first.
+        let (code, tag, _) = servo.read_command().await;
+        assert_eq!(code, GetPoseBufferAvailableSpaceCommand::new().code());
+        servo
+            .write_reply(
+                tag,
+                &GetPoseBufferAvailableSpaceReply {
+                    available: 10_usize,
+                },
+            )
+            .await;
+
+        // Reject every push while reporting space left, and only move the buffer (by draining it,
+        //  or by emptying it) once it's clear that the pose isn't pushed again before that.
+        for attempt in 1..=super::Handle::MAX_PUSH_ATTEMPTS {
+            let (code, tag, _) = servo.read_command().await;
+            assert_eq!(
+                code,
+                PushIntoPoseBufferCommand::new([0_f64; 5], 0.05_f64).code()
+            );
+
+            servo
+                .write_reply(
+                    tag,
+                    &PushIntoPoseBufferReply {
+                        accepted: false,
+                        index: 0_usize,
+                        remaining_capacity: 10_usize,
+                    },
+                )
+                .await;
+
+            if attempt == super::Handle::MAX_PUSH_ATTEMPTS {
+                break;
+            }
+
+            assert!(
+                time::timeout(Duration::from_millis(200_u64), servo.read_command())
+                    .await
+                    .is_err(),
+                "Rejected pose pushed again before the buffer moved"
+            );
+
+            if attempt % 2_usize == 1_usize {
+                servo
+                    .write_event(
+                        PoseBufferDrainEvent::CODE,
+                        &PoseBufferDrainEvent {
+                            available: 11_usize,
+                        },
+                    )
+                    .await;
+            } else {
+                servo
+                    .write_event(PoseBufferEmptyEvent::CODE, &PoseBufferEmptyEvent {})
+                    .await;
+            }
+        }
+
+        let result = time::timeout(Duration::from_secs(5_u64), stream_task)
+            .await
+            .expect("Streaming the poses did not finish")
+            .unwrap();
+
+        assert!(matches!(
+            result,
+            Err(Error::PoseRejected {
+                attempts: super::Handle::MAX_PUSH_ATTEMPTS
+            })
+        ));
+
+        cancellation_token.cancel();
+    }
+
+    #[test]
+    pub fn code_names_registered() {
+        ServoCom::register_code_names();
+
+        assert_eq!(
+            CommandCode::new(0x100_u32).name(),
+            Some("PushIntoPoseBuffer")
+        );
+        assert_eq!(EventCode::new(0x3_u32).name(), Some("ServoTelemetry"));
     }
 }
@@ -1,20 +1,52 @@
 use com::client::Reply;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+/// Reply to the get firmware info command.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FirmwareInfoReply {
+    pub version: String,
+    pub protocol: u32,
+    pub capabilities: Vec<String>,
+}
+
+impl Reply for FirmwareInfoReply {}
+
+/// Reply to the keep alive command.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeepAliveReply {}
+
+impl Reply for KeepAliveReply {}
+
+/// Reply to the set zero offset command.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SetZeroOffsetReply {}
+
+impl Reply for SetZeroOffsetReply {}
 
 /// Reply to the push into pose buffer command.
-#[derive(Deserialize)]
-pub struct PushIntoPoseBufferReply {}
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PushIntoPoseBufferReply {
+    pub accepted: bool, // False if the pose was rejected because the buffer is full.
+    pub index: usize,   // The index in the buffer the pose was stored at.
+    pub remaining_capacity: usize, // The number of poses that can still be pushed.
+}
 
 impl Reply for PushIntoPoseBufferReply {}
 
 /// Reply to the clear pose buffer command.
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct ClearPoseBufferReply {}
 
 impl Reply for ClearPoseBufferReply {}
 
 /// Reply to the get pose buffer capacity command.
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct GetPoseBufferCapacityReply {
     pub capacity: usize,
 }
@@ -22,9 +54,19 @@ pub struct GetPoseBufferCapacityReply {
 impl Reply for GetPoseBufferCapacityReply {}
 
 /// Reply to the get pose buffer available space command.
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct GetPoseBufferAvailableSpaceReply {
     pub available: usize,
 }
 
 impl Reply for GetPoseBufferAvailableSpaceReply {}
+
+/// Reply to the get current pose command.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct GetCurrentPoseReply {
+    pub angles: [f64; 5],
+}
+
+impl Reply for GetCurrentPoseReply {}
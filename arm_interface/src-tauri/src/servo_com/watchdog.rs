@@ -0,0 +1,168 @@
+use std::sync::Arc;
+
+use com::client;
+use tokio::{
+    select,
+    time::{self, Duration, MissedTickBehavior},
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::error::Error;
+
+use super::{commands::KeepAliveCommand, replies::KeepAliveReply};
+
+/// This struct represents the configuration of the watchdog.
+#[derive(Debug, Clone)]
+pub struct WatchdogConfig {
+    interval: Duration, // The interval between two keep alive commands.
+    timeout: Duration,  // The time to wait for the keep alive reply.
+}
+
+impl WatchdogConfig {
+    pub fn new(interval: Duration, timeout: Duration) -> Self {
+        Self { interval, timeout }
+    }
+
+    #[inline]
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    #[inline]
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(250_u64),
+            timeout: Duration::from_millis(500_u64),
+        }
+    }
+}
+
+/// This struct represents the watchdog, which periodically sends a keep alive command to the
+///  servo controller. The servo controller stops when the keep alive isn't refreshed, so when
+///  the client dies the arm doesn't keep executing the buffered poses.
+pub struct Watchdog {
+    config: WatchdogConfig,
+    handle: Arc<client::Handle>,
+}
+
+impl Watchdog {
+    pub(super) fn new(config: WatchdogConfig, handle: Arc<client::Handle>) -> Self {
+        Self { config, handle }
+    }
+
+    /// Send a single keep alive command, failing if the reply doesn't arrive in time.
+    pub(self) async fn keep_alive(&self, cancellation_token: &CancellationToken) -> Result<(), Error> {
+        let command = KeepAliveCommand::new();
+
+        select! {
+            x = self.handle.serde_write_cmd_wc::<_, KeepAliveReply>(command, cancellation_token) => {
+                x.map(|_| ()).map_err(Error::from)
+            }
+            _ = time::sleep(self.config.timeout) => Err(Error::WatchdogTimeout),
+        }
+    }
+
+    /// Run the watchdog until cancelled, returns `Error::WatchdogTimeout` when the servo controller
+    ///  doesn't reply in time, after which the connection should be considered dead.
+    pub(crate) async fn run(&mut self, cancellation_token: CancellationToken) -> Result<(), Error> {
+        let mut interval = time::interval(self.config.interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            select! {
+                _ = interval.tick() => {}
+                _ = cancellation_token.cancelled() => return Ok(()),
+            }
+
+            // Cancellation while waiting for the reply isn't a failure of the servo controller.
+            select! {
+                biased;
+                _ = cancellation_token.cancelled() => return Ok(()),
+                x = self.keep_alive(&cancellation_token) => x?,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use com::client::Command;
+    use tokio::time::{self, Duration, Instant};
+    use tokio_util::sync::CancellationToken;
+
+    use crate::error::Error;
+    use crate::servo_com::{
+        commands::KeepAliveCommand, mock::MockServo, replies::KeepAliveReply,
+        watchdog::WatchdogConfig, ServoCom,
+    };
+
+    #[tokio::test]
+    pub async fn keep_alive_cadence() {
+        let (mut servo, client_handle) = MockServo::start().await;
+        let (_worker, handle) = ServoCom::from_client(client_handle);
+        let cancellation_token = CancellationToken::new();
+
+        // Run the watchdog with a short interval.
+        let mut watchdog = handle.watchdog(WatchdogConfig::new(
+            Duration::from_millis(50_u64),
+            Duration::from_secs(1_u64),
+        ));
+        let watchdog_task = tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move { watchdog.run(cancellation_token).await }
+        });
+
+        // Reply to a couple of keep alive commands, storing when they arrived.
+        let mut instants: Vec<Instant> = Vec::new();
+
+        for _ in 0..5 {
+            let (code, tag, _) = servo.read_command().await;
+            assert_eq!(code, KeepAliveCommand::new().code());
+
+            instants.push(Instant::now());
+            servo.write_reply(tag, &KeepAliveReply {}).await;
+        }
+
+        // Make sure that the keep alive commands were sent at the configured cadence.
+        for pair in instants.windows(2) {
+            let elapsed = pair[1] - pair[0];
+
+            assert!(elapsed >= Duration::from_millis(30_u64));
+            assert!(elapsed <= Duration::from_millis(150_u64));
+        }
+
+        cancellation_token.cancel();
+        assert!(watchdog_task.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    pub async fn keep_alive_timeout() {
+        let (_servo, client_handle) = MockServo::start().await;
+        let (_worker, handle) = ServoCom::from_client(client_handle);
+
+        // The mock servo never replies, so the watchdog should time out.
+        let mut watchdog = handle.watchdog(WatchdogConfig::new(
+            Duration::from_millis(10_u64),
+            Duration::from_millis(50_u64),
+        ));
+
+        let result = time::timeout(
+            Duration::from_secs(5_u64),
+            watchdog.run(CancellationToken::new()),
+        )
+        .await
+        .expect("Watchdog did not time out");
+
+        assert!(matches!(result, Err(Error::WatchdogTimeout)));
+
+        // The keep alive that timed out doesn't leave its reply subscriber behind.
+        tokio::task::yield_now().await;
+        assert_eq!(handle.handle.pending_commands().await, 0_usize);
+    }
+}
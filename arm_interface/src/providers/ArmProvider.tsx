@@ -50,6 +50,7 @@ export interface IVariableArmState {
 export const ArmContext = React.createContext<IArmContext>({} as IArmContext);
 
 export interface IArmProviderProps {
+  armId?: number;
   children: any;
 }
 
@@ -58,6 +59,7 @@ export const useArm = (): IArmContext => {
 };
 
 export const ArmProvider = ({
+  armId = 0,
   children,
 }: IArmProviderProps): React.ReactElement => {
   // Keep track of the kinematic parameters.
@@ -95,7 +97,7 @@ export const ArmProvider = ({
 
   // Listen for the arm state changed event.
   useListen<IArmStateChangedEvent>(
-    "arm:state-changed",
+    `arm:${armId}:state-changed`,
     (event: IArmStateChangedEvent): void => {
       setVariableArmState({
         kinematicState: event.kinematicState,
@@ -115,20 +117,20 @@ export const ArmProvider = ({
       // Get the kinematic state.
       const getKinematicStateResponse: IGetKinematicStateResponse =
         await invoke<IGetKinematicStateResponse>("get_kinematic_state", {
-          command: {},
+          armId,
         });
       if (cancelled) return;
 
       // Get the kinematic parameters.
       const kinematicParameters: IKinematicParameters =
         await invoke<IKinematicParameters>("get_kinematic_parameters", {
-          command: {},
+          armId,
         });
       if (cancelled) return;
 
       // Get the vertices.
       const getVerticesResponse: IGetVerticesResponse =
-        await invoke<IGetVerticesResponse>("get_vertices", { command: {} });
+        await invoke<IGetVerticesResponse>("get_vertices", { armId });
       if (cancelled) return;
 
       // Set the kinematic parameters.
@@ -149,7 +151,7 @@ export const ArmProvider = ({
     return (): void => {
       cancelled = true;
     };
-  }, [setKinematicParameters, setVariableArmState, setIsLoading]);
+  }, [armId, setKinematicParameters, setVariableArmState, setIsLoading]);
 
   return (
     <ArmContext.Provider
@@ -0,0 +1,112 @@
+use tokio::time::Duration;
+
+use super::retry_policy::RetryPolicy;
+
+/// This struct represents the configuration of a client.
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    pub transmitter_capacity: usize, // The number of packets that can be queued for transmission.
+    pub event_channel_capacity: usize, // The number of events buffered per channel subscriber.
+    pub commands_per_second: Option<f64>, // The maximum number of commands sent per second.
+    pub packet_read_timeout: Option<Duration>, // The time a started packet must be received in.
+    pub max_subscribers_per_event: Option<usize>, // The maximum number of subscribers per event.
+    pub large_reply_size: Option<usize>, // The reply size (in bytes) above which a warning is logged.
+    pub retry_policy: RetryPolicy,       // How long to wait before retrying to connect.
+    pub max_attempts: Option<usize>,     // The maximum number of attempts to connect.
+}
+
+impl ClientConfig {
+    /// The transmitter capacity used when none is configured.
+    pub const DEFAULT_TRANSMITTER_CAPACITY: usize = 64_usize;
+
+    /// The event channel capacity used when none is configured.
+    pub const DEFAULT_EVENT_CHANNEL_CAPACITY: usize = 64_usize;
+
+    pub fn with_transmitter_capacity(mut self, transmitter_capacity: usize) -> Self {
+        assert!(transmitter_capacity > 0_usize);
+
+        self.transmitter_capacity = transmitter_capacity;
+
+        self
+    }
+
+    pub fn with_event_channel_capacity(mut self, event_channel_capacity: usize) -> Self {
+        assert!(event_channel_capacity > 0_usize);
+
+        self.event_channel_capacity = event_channel_capacity;
+
+        self
+    }
+
+    pub fn with_commands_per_second(mut self, commands_per_second: Option<f64>) -> Self {
+        self.commands_per_second = commands_per_second;
+
+        self
+    }
+
+    /// Set the deadline for receiving a packet once its first byte arrived, this is unrelated to
+    ///  how long the connection may be idle (which is up to the keep alive).
+    pub fn with_packet_read_timeout(mut self, packet_read_timeout: Option<Duration>) -> Self {
+        self.packet_read_timeout = packet_read_timeout;
+
+        self
+    }
+
+    /// Limit the number of subscribers a single event can have, so leaking subscriptions can't
+    ///  slow down the handling of every received event.
+    pub fn with_max_subscribers_per_event(
+        mut self,
+        max_subscribers_per_event: Option<usize>,
+    ) -> Self {
+        assert!(max_subscribers_per_event != Some(0_usize));
+
+        self.max_subscribers_per_event = max_subscribers_per_event;
+
+        self
+    }
+
+    /// Log a warning (with the start of the reply) for every reply larger than the given size,
+    ///  which helps finding out why a reply fails to deserialize.
+    pub fn with_large_reply_size(mut self, large_reply_size: Option<usize>) -> Self {
+        self.large_reply_size = large_reply_size;
+
+        self
+    }
+
+    /// Set how long `Client::connect_with_retry` waits before retrying after a failed attempt.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        // A factor below one (or NaN) would shrink the delay, or make it impossible to compute.
+        if let RetryPolicy::Exponential { factor, .. } = retry_policy {
+            assert!(factor >= 1_f64);
+        }
+
+        self.retry_policy = retry_policy;
+
+        self
+    }
+
+    /// Limit the number of attempts `Client::connect_with_retry` makes before giving up, without
+    ///  a limit it keeps retrying for as long as the retry policy allows.
+    pub fn with_max_attempts(mut self, max_attempts: Option<usize>) -> Self {
+        assert!(max_attempts != Some(0_usize));
+
+        self.max_attempts = max_attempts;
+
+        self
+    }
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            transmitter_capacity: Self::DEFAULT_TRANSMITTER_CAPACITY,
+            event_channel_capacity: Self::DEFAULT_EVENT_CHANNEL_CAPACITY,
+            commands_per_second: None,
+            packet_read_timeout: None,
+            max_subscribers_per_event: None,
+            large_reply_size: None,
+            retry_policy: RetryPolicy::None,
+            max_attempts: None,
+        }
+    }
+}
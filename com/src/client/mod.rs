@@ -1,6 +1,9 @@
-use std::sync::{
-    atomic::{AtomicU64, Ordering},
-    Arc,
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
 };
 
 use serde::{de::DeserializeOwned, Serialize};
@@ -12,17 +15,26 @@ use tokio::{
     },
     select,
     sync::{mpsc, oneshot},
+    time,
 };
 use tokio_util::sync::CancellationToken;
+use tracing::{field, warn, Instrument, Span};
 
 use crate::{
     error::Error,
     proto::{CommandCode, EventCode, Packet, Tag},
 };
 
-use self::receiver::SubscriberId;
+use self::{
+    config::ClientConfig,
+    rate_limiter::RateLimiter,
+    receiver::{ReplySizeStats, ReplySubscription, SubscriberId, Subscription},
+};
 
+pub mod config;
+pub mod rate_limiter;
 pub mod receiver;
+pub mod retry_policy;
 pub mod transmitter;
 
 /// This trait means that the thing implementing it is a command.
@@ -32,7 +44,7 @@ pub trait Command: Serialize + Send {
 }
 
 /// This trait means that the thing implemting it is a reply.
-pub trait Reply: DeserializeOwned + Send {}
+pub trait Reply: DeserializeOwned + Send + 'static {}
 
 /// This trait means that the thing implementing it is an event.
 pub trait Event: DeserializeOwned + Send {
@@ -40,8 +52,66 @@ pub trait Event: DeserializeOwned + Send {
     fn code(&self) -> EventCode;
 }
 
-/// This struct represents the tag generator.
-pub(self) struct TagGenerator {
+/// The number of times serializing a value is attempted, as long as it fails for a reason other
+///  than the value itself.
+const SERIALIZE_ATTEMPTS: usize = 2_usize;
+
+/// Serialize the given value, keeping the cause of the error if it fails. Failures to write the
+///  serialized value are retried, while values that can't be serialized fail right away.
+fn serialize<T>(value: &T) -> Result<Vec<u8>, Error>
+where
+    T: Serialize + ?Sized,
+{
+    let mut attempt = 1_usize;
+
+    loop {
+        let error = match rmp_serde::to_vec(value) {
+            Ok(value) => return Ok(value),
+            Err(error) => error,
+        };
+
+        let data_error = !matches!(error, rmp_serde::encode::Error::InvalidValueWrite(_));
+        if data_error || attempt >= SERIALIZE_ATTEMPTS {
+            return Err(Error::SerdeSerError {
+                message: error.to_string().into(),
+                data_error,
+            });
+        }
+
+        attempt += 1_usize;
+    }
+}
+
+/// Deserialize the given bytes, keeping the cause of the error if it fails.
+///
+/// A value with more fields than the type has, which happens when the peer runs a newer version
+///  of the protocol, is reported as a schema mismatch. Types that should catch this when encoded
+///  as a map need `#[serde(deny_unknown_fields)]`, otherwise the extra fields are ignored.
+fn deserialize<T>(value: &[u8]) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    rmp_serde::from_slice(value).map_err(|error| {
+        let message = match error {
+            // Encoded as an array, with elements left after all the fields have been read.
+            rmp_serde::decode::Error::LengthMismatch(length) => format!(
+                "unexpected field (schema mismatch), only expected {} fields",
+                length
+            ),
+            // Encoded as a map, with a field the type doesn't know about.
+            rmp_serde::decode::Error::Syntax(message) if message.starts_with("unknown field") => {
+                format!("unexpected field (schema mismatch), {}", message)
+            }
+            error => error.to_string(),
+        };
+
+        Error::DeserializeError(message.into())
+    })
+}
+
+/// This struct represents the tag generator, it never generates `Tag::NO_REPLY` since that one
+///  is reserved for commands without a reply.
+struct TagGenerator {
     counter: Arc<AtomicU64>,
 }
 
@@ -49,13 +119,19 @@ impl TagGenerator {
     /// Create a new tag generator.
     pub fn new() -> Self {
         Self {
-            counter: Arc::new(AtomicU64::new(0)),
+            counter: Arc::new(AtomicU64::new(Tag::NO_REPLY.inner() + 1_u64)),
         }
     }
 
-    /// Generate a new tag.
+    /// Generate a new tag, skipping the reserved tag when the counter wraps around.
     pub fn generate(&self) -> Tag {
-        Tag::new(self.counter.fetch_add(1_u64, Ordering::Relaxed))
+        loop {
+            let tag = Tag::new(self.counter.fetch_add(1_u64, Ordering::SeqCst));
+
+            if tag != Tag::NO_REPLY {
+                return tag;
+            }
+        }
     }
 }
 
@@ -70,22 +146,139 @@ impl Client {
     where
         A: ToSocketAddrs,
     {
-        // Connect to the given address.
+        Self::connect_with_config(addr, &ClientConfig::default()).await
+    }
+
+    /// Connect to the given address, limiting the number of commands sent per second (if given).
+    pub async fn connect_with_rate_limit<A>(
+        addr: A,
+        commands_per_second: Option<f64>,
+    ) -> Result<(Handle, Worker<OwnedReadHalf, OwnedWriteHalf>), Error>
+    where
+        A: ToSocketAddrs,
+    {
+        let config = ClientConfig::default().with_commands_per_second(commands_per_second);
+
+        Self::connect_with_config(addr, &config).await
+    }
+
+    /// Connect to the given address using the given configuration.
+    pub async fn connect_with_config<A>(
+        addr: A,
+        config: &ClientConfig,
+    ) -> Result<(Handle, Worker<OwnedReadHalf, OwnedWriteHalf>), Error>
+    where
+        A: ToSocketAddrs,
+    {
+        // Connect to the given address, disabling Nagle's algorithm since small commands
+        //  shouldn't be held back waiting for more data.
         let stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+
+        // Create the handle and the worker.
+        Ok(Self::from_stream_with_config(stream, config))
+    }
+
+    /// Connect to the given address using the given configuration, waiting and trying again after
+    ///  a failed attempt as the retry policy of the configuration prescribes.
+    ///
+    /// Fails with the error of the last attempt once the maximum number of attempts has been made,
+    ///  or after the first failed attempt if the retry policy doesn't retry.
+    pub async fn connect_with_retry<A>(
+        addr: A,
+        config: &ClientConfig,
+    ) -> Result<(Handle, Worker<OwnedReadHalf, OwnedWriteHalf>), Error>
+    where
+        A: ToSocketAddrs + Clone,
+    {
+        let mut attempts = 0_usize;
+
+        loop {
+            let error = match Self::connect_with_config(addr.clone(), config).await {
+                Ok(x) => return Ok(x),
+                Err(error) => error,
+            };
+
+            warn!(%error, attempts, "Failed to connect");
+
+            attempts += 1_usize;
+
+            if config.max_attempts.is_some_and(|x| attempts >= x) {
+                return Err(error);
+            }
+
+            let Some(delay) = config.retry_policy.delay(attempts - 1_usize) else {
+                return Err(error);
+            };
+
+            time::sleep(delay).await;
+        }
+    }
+
+    /// Create the handle and the worker for the given (already connected) stream, this allows the
+    ///  caller to dial it in another way or to configure the socket first.
+    pub fn from_stream(stream: TcpStream) -> (Handle, Worker<OwnedReadHalf, OwnedWriteHalf>) {
+        Self::from_stream_with_config(stream, &ClientConfig::default())
+    }
+
+    /// Create the handle and the worker for the given (already connected) stream using the given
+    ///  configuration.
+    pub fn from_stream_with_config(
+        stream: TcpStream,
+        config: &ClientConfig,
+    ) -> (Handle, Worker<OwnedReadHalf, OwnedWriteHalf>) {
+        let peer_addr = stream.peer_addr();
+        let local_addr = stream.local_addr();
 
         // Split the stream into the reader and writer.
         let (reader, writer) = stream.into_split();
 
+        let (mut handle, worker) = Self::from_split(reader, writer, config);
+
+        // Tell the connections apart in the logs by the address of the server.
+        if let Ok(peer_addr) = peer_addr {
+            worker.span.record("peer", field::display(peer_addr));
+        }
+
+        // Keep the addresses for diagnostics, the stream itself is owned by the worker from now on.
+        handle.peer_addr = peer_addr.ok();
+        handle.local_addr = local_addr.ok();
+
+        (handle, worker)
+    }
+
+    /// Create the handle and the worker for the given reader and writer, these don't have to be
+    ///  the halves of a socket (e.g. an in-memory stream in tests).
+    pub fn from_split<R, W>(reader: R, writer: W, config: &ClientConfig) -> (Handle, Worker<R, W>)
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
         // Create the transmitter and receiver.
-        let (transmitter_worker, transmitter_handle) = transmitter::Transmitter::new(writer);
-        let (receiver_worker, receiver_handle) = receiver::Receiver::new(reader);
+        let (transmitter_worker, transmitter_handle) =
+            transmitter::Transmitter::new(writer, config.transmitter_capacity);
+        let (receiver_worker, receiver_handle) = receiver::Receiver::new(
+            reader,
+            config.event_channel_capacity,
+            config.max_subscribers_per_event,
+        );
+        let receiver_worker = receiver_worker
+            .with_packet_read_timeout(config.packet_read_timeout)
+            .with_large_reply_size(config.large_reply_size);
 
-        // Create the worker and the handle.
-        let worker = Worker::new(receiver_worker, transmitter_worker);
-        let handle = Handle::new(transmitter_handle, receiver_handle);
+        // Create the worker and the handle, sharing the token that signals the termination of the
+        //  worker.
+        let terminated = CancellationToken::new();
+        let worker = Worker::new(receiver_worker, transmitter_worker, terminated.clone());
+        let handle = Handle::new(
+            transmitter_handle,
+            receiver_handle,
+            config.commands_per_second.map(RateLimiter::new),
+            terminated,
+        );
 
         // Return the handle and the worker.
-        Ok((handle, worker))
+        (handle, worker)
     }
 }
 
@@ -97,6 +290,8 @@ where
 {
     receiver_worker: receiver::Worker<R>,
     transmitter_worker: transmitter::Worker<W>,
+    span: Span, // The span everything that happens on the connection is logged in.
+    terminated: CancellationToken, // Triggered once the worker stops running.
 }
 
 impl<R, W> Worker<R, W>
@@ -108,20 +303,32 @@ where
     pub(self) fn new(
         receiver_worker: receiver::Worker<R>,
         transmitter_worker: transmitter::Worker<W>,
+        terminated: CancellationToken,
     ) -> Self {
         Self {
             receiver_worker,
             transmitter_worker,
+            span: tracing::info_span!("connection", peer = field::Empty),
+            terminated,
         }
     }
 
     /// Run the worker.
     pub async fn run(&mut self, cancellation_token: CancellationToken) -> Result<(), Error> {
+        let span = self.span.clone();
+
+        // Let the handle know the worker stopped, no matter how it exits.
+        let _terminated = self.terminated.clone().drop_guard();
+
         // Run the receiver and transmitter workers, exiting when one of them exits.
-        select!(
-            x = self.receiver_worker.run(cancellation_token.clone()) => x,
-            x = self.transmitter_worker.run(cancellation_token) => x
-        )
+        async {
+            select!(
+                x = self.receiver_worker.run(cancellation_token.clone()) => x,
+                x = self.transmitter_worker.run(cancellation_token) => x
+            )
+        }
+        .instrument(span)
+        .await
     }
 }
 
@@ -129,6 +336,10 @@ pub struct Handle {
     tag_generator: TagGenerator,
     transmitter_handle: transmitter::Handle,
     receiver_handle: receiver::Handle,
+    rate_limiter: Option<RateLimiter>,
+    terminated: CancellationToken, // Triggered once the worker stops running.
+    peer_addr: Option<SocketAddr>, // The address of the server, if connected over a socket.
+    local_addr: Option<SocketAddr>, // The address of our end, if connected over a socket.
 }
 
 impl Handle {
@@ -136,12 +347,84 @@ impl Handle {
     pub(self) fn new(
         transmitter_handle: transmitter::Handle,
         receiver_handle: receiver::Handle,
+        rate_limiter: Option<RateLimiter>,
+        terminated: CancellationToken,
     ) -> Self {
         Self {
             tag_generator: TagGenerator::new(),
             transmitter_handle,
             receiver_handle,
+            rate_limiter,
+            terminated,
+            peer_addr: None,
+            local_addr: None,
+        }
+    }
+
+    /// Close the connection, the commands that have been written before are still sent after which
+    ///  the stream is shut down. Waits until the worker has stopped running, so it must be running
+    ///  for this to return.
+    pub async fn close(self) -> Result<(), Error> {
+        // The worker may already have stopped (and dropped the channel), which is fine.
+        match self.transmitter_handle.close().await {
+            Ok(()) | Err(Error::Disconnected) => {}
+            Err(e) => return Err(e),
         }
+
+        self.terminated.cancelled().await;
+
+        Ok(())
+    }
+
+    /// Get the number of commands that have been written but haven't received their reply yet.
+    pub async fn pending_commands(&self) -> usize {
+        self.receiver_handle
+            .subscribers()
+            .pending_reply_count()
+            .await
+    }
+
+    /// Cancel all the commands that are waiting for their reply, the callers waiting in
+    ///  `write_serializable_command` fail with `Error::Cancelled` and reply closures are dropped
+    ///  without being called. Replies that arrive for them later on are ignored.
+    ///
+    /// # Returns
+    ///
+    /// The number of commands that have been cancelled.
+    pub async fn cancel_all_commands(&self) -> usize {
+        self.receiver_handle
+            .subscribers()
+            .cancel_all_replies()
+            .await
+    }
+
+    /// Get the minimum, maximum and average size of the replies received for the command with the
+    ///  given code, or None if no reply has been received for it yet.
+    pub async fn reply_size_stats(&self, code: CommandCode) -> Option<ReplySizeStats> {
+        self.receiver_handle
+            .subscribers()
+            .reply_size_stats(code)
+            .await
+    }
+
+    /// Get the address of the server as it was when connecting, or None if the client wasn't
+    ///  created from a socket.
+    #[inline]
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+
+    /// Get the local address of the connection as it was when connecting, or None if the client
+    ///  wasn't created from a socket.
+    #[inline]
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// Get the number of commands per second the handle is limited to (if rate limited).
+    #[inline]
+    pub fn rate_limit(&self) -> Option<f64> {
+        self.rate_limiter.as_ref().map(|x| x.rate())
     }
 
     pub async fn serde_write_cmd_wc<C, R>(
@@ -154,24 +437,48 @@ impl Handle {
         R: Reply,
     {
         select! {
-            result = self.write_serializable_command::<C, R>(command) => result,
+            result = self.write_serializable_command_wc::<C, R>(command, cancellation_token) => result,
             _ = cancellation_token.cancelled() => Err(Error::Cancelled),
         }
     }
 
     pub async fn write_serializable_command<C, R>(&self, command: C) -> Result<R, Error>
+    where
+        C: Command,
+        R: Reply,
+    {
+        self.write_serializable_command_wc(command, &CancellationToken::new())
+            .await
+    }
+
+    /// Write the given serializable command and wait for its reply, the wait for the rate limiter
+    ///  (if any) stops once the given cancellation token is cancelled.
+    async fn write_serializable_command_wc<C, R>(
+        &self,
+        command: C,
+        cancellation_token: &CancellationToken,
+    ) -> Result<R, Error>
     where
         C: Command,
         R: Reply,
     {
         let (sender, receiver) = oneshot::channel::<Result<R, Error>>();
 
-        self.write_serializable_command_reply_to_closure(command, move |x| {
-            let _ = sender.send(x);
-        })
-        .await?;
+        // The reply subscriber is removed if this future is dropped before the reply arrives.
+        let reply_subscription = self
+            .write_serializable_command_reply_to_closure_wc(
+                command,
+                move |x| {
+                    let _ = sender.send(x);
+                },
+                cancellation_token,
+            )
+            .await?;
+
+        let result = receiver.await.map_err(|_| Error::Cancelled).and_then(|x| x);
+        reply_subscription.disarm();
 
-        receiver.await.map_err(|_| Error::Cancelled).and_then(|x| x)
+        result
     }
 
     /// Write the given serializable command and reply to the given closure.
@@ -180,6 +487,27 @@ impl Handle {
         command: S,
         closure: impl FnOnce(Result<R, Error>) + Send + Sync + 'static,
     ) -> Result<(), Error>
+    where
+        S: Command,
+        R: Reply,
+    {
+        self.write_serializable_command_reply_to_closure_wc(
+            command,
+            closure,
+            &CancellationToken::new(),
+        )
+        .await
+        .map(ReplySubscription::disarm)
+    }
+
+    /// Write the given serializable command and reply to the given closure, the wait for the rate
+    ///  limiter (if any) stops once the given cancellation token is cancelled.
+    async fn write_serializable_command_reply_to_closure_wc<S, R>(
+        &self,
+        command: S,
+        closure: impl FnOnce(Result<R, Error>) + Send + Sync + 'static,
+        cancellation_token: &CancellationToken,
+    ) -> Result<ReplySubscription, Error>
     where
         S: Command,
         R: Reply,
@@ -188,13 +516,18 @@ impl Handle {
         let code = command.code();
 
         // Serialize the command to a byte vector.
-        let value = rmp_serde::to_vec(&command).map_err(|_| Error::SerdeSerError)?;
+        let value = serialize(&command)?;
 
         // Write the serialized command and return it's result.
-        self.write_command_reply_to_closure(code, value, move |x| {
-            // Decode the received reply and call the closure with either the error or the result.
-            closure(rmp_serde::from_slice(&x).map_err(|_| Error::DeserializeError))
-        })
+        self.write_command_reply_to_closure_wc(
+            code,
+            value,
+            move |x| {
+                // Decode the received reply and call the closure with either the error or the result.
+                closure(deserialize(&x))
+            },
+            cancellation_token,
+        )
         .await
     }
 
@@ -205,21 +538,75 @@ impl Handle {
         value: Vec<u8>,
         closure: impl FnOnce(Vec<u8>) + Send + Sync + 'static,
     ) -> Result<(), Error> {
+        self.write_command_reply_to_closure_wc(code, value, closure, &CancellationToken::new())
+            .await
+            .map(ReplySubscription::disarm)
+    }
+
+    /// Write the given command and call the given closure when the reply is received, the wait
+    ///  for the rate limiter (if any) stops once the given cancellation token is cancelled. The
+    ///  returned subscription removes the reply subscriber when dropped, unless it's disarmed.
+    async fn write_command_reply_to_closure_wc(
+        &self,
+        code: CommandCode,
+        value: Vec<u8>,
+        closure: impl FnOnce(Vec<u8>) + Send + Sync + 'static,
+        cancellation_token: &CancellationToken,
+    ) -> Result<ReplySubscription, Error> {
+        // Wait for the rate limiter to allow sending another command (if there is one), a token
+        //  is only taken once granted so cancelling the wait never consumes one.
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire(cancellation_token).await?;
+        }
+
         // Generate the tag of the command and create the packet.
         let tag = self.tag_generator.generate();
         let packet = Packet::Command(code, tag, value);
 
-        // Subscribe to the reply.
+        // Subscribe to the reply, the subscriber is removed again if writing the packet fails.
         self.receiver_handle
             .subscribers()
-            .subscribe_to_reply_with_closure(tag, closure)
+            .subscribe_to_reply_with_closure(code, tag, closure)
             .await?;
+        let reply_subscription =
+            ReplySubscription::new(self.receiver_handle.subscribers().clone(), tag);
 
         // Write the packet to the transmitter.
         self.transmitter_handle.write_packet(packet).await?;
 
-        // Return success.
-        Ok(())
+        // Return the subscription to the reply.
+        Ok(reply_subscription)
+    }
+
+    /// Write the given command without waiting for a reply and without blocking, so it can be
+    ///  used from real-time loops. Returns `Error::WouldBlock` if the transmitter's queue is full or
+    ///  the rate limiter (if any) has no token available right now.
+    pub fn try_write_command_no_reply(
+        &self,
+        code: CommandCode,
+        value: Vec<u8>,
+    ) -> Result<(), Error> {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            if !rate_limiter.try_acquire() {
+                return Err(Error::WouldBlock);
+            }
+        }
+
+        // No reply is expected, so the command gets the reserved tag.
+        let packet = Packet::Command(code, Tag::NO_REPLY, value);
+
+        self.transmitter_handle.try_write_packet(packet)
+    }
+
+    /// Enable or disable flushing every packet as soon as it's written, when streaming many
+    ///  commands it's disabled and `flush` is called once they've all been written.
+    pub async fn set_auto_flush(&self, auto_flush: bool) -> Result<(), Error> {
+        self.transmitter_handle.set_auto_flush(auto_flush).await
+    }
+
+    /// Flush the commands that have been written while auto flush was disabled.
+    pub async fn flush(&self) -> Result<(), Error> {
+        self.transmitter_handle.flush().await
     }
 
     /// Subscribe to the given event in a way that the closure gets called when it's sent.
@@ -234,11 +621,132 @@ impl Handle {
         self.receiver_handle
             .subscribers()
             .subscribe_to_event_with_closure(code, move |x| {
-                closure(rmp_serde::from_slice(&x).map_err(|_| Error::DeserializeError))
+                closure(deserialize(&x))
             })
             .await
     }
 
+    /// Subscribe to the given event like `serde_sub_to_ev` does, but return a subscription that
+    ///  unsubscribes when it's dropped instead of the subscriber id.
+    pub async fn serde_sub_to_ev_guarded<E>(
+        &self,
+        code: EventCode,
+        closure: impl Fn(Result<E, Error>) + Send + Sync + 'static,
+    ) -> Result<Subscription, Error>
+    where
+        E: Event,
+    {
+        let subscriber_id = self.serde_sub_to_ev(code, closure).await?;
+
+        Ok(self.guard_ev_sub(code, subscriber_id))
+    }
+
+    /// Wrap the subscriber that has the given id in a subscription, so that it gets unsubscribed
+    ///  from the given event when the subscription is dropped.
+    pub fn guard_ev_sub(&self, code: EventCode, subscriber_id: SubscriberId) -> Subscription {
+        Subscription::new(
+            self.receiver_handle.subscribers().clone(),
+            code,
+            subscriber_id,
+        )
+    }
+
+    /// Subscribe to the given event using a channel, the raw values of the event are buffered up to
+    ///  the configured event channel capacity, and dropped when the channel is full.
+    pub async fn sub_ev_with_channel(
+        &self,
+        code: EventCode,
+    ) -> Result<(SubscriberId, mpsc::Receiver<Arc<[u8]>>), Error> {
+        self.receiver_handle
+            .subscribers()
+            .subscribe_to_event_with_channel(code)
+            .await
+    }
+
+    /// Wait for the next occurrence of the given event, subscribing only for as long as it takes.
+    ///  The subscription is removed once the event arrives, the wait gets cancelled, or the
+    ///  future is dropped (for example by a timeout). If caching is enabled for the event, the
+    ///  cached value counts as its next occurrence.
+    pub async fn next_event<E>(
+        &self,
+        code: EventCode,
+        cancellation_token: &CancellationToken,
+    ) -> Result<E, Error>
+    where
+        E: Event,
+    {
+        let (subscriber_id, mut receiver) = self.sub_ev_with_channel(code).await?;
+        let _subscription = self.guard_ev_sub(code, subscriber_id);
+
+        let value = select! {
+            x = receiver.recv() => x.ok_or(Error::Generic("Event channel closed".into()))?,
+            _ = cancellation_token.cancelled() => return Err(Error::Cancelled),
+        };
+
+        deserialize(&value)
+    }
+
+    /// Subscribe to the given event, the closure receives the code along with the raw value.
+    pub async fn sub_ev_with_code(
+        &self,
+        code: EventCode,
+        closure: impl Fn(EventCode, Arc<[u8]>) + Send + Sync + 'static,
+    ) -> Result<SubscriberId, Error> {
+        self.receiver_handle
+            .subscribers()
+            .subscribe_to_event_with_closure2(code, closure)
+            .await
+    }
+
+    /// Subscribe to all the events, the closure receives the code and the raw value of every event
+    ///  so it can be used for logging or tracing.
+    pub async fn sub_all_ev(
+        &self,
+        closure: impl Fn(EventCode, Arc<[u8]>) + Send + Sync + 'static,
+    ) -> Result<SubscriberId, Error> {
+        self.receiver_handle
+            .subscribers()
+            .subscribe_to_all_events(closure)
+            .await
+    }
+
+    /// Unsubscribe the subscriber that has the given id from all the events.
+    pub async fn unsub_all_ev(&self, subscriber_id: SubscriberId) -> Result<(), Error> {
+        self.receiver_handle
+            .subscribers()
+            .unsubscribe_from_all_events(subscriber_id)
+            .await
+    }
+
+    /// Set the hook that gets called with the code and raw value of events nobody subscribed to,
+    ///  which is useful to log or count events the client doesn't know about.
+    pub async fn set_unhandled_ev_hook(
+        &self,
+        closure: impl Fn(EventCode, Arc<[u8]>) + Send + Sync + 'static,
+    ) -> Result<(), Error> {
+        self.receiver_handle
+            .subscribers()
+            .set_unhandled_event_hook(closure)
+            .await
+    }
+
+    /// Remove the unhandled event hook, events nobody subscribed to get dropped again.
+    pub async fn clear_unhandled_ev_hook(&self) -> Result<(), Error> {
+        self.receiver_handle
+            .subscribers()
+            .clear_unhandled_event_hook()
+            .await
+    }
+
+    /// Enable caching of the given event, so that late subscribers immediately receive the most
+    ///  recent value of it.
+    pub async fn enable_ev_cache(&self, code: EventCode) -> Result<(), Error> {
+        self.receiver_handle
+            .subscribers()
+            .enable_event_caching(code)
+            .await
+    }
+
     /// Unsubscribe the subscriber that has the given id from the given event.
     pub async fn unsub_ev(
         &self,
@@ -251,3 +759,548 @@ impl Handle {
             .await
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+    use std::collections::HashSet;
+    use std::sync::{atomic::AtomicU64, Arc};
+
+    use serde::{Deserialize, Serialize};
+    use tokio::io::{AsyncWriteExt, BufReader, BufWriter};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::select;
+    use tokio::sync::{mpsc, oneshot};
+    use tokio::time::{self, Duration, Instant};
+    use tokio_util::sync::CancellationToken;
+    use tracing_test::traced_test;
+
+    use crate::client::{
+        config::ClientConfig, deserialize, rate_limiter::RateLimiter, retry_policy::RetryPolicy,
+        serialize, Client, Command, Event, Reply, TagGenerator,
+    };
+    use crate::error::Error;
+    use crate::net::{PacketReader, PacketWriter};
+    use crate::proto::{CommandCode, EventCode, Packet, Tag};
+
+    #[test]
+    pub fn generated_tags_unique_and_not_reserved() {
+        let tag_generator = TagGenerator::new();
+        let tags: HashSet<Tag> = (0..1000_usize).map(|_| tag_generator.generate()).collect();
+
+        assert_eq!(tags.len(), 1000_usize);
+        assert!(!tags.contains(&Tag::NO_REPLY));
+
+        // The reserved tag is skipped when the counter wraps around.
+        let tag_generator = TagGenerator {
+            counter: Arc::new(AtomicU64::new(u64::MAX)),
+        };
+
+        assert_eq!(tag_generator.generate(), Tag::new(u64::MAX));
+        assert_eq!(tag_generator.generate(), Tag::new(1_u64));
+    }
+
+    #[test]
+    pub fn deserialize_mismatched_payload() {
+        // Serialize something that doesn't look like the expected type at all.
+        let value: Vec<u8> = serialize("not a number").unwrap();
+
+        // Make sure that the error keeps the cause.
+        match deserialize::<u64>(&value) {
+            Err(Error::DeserializeError(message)) => assert!(!message.is_empty()),
+            x => panic!("Expected deserialization error, got: {:?}", x),
+        }
+    }
+
+    #[test]
+    pub fn deserialize_unexpected_field() {
+        #[derive(Serialize)]
+        struct NewerReply {
+            available: usize,
+            reserved: usize,
+        }
+
+        #[derive(Deserialize, Debug)]
+        #[serde(deny_unknown_fields)]
+        struct OlderReply {
+            #[allow(dead_code)]
+            available: usize,
+        }
+
+        let newer_reply = NewerReply {
+            available: 4_usize,
+            reserved: 2_usize,
+        };
+
+        // The extra field is caught both when encoded as an array and when encoded as a map.
+        for value in [
+            rmp_serde::to_vec(&newer_reply).unwrap(),
+            rmp_serde::to_vec_named(&newer_reply).unwrap(),
+        ] {
+            match deserialize::<OlderReply>(&value) {
+                Err(Error::DeserializeError(message)) => {
+                    assert!(message.starts_with("unexpected field (schema mismatch)"))
+                }
+                x => panic!("Expected deserialization error, got: {:?}", x),
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    pub async fn connect_with_retry_gives_up() {
+        // Get an address nobody listens on.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let config = ClientConfig::default()
+            .with_retry_policy(RetryPolicy::Fixed(Duration::from_secs(1_u64)))
+            .with_max_attempts(Some(3_usize));
+
+        let start: Instant = Instant::now();
+        let result = Client::connect_with_retry(addr, &config).await;
+
+        // Three attempts with a wait between each of them, failing with why the last one failed.
+        assert!(matches!(
+            result,
+            Err(Error::IOError(ref error)) if error.kind() == std::io::ErrorKind::ConnectionRefused
+        ));
+        assert!(start.elapsed() >= Duration::from_secs(2_u64));
+        assert!(start.elapsed() < Duration::from_secs(3_u64));
+    }
+
+    #[tokio::test(start_paused = true)]
+    pub async fn rate_limited_burst() {
+        // Create a rate limited client on top of an in-memory stream.
+        let (stream, _peer) = tokio::io::duplex(4096_usize);
+        let (reader, writer) = tokio::io::split(stream);
+        let config = ClientConfig::default().with_commands_per_second(Some(10_f64));
+        let (handle, _worker) = Client::from_split(reader, writer, &config);
+
+        // Send a burst of 20 commands and measure how long it took.
+        let start: Instant = Instant::now();
+
+        for _ in 0..20 {
+            handle
+                .write_command_reply_to_closure(CommandCode::new(0_u32), Vec::new(), |_| {})
+                .await
+                .unwrap();
+        }
+
+        let elapsed: Duration = start.elapsed();
+
+        // At 10 commands per second, 20 commands should take roughly 2 seconds.
+        assert!(elapsed >= Duration::from_millis(1800_u64));
+        assert!(elapsed <= Duration::from_millis(2200_u64));
+    }
+
+    #[tokio::test(start_paused = true)]
+    pub async fn rate_limiter_wait_cancelled() {
+        // Take the only token of a rate limiter that allows one command per second.
+        let rate_limiter = RateLimiter::new(1_f64);
+        let cancellation_token = CancellationToken::new();
+        rate_limiter.acquire(&cancellation_token).await.unwrap();
+
+        // Cancel the wait for the next token long before it becomes available.
+        tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move {
+                time::sleep(Duration::from_millis(100_u64)).await;
+                cancellation_token.cancel();
+            }
+        });
+
+        let start: Instant = Instant::now();
+        assert!(matches!(
+            rate_limiter.acquire(&cancellation_token).await,
+            Err(Error::Cancelled)
+        ));
+        assert!(start.elapsed() < Duration::from_millis(200_u64));
+
+        // The cancelled wait didn't consume the next token.
+        time::sleep(Duration::from_secs(1_u64)).await;
+        assert!(rate_limiter.try_acquire());
+    }
+
+    #[tokio::test]
+    pub async fn small_channel_capacities() {
+        let (stream, _peer) = tokio::io::duplex(4096_usize);
+        let (reader, writer) = tokio::io::split(stream);
+        let config = ClientConfig::default()
+            .with_transmitter_capacity(2_usize)
+            .with_event_channel_capacity(1_usize);
+        let (handle, _worker) = Client::from_split(reader, writer, &config);
+
+        // Without a running worker only two commands fit in the transmitter.
+        for _ in 0..2 {
+            handle
+                .try_write_command_no_reply(CommandCode::new(0_u32), Vec::new())
+                .unwrap();
+        }
+        assert!(matches!(
+            handle.try_write_command_no_reply(CommandCode::new(0_u32), Vec::new()),
+            Err(Error::WouldBlock)
+        ));
+
+        // The channel subscriber is created with the configured capacity.
+        let (_, receiver) = handle
+            .sub_ev_with_channel(EventCode::new(0_u32))
+            .await
+            .unwrap();
+        assert_eq!(receiver.max_capacity(), 1_usize);
+    }
+
+    #[tokio::test]
+    pub async fn connection_addresses_kept() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let (handle, _worker) = Client::connect(addr).await.unwrap();
+        let (_server_stream, client_addr) = listener.accept().await.unwrap();
+
+        assert_eq!(handle.peer_addr(), Some(addr));
+        assert_eq!(handle.local_addr(), Some(client_addr));
+
+        // Clients that aren't created from a socket have no addresses.
+        let (stream, _peer) = tokio::io::duplex(64_usize);
+        let (reader, writer) = tokio::io::split(stream);
+        let (handle, _worker) = Client::from_split(reader, writer, &ClientConfig::default());
+
+        assert_eq!(handle.peer_addr(), None);
+        assert_eq!(handle.local_addr(), None);
+    }
+
+    #[tokio::test]
+    pub async fn client_from_configured_stream() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // Configure the socket before handing it to the client.
+        let stream = TcpStream::connect(addr).await.unwrap();
+        stream.set_nodelay(true).unwrap();
+        let (handle, mut worker) = Client::from_stream(stream);
+
+        let cancellation_token = CancellationToken::new();
+        tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move { worker.run(cancellation_token).await }
+        });
+
+        // Echo the value of the command back in the reply from the mock server.
+        let (server_stream, _) = listener.accept().await.unwrap();
+        let server_task = tokio::spawn(async move {
+            let (reader, writer) = server_stream.into_split();
+            let mut buf_reader = BufReader::new(reader);
+            let mut buf_writer = BufWriter::new(writer);
+
+            let Packet::Command(code, tag, value) =
+                PacketReader::read(&mut buf_reader).await.unwrap()
+            else {
+                panic!("Expected a command packet");
+            };
+            assert_eq!(code, CommandCode::new(3_u32));
+
+            PacketWriter::write(&mut buf_writer, &Packet::Reply(tag, value))
+                .await
+                .unwrap();
+            buf_writer.flush().await.unwrap();
+        });
+
+        let (sender, receiver) = oneshot::channel::<Vec<u8>>();
+        handle
+            .write_command_reply_to_closure(CommandCode::new(3_u32), vec![1_u8, 2_u8], |x| {
+                let _ = sender.send(x);
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            tokio::time::timeout(Duration::from_secs(5_u64), receiver)
+                .await
+                .unwrap()
+                .unwrap(),
+            vec![1_u8, 2_u8]
+        );
+
+        server_task.await.unwrap();
+        cancellation_token.cancel();
+    }
+
+    /// Event that's only used by the tests.
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct TestEvent {
+        value: u32,
+    }
+
+    impl Event for TestEvent {
+        fn code(&self) -> EventCode {
+            EventCode::new(5_u32)
+        }
+    }
+
+    /// Command that's only used by the tests.
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct TestCommand {
+        value: u32,
+    }
+
+    impl Command for TestCommand {
+        fn code(&self) -> CommandCode {
+            CommandCode::new(3_u32)
+        }
+    }
+
+    /// Reply that's only used by the tests.
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct TestReply {
+        value: u32,
+    }
+
+    impl Reply for TestReply {}
+
+    /// Command that can never be serialized, like one with a broken `Serialize` implementation.
+    struct UnserializableCommand;
+
+    impl Serialize for UnserializableCommand {
+        fn serialize<S>(&self, _: S) -> Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            Err(serde::ser::Error::custom("Unserializable"))
+        }
+    }
+
+    impl Command for UnserializableCommand {
+        fn code(&self) -> CommandCode {
+            CommandCode::new(3_u32)
+        }
+    }
+
+    #[tokio::test]
+    pub async fn unserializable_command_not_subscribed() {
+        let (stream, _peer) = tokio::io::duplex(4096_usize);
+        let (reader, writer) = tokio::io::split(stream);
+        let (handle, _worker) = Client::from_split(reader, writer, &ClientConfig::default());
+
+        assert!(matches!(
+            handle
+                .write_serializable_command::<_, TestReply>(UnserializableCommand)
+                .await,
+            Err(Error::SerdeSerError {
+                data_error: true,
+                ..
+            })
+        ));
+
+        // The command failed before it was sent, so nobody is waiting for a reply to it.
+        assert_eq!(handle.pending_commands().await, 0_usize);
+    }
+
+    #[tokio::test]
+    pub async fn cancel_all_pending_commands() {
+        let (stream, _peer) = tokio::io::duplex(4096_usize);
+        let (reader, writer) = tokio::io::split(stream);
+        let (handle, mut worker) = Client::from_split(reader, writer, &ClientConfig::default());
+
+        let cancellation_token = CancellationToken::new();
+
+        // The peer never replies, so the commands stay pending until they're cancelled.
+        let cancel = async {
+            while handle.pending_commands().await < 3_usize {
+                tokio::task::yield_now().await;
+            }
+
+            handle.cancel_all_commands().await
+        };
+
+        let results = select! {
+            x = async {
+                tokio::join!(
+                    handle.write_serializable_command::<_, TestReply>(TestCommand { value: 1_u32 }),
+                    handle.write_serializable_command::<_, TestReply>(TestCommand { value: 2_u32 }),
+                    handle.write_serializable_command::<_, TestReply>(TestCommand { value: 3_u32 }),
+                    cancel,
+                )
+            } => x,
+            _ = worker.run(cancellation_token.clone()) => panic!("Worker exited"),
+        };
+
+        assert!(matches!(results.0, Err(Error::Cancelled)));
+        assert!(matches!(results.1, Err(Error::Cancelled)));
+        assert!(matches!(results.2, Err(Error::Cancelled)));
+        assert_eq!(results.3, 3_usize);
+        assert_eq!(handle.pending_commands().await, 0_usize);
+    }
+
+    #[tokio::test]
+    pub async fn abandoned_commands_unsubscribed() {
+        let (stream, _peer) = tokio::io::duplex(4096_usize);
+        let (reader, writer) = tokio::io::split(stream);
+        let (handle, _worker) = Client::from_split(reader, writer, &ClientConfig::default());
+
+        // The peer never replies, so the caller gives up waiting after a timeout.
+        let result = time::timeout(
+            Duration::from_millis(50_u64),
+            handle.write_serializable_command::<_, TestReply>(TestCommand { value: 1_u32 }),
+        )
+        .await;
+        assert!(result.is_err());
+
+        // Or when its cancellation token is cancelled.
+        let cancellation_token = CancellationToken::new();
+        let result = select! {
+            x = handle.serde_write_cmd_wc::<_, TestReply>(TestCommand { value: 2_u32 }, &cancellation_token) => x,
+            _ = async {
+                time::sleep(Duration::from_millis(50_u64)).await;
+                cancellation_token.cancel();
+                std::future::pending::<()>().await
+            } => unreachable!(),
+        };
+        assert!(matches!(result, Err(Error::Cancelled)));
+
+        // Give the spawned unsubscribes a chance to run, neither reply subscriber is left behind.
+        tokio::task::yield_now().await;
+        assert_eq!(handle.pending_commands().await, 0_usize);
+    }
+
+    #[tokio::test]
+    pub async fn next_event_unsubscribes() {
+        let (stream, peer) = tokio::io::duplex(4096_usize);
+        let (reader, writer) = tokio::io::split(stream);
+        let (handle, mut worker) = Client::from_split(reader, writer, &ClientConfig::default());
+
+        let cancellation_token = CancellationToken::new();
+        tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move { worker.run(cancellation_token).await }
+        });
+
+        let code = EventCode::new(5_u32);
+        let mut peer = BufWriter::new(peer);
+
+        // Publish the event once the client is waiting for it.
+        let (event, _) = tokio::join!(
+            time::timeout(
+                Duration::from_secs(5_u64),
+                handle.next_event::<TestEvent>(code, &cancellation_token)
+            ),
+            async {
+                time::sleep(Duration::from_millis(10_u64)).await;
+
+                let value = serialize(&TestEvent { value: 42_u32 }).unwrap();
+                PacketWriter::write(&mut peer, &Packet::Event(code, value))
+                    .await
+                    .unwrap();
+            }
+        );
+        assert_eq!(event.unwrap().unwrap(), TestEvent { value: 42_u32 });
+
+        // Give the dropped subscription a chance to unsubscribe.
+        tokio::task::yield_now().await;
+
+        // Nobody is subscribed to the event anymore, so the next one ends up in the hook.
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        handle
+            .set_unhandled_ev_hook(move |code, _| {
+                let _ = sender.send(code);
+            })
+            .await
+            .unwrap();
+
+        let value = serialize(&TestEvent { value: 43_u32 }).unwrap();
+        PacketWriter::write(&mut peer, &Packet::Event(code, value))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            time::timeout(Duration::from_secs(5_u64), receiver.recv())
+                .await
+                .unwrap(),
+            Some(code)
+        );
+
+        cancellation_token.cancel();
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    pub async fn command_round_trip_traced() {
+        let (stream, peer) = tokio::io::duplex(4096_usize);
+        let (reader, writer) = tokio::io::split(stream);
+        let (handle, mut worker) = Client::from_split(reader, writer, &ClientConfig::default());
+
+        let cancellation_token = CancellationToken::new();
+
+        // The worker runs on this task (instead of being spawned) so its logs end up in the
+        //  span of the test.
+        let (sender, receiver) = oneshot::channel::<Vec<u8>>();
+        let round_trip = async {
+            handle
+                .write_command_reply_to_closure(CommandCode::new(3_u32), vec![1_u8], |x| {
+                    let _ = sender.send(x);
+                })
+                .await
+                .unwrap();
+
+            // Echo the value of the command back in the reply.
+            let (reader, writer) = tokio::io::split(peer);
+            let mut buf_reader = BufReader::new(reader);
+            let mut buf_writer = BufWriter::new(writer);
+
+            let Packet::Command(_, tag, value) = PacketReader::read(&mut buf_reader).await.unwrap()
+            else {
+                panic!("Expected a command packet");
+            };
+            PacketWriter::write(&mut buf_writer, &Packet::Reply(tag, value))
+                .await
+                .unwrap();
+
+            receiver.await.unwrap()
+        };
+
+        let value = select! {
+            x = round_trip => x,
+            _ = worker.run(cancellation_token.clone()) => panic!("Worker exited"),
+        };
+        assert_eq!(value, vec![1_u8]);
+
+        assert!(logs_contain("connection"));
+        assert!(logs_contain("Sending command command=3"));
+        assert!(logs_contain("Received reply"));
+    }
+
+    #[tokio::test]
+    pub async fn write_after_close_disconnected() {
+        let (stream, peer) = tokio::io::duplex(4096_usize);
+        let (reader, writer) = tokio::io::split(stream);
+        let (handle, mut worker) = Client::from_split(reader, writer, &ClientConfig::default());
+
+        let worker = tokio::spawn(async move { worker.run(CancellationToken::new()).await });
+
+        // Keep another handle to the transmitter around, to write after the client is closed.
+        let transmitter_handle = handle.transmitter_handle.clone();
+
+        handle
+            .try_write_command_no_reply(CommandCode::new(3_u32), vec![1_u8])
+            .unwrap();
+        time::timeout(Duration::from_secs(5_u64), handle.close())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(worker.await.unwrap().is_ok());
+
+        // The queued command was still sent, after which the stream was shut down.
+        let mut buf_reader = BufReader::new(peer);
+        let Packet::Command(code, _, value) = PacketReader::read(&mut buf_reader).await.unwrap()
+        else {
+            panic!("Expected a command packet");
+        };
+        assert_eq!((code, value), (CommandCode::new(3_u32), vec![1_u8]));
+        assert!(PacketReader::read(&mut buf_reader).await.is_err());
+
+        let packet = Packet::Command(CommandCode::new(3_u32), Tag::NO_REPLY, vec![1_u8]);
+        assert!(matches!(
+            transmitter_handle.write_packet(packet).await,
+            Err(Error::Disconnected)
+        ));
+    }
+}
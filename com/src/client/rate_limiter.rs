@@ -0,0 +1,94 @@
+use tokio::{
+    select,
+    sync::Mutex,
+    time::{self, Duration, Instant},
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::error::Error;
+
+/// This struct represents the state of the token bucket.
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// This struct represents a token-bucket rate limiter.
+///
+/// The bucket holds at most a single token, which means that the commands are spaced out evenly
+///  at the configured rate instead of being allowed to burst.
+pub(crate) struct RateLimiter {
+    rate: f64,
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    /// The maximum number of tokens the bucket can hold.
+    pub(self) const CAPACITY: f64 = 1_f64;
+
+    /// Create a new rate limiter that allows the given number of commands per second.
+    pub(crate) fn new(rate: f64) -> Self {
+        assert!(rate > 0_f64);
+
+        Self {
+            rate,
+            bucket: Mutex::new(Bucket {
+                tokens: Self::CAPACITY,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// Get the number of commands per second.
+    #[inline(always)]
+    pub(crate) fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Wait until a token is available and take it, or until the given cancellation token is
+    ///  cancelled in which case `Error::Cancelled` is returned and no token is taken.
+    pub(crate) async fn acquire(
+        &self,
+        cancellation_token: &CancellationToken,
+    ) -> Result<(), Error> {
+        loop {
+            // Try to take a token, getting how long to wait if there's none.
+            let wait = select! {
+                mut bucket = self.bucket.lock() => match self.take_token(&mut bucket) {
+                    Ok(()) => return Ok(()),
+                    Err(wait) => wait,
+                },
+                _ = cancellation_token.cancelled() => return Err(Error::Cancelled),
+            };
+
+            // Wait for the next token to become available, unless cancelled first.
+            select! {
+                _ = time::sleep(wait) => {}
+                _ = cancellation_token.cancelled() => return Err(Error::Cancelled),
+            }
+        }
+    }
+
+    /// Take a token if one is available right now, without waiting for it.
+    pub(crate) fn try_acquire(&self) -> bool {
+        match self.bucket.try_lock() {
+            Ok(mut bucket) => self.take_token(&mut bucket).is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    /// Refill the bucket and take a token, returning how long to wait if there's none.
+    pub(self) fn take_token(&self, bucket: &mut Bucket) -> Result<(), Duration> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(Self::CAPACITY);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1_f64 {
+            bucket.tokens -= 1_f64;
+            return Ok(());
+        }
+
+        Err(Duration::from_secs_f64((1_f64 - bucket.tokens) / self.rate))
+    }
+}
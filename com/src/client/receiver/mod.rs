@@ -7,16 +7,18 @@ use std::{
 };
 
 use tokio::{
-    io::{AsyncRead, BufReader},
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
     select,
-    sync::{mpsc, oneshot, RwLock},
+    sync::{mpsc, RwLock},
+    time::{self, Duration},
 };
 use tokio_util::sync::CancellationToken;
+use tracing::{debug, trace, warn};
 
 use crate::{
     error::Error,
     net::PacketReader,
-    proto::{EventCode, Packet, Tag},
+    proto::{CommandCode, EventCode, Packet, Tag},
 };
 
 /// This struct represents a subscriber id.
@@ -37,9 +39,106 @@ impl SubscriberId {
     }
 }
 
+/// This struct represents a subscription to an event, which unsubscribes from the event when it's
+///  dropped so the subscriber (and everything its closure holds) doesn't leak.
+pub struct Subscription {
+    subscribers: Subscribers,
+    event: EventCode,
+    subscriber_id: SubscriberId,
+}
+
+impl Subscription {
+    /// Create a new subscription for the subscriber with the given id.
+    pub(crate) fn new(
+        subscribers: Subscribers,
+        event: EventCode,
+        subscriber_id: SubscriberId,
+    ) -> Self {
+        Self {
+            subscribers,
+            event,
+            subscriber_id,
+        }
+    }
+
+    /// Get the code of the event that is subscribed to.
+    #[inline(always)]
+    pub fn event(&self) -> EventCode {
+        self.event
+    }
+
+    /// Get the id of the subscriber.
+    #[inline(always)]
+    pub fn subscriber_id(&self) -> SubscriberId {
+        self.subscriber_id
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let subscribers = self.subscribers.clone();
+        let event = self.event;
+        let subscriber_id = self.subscriber_id;
+
+        // Unsubscribing is async, so spawn it on the runtime (if it's still there).
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(async move {
+                let _ = subscribers
+                    .unsubscribe_from_event(event, subscriber_id)
+                    .await;
+            });
+        }
+    }
+}
+
+/// This struct represents a subscription to the reply of a command, which unsubscribes from the
+///  reply when it's dropped before being disarmed, so a command whose caller stopped waiting (for
+///  example after a timeout) doesn't leak its reply subscriber.
+pub(crate) struct ReplySubscription {
+    subscribers: Subscribers,
+    tag: Tag,
+    armed: bool,
+}
+
+impl ReplySubscription {
+    /// Create a new subscription for the reply with the given tag.
+    pub(crate) fn new(subscribers: Subscribers, tag: Tag) -> Self {
+        Self {
+            subscribers,
+            tag,
+            armed: true,
+        }
+    }
+
+    /// Keep the reply subscriber when the subscription is dropped, because the reply has been
+    ///  received or should still be delivered to its closure.
+    #[inline(always)]
+    pub(crate) fn disarm(mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for ReplySubscription {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+
+        let subscribers = self.subscribers.clone();
+        let tag = self.tag;
+
+        // Unsubscribing is async, so spawn it on the runtime (if it's still there).
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(async move {
+                let _ = subscribers.unsubscribe_from_reply(tag).await;
+            });
+        }
+    }
+}
+
 /// This struct represents the subscriber id generator.
 #[derive(Clone)]
-pub(self) struct SubscriberIdGenerator {
+struct SubscriberIdGenerator {
     counter: Arc<AtomicU64>,
 }
 
@@ -57,6 +156,41 @@ impl SubscriberIdGenerator {
     }
 }
 
+/// This struct represents the sizes of the replies received for a command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReplySizeStats {
+    pub count: u64, // The number of replies received.
+    pub min: usize, // The size of the smallest reply (in bytes).
+    pub max: usize, // The size of the largest reply (in bytes).
+    pub total: u64, // The sum of the sizes of all replies (in bytes).
+}
+
+impl ReplySizeStats {
+    /// Create the stats of a single reply with the given size.
+    pub fn new(size: usize) -> Self {
+        Self {
+            count: 1_u64,
+            min: size,
+            max: size,
+            total: size as u64,
+        }
+    }
+
+    /// Add a reply with the given size to the stats.
+    pub fn record(&mut self, size: usize) {
+        self.count += 1_u64;
+        self.min = self.min.min(size);
+        self.max = self.max.max(size);
+        self.total += size as u64;
+    }
+
+    /// Get the average size of the replies (in bytes).
+    #[inline(always)]
+    pub fn average(&self) -> f64 {
+        self.total as f64 / self.count as f64
+    }
+}
+
 /// This struct represents the receiver.
 pub(super) struct Receiver<R>
 where
@@ -69,10 +203,16 @@ impl<R> Receiver<R>
 where
     R: AsyncRead + Unpin,
 {
-    /// Create a new receiver for the given reader.
-    pub(super) fn new(reader: R) -> (Worker<R>, Handle) {
+    /// Create a new receiver for the given reader, channel subscribers buffer up to the given
+    ///  number of events and each event accepts up to the given number of subscribers (if any).
+    #[allow(clippy::new_ret_no_self)] // The receiver itself is split into a worker and a handle.
+    pub(super) fn new(
+        reader: R,
+        event_channel_capacity: usize,
+        max_subscribers_per_event: Option<usize>,
+    ) -> (Worker<R>, Handle) {
         // Create the subscribers.
-        let subscribers = Subscribers::new();
+        let subscribers = Subscribers::new(event_channel_capacity, max_subscribers_per_event);
 
         // Create the worker and handle.
         let worker = Worker::new(reader, subscribers.clone());
@@ -84,60 +224,146 @@ where
 }
 
 /// This enum represents a reply subscriber.
-pub(self) enum ReplySubscriber {
+enum ReplySubscriber {
     /// A closure that will receive the reply.
     Closure(Box<dyn FnOnce(Vec<u8>) + Send + Sync + 'static>),
 }
 
-/// This enum represents an event subscriber.
-pub(self) enum EventSubscriber {
+/// This enum represents an event subscriber, the value of an event is shared between all its
+///  subscribers instead of being copied for each of them.
+enum EventSubscriber {
     /// A closure that will receive the event.
-    Closure(Box<dyn Fn(Vec<u8>) + Send + Sync + 'static>),
+    Closure(Box<dyn Fn(Arc<[u8]>) + Send + Sync + 'static>),
+    /// A closure that will receive the event along with its code.
+    ClosureWithCode(Box<dyn Fn(EventCode, Arc<[u8]>) + Send + Sync + 'static>),
+    /// A channel that will receive the event, the event is dropped if the channel is full.
+    Channel(mpsc::Sender<Arc<[u8]>>),
 }
 
+impl EventSubscriber {
+    /// Deliver the given event to the subscriber.
+    pub(self) fn deliver(&self, event: EventCode, value: Arc<[u8]>) {
+        match self {
+            EventSubscriber::Closure(closure) => closure(value),
+            EventSubscriber::ClosureWithCode(closure) => closure(event, value),
+            EventSubscriber::Channel(sender) => match sender.try_send(value) {
+                Ok(()) => {}
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    warn!(event = event.inner(), "Dropped event, channel full");
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => {
+                    warn!(event = event.inner(), "Dropped event, channel closed");
+                }
+            },
+        }
+    }
+}
+
+/// The last value of each event that has caching enabled (if it has been received).
+type EventCache = HashMap<EventCode, Option<Arc<[u8]>>>;
+
+/// A list of event subscribers, shared so it can be locked without holding the map's lock.
+type EventSubscriberList = Arc<RwLock<Vec<(SubscriberId, EventSubscriber)>>>;
+
 /// This struct is a clonable representation of the subscribers.
 #[derive(Clone)]
 pub(crate) struct Subscribers {
-    reply_subscribers: Arc<RwLock<HashMap<Tag, ReplySubscriber>>>,
-    event_subscribers:
-        Arc<RwLock<HashMap<EventCode, Arc<RwLock<Vec<(SubscriberId, EventSubscriber)>>>>>>,
+    reply_subscribers: Arc<RwLock<HashMap<Tag, (CommandCode, ReplySubscriber)>>>,
+    reply_size_stats: Arc<RwLock<HashMap<CommandCode, ReplySizeStats>>>,
+    event_subscribers: Arc<RwLock<HashMap<EventCode, EventSubscriberList>>>,
+    all_event_subscribers: EventSubscriberList,
+    unhandled_event_hook: Arc<RwLock<Option<EventSubscriber>>>,
+    event_cache: Arc<RwLock<EventCache>>,
+    event_channel_capacity: usize,
+    max_subscribers_per_event: Option<usize>, // The maximum number of subscribers of one event.
     subscriber_id_generator: SubscriberIdGenerator,
 }
 
 impl Subscribers {
     /// Create a new subscribers.
-    pub(self) fn new() -> Self {
+    pub(self) fn new(
+        event_channel_capacity: usize,
+        max_subscribers_per_event: Option<usize>,
+    ) -> Self {
         Self {
             reply_subscribers: Arc::new(RwLock::new(HashMap::new())),
+            reply_size_stats: Arc::new(RwLock::new(HashMap::new())),
             event_subscribers: Arc::new(RwLock::new(HashMap::new())),
+            all_event_subscribers: Arc::new(RwLock::new(Vec::new())),
+            unhandled_event_hook: Arc::new(RwLock::new(None)),
+            event_cache: Arc::new(RwLock::new(HashMap::new())),
+            event_channel_capacity,
+            max_subscribers_per_event,
             subscriber_id_generator: SubscriberIdGenerator::new(),
         }
     }
 
-    /// Takes the reply subscriber that has the given tag.
-    pub(self) async fn take_reply_subscriber_with_tag(&self, tag: Tag) -> Option<ReplySubscriber> {
+    /// Takes the reply subscriber that has the given tag, along with the code of its command.
+    pub(self) async fn take_reply_subscriber_with_tag(
+        &self,
+        tag: Tag,
+    ) -> Option<(CommandCode, ReplySubscriber)> {
         let mut reply_subscribers = self.reply_subscribers.write().await;
         reply_subscribers.remove(&tag)
     }
 
+    /// Add a reply of the given size to the reply size stats of the given command.
+    pub(self) async fn record_reply_size(&self, code: CommandCode, size: usize) {
+        let mut reply_size_stats = self.reply_size_stats.write().await;
+
+        reply_size_stats
+            .entry(code)
+            .and_modify(|x| x.record(size))
+            .or_insert_with(|| ReplySizeStats::new(size));
+    }
+
+    /// Get the sizes of the replies received for the given command (if any were received).
+    pub(super) async fn reply_size_stats(&self, code: CommandCode) -> Option<ReplySizeStats> {
+        self.reply_size_stats.read().await.get(&code).copied()
+    }
+
     /// Get the event subscribers that subscribed to the given event.
     pub(self) async fn get_event_subscribers_with_tag(
         &self,
         event: EventCode,
-    ) -> Option<Arc<RwLock<Vec<(SubscriberId, EventSubscriber)>>>> {
+    ) -> Option<EventSubscriberList> {
         let event_subscribers = self.event_subscribers.read().await;
-        event_subscribers.get(&event).map(|x| x.clone())
+        event_subscribers.get(&event).cloned()
+    }
+
+    /// Enable caching of the last value of the given event, so that subscribers that subscribe
+    ///  after the event has been received still get the most recent value.
+    pub(super) async fn enable_event_caching(&self, event: EventCode) -> Result<(), Error> {
+        // Register the event in the cache (without a value) if it's not registered yet.
+        let mut event_cache = self.event_cache.write().await;
+        event_cache.entry(event).or_insert(None);
+
+        // Return success.
+        Ok(())
+    }
+
+    /// Store the given value as the last value of the given event, if caching is enabled for it.
+    pub(self) async fn cache_event_value(&self, event: EventCode, value: &Arc<[u8]>) {
+        let mut event_cache = self.event_cache.write().await;
+
+        if let Some(cached_value) = event_cache.get_mut(&event) {
+            *cached_value = Some(value.clone());
+        }
     }
 
-    /// Subscribe to the event that has the given event.
+    /// Get the cached last value of the given event (if caching is enabled and a value is present).
+    pub(self) async fn get_cached_event_value(&self, event: EventCode) -> Option<Arc<[u8]>> {
+        let event_cache = self.event_cache.read().await;
+        event_cache.get(&event).cloned().flatten()
+    }
+
+    /// Subscribe to the event that has the given event, fails with `Error::TooManySubscribers` if
+    ///  the event already has the maximum number of subscribers.
     pub(self) async fn subscribe_to_event(
         &self,
         event: EventCode,
         subscriber: EventSubscriber,
     ) -> Result<SubscriberId, Error> {
-        // Generate the subscriber id.
-        let subscriber_id = self.subscriber_id_generator.generate();
-
         // Acquire the lock for the event subscribers.
         let mut event_subscribers = self.event_subscribers.write().await;
 
@@ -148,6 +374,22 @@ impl Subscribers {
             .write()
             .await;
 
+        // Make sure the event doesn't get more subscribers than allowed.
+        if let Some(max_subscribers_per_event) = self.max_subscribers_per_event {
+            if subscribers.len() >= max_subscribers_per_event {
+                warn!(event = event.inner(), "Too many subscribers for event");
+                return Err(Error::TooManySubscribers);
+            }
+        }
+
+        // Generate the subscriber id.
+        let subscriber_id = self.subscriber_id_generator.generate();
+
+        // If the event is cached, deliver the last value to the new subscriber immediately.
+        if let Some(value) = self.get_cached_event_value(event).await {
+            subscriber.deliver(event, value);
+        }
+
         // Add the subscriber to the list of subscribers.
         subscribers.push((subscriber_id, subscriber));
 
@@ -165,7 +407,7 @@ impl Subscribers {
         let event_subscribers = self.event_subscribers.read().await;
 
         // Get all the subscribers of the event.
-        if let Some(subscribers) = event_subscribers.get(&event).map(|x| x.clone()) {
+        if let Some(subscribers) = event_subscribers.get(&event).cloned() {
             // Acquire a lock on the subscribers list.
             let mut subscribers = subscribers.write().await;
 
@@ -202,7 +444,7 @@ impl Subscribers {
         closure: F,
     ) -> Result<SubscriberId, Error>
     where
-        F: Fn(Vec<u8>) + Send + Sync + 'static,
+        F: Fn(Arc<[u8]>) + Send + Sync + 'static,
     {
         // Subscribe to the event.
         let subscriber_id = self
@@ -213,15 +455,112 @@ impl Subscribers {
         Ok(subscriber_id)
     }
 
-    /// Subscribe to the reply that has the given tag.
+    /// Subscribe to the event that has the given code using a channel, which buffers up to the
+    ///  configured event channel capacity.
+    pub(super) async fn subscribe_to_event_with_channel(
+        &self,
+        event: EventCode,
+    ) -> Result<(SubscriberId, mpsc::Receiver<Arc<[u8]>>), Error> {
+        // Create the channel.
+        let (sender, receiver) = mpsc::channel(self.event_channel_capacity);
+
+        // Subscribe to the event.
+        let subscriber_id = self
+            .subscribe_to_event(event, EventSubscriber::Channel(sender))
+            .await?;
+
+        // Return the subscriber id and the receiver.
+        Ok((subscriber_id, receiver))
+    }
+
+    /// Subscribe to the event that has the given code using the given closure, which also receives
+    ///  the code of the event.
+    pub(super) async fn subscribe_to_event_with_closure2<F>(
+        &self,
+        event: EventCode,
+        closure: F,
+    ) -> Result<SubscriberId, Error>
+    where
+        F: Fn(EventCode, Arc<[u8]>) + Send + Sync + 'static,
+    {
+        self.subscribe_to_event(event, EventSubscriber::ClosureWithCode(Box::new(closure)))
+            .await
+    }
+
+    /// Subscribe to all the events using the given closure, this is meant for consumers such as
+    ///  logging or tracing that are interested in every event.
+    pub(super) async fn subscribe_to_all_events<F>(&self, closure: F) -> Result<SubscriberId, Error>
+    where
+        F: Fn(EventCode, Arc<[u8]>) + Send + Sync + 'static,
+    {
+        // Generate the subscriber id.
+        let subscriber_id = self.subscriber_id_generator.generate();
+
+        // Add the subscriber to the list of catch-all subscribers.
+        let mut all_event_subscribers = self.all_event_subscribers.write().await;
+        all_event_subscribers.push((
+            subscriber_id,
+            EventSubscriber::ClosureWithCode(Box::new(closure)),
+        ));
+
+        // Return the subscriber id.
+        Ok(subscriber_id)
+    }
+
+    /// Unsubscribe the catch-all subscriber with the given id.
+    pub(super) async fn unsubscribe_from_all_events(
+        &self,
+        subscriber_id: SubscriberId,
+    ) -> Result<(), Error> {
+        let mut all_event_subscribers = self.all_event_subscribers.write().await;
+
+        // Remove the subscriber that has the given id, and return an error if it wasn't there.
+        let initial_len = all_event_subscribers.len();
+        all_event_subscribers.retain(|(x, _)| *x != subscriber_id);
+
+        if initial_len == all_event_subscribers.len() {
+            Err(Error::Generic(
+                format!(
+                    "No catch-all event subscriber with id {} found",
+                    subscriber_id.inner()
+                )
+                .into(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Set the hook that is called with the events that have no subscriber for their code, this
+    ///  replaces the previous hook (if any). Without a hook these events are dropped.
+    pub(super) async fn set_unhandled_event_hook<F>(&self, closure: F) -> Result<(), Error>
+    where
+        F: Fn(EventCode, Arc<[u8]>) + Send + Sync + 'static,
+    {
+        let mut unhandled_event_hook = self.unhandled_event_hook.write().await;
+        *unhandled_event_hook = Some(EventSubscriber::ClosureWithCode(Box::new(closure)));
+
+        Ok(())
+    }
+
+    /// Remove the unhandled event hook, so that events without a subscriber are dropped again.
+    pub(super) async fn clear_unhandled_event_hook(&self) -> Result<(), Error> {
+        let mut unhandled_event_hook = self.unhandled_event_hook.write().await;
+        *unhandled_event_hook = None;
+
+        Ok(())
+    }
+
+    /// Subscribe to the reply that has the given tag, for a command with the given code.
     pub(self) async fn subscribe_to_reply(
         &self,
+        code: CommandCode,
         tag: Tag,
         subscriber: ReplySubscriber,
     ) -> Result<(), Error> {
         // Insert the channel into the reply subscribers.
         let mut reply_subscribers = self.reply_subscribers.write().await;
-        reply_subscribers.entry(tag).or_insert(subscriber);
+        reply_subscribers.entry(tag).or_insert((code, subscriber));
 
         // Return success.
         Ok(())
@@ -230,6 +569,7 @@ impl Subscribers {
     /// Subscribe to the reply that has the given tag using the given closure.
     pub(super) async fn subscribe_to_reply_with_closure<F>(
         &self,
+        code: CommandCode,
         tag: Tag,
         closure: F,
     ) -> Result<(), Error>
@@ -237,13 +577,25 @@ impl Subscribers {
         F: FnOnce(Vec<u8>) + Send + Sync + 'static,
     {
         // Subscribe.
-        self.subscribe_to_reply(tag, ReplySubscriber::Closure(Box::new(closure)))
+        self.subscribe_to_reply(code, tag, ReplySubscriber::Closure(Box::new(closure)))
             .await?;
 
         // Return the receiver.
         Ok(())
     }
 
+    /// Get the number of reply subscribers that are still waiting for their reply.
+    pub(super) async fn pending_reply_count(&self) -> usize {
+        self.reply_subscribers.read().await.len()
+    }
+
+    /// Remove all the reply subscribers, they're dropped without being called so the callers
+    ///  that are waiting for a reply are woken up. Returns the number of removed subscribers.
+    pub(super) async fn cancel_all_replies(&self) -> usize {
+        let mut reply_subscribers = self.reply_subscribers.write().await;
+        reply_subscribers.drain().count()
+    }
+
     /// Unsubscribe from the reply with the given tag.
     pub(super) async fn unsubscribe_from_reply(&self, tag: Tag) -> Result<(), Error> {
         // Acquire a write lock to the write subscribers.
@@ -251,12 +603,12 @@ impl Subscribers {
 
         // Remove the subscriber, and return either success or error depending on if
         //  it was removed.
-        if let Some(_) = reply_subscribers.remove(&tag) {
+        if reply_subscribers.remove(&tag).is_some() {
+            Ok(())
+        } else {
             Err(Error::Generic(
                 format!("Could not find reply subscriber for tag: {}", tag.inner()).into(),
             ))
-        } else {
-            Ok(())
         }
     }
 }
@@ -267,62 +619,150 @@ where
 {
     buf_reader: BufReader<R>,
     subscribers: Subscribers,
+    packet_read_timeout: Option<Duration>,
+    large_reply_size: Option<usize>, // The reply size (in bytes) above which a warning is logged.
 }
 
 impl<R> Worker<R>
 where
     R: AsyncRead + Unpin,
 {
+    /// The number of bytes at the start of a large reply that are logged.
+    const LARGE_REPLY_PREVIEW_LEN: usize = 16_usize;
+
     /// Create a new worker.
     pub(self) fn new(reader: R, subscribers: Subscribers) -> Self {
         Self {
             buf_reader: BufReader::new(reader),
             subscribers,
+            packet_read_timeout: None,
+            large_reply_size: None,
         }
     }
 
+    /// Set the deadline for receiving the rest of a packet once its first byte arrived.
+    pub(super) fn with_packet_read_timeout(
+        mut self,
+        packet_read_timeout: Option<Duration>,
+    ) -> Self {
+        self.packet_read_timeout = packet_read_timeout;
+
+        self
+    }
+
+    /// Set the reply size (in bytes) above which a warning with the start of the reply is logged.
+    pub(super) fn with_large_reply_size(mut self, large_reply_size: Option<usize>) -> Self {
+        self.large_reply_size = large_reply_size;
+
+        self
+    }
+
     /// Handle the given event.
     pub(self) async fn handle_event(&mut self, event: EventCode, value: Vec<u8>) -> Result<(), Error> {
+        // Move the value into a shared buffer once, so the subscribers don't each get a copy.
+        let value: Arc<[u8]> = value.into();
+
+        // Store the value for late subscribers (if caching is enabled for the event).
+        self.subscribers.cache_event_value(event, &value).await;
+
+        // Keep track of whether anyone subscribed to the code of the event.
+        let mut handled = false;
+
         if let Some(subscribers) = self.subscribers.get_event_subscribers_with_tag(event).await {
             // Acquire the lock for the subscribers.
             let subscribers = subscribers.read().await;
 
             // Iterate over the subscribers and send the event to them.
-            for subscriber in subscribers.iter() {
-                // Match the subscriber.
-                match subscriber {
-                    // Call the closure with the event.
-                    (_, EventSubscriber::Closure(closure)) => {
-                        closure(value.clone());
-                    }
-                }
+            for (_, subscriber) in subscribers.iter() {
+                subscriber.deliver(event, value.clone());
+            }
+
+            handled = !subscribers.is_empty();
+        }
+
+        // Pass the event to the unhandled event hook if there was no subscriber for it, since
+        //  this usually means that the client and server disagree on the codes.
+        if !handled {
+            if let Some(hook) = self.subscribers.unhandled_event_hook.read().await.as_ref() {
+                hook.deliver(event, value.clone());
             }
         }
 
+        // Send the event to the catch-all subscribers as well.
+        let all_event_subscribers = self.subscribers.all_event_subscribers.read().await;
+        for (_, subscriber) in all_event_subscribers.iter() {
+            subscriber.deliver(event, value.clone());
+        }
+
         Ok(())
     }
 
     /// Handle the given reply.
     pub(self) async fn handle_reply(&mut self, tag: Tag, value: Vec<u8>) -> Result<(), Error> {
         // Take the reply subscriber with the given tag.
-        if let Some(subscriber) = self.subscribers.take_reply_subscriber_with_tag(tag).await {
+        if let Some((code, subscriber)) = self.subscribers.take_reply_subscriber_with_tag(tag).await
+        {
+            // Keep track of the reply sizes, and warn about large replies since they often are
+            //  the ones that fail to deserialize.
+            self.subscribers.record_reply_size(code, value.len()).await;
+
+            if self.large_reply_size.is_some_and(|x| value.len() > x) {
+                warn!(
+                    code = code.inner(),
+                    name = code.name(),
+                    tag = tag.inner(),
+                    len = value.len(),
+                    start = ?&value[..value.len().min(Self::LARGE_REPLY_PREVIEW_LEN)],
+                    "Received large reply"
+                );
+            }
+
             // Match the subscriber.
             match subscriber {
                 // Call the closure with the value.
                 ReplySubscriber::Closure(closure) => closure(value),
             }
+        } else {
+            debug!(tag = tag.inner(), "Received reply nobody is waiting for");
         }
 
         Ok(())
     }
 
     /// Read a packet from the buffered reader.
+    ///
+    /// If the stream ends before the first byte of a packet the peer closed the connection
+    ///  cleanly, which is reported as `Error::ConnectionClosed`. If it ends in the middle of a
+    ///  packet the IO error is returned instead.
+    ///
+    /// If a packet read timeout is configured, waiting for the start of a packet is unbounded,
+    ///  but once it started the rest must arrive within the timeout. If it doesn't the stream is
+    ///  left in the middle of a packet, so the connection can't be used anymore.
     pub(self) async fn read_packet(
         &mut self,
         cancellation_token: &CancellationToken,
     ) -> Result<Packet, Error> {
+        // Wait for the first byte of the packet without consuming it.
+        let closed = select! {
+            x = self.buf_reader.fill_buf() => x?.is_empty(),
+            _ = cancellation_token.cancelled() => return Err(Error::Cancelled),
+        };
+
+        if closed {
+            return Err(Error::ConnectionClosed);
+        }
+
+        let Some(packet_read_timeout) = self.packet_read_timeout else {
+            return select! {
+                x = PacketReader::read(&mut self.buf_reader) => x,
+                _ = cancellation_token.cancelled() => Err(Error::Cancelled),
+            };
+        };
+
         select! {
-            x = PacketReader::read(&mut self.buf_reader) => x,
+            x = time::timeout(packet_read_timeout, PacketReader::read(&mut self.buf_reader)) => {
+                x.map_err(|_| Error::Timeout)?
+            }
             _ = cancellation_token.cancelled() => Err(Error::Cancelled),
         }
     }
@@ -336,9 +776,20 @@ where
             // Call the appropriate handler for the packet.
             match packet {
                 // Handle the event.
-                Packet::Event(event, value) => self.handle_event(event, value).await?,
+                Packet::Event(event, value) => {
+                    trace!(
+                        event = event.inner(),
+                        name = event.name(),
+                        len = value.len(),
+                        "Received event"
+                    );
+                    self.handle_event(event, value).await?
+                }
                 // Handle the reply.
-                Packet::Reply(tag, value) => self.handle_reply(tag, value).await?,
+                Packet::Reply(tag, value) => {
+                    trace!(tag = tag.inner(), len = value.len(), "Received reply");
+                    self.handle_reply(tag, value).await?
+                }
                 // Return an error if a command packet is received.
                 _ => {
                     return Err(Error::Generic(
@@ -366,3 +817,336 @@ impl Handle {
         &self.subscribers
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::io::AsyncWriteExt;
+    use tokio::time::{self, Duration};
+    use tokio_util::sync::CancellationToken;
+
+    use crate::client::receiver::{Receiver, Subscription};
+    use crate::error::Error;
+    use crate::proto::{CommandCode, EventCode, Packet, Tag};
+
+    /// The events received by a closure, along with their codes.
+    type ReceivedEvents = Arc<Mutex<Vec<(EventCode, Vec<u8>)>>>;
+
+    #[tokio::test]
+    pub async fn cached_event_delivered_to_late_subscriber() {
+        // Create a receiver that will never receive anything from the reader.
+        let (mut worker, handle) = Receiver::new(tokio::io::empty(), 64_usize, None);
+
+        // Enable caching for the event and publish it before anyone subscribed.
+        let event: EventCode = EventCode::new(1_u32);
+        handle.subscribers().enable_event_caching(event).await.unwrap();
+        worker.handle_event(event, vec![1_u8, 2_u8, 3_u8]).await.unwrap();
+
+        // Subscribe after the event has been published.
+        let received: Arc<Mutex<Option<Vec<u8>>>> = Arc::new(Mutex::new(None));
+        handle
+            .subscribers()
+            .subscribe_to_event_with_closure(event, {
+                let received = received.clone();
+                move |x| *received.lock().unwrap() = Some(x.to_vec())
+            })
+            .await
+            .unwrap();
+
+        // Make sure that the cached value got delivered.
+        assert_eq!(*received.lock().unwrap(), Some(vec![1_u8, 2_u8, 3_u8]));
+    }
+
+    #[tokio::test]
+    pub async fn subscribers_per_event_limited() {
+        let (_worker, handle) = Receiver::new(tokio::io::empty(), 64_usize, Some(2_usize));
+
+        let event: EventCode = EventCode::new(1_u32);
+        for _ in 0_usize..2_usize {
+            handle
+                .subscribers()
+                .subscribe_to_event_with_closure(event, |_| {})
+                .await
+                .unwrap();
+        }
+
+        // The third subscriber of the same event is refused.
+        assert!(matches!(
+            handle
+                .subscribers()
+                .subscribe_to_event_with_closure(event, |_| {})
+                .await,
+            Err(Error::TooManySubscribers)
+        ));
+
+        // Other events are limited separately.
+        handle
+            .subscribers()
+            .subscribe_to_event_with_channel(EventCode::new(2_u32))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn reply_size_stats_updated() {
+        let (worker, handle) = Receiver::new(tokio::io::empty(), 64_usize, None);
+        let mut worker = worker.with_large_reply_size(Some(8_usize));
+
+        let code: CommandCode = CommandCode::new(1_u32);
+        assert_eq!(handle.subscribers().reply_size_stats(code).await, None);
+
+        for (tag, size) in [(Tag::new(1_u64), 4_usize), (Tag::new(2_u64), 32_usize)] {
+            handle
+                .subscribers()
+                .subscribe_to_reply_with_closure(code, tag, |_| {})
+                .await
+                .unwrap();
+            worker.handle_reply(tag, vec![0_u8; size]).await.unwrap();
+        }
+
+        let stats = handle.subscribers().reply_size_stats(code).await.unwrap();
+        assert_eq!(stats.count, 2_u64);
+        assert_eq!((stats.min, stats.max), (4_usize, 32_usize));
+        assert_eq!(stats.average(), 18_f64);
+
+        // Replies nobody is waiting for can't be attributed to a command.
+        worker
+            .handle_reply(Tag::new(3_u64), vec![0_u8; 64_usize])
+            .await
+            .unwrap();
+        assert_eq!(
+            handle.subscribers().reply_size_stats(code).await,
+            Some(stats)
+        );
+    }
+
+    #[tokio::test]
+    pub async fn uncached_event_not_delivered_to_late_subscriber() {
+        let (mut worker, handle) = Receiver::new(tokio::io::empty(), 64_usize, None);
+
+        // Publish the event without caching being enabled.
+        let event: EventCode = EventCode::new(1_u32);
+        worker.handle_event(event, vec![1_u8]).await.unwrap();
+
+        // Subscribe after the event has been published.
+        let received: Arc<Mutex<Option<Vec<u8>>>> = Arc::new(Mutex::new(None));
+        handle
+            .subscribers()
+            .subscribe_to_event_with_closure(event, {
+                let received = received.clone();
+                move |x| *received.lock().unwrap() = Some(x.to_vec())
+            })
+            .await
+            .unwrap();
+
+        // Make sure that nothing got delivered.
+        assert_eq!(*received.lock().unwrap(), None);
+    }
+
+    #[tokio::test]
+    pub async fn closure_receives_event_code() {
+        let (mut worker, handle) = Receiver::new(tokio::io::empty(), 64_usize, None);
+
+        let event: EventCode = EventCode::new(7_u32);
+        let other_event: EventCode = EventCode::new(8_u32);
+
+        // Subscribe to a single event, and to all the events.
+        let received: ReceivedEvents = Arc::new(Mutex::new(Vec::new()));
+        handle
+            .subscribers()
+            .subscribe_to_event_with_closure2(event, {
+                let received = received.clone();
+                move |code, x| received.lock().unwrap().push((code, x.to_vec()))
+            })
+            .await
+            .unwrap();
+
+        let received_all: Arc<Mutex<Vec<EventCode>>> = Arc::new(Mutex::new(Vec::new()));
+        let subscriber_id = handle
+            .subscribers()
+            .subscribe_to_all_events({
+                let received_all = received_all.clone();
+                move |code, _| received_all.lock().unwrap().push(code)
+            })
+            .await
+            .unwrap();
+
+        worker.handle_event(event, vec![1_u8]).await.unwrap();
+        worker.handle_event(other_event, vec![2_u8]).await.unwrap();
+
+        // The code specific subscriber only gets its own event, the catch-all one gets both.
+        assert_eq!(*received.lock().unwrap(), vec![(event, vec![1_u8])]);
+        assert_eq!(*received_all.lock().unwrap(), vec![event, other_event]);
+
+        // Nothing should be received anymore after unsubscribing.
+        handle
+            .subscribers()
+            .unsubscribe_from_all_events(subscriber_id)
+            .await
+            .unwrap();
+        worker.handle_event(other_event, vec![3_u8]).await.unwrap();
+        assert_eq!(received_all.lock().unwrap().len(), 2_usize);
+    }
+
+    #[tokio::test]
+    pub async fn event_value_shared_between_subscribers() {
+        let (mut worker, handle) = Receiver::new(tokio::io::empty(), 64_usize, None);
+
+        let event: EventCode = EventCode::new(1_u32);
+
+        let mut receivers = Vec::new();
+        for _ in 0_usize..3_usize {
+            let (_, receiver) = handle
+                .subscribers()
+                .subscribe_to_event_with_channel(event)
+                .await
+                .unwrap();
+            receivers.push(receiver);
+        }
+
+        worker
+            .handle_event(event, vec![0_u8; 4096_usize])
+            .await
+            .unwrap();
+
+        let mut values = Vec::new();
+        for receiver in receivers.iter_mut() {
+            values.push(receiver.try_recv().unwrap());
+        }
+
+        // Every subscriber holds the same buffer, nothing else holds on to it.
+        assert!(values.iter().all(|x| Arc::ptr_eq(x, &values[0_usize])));
+        assert_eq!(Arc::strong_count(&values[0_usize]), 3_usize);
+    }
+
+    #[tokio::test]
+    pub async fn unhandled_event_hook_fires() {
+        let (mut worker, handle) = Receiver::new(tokio::io::empty(), 64_usize, None);
+
+        let event: EventCode = EventCode::new(1_u32);
+        let unknown_event: EventCode = EventCode::new(2_u32);
+
+        handle
+            .subscribers()
+            .subscribe_to_event_with_closure(event, |_| {})
+            .await
+            .unwrap();
+
+        let unhandled: ReceivedEvents = Arc::new(Mutex::new(Vec::new()));
+        handle
+            .subscribers()
+            .set_unhandled_event_hook({
+                let unhandled = unhandled.clone();
+                move |code, x| unhandled.lock().unwrap().push((code, x.to_vec()))
+            })
+            .await
+            .unwrap();
+
+        worker.handle_event(event, vec![1_u8]).await.unwrap();
+        worker
+            .handle_event(unknown_event, vec![2_u8])
+            .await
+            .unwrap();
+
+        // Only the event without a subscriber should reach the hook.
+        assert_eq!(
+            *unhandled.lock().unwrap(),
+            vec![(unknown_event, vec![2_u8])]
+        );
+
+        // Without the hook the event is simply dropped.
+        handle
+            .subscribers()
+            .clear_unhandled_event_hook()
+            .await
+            .unwrap();
+        worker
+            .handle_event(unknown_event, vec![3_u8])
+            .await
+            .unwrap();
+        assert_eq!(unhandled.lock().unwrap().len(), 1_usize);
+    }
+
+    #[tokio::test]
+    pub async fn dropped_subscription_unsubscribes() {
+        let (mut worker, handle) = Receiver::new(tokio::io::empty(), 64_usize, None);
+
+        let event: EventCode = EventCode::new(1_u32);
+
+        let received: ReceivedEvents = Arc::new(Mutex::new(Vec::new()));
+        let subscriber_id = handle
+            .subscribers()
+            .subscribe_to_event_with_closure2(event, {
+                let received = received.clone();
+                move |code, x| received.lock().unwrap().push((code, x.to_vec()))
+            })
+            .await
+            .unwrap();
+        let subscription = Subscription::new(handle.subscribers().clone(), event, subscriber_id);
+
+        worker.handle_event(event, vec![1_u8]).await.unwrap();
+
+        // Drop the subscription and give the spawned unsubscribe a chance to run.
+        drop(subscription);
+        tokio::task::yield_now().await;
+
+        worker.handle_event(event, vec![2_u8]).await.unwrap();
+        assert_eq!(*received.lock().unwrap(), vec![(event, vec![1_u8])]);
+
+        // The subscriber is gone, so it can't be unsubscribed again.
+        assert!(handle
+            .subscribers()
+            .unsubscribe_from_event(event, subscriber_id)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    pub async fn stalled_packet_times_out() {
+        let (reader, mut writer) = tokio::io::duplex(64_usize);
+        let (worker, _handle) = Receiver::new(reader, 64_usize, None);
+        let mut worker = worker.with_packet_read_timeout(Some(Duration::from_millis(100_u64)));
+
+        let mut worker_task =
+            tokio::spawn(async move { worker.run(CancellationToken::new()).await });
+
+        // Being idle for longer than the timeout is fine, as long as no packet has started.
+        time::sleep(Duration::from_millis(500_u64)).await;
+        assert!(!worker_task.is_finished());
+
+        // Send the header of an event, but never its value.
+        writer.write_u8(Packet::EVENT_IDENTIFIER).await.unwrap();
+        writer.write_u32(1_u32).await.unwrap();
+
+        let result = time::timeout(Duration::from_secs(1_u64), &mut worker_task)
+            .await
+            .expect("Stalled packet did not time out")
+            .unwrap();
+        assert!(matches!(result, Err(Error::Timeout)));
+    }
+
+    #[tokio::test]
+    pub async fn closed_between_and_within_packets() {
+        // The writer is closed before anything was sent.
+        let (reader, writer) = tokio::io::duplex(64_usize);
+        let (mut worker, _handle) = Receiver::new(reader, 64_usize, None);
+        drop(writer);
+
+        assert!(matches!(
+            worker.run(CancellationToken::new()).await,
+            Err(Error::ConnectionClosed)
+        ));
+
+        // The writer is closed in the middle of a packet.
+        let (reader, mut writer) = tokio::io::duplex(64_usize);
+        let (mut worker, _handle) = Receiver::new(reader, 64_usize, None);
+        writer.write_u8(Packet::EVENT_IDENTIFIER).await.unwrap();
+        drop(writer);
+
+        assert!(matches!(
+            worker.run(CancellationToken::new()).await,
+            Err(Error::IOError(_))
+        ));
+    }
+}
@@ -0,0 +1,58 @@
+use tokio::time::Duration;
+
+/// This enum represents how long to wait before retrying to connect after a failed attempt.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum RetryPolicy {
+    /// Don't retry, the first failed attempt is the last one.
+    #[default]
+    None,
+    /// Wait the same time before every retry.
+    Fixed(Duration),
+    /// Wait `base` before the first retry, and `factor` times as long before every next one, but
+    ///  never longer than `max`. The factor must be at least one.
+    Exponential {
+        base: Duration,
+        max: Duration,
+        factor: f64,
+    },
+}
+
+impl RetryPolicy {
+    /// Get the time to wait before the retry with the given index (the first retry has index
+    ///  zero), or None if the policy doesn't retry.
+    pub fn delay(&self, retry: usize) -> Option<Duration> {
+        match self {
+            RetryPolicy::None => None,
+            RetryPolicy::Fixed(delay) => Some(*delay),
+            RetryPolicy::Exponential { base, max, factor } => {
+                // The delay overflows to infinity after enough retries, which the cap takes care of.
+                let delay = base.as_secs_f64() * factor.powf(retry as f64);
+
+                Some(Duration::from_secs_f64(delay.min(max.as_secs_f64())))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use tokio::time::Duration;
+
+    use crate::client::retry_policy::RetryPolicy;
+
+    #[test]
+    pub fn exponential_delay_capped() {
+        let policy = RetryPolicy::Exponential {
+            base: Duration::from_millis(100_u64),
+            max: Duration::from_secs(2_u64),
+            factor: 2_f64,
+        };
+
+        assert_eq!(policy.delay(0_usize), Some(Duration::from_millis(100_u64)));
+        assert_eq!(policy.delay(3_usize), Some(Duration::from_millis(800_u64)));
+        assert_eq!(policy.delay(5_usize), Some(Duration::from_secs(2_u64)));
+        assert_eq!(policy.delay(10_000_usize), Some(Duration::from_secs(2_u64)));
+
+        assert_eq!(RetryPolicy::None.delay(0_usize), None);
+    }
+}
@@ -1,11 +1,12 @@
 use crate::{error::Error, net::PacketWriter, proto::Packet};
 
 use tokio::{
-    io::{AsyncWrite, BufWriter},
+    io::{AsyncWrite, AsyncWriteExt, BufWriter},
     select,
-    sync::mpsc,
+    sync::mpsc::{self, error::TrySendError},
 };
 use tokio_util::sync::CancellationToken;
+use tracing::trace;
 
 /// This struct represents the client transmitter.
 pub(crate) struct Transmitter<W>
@@ -19,14 +20,11 @@ impl<W> Transmitter<W>
 where
     W: AsyncWrite + Unpin,
 {
-    /// The capacity of the instruction channel.
-    pub(self) const INSTRUCTION_CHANNEL_CAPACITY: usize = 64_usize;
-
-    /// Create a new transmitter with the given writer.
-    pub(super) fn new(writer: W) -> (Worker<W>, Handle) {
+    /// Create a new transmitter with the given writer, queueing up to the given number of packets.
+    #[allow(clippy::new_ret_no_self)] // The transmitter itself is split into a worker and a handle.
+    pub(super) fn new(writer: W, capacity: usize) -> (Worker<W>, Handle) {
         // Create the instruction channel.
-        let (instruction_sender, instruction_receiver) =
-            mpsc::channel(Self::INSTRUCTION_CHANNEL_CAPACITY);
+        let (instruction_sender, instruction_receiver) = mpsc::channel(capacity);
 
         // Create the worker and handle.
         let handle = Handle::new(instruction_sender);
@@ -38,8 +36,11 @@ where
 }
 
 /// This enum represents an instruction that can be sent to the worker.
-pub(self) enum Instruction {
+enum Instruction {
     WritePacket(Packet),
+    SetAutoFlush(bool),
+    Flush,
+    Close,
 }
 
 /// This struct represents the worker that will perform the transmitting.
@@ -49,6 +50,7 @@ where
 {
     instruction_receiver: mpsc::Receiver<Instruction>,
     buf_writer: BufWriter<W>,
+    auto_flush: bool, // Whether every packet is flushed as soon as it's written.
 }
 
 impl<W> Worker<W>
@@ -60,21 +62,75 @@ where
         Self {
             instruction_receiver,
             buf_writer: BufWriter::new(writer),
+            auto_flush: true,
         }
     }
 
-    /// Write the given packet to the buffered writer.
+    /// Write the given packet to the buffered writer, only flushing it if auto flush is enabled.
     pub(self) async fn write_packet(
         &mut self,
         packet: Packet,
         cancellation_token: &CancellationToken,
     ) -> Result<(), Error> {
+        match &packet {
+            Packet::Command(command, tag, value) => trace!(
+                command = command.inner(),
+                name = command.name(),
+                tag = tag.inner(),
+                len = value.len(),
+                "Sending command"
+            ),
+            Packet::Event(event, value) => trace!(
+                event = event.inner(),
+                name = event.name(),
+                len = value.len(),
+                "Sending event"
+            ),
+            Packet::Reply(tag, value) => {
+                trace!(tag = tag.inner(), len = value.len(), "Sending reply")
+            }
+        }
+
+        if !self.auto_flush {
+            return select! {
+                x = PacketWriter::write_unflushed(&mut self.buf_writer, &packet) => x,
+                _ = cancellation_token.cancelled() => Err(Error::Cancelled),
+            };
+        }
+
         select! {
             x = PacketWriter::write(&mut self.buf_writer, &packet) => x,
             _ = cancellation_token.cancelled() => Err(Error::Cancelled),
         }
     }
 
+    /// Flush the packets that have been written to the buffered writer.
+    pub(self) async fn flush(
+        &mut self,
+        cancellation_token: &CancellationToken,
+    ) -> Result<(), Error> {
+        select! {
+            x = self.buf_writer.flush() => Ok(x?),
+            _ = cancellation_token.cancelled() => Err(Error::Cancelled),
+        }
+    }
+
+    /// Enable or disable auto flush, the packets that have been written while it was disabled are
+    ///  flushed when it gets enabled again.
+    pub(self) async fn set_auto_flush(
+        &mut self,
+        auto_flush: bool,
+        cancellation_token: &CancellationToken,
+    ) -> Result<(), Error> {
+        self.auto_flush = auto_flush;
+
+        if auto_flush {
+            self.flush(cancellation_token).await?;
+        }
+
+        Ok(())
+    }
+
     /// Read an instruction from the instruction receiver.
     pub(self) async fn read_instruction_from_receiver(
         &mut self,
@@ -88,7 +144,8 @@ where
 
     /// Run the worker.
     pub(super) async fn run(&mut self, cancellation_token: CancellationToken) -> Result<(), Error> {
-        // Keep reading instructions until the cancellation token is triggered.
+        // Keep reading instructions until the cancellation token is triggered, or until the
+        //  channel is closed and all the instructions that were queued before have been handled.
         while let Some(instruction) = self
             .read_instruction_from_receiver(&cancellation_token)
             .await?
@@ -98,10 +155,19 @@ where
                 Instruction::WritePacket(packet) => {
                     self.write_packet(packet, &cancellation_token).await?
                 }
+                Instruction::SetAutoFlush(auto_flush) => {
+                    self.set_auto_flush(auto_flush, &cancellation_token).await?
+                }
+                Instruction::Flush => self.flush(&cancellation_token).await?,
+                Instruction::Close => self.instruction_receiver.close(),
             }
         }
 
-        Ok(())
+        // Flush whatever is left and shut down the writer, so the peer sees the end of the stream.
+        select! {
+            x = self.buf_writer.shutdown() => Ok(x?),
+            _ = cancellation_token.cancelled() => Err(Error::Cancelled),
+        }
     }
 }
 
@@ -123,12 +189,23 @@ impl Handle {
         self.instruction_sender
             .send(instruction)
             .await
-            .map_err(|_| Error::Generic("Failed to send instruction to worker.".into()))?;
+            .map_err(|_| Error::Disconnected)?;
 
         // Return success.
         Ok(())
     }
 
+    /// Send the given instruction to the worker without waiting, returning `Error::WouldBlock` if
+    ///  the instruction channel is full.
+    pub(self) fn try_send_instruction(&self, instruction: Instruction) -> Result<(), Error> {
+        self.instruction_sender
+            .try_send(instruction)
+            .map_err(|e| match e {
+                TrySendError::Full(_) => Error::WouldBlock,
+                TrySendError::Closed(_) => Error::Disconnected,
+            })
+    }
+
     /// Send the write packet instruction to the worker.
     pub(crate) async fn write_packet(&self, packet: Packet) -> Result<(), Error> {
         // Create the instruction.
@@ -140,5 +217,137 @@ impl Handle {
         // Return success.
         Ok(())
     }
+
+    /// Send the write packet instruction to the worker without waiting for room in the
+    ///  instruction channel, meant for real-time loops that can't block.
+    pub(crate) fn try_write_packet(&self, packet: Packet) -> Result<(), Error> {
+        self.try_send_instruction(Instruction::WritePacket(packet))
+    }
+
+    /// Tell the worker whether it should flush every packet as soon as it's written, disabling
+    ///  this allows many packets to be sent at once using `flush`.
+    pub(crate) async fn set_auto_flush(&self, auto_flush: bool) -> Result<(), Error> {
+        self.send_instruction(Instruction::SetAutoFlush(auto_flush))
+            .await
+    }
+
+    /// Tell the worker to flush the packets that have been written so far.
+    pub(crate) async fn flush(&self) -> Result<(), Error> {
+        self.send_instruction(Instruction::Flush).await
+    }
+
+    /// Tell the worker to stop accepting instructions, the ones that were queued before are still
+    ///  handled after which the writer is shut down and the worker exits.
+    pub(crate) async fn close(&self) -> Result<(), Error> {
+        self.send_instruction(Instruction::Close).await
+    }
 }
 
+#[cfg(test)]
+pub mod tests {
+    use std::pin::Pin;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use std::task::{Context, Poll};
+
+    use tokio::io::AsyncWrite;
+    use tokio::time::{self, Duration};
+    use tokio_util::sync::CancellationToken;
+
+    use crate::client::transmitter::Transmitter;
+    use crate::error::Error;
+    use crate::proto::{EventCode, Packet};
+
+    /// Writer that discards everything, but counts the bytes written and the number of flushes.
+    #[derive(Clone, Default)]
+    struct CountingWriter {
+        written: Arc<AtomicUsize>,
+        flushes: Arc<AtomicUsize>,
+    }
+
+    impl AsyncWrite for CountingWriter {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            self.written.fetch_add(buf.len(), Ordering::SeqCst);
+
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            self.flushes.fetch_add(1_usize, Ordering::SeqCst);
+
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    pub fn try_write_packet_would_block() {
+        // Create a transmitter without running its worker, so the channel never drains.
+        let (stream, _peer) = tokio::io::duplex(64_usize);
+        let (_worker, handle) = Transmitter::new(stream, 64_usize);
+
+        // Fill the instruction channel.
+        for _ in 0..64_usize {
+            handle
+                .try_write_packet(Packet::Event(EventCode::new(0_u32), Vec::new()))
+                .unwrap();
+        }
+
+        // The next packet doesn't fit anymore.
+        assert!(matches!(
+            handle.try_write_packet(Packet::Event(EventCode::new(0_u32), Vec::new())),
+            Err(Error::WouldBlock)
+        ));
+    }
+
+    #[tokio::test]
+    pub async fn single_flush_without_auto_flush() {
+        let writer = CountingWriter::default();
+        let (mut worker, handle) = Transmitter::new(writer.clone(), 64_usize);
+
+        let cancellation_token = CancellationToken::new();
+        tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move { worker.run(cancellation_token).await }
+        });
+
+        // Write ten packets with auto flush disabled, each takes nine bytes on the wire.
+        handle.set_auto_flush(false).await.unwrap();
+        for _ in 0..10_usize {
+            handle
+                .write_packet(Packet::Event(EventCode::new(0_u32), Vec::new()))
+                .await
+                .unwrap();
+        }
+
+        // Nothing reaches the writer until the packets get flushed.
+        time::sleep(Duration::from_millis(50_u64)).await;
+        assert_eq!(writer.written.load(Ordering::SeqCst), 0_usize);
+        assert_eq!(writer.flushes.load(Ordering::SeqCst), 0_usize);
+
+        handle.flush().await.unwrap();
+
+        time::timeout(Duration::from_secs(5_u64), async {
+            while writer.flushes.load(Ordering::SeqCst) == 0_usize {
+                time::sleep(Duration::from_millis(1_u64)).await;
+            }
+        })
+        .await
+        .expect("Packets were not flushed");
+
+        time::sleep(Duration::from_millis(50_u64)).await;
+        assert_eq!(writer.written.load(Ordering::SeqCst), 90_usize);
+        assert_eq!(writer.flushes.load(Ordering::SeqCst), 1_usize);
+
+        cancellation_token.cancel();
+    }
+}
@@ -10,8 +10,21 @@ pub enum Error {
     Generic(Cow<'static, str>),
     #[error("Operation cancelled")]
     Cancelled,
-    #[error("Serde serialization error")]
-    SerdeSerError,
-    #[error("Deserialization error")]
-    DeserializeError,
+    #[error("Serde serialization error: {message}")]
+    SerdeSerError {
+        message: Cow<'static, str>,
+        data_error: bool, // The value itself can't be serialized, so retrying won't help.
+    },
+    #[error("Deserialization error: {0}")]
+    DeserializeError(Cow<'static, str>),
+    #[error("Operation would block")]
+    WouldBlock,
+    #[error("Operation timed out")]
+    Timeout,
+    #[error("Connection has been closed")]
+    Disconnected,
+    #[error("Connection closed by the peer")]
+    ConnectionClosed,
+    #[error("Too many subscribers for the event")]
+    TooManySubscribers,
 }
@@ -1,5 +1,7 @@
+mod packet_codec;
 mod packet_reader;
 mod packet_writer;
 
+pub use packet_codec::PacketCodec;
 pub(crate) use packet_reader::PacketReader;
 pub(crate) use packet_writer::PacketWriter;
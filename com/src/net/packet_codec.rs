@@ -0,0 +1,171 @@
+use tokio_util::{
+    bytes::{Buf, BufMut, BytesMut},
+    codec::{Decoder, Encoder},
+};
+
+use crate::{
+    error::Error,
+    proto::{CommandCode, EventCode, Packet, Tag},
+};
+
+/// This struct frames complete packets so they can be used with `tokio_util::codec::Framed`, the
+///  wire format is identical to the one of the `PacketReader` and `PacketWriter`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PacketCodec;
+
+impl PacketCodec {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Get the length of the header (everything up to and including the value length) of the
+    ///  packet with the given identifier.
+    pub(self) fn header_len(identifier: u8) -> Result<usize, Error> {
+        match identifier {
+            Packet::EVENT_IDENTIFIER => Ok(1_usize + 4_usize + 4_usize),
+            Packet::COMMAND_IDENTIFIER => Ok(1_usize + 4_usize + 8_usize + 4_usize),
+            Packet::REPLY_IDENTIFIER => Ok(1_usize + 8_usize + 4_usize),
+            _ => Err(Error::Generic(
+                format!("Invalid identifier: {}", identifier).into(),
+            )),
+        }
+    }
+
+    /// Write the given value (prefixed by its length) to the given buffer.
+    pub(self) fn put_value(dst: &mut BytesMut, value: &[u8]) {
+        dst.put_u32(value.len() as u32);
+        dst.put_slice(value);
+    }
+}
+
+impl Decoder for PacketCodec {
+    type Item = Packet;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        // Peek at the identifier so we know how long the header is.
+        let Some(identifier) = src.first().copied() else {
+            return Ok(None);
+        };
+        let header_len = Self::header_len(identifier)?;
+
+        if src.len() < header_len {
+            src.reserve(header_len - src.len());
+            return Ok(None);
+        }
+
+        // The value length is always the last field of the header.
+        let value_len = u32::from_be_bytes(
+            src[header_len - 4_usize..header_len]
+                .try_into()
+                .expect("Slice has a length of four bytes"),
+        ) as usize;
+
+        // Wait until the complete packet has been received.
+        let packet_len = header_len + value_len;
+        if src.len() < packet_len {
+            src.reserve(packet_len - src.len());
+            return Ok(None);
+        }
+
+        src.advance(1_usize);
+
+        let packet = match identifier {
+            Packet::EVENT_IDENTIFIER => {
+                let event = EventCode::new(src.get_u32());
+                src.advance(4_usize);
+
+                Packet::Event(event, src.split_to(value_len).to_vec())
+            }
+            Packet::COMMAND_IDENTIFIER => {
+                let command = CommandCode::new(src.get_u32());
+                let tag = Tag::new(src.get_u64());
+                src.advance(4_usize);
+
+                Packet::Command(command, tag, src.split_to(value_len).to_vec())
+            }
+            _ => {
+                let tag = Tag::new(src.get_u64());
+                src.advance(4_usize);
+
+                Packet::Reply(tag, src.split_to(value_len).to_vec())
+            }
+        };
+
+        Ok(Some(packet))
+    }
+}
+
+impl Encoder<Packet> for PacketCodec {
+    type Error = Error;
+
+    fn encode(&mut self, item: Packet, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        match item {
+            Packet::Event(event, value) => {
+                dst.reserve(Self::header_len(Packet::EVENT_IDENTIFIER)? + value.len());
+                dst.put_u8(Packet::EVENT_IDENTIFIER);
+                dst.put_u32(event.inner());
+
+                Self::put_value(dst, &value);
+            }
+            Packet::Command(command, tag, value) => {
+                dst.reserve(Self::header_len(Packet::COMMAND_IDENTIFIER)? + value.len());
+                dst.put_u8(Packet::COMMAND_IDENTIFIER);
+                dst.put_u32(command.inner());
+                dst.put_u64(tag.inner());
+
+                Self::put_value(dst, &value);
+            }
+            Packet::Reply(tag, value) => {
+                dst.reserve(Self::header_len(Packet::REPLY_IDENTIFIER)? + value.len());
+                dst.put_u8(Packet::REPLY_IDENTIFIER);
+                dst.put_u64(tag.inner());
+
+                Self::put_value(dst, &value);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use tokio::io::BufWriter;
+    use tokio_util::{
+        bytes::BytesMut,
+        codec::{Decoder, Encoder},
+    };
+
+    use crate::net::{PacketCodec, PacketWriter};
+    use crate::proto::{CommandCode, EventCode, Packet, Tag};
+
+    #[tokio::test]
+    pub async fn encode_decode_round_trip() {
+        let packets = [
+            Packet::Event(EventCode::new(0x10_u32), vec![1_u8, 2_u8, 3_u8]),
+            Packet::Command(CommandCode::new(0x20_u32), Tag::new(7_u64), vec![4_u8]),
+            Packet::Reply(Tag::new(8_u64), Vec::new()),
+        ];
+
+        let mut codec = PacketCodec::new();
+
+        for packet in packets {
+            // The encoded packet must be byte-identical to the one of the packet writer.
+            let mut buf_writer = BufWriter::new(Vec::new());
+            PacketWriter::write(&mut buf_writer, &packet).await.unwrap();
+            let written = buf_writer.into_inner();
+
+            let mut buf = BytesMut::new();
+            codec.encode(packet.clone(), &mut buf).unwrap();
+            assert_eq!(&buf[..], &written[..]);
+
+            // A partial packet isn't decoded until the rest arrives.
+            let mut partial = BytesMut::from(&written[..written.len() - 1_usize]);
+            assert_eq!(codec.decode(&mut partial).unwrap(), None);
+
+            assert_eq!(codec.decode(&mut buf).unwrap(), Some(packet));
+            assert!(buf.is_empty());
+        }
+    }
+}
@@ -33,7 +33,7 @@ where
         let len = buf_reader.read_u32().await?;
 
         // Allocate a new vector to contain the value and read it from the reader.
-        let mut value = Vec::<u8>::with_capacity(len as usize);
+        let mut value = vec![0_u8; len as usize];
         _ = buf_reader.read_exact(&mut value).await?;
 
         // Return the read value.
@@ -31,7 +31,7 @@ where
     /// Returns `Ok(())` if the write operation is successful, otherwise returns an `Error`.
     pub(self) async fn write_value(
         buf_writer: &mut BufWriter<W>,
-        value: &Vec<u8>,
+        value: &[u8],
     ) -> Result<(), Error> {
         buf_writer.write_u32(value.len() as u32).await?;
         buf_writer.write_all(value).await?;
@@ -69,15 +69,13 @@ where
     pub(self) async fn write_event(
         buf_writer: &mut BufWriter<W>,
         event: &EventCode,
-        value: &Vec<u8>,
+        value: &[u8],
     ) -> Result<(), Error> {
         buf_writer.write_u8(Packet::EVENT_IDENTIFIER).await?;
         buf_writer.write_u32(event.inner()).await?;
 
         Self::write_value(buf_writer, value).await?;
 
-        buf_writer.flush().await?;
-
         Ok(())
     }
 
@@ -97,7 +95,7 @@ where
         buf_writer: &mut BufWriter<W>,
         command: &CommandCode,
         tag: &Tag,
-        value: &Vec<u8>,
+        value: &[u8],
     ) -> Result<(), Error> {
         buf_writer.write_u8(Packet::COMMAND_IDENTIFIER).await?;
         buf_writer.write_u32(command.inner()).await?;
@@ -105,8 +103,6 @@ where
         Self::write_tag(buf_writer, tag).await?;
         Self::write_value(buf_writer, value).await?;
 
-        buf_writer.flush().await?;
-
         Ok(())
     }
 
@@ -124,19 +120,36 @@ where
     pub(self) async fn write_reply(
         buf_writer: &mut BufWriter<W>,
         tag: &Tag,
-        value: &Vec<u8>,
+        value: &[u8],
     ) -> Result<(), Error> {
         buf_writer.write_u8(Packet::REPLY_IDENTIFIER).await?;
 
         Self::write_tag(buf_writer, tag).await?;
         Self::write_value(buf_writer, value).await?;
 
+        Ok(())
+    }
+
+    /// Write the given packet to the given buffered writer and flush it.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf_writer` - The buffered writer to write to.
+    /// * `packet` - The packet to write.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the write operation is successful, otherwise returns an `Error`.
+    pub(crate) async fn write(buf_writer: &mut BufWriter<W>, packet: &Packet) -> Result<(), Error> {
+        Self::write_unflushed(buf_writer, packet).await?;
+
         buf_writer.flush().await?;
 
         Ok(())
     }
 
-    /// Write the given packet to the given buffered writer.
+    /// Write the given packet to the given buffered writer without flushing it, so multiple
+    ///  packets can be sent at once.
     ///
     /// # Arguments
     ///
@@ -146,7 +159,10 @@ where
     /// # Returns
     ///
     /// Returns `Ok(())` if the write operation is successful, otherwise returns an `Error`.
-    pub(crate) async fn write(buf_writer: &mut BufWriter<W>, packet: &Packet) -> Result<(), Error> {
+    pub(crate) async fn write_unflushed(
+        buf_writer: &mut BufWriter<W>,
+        packet: &Packet,
+    ) -> Result<(), Error> {
         match packet {
             Packet::Event(event, value) => Self::write_event(buf_writer, event, value).await,
             Packet::Command(command, tag, value) => {
@@ -1,4 +1,10 @@
-#[derive(Clone, Copy, Debug, PartialEq, Hash, Eq, PartialOrd, Ord)]
+use serde::{Deserialize, Serialize};
+
+mod names;
+
+pub use names::{register_command_names, register_event_names};
+
+#[derive(Clone, Copy, Debug, PartialEq, Hash, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct EventCode(u32);
 
 impl EventCode {
@@ -16,12 +22,34 @@ impl EventCode {
     pub fn inner(&self) -> u32 {
         self.0
     }
+
+    /// Get the human-readable name of the event, if it has been registered.
+    pub fn name(&self) -> Option<&'static str> {
+        names::event_name(*self)
+    }
+
+    /// Create the event code from its representation on the wire, which is big-endian.
+    #[inline(always)]
+    pub const fn from_be_bytes(bytes: [u8; 4]) -> Self {
+        Self(u32::from_be_bytes(bytes))
+    }
+
+    /// Get the representation of the event code on the wire, which is big-endian.
+    #[inline(always)]
+    pub const fn to_be_bytes(&self) -> [u8; 4] {
+        self.0.to_be_bytes()
+    }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Hash, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct CommandCode(u32);
 
 impl CommandCode {
+    #[inline(always)]
+    pub const fn const_new(inner: u32) -> Self {
+        Self(inner)
+    }
+
     #[inline(always)]
     pub fn new(inner: u32) -> Self {
         Self(inner)
@@ -31,12 +59,32 @@ impl CommandCode {
     pub fn inner(&self) -> u32 {
         self.0
     }
+
+    /// Get the human-readable name of the command, if it has been registered.
+    pub fn name(&self) -> Option<&'static str> {
+        names::command_name(*self)
+    }
+
+    /// Create the command code from its representation on the wire, which is big-endian.
+    #[inline(always)]
+    pub const fn from_be_bytes(bytes: [u8; 4]) -> Self {
+        Self(u32::from_be_bytes(bytes))
+    }
+
+    /// Get the representation of the command code on the wire, which is big-endian.
+    #[inline(always)]
+    pub const fn to_be_bytes(&self) -> [u8; 4] {
+        self.0.to_be_bytes()
+    }
 }
 
-#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Tag(u64);
 
 impl Tag {
+    /// The tag of commands that don't expect a reply, it's never generated for other commands.
+    pub const NO_REPLY: Tag = Tag(0_u64);
+
     #[inline(always)]
     pub fn new(inner: u64) -> Self {
         Self(inner)
@@ -46,13 +94,27 @@ impl Tag {
     pub fn inner(&self) -> u64 {
         self.0
     }
+
+    /// Create the tag from its representation on the wire, which is big-endian.
+    #[inline(always)]
+    pub const fn from_be_bytes(bytes: [u8; 8]) -> Self {
+        Self(u64::from_be_bytes(bytes))
+    }
+
+    /// Get the representation of the tag on the wire, which is big-endian.
+    #[inline(always)]
+    pub const fn to_be_bytes(&self) -> [u8; 8] {
+        self.0.to_be_bytes()
+    }
 }
 
-#[derive(Debug)]
+/// This enum represents a packet, it can be serialized so a session can be recorded and replayed,
+///  the values are kept as raw bytes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Packet {
-    Event(EventCode, Vec<u8>),
-    Command(CommandCode, Tag, Vec<u8>),
-    Reply(Tag, Vec<u8>),
+    Event(EventCode, #[serde(with = "serde_bytes")] Vec<u8>),
+    Command(CommandCode, Tag, #[serde(with = "serde_bytes")] Vec<u8>),
+    Reply(Tag, #[serde(with = "serde_bytes")] Vec<u8>),
 }
 
 impl Packet {
@@ -60,3 +122,65 @@ impl Packet {
     pub const COMMAND_IDENTIFIER: u8 = 0x01_u8;
     pub const REPLY_IDENTIFIER: u8 = 0x02_u8;
 }
+
+#[cfg(test)]
+pub mod tests {
+    use tokio::io::BufWriter;
+
+    use crate::net::PacketWriter;
+    use crate::proto::{register_command_names, CommandCode, EventCode, Packet, Tag};
+
+    #[test]
+    pub fn packet_json_round_trip() {
+        let packets = [
+            Packet::Event(EventCode::new(0x10_u32), vec![0_u8, 1_u8, 255_u8]),
+            Packet::Command(CommandCode::new(0x20_u32), Tag::new(7_u64), vec![4_u8]),
+            Packet::Reply(Tag::NO_REPLY, Vec::new()),
+        ];
+
+        for packet in packets {
+            let json = serde_json::to_string(&packet).unwrap();
+
+            assert_eq!(serde_json::from_str::<Packet>(&json).unwrap(), packet);
+        }
+    }
+
+    #[test]
+    pub fn registered_names_resolved() {
+        register_command_names(&[(CommandCode::new(0x7F00_u32), "Example")]);
+
+        assert_eq!(CommandCode::new(0x7F00_u32).name(), Some("Example"));
+        assert_eq!(CommandCode::new(0x7F01_u32).name(), None);
+
+        // Commands and events don't share their names.
+        assert_eq!(EventCode::new(0x7F00_u32).name(), None);
+    }
+
+    #[tokio::test]
+    pub async fn wire_bytes_match_packet_writer() {
+        let code = CommandCode::from_be_bytes([0x00_u8, 0x00_u8, 0x01_u8, 0x02_u8]);
+        let tag = Tag::from_be_bytes([0x01_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0x2A_u8]);
+        assert_eq!(code, CommandCode::new(0x0102_u32));
+        assert_eq!(tag, Tag::new(0x0100_0000_0000_002A_u64));
+
+        let mut buf_writer = BufWriter::new(Vec::new());
+        PacketWriter::write(&mut buf_writer, &Packet::Command(code, tag, Vec::new()))
+            .await
+            .unwrap();
+        let bytes = buf_writer.into_inner();
+
+        // The identifier is followed by the code and the tag.
+        assert_eq!(bytes[1_usize..5_usize], code.to_be_bytes());
+        assert_eq!(bytes[5_usize..13_usize], tag.to_be_bytes());
+
+        let event = EventCode::new(0xDEAD_BEEF_u32);
+        let mut buf_writer = BufWriter::new(Vec::new());
+        PacketWriter::write(&mut buf_writer, &Packet::Event(event, Vec::new()))
+            .await
+            .unwrap();
+        let bytes = buf_writer.into_inner();
+
+        assert_eq!(bytes[1_usize..5_usize], event.to_be_bytes());
+        assert_eq!(EventCode::from_be_bytes(event.to_be_bytes()), event);
+    }
+}
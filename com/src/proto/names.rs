@@ -0,0 +1,54 @@
+use std::{
+    collections::HashMap,
+    sync::{OnceLock, RwLock},
+};
+
+use super::{CommandCode, EventCode};
+
+/// This struct holds the human-readable names of the known codes, keyed by their raw values.
+#[derive(Default)]
+struct Names {
+    commands: HashMap<u32, &'static str>,
+    events: HashMap<u32, &'static str>,
+}
+
+/// Get the registration table, which is created when it's first used.
+fn names() -> &'static RwLock<Names> {
+    static NAMES: OnceLock<RwLock<Names>> = OnceLock::new();
+
+    NAMES.get_or_init(Default::default)
+}
+
+/// Register the names of the given command codes, so they show up in the logs. Registering a
+///  code again replaces its name.
+pub fn register_command_names(names_of_codes: &[(CommandCode, &'static str)]) {
+    let mut names = names().write().unwrap_or_else(|e| e.into_inner());
+
+    for &(code, name) in names_of_codes {
+        names.commands.insert(code.inner(), name);
+    }
+}
+
+/// Register the names of the given event codes, so they show up in the logs. Registering a code
+///  again replaces its name.
+pub fn register_event_names(names_of_codes: &[(EventCode, &'static str)]) {
+    let mut names = names().write().unwrap_or_else(|e| e.into_inner());
+
+    for &(code, name) in names_of_codes {
+        names.events.insert(code.inner(), name);
+    }
+}
+
+/// Get the registered name of the given command code.
+pub(super) fn command_name(code: CommandCode) -> Option<&'static str> {
+    let names = names().read().unwrap_or_else(|e| e.into_inner());
+
+    names.commands.get(&code.inner()).copied()
+}
+
+/// Get the registered name of the given event code.
+pub(super) fn event_name(code: EventCode) -> Option<&'static str> {
+    let names = names().read().unwrap_or_else(|e| e.into_inner());
+
+    names.events.get(&code.inner()).copied()
+}
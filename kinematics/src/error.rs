@@ -1,7 +1,23 @@
 use thiserror::Error;
 
+use crate::inverse::algorithms::heuristic::HeuristicInverseKinematicsAlgorithmError;
+
 #[derive(Error, Debug)]
 pub enum KinematicError {
     #[error("Inversion failure")]
     InversionFailure,
+    #[error("Near singularity")]
+    NearSingularity,
+    #[error("Joint {joint} violates its limits with value {value}")]
+    JointLimitViolated { joint: usize, value: f64 },
+    #[error("Joint {joint} has a non-finite angle {value}")]
+    NonFiniteAngle { joint: usize, value: f64 },
+    #[error("Target unreachable")]
+    Unreachable,
+    #[error("Maximum number of iterations ({iterations}) exceeded")]
+    MaxIterationsExceeded { iterations: usize },
+    #[error("Invalid parameters: {0}")]
+    InvalidParameters(String),
+    #[error("Heuristic inverse kinematics algorithm error: {0}")]
+    HeuristicAlgorithm(#[from] HeuristicInverseKinematicsAlgorithmError),
 }
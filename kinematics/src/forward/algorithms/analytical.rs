@@ -1,9 +1,10 @@
 use nalgebra::{Matrix3, Vector3};
 
-use crate::forward::algorithms::ForwardKinematicAlgorithm;
+use crate::forward::algorithms::{matrix_to_euler_angles, ForwardKinematicAlgorithm};
 use crate::model::{KinematicParameters, KinematicState};
 
-/// Analytical forward kinematic approach, see the derivation notebook for the specifics.
+/// Analytical forward kinematic approach, see the derivation notebook for the specifics. The
+///  positions are computed relative to the base and then transformed into world coordinates.
 pub struct AnalyticalFKAlgorithm {}
 
 impl Default for AnalyticalFKAlgorithm {
@@ -15,29 +16,31 @@ impl Default for AnalyticalFKAlgorithm {
 impl ForwardKinematicAlgorithm for AnalyticalFKAlgorithm {
     fn limb0_position_vector(
         &self,
-        &KinematicParameters { l_0, .. }: &KinematicParameters,
+        params @ &KinematicParameters { l_0, .. }: &KinematicParameters,
         &KinematicState { .. }: &KinematicState,
     ) -> Vector3<f64> {
-        Vector3::<f64>::new(0_f64, l_0, 0_f64)
+        params.base_to_world(&Vector3::<f64>::new(0_f64, l_0, 0_f64))
     }
 
     fn limb1_position_vector(
         &self,
-        &KinematicParameters { l_0, l_1, .. }: &KinematicParameters,
+        params @ &KinematicParameters { l_0, l_1, .. }: &KinematicParameters,
         &KinematicState {
             theta_0, theta_1, ..
         }: &KinematicState,
     ) -> Vector3<f64> {
-        Vector3::<f64>::new(
+        let position = Vector3::<f64>::new(
             l_1 * theta_0.sin() * theta_1.sin(),
             l_0 + l_1 * theta_1.cos(),
             -l_1 * theta_1.sin() * theta_0.cos(),
-        )
+        );
+
+        params.base_to_world(&position)
     }
 
     fn limb2_position_vector(
         &self,
-        &KinematicParameters { l_0, l_1, l_2, .. }: &KinematicParameters,
+        params @ &KinematicParameters { l_0, l_1, l_2, .. }: &KinematicParameters,
         &KinematicState {
             theta_0,
             theta_1,
@@ -45,16 +48,18 @@ impl ForwardKinematicAlgorithm for AnalyticalFKAlgorithm {
             ..
         }: &KinematicState,
     ) -> Vector3<f64> {
-        Vector3::<f64>::new(
+        let position = Vector3::<f64>::new(
             (l_1 * theta_1.sin() + l_2 * (theta_1 + theta_2).sin()) * theta_0.sin(),
             l_0 + l_1 * theta_1.cos() + l_2 * (theta_1 + theta_2).cos(),
             -(l_1 * theta_1.sin() + l_2 * (theta_1 + theta_2).sin()) * theta_0.cos(),
-        )
+        );
+
+        params.base_to_world(&position)
     }
 
     fn limb3_position_vector(
         &self,
-        &KinematicParameters {
+        params @ &KinematicParameters {
             l_0, l_1, l_2, l_3, ..
         }: &KinematicParameters,
         &KinematicState {
@@ -65,7 +70,7 @@ impl ForwardKinematicAlgorithm for AnalyticalFKAlgorithm {
             ..
         }: &KinematicState,
     ) -> Vector3<f64> {
-        Vector3::<f64>::new(
+        let position = Vector3::<f64>::new(
             (l_1 * theta_1.sin()
                 + l_2 * (theta_1 + theta_2).sin()
                 + l_3 * (theta_1 + theta_2 + theta_3).sin())
@@ -77,17 +82,20 @@ impl ForwardKinematicAlgorithm for AnalyticalFKAlgorithm {
                 + l_2 * (theta_1 + theta_2).sin()
                 + l_3 * (theta_1 + theta_2 + theta_3).sin())
                 * theta_0.cos(),
-        )
+        );
+
+        params.base_to_world(&position)
     }
 
     fn limb4_position_vector(
         &self,
-        &KinematicParameters {
+        params @ &KinematicParameters {
             l_0,
             l_1,
             l_2,
             l_3,
             l_4,
+            ..
         }: &KinematicParameters,
         &KinematicState {
             theta_0,
@@ -97,7 +105,7 @@ impl ForwardKinematicAlgorithm for AnalyticalFKAlgorithm {
             ..
         }: &KinematicState,
     ) -> Vector3<f64> {
-        Vector3::<f64>::new(
+        let position = Vector3::<f64>::new(
             (l_1 * theta_1.sin()
                 + l_2 * (theta_1 + theta_2).sin()
                 + l_3 * (theta_1 + theta_2 + theta_3).sin()
@@ -112,20 +120,24 @@ impl ForwardKinematicAlgorithm for AnalyticalFKAlgorithm {
                 + l_3 * (theta_1 + theta_2 + theta_3).sin()
                 + l_4 * (theta_1 + theta_2 + theta_3).sin())
                 * theta_0.cos(),
-        )
+        );
+
+        params.base_to_world(&position)
     }
 
     fn limb4_euler_angles(
         &self,
-        &KinematicParameters { .. }: &KinematicParameters,
-        &KinematicState { .. }: &KinematicState,
+        params: &KinematicParameters,
+        state: &KinematicState,
     ) -> Vector3<f64> {
-        todo!()
+        matrix_to_euler_angles(&self.limb4_orientation_matrix(params, state))
     }
 
+    /// The orientation is $R_y(-\theta_0) R_x(-(\theta_1 + \theta_2 + \theta_3)) R_y(-\theta_4)$,
+    ///  rotated by the orientation of the base.
     fn limb4_orientation_matrix(
         &self,
-        &KinematicParameters { .. }: &KinematicParameters,
+        params: &KinematicParameters,
         &KinematicState {
             theta_0,
             theta_1,
@@ -134,7 +146,7 @@ impl ForwardKinematicAlgorithm for AnalyticalFKAlgorithm {
             theta_4,
         }: &KinematicState,
     ) -> Matrix3<f64> {
-        Matrix3::<f64>::new(
+        let orientation = Matrix3::<f64>::new(
             -theta_0.sin() * theta_4.sin() * (theta_1 + theta_2 + theta_3).cos()
                 + theta_0.cos() * theta_4.cos(),
             theta_0.sin() * (theta_1 + theta_2 + theta_3).sin(),
@@ -148,7 +160,9 @@ impl ForwardKinematicAlgorithm for AnalyticalFKAlgorithm {
             -(theta_1 + theta_2 + theta_3).sin() * theta_0.cos(),
             -theta_0.sin() * theta_4.sin()
                 + theta_0.cos() * theta_4.cos() * (theta_1 + theta_2 + theta_3).cos(),
-        )
+        );
+
+        params.base_transform.rotation.to_rotation_matrix().matrix() * orientation
     }
 }
 
@@ -157,7 +171,7 @@ pub mod tests {
     use nalgebra::Vector3;
 
     use crate::forward::algorithms::analytical::AnalyticalFKAlgorithm;
-    use crate::forward::algorithms::ForwardKinematicAlgorithm;
+    use crate::forward::algorithms::{euler_angles_to_matrix, ForwardKinematicAlgorithm};
     use crate::model::{KinematicParameters, KinematicState};
 
     #[test]
@@ -175,8 +189,7 @@ pub mod tests {
         let params: KinematicParameters = KinematicParameters::default();
 
         // Create the analytical solver.
-        let solver: AnalyticalFKAlgorithm =
-            AnalyticalFKAlgorithm::default();
+        let solver: AnalyticalFKAlgorithm = AnalyticalFKAlgorithm::default();
 
         // Make sure that the fourth limb position vector is correct.
         assert_eq!(
@@ -184,4 +197,36 @@ pub mod tests {
             Vector3::new(0_f64, params.sum_of_link_lengths(), 0_f64)
         );
     }
+
+    #[test]
+    pub fn euler_angles_match_orientation_matrix() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let solver: AnalyticalFKAlgorithm = AnalyticalFKAlgorithm::default();
+
+        // Generate pseudo-random angles in the range [-pi, pi) using a linear congruential
+        //  generator, so the test is reproducible.
+        let mut seed: u64 = 0x2545F4914F6CDD1D_u64;
+        let mut next_angle = || {
+            seed = seed
+                .wrapping_mul(6364136223846793005_u64)
+                .wrapping_add(1442695040888963407_u64);
+
+            ((seed >> 11) as f64 / (1_u64 << 53) as f64 - 0.5_f64) * 2_f64 * std::f64::consts::PI
+        };
+
+        for _ in 0..1000 {
+            let state: KinematicState = KinematicState {
+                theta_0: next_angle(),
+                theta_1: next_angle(),
+                theta_2: next_angle(),
+                theta_3: next_angle(),
+                theta_4: next_angle(),
+            };
+
+            let matrix = solver.limb4_orientation_matrix(&params, &state);
+            let euler_angles = solver.limb4_euler_angles(&params, &state);
+
+            assert!((euler_angles_to_matrix(&euler_angles) - matrix).norm() < 1e-9_f64);
+        }
+    }
 }
@@ -1,8 +1,8 @@
 use std::sync::Arc;
 
-use nalgebra::{Matrix3, Vector3};
+use nalgebra::{Matrix3, Matrix3x5, Rotation3, UnitQuaternion, Vector3, Vector5};
 
-use crate::model::{KinematicParameters, KinematicState};
+use crate::model::{ArmGeometry, KinematicParameters, KinematicState, Link};
 
 pub mod analytical;
 
@@ -42,7 +42,24 @@ pub trait ForwardKinematicAlgorithm: Send + Sync {
         state: &KinematicState,
     ) -> Vector3<f64>;
 
-    /// Compute the vector of euler angles for the end-effector of the fourth limb.
+    /// Compute the end-effector position of the given link.
+    fn link_position_vector(
+        &self,
+        link: Link,
+        params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> Vector3<f64> {
+        match link {
+            Link::Column => self.limb0_position_vector(params, state),
+            Link::UpperArm => self.limb1_position_vector(params, state),
+            Link::Forearm => self.limb2_position_vector(params, state),
+            Link::Wrist => self.limb3_position_vector(params, state),
+            Link::Hand => self.limb4_position_vector(params, state),
+        }
+    }
+
+    /// Compute the vector of euler angles for the end-effector of the fourth limb, following the
+    ///  convention of `euler_angles_to_matrix`.
     fn limb4_euler_angles(
         &self,
         params: &KinematicParameters,
@@ -55,16 +72,55 @@ pub trait ForwardKinematicAlgorithm: Send + Sync {
         params: &KinematicParameters,
         state: &KinematicState,
     ) -> Matrix3<f64>;
+
+    /// Compute the orientation of the end-effector of the fourth limb as a unit quaternion, which
+    ///  is what renderers and slerp-based interpolation expect.
+    fn limb4_orientation_quaternion(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> UnitQuaternion<f64> {
+        UnitQuaternion::from_rotation_matrix(&Rotation3::from_matrix_unchecked(
+            self.limb4_orientation_matrix(params, state),
+        ))
+    }
+
+    /// Compute the position of the tool tip, which is offset from the flange (the end of the
+    ///  fifth limb) by the tool offset expressed in the orientation of the flange.
+    fn limb4_tool_position_vector(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> Vector3<f64> {
+        self.limb4_position_vector(params, state)
+            + self.limb4_orientation_matrix(params, state) * params.tool_offset.translation.vector
+    }
+}
+
+/// Compute all the vertices of the arm (the base followed by the end of each link in `Link::ALL`).
+///  The vertices are in world coordinates, so the first one is the origin of the base.
+pub fn compute_vertices(
+    algorithm: &Arc<dyn ForwardKinematicAlgorithm>,
+    params: &KinematicParameters,
+    state: &KinematicState,
+) -> Vec<Vector3<f64>> {
+    std::iter::once(params.base_transform.translation.vector)
+        .chain(
+            Link::ALL
+                .into_iter()
+                .map(|link| algorithm.link_position_vector(link, params, state)),
+        )
+        .collect()
 }
 
-/// Compute all the vertices of the arm.
+/// Compute all the vertices of the arm, this is a convenience wrapper for the five link arm.
 pub fn compute_arm_vertices(
     algorithm: &Arc<dyn ForwardKinematicAlgorithm>,
     params: &KinematicParameters,
     state: &KinematicState,
 ) -> [Vector3<f64>; 6] {
     [
-        Vector3::<f64>::zeros(),
+        params.base_transform.translation.vector,
         algorithm.limb0_position_vector(params, state),
         algorithm.limb1_position_vector(params, state),
         algorithm.limb2_position_vector(params, state),
@@ -72,3 +128,290 @@ pub fn compute_arm_vertices(
         algorithm.limb4_position_vector(params, state),
     ]
 }
+
+/// Compute the geometry of the arm (all the vertices), this is useful for logging.
+pub fn compute_arm_geometry(
+    algorithm: &Arc<dyn ForwardKinematicAlgorithm>,
+    params: &KinematicParameters,
+    state: &KinematicState,
+) -> ArmGeometry {
+    ArmGeometry::from(compute_arm_vertices(algorithm, params, state))
+}
+
+/// Compute the center of mass of the arm, assuming the mass of each link lies in its middle.
+pub fn compute_center_of_mass(
+    algorithm: &Arc<dyn ForwardKinematicAlgorithm>,
+    params: &KinematicParameters,
+    state: &KinematicState,
+) -> Vector3<f64> {
+    let vertices = compute_vertices(algorithm, params, state);
+
+    let total_mass: f64 = params.link_masses.iter().sum();
+    let weighted_sum: Vector3<f64> = vertices
+        .windows(2)
+        .zip(params.link_masses.iter())
+        .map(|(link, mass)| (link[0] + link[1]) / 2_f64 * *mass)
+        .sum();
+
+    weighted_sum / total_mass
+}
+
+/// Compute the manipulability (Yoshikawa's measure $\sqrt{\det(J J^T)}$) of the end-effector
+///  position, this is zero in a singular pose and grows the better the arm is conditioned.
+///
+/// The jacobian is approximated using central differences, so it works with any algorithm.
+pub fn compute_manipulability(
+    algorithm: &Arc<dyn ForwardKinematicAlgorithm>,
+    params: &KinematicParameters,
+    state: &KinematicState,
+) -> f64 {
+    const STEP: f64 = 1e-6_f64;
+
+    let angles: Vector5<f64> = state.into();
+    let mut jacobian = Matrix3x5::<f64>::zeros();
+
+    for joint in 0..5_usize {
+        let mut forward = angles;
+        forward[joint] += STEP;
+        let mut backward = angles;
+        backward[joint] -= STEP;
+
+        let delta = algorithm.limb4_position_vector(params, &KinematicState::from(forward))
+            - algorithm.limb4_position_vector(params, &KinematicState::from(backward));
+
+        jacobian.set_column(joint, &(delta / (2_f64 * STEP)));
+    }
+
+    (jacobian * jacobian.transpose())
+        .determinant()
+        .max(0_f64)
+        .sqrt()
+}
+
+/// Convert the given euler angles $(\phi, \theta, \psi)$ into an orientation matrix.
+///
+/// The angles are intrinsic $y$-$x$-$y$ euler angles, meaning that the orientation is
+///  $R_y(\phi) R_x(\theta) R_y(\psi)$. This matches the arm, since the base and the last joint
+///  rotate around the $y$ axis (pointing up) and the joints in between around the $x$ axis.
+pub fn euler_angles_to_matrix(euler_angles: &Vector3<f64>) -> Matrix3<f64> {
+    let y_axis = Vector3::<f64>::y_axis();
+    let x_axis = Vector3::<f64>::x_axis();
+
+    (Rotation3::from_axis_angle(&y_axis, euler_angles.x)
+        * Rotation3::from_axis_angle(&x_axis, euler_angles.y)
+        * Rotation3::from_axis_angle(&y_axis, euler_angles.z))
+    .into_inner()
+}
+
+/// Convert the given orientation matrix into euler angles, following the convention of
+///  `euler_angles_to_matrix`.
+///
+/// The angle $\theta$ is always in the range $[0, \pi]$, when it's (close to) zero or $\pi$ the
+///  first and last rotation are around the same axis, so $\psi$ is set to zero.
+pub fn matrix_to_euler_angles(matrix: &Matrix3<f64>) -> Vector3<f64> {
+    let sin_theta = matrix[(0, 1)].hypot(matrix[(2, 1)]);
+    let theta = sin_theta.atan2(matrix[(1, 1)]);
+
+    // Handle the gimbal lock, where only the sum (or difference) of the angles is known.
+    if sin_theta < 1e-9_f64 {
+        let phi = match matrix[(1, 1)] > 0_f64 {
+            true => matrix[(0, 2)].atan2(matrix[(0, 0)]),
+            false => (-matrix[(0, 2)]).atan2(matrix[(0, 0)]),
+        };
+
+        return Vector3::<f64>::new(phi, theta, 0_f64);
+    }
+
+    Vector3::<f64>::new(
+        matrix[(0, 1)].atan2(matrix[(2, 1)]),
+        theta,
+        matrix[(1, 0)].atan2(-matrix[(1, 2)]),
+    )
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Arc;
+
+    use crate::forward::algorithms::analytical::AnalyticalFKAlgorithm;
+    use crate::forward::algorithms::{
+        compute_arm_geometry, compute_arm_vertices, compute_manipulability, compute_vertices,
+        euler_angles_to_matrix, matrix_to_euler_angles, ForwardKinematicAlgorithm,
+    };
+    use crate::model::{ArmGeometry, KinematicParameters, KinematicState, Link};
+    use nalgebra::{Isometry3, Vector3, Vector5};
+
+    #[test]
+    pub fn vertex_for_base_and_every_link() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let state: KinematicState = KinematicState::default();
+        let algorithm: Arc<dyn ForwardKinematicAlgorithm> =
+            Arc::new(AnalyticalFKAlgorithm::default());
+
+        // Compute the vertices link by link.
+        let vertices = compute_vertices(&algorithm, &params, &state);
+
+        // Make sure that there's a vertex for the base and for each link, and that the last one
+        //  is the end-effector.
+        assert_eq!(vertices.len(), params.link_count() + 1_usize);
+        assert_eq!(vertices[0], nalgebra::Vector3::<f64>::zeros());
+        assert_eq!(
+            vertices[params.link_count()],
+            algorithm.limb4_position_vector(&params, &state)
+        );
+    }
+
+    #[test]
+    pub fn vertices_for_five_links() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let state: KinematicState = KinematicState::default();
+        let algorithm: Arc<dyn ForwardKinematicAlgorithm> =
+            Arc::new(AnalyticalFKAlgorithm::default());
+
+        // The convenience wrapper should match the link by link function.
+        assert_eq!(
+            compute_arm_vertices(&algorithm, &params, &state).to_vec(),
+            compute_vertices(&algorithm, &params, &state)
+        );
+
+        // Every link ends as far from where it starts as it's long.
+        let vertices = compute_vertices(&algorithm, &params, &state);
+        for (link, ends) in Link::ALL.into_iter().zip(vertices.windows(2)) {
+            assert!(((ends[1] - ends[0]).norm() - link.length(&params)).abs() < 1e-9_f64);
+        }
+    }
+
+    #[test]
+    pub fn geometry_total_length() {
+        // Create the default kinematic parameters and state.
+        let params: KinematicParameters = KinematicParameters::default();
+        let state: KinematicState = KinematicState::default();
+
+        // Create the analytical forward kinematics algorithm.
+        let algorithm: Arc<dyn ForwardKinematicAlgorithm> =
+            Arc::new(AnalyticalFKAlgorithm::default());
+
+        // Compute the geometry of the arm.
+        let geometry: ArmGeometry = compute_arm_geometry(&algorithm, &params, &state);
+
+        // Make sure that the total length matches the sum of the link lengths.
+        assert!((geometry.total_length() - params.sum_of_link_lengths()).abs() < 1e-9_f64);
+
+        // Make sure that all the vertices are displayed.
+        assert_eq!(geometry.to_string().lines().count(), 7_usize);
+    }
+
+    #[test]
+    pub fn orientation_quaternion_matches_matrix() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let algorithm: Arc<dyn ForwardKinematicAlgorithm> =
+            Arc::new(AnalyticalFKAlgorithm::default());
+
+        for state in [KinematicState::default(), KinematicState::ready()] {
+            let matrix = algorithm.limb4_orientation_matrix(&params, &state);
+            let quaternion = algorithm.limb4_orientation_quaternion(&params, &state);
+
+            assert!((quaternion.to_rotation_matrix().into_inner() - matrix).norm() < 1e-9_f64);
+        }
+    }
+
+    #[test]
+    pub fn euler_angles_round_trip_in_gimbal_lock() {
+        for theta in [0_f64, std::f64::consts::PI] {
+            let matrix =
+                euler_angles_to_matrix(&nalgebra::Vector3::<f64>::new(0.3_f64, theta, 0.2_f64));
+            let euler_angles = matrix_to_euler_angles(&matrix);
+
+            assert!((euler_angles_to_matrix(&euler_angles) - matrix).norm() < 1e-9_f64);
+        }
+    }
+
+    #[test]
+    pub fn ready_pose_is_well_conditioned() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let algorithm: Arc<dyn ForwardKinematicAlgorithm> =
+            Arc::new(AnalyticalFKAlgorithm::default());
+
+        // The ready pose must lie within the joint limits.
+        let ready: KinematicState = KinematicState::ready();
+        assert!(ready.check_joint_limits(&params).is_ok());
+
+        // The end-effector must be within reach and above the base for both poses.
+        for state in [KinematicState::default(), ready.clone()] {
+            let position = algorithm.limb4_position_vector(&params, &state);
+
+            assert!(position.iter().all(|x| x.is_finite()));
+            assert!(position.norm() <= params.sum_of_link_lengths() + 1e-9_f64);
+            assert!(position.y > 0_f64);
+        }
+
+        // The straight-up pose is singular, while the ready pose isn't.
+        let straight: KinematicState = KinematicState::from(Vector5::<f64>::zeros());
+        assert!(compute_manipulability(&algorithm, &params, &straight) < 1e-3_f64);
+        assert!(compute_manipulability(&algorithm, &params, &ready) > 1_f64);
+    }
+
+    #[test]
+    pub fn vertices_follow_base_transform() {
+        let state: KinematicState = KinematicState::ready();
+        let algorithm: Arc<dyn ForwardKinematicAlgorithm> =
+            Arc::new(AnalyticalFKAlgorithm::default());
+
+        // Mount the arm on a wall, so rotated a quarter turn around the x axis and moved up.
+        let base_transform = Isometry3::<f64>::new(
+            Vector3::<f64>::new(0_f64, 1_f64, 0_f64),
+            Vector3::<f64>::x() * std::f64::consts::FRAC_PI_2,
+        );
+        let params: KinematicParameters = KinematicParameters::default();
+        let mounted_params: KinematicParameters = KinematicParameters {
+            base_transform,
+            ..KinematicParameters::default()
+        };
+
+        let vertices = compute_arm_vertices(&algorithm, &params, &state);
+        let mounted_vertices = compute_arm_vertices(&algorithm, &mounted_params, &state);
+
+        // The base sits at the translation, and the up pointing first link now points along z.
+        assert_eq!(
+            mounted_vertices[0],
+            Vector3::<f64>::new(0_f64, 1_f64, 0_f64)
+        );
+        assert!(
+            (mounted_vertices[1] - Vector3::<f64>::new(0_f64, 1_f64, params.l_0)).norm() < 1e-9_f64
+        );
+
+        for (vertex, mounted_vertex) in vertices.iter().zip(mounted_vertices.iter()) {
+            let expected = base_transform.transform_point(&(*vertex).into()).coords;
+
+            assert!((mounted_vertex - expected).norm() < 1e-9_f64);
+        }
+    }
+
+    #[test]
+    pub fn tool_position_follows_flange() {
+        let state: KinematicState = KinematicState::ready();
+        let algorithm: Arc<dyn ForwardKinematicAlgorithm> =
+            Arc::new(AnalyticalFKAlgorithm::default());
+
+        // Without a tool the tip is the flange.
+        let params: KinematicParameters = KinematicParameters::default();
+        assert_eq!(
+            algorithm.limb4_tool_position_vector(&params, &state),
+            algorithm.limb4_position_vector(&params, &state)
+        );
+
+        // A tool of 100mm along the z axis of the flange.
+        let params: KinematicParameters = KinematicParameters {
+            tool_offset: Isometry3::<f64>::translation(0_f64, 0_f64, 0.1_f64),
+            ..KinematicParameters::default()
+        };
+
+        let offset = algorithm.limb4_tool_position_vector(&params, &state)
+            - algorithm.limb4_position_vector(&params, &state);
+        let expected = algorithm.limb4_orientation_matrix(&params, &state)
+            * Vector3::<f64>::new(0_f64, 0_f64, 0.1_f64);
+
+        assert!((offset.norm() - 0.1_f64).abs() < 1e-9_f64);
+        assert!((offset - expected).norm() < 1e-9_f64);
+    }
+}
@@ -0,0 +1,377 @@
+use std::f64::consts::{PI, TAU};
+
+use nalgebra::{Rotation3, Vector3};
+
+use crate::error::KinematicError;
+use crate::forward::algorithms::analytical::AnalyticalFKAlgorithm;
+use crate::forward::algorithms::ForwardKinematicAlgorithm;
+use crate::inverse::algorithms::InverseKinematicAlgorithm;
+use crate::model::{KinematicParameters, KinematicState};
+
+/// This enum represents the two ways the shoulder and elbow can reach the same wrist center.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElbowConfiguration {
+    Positive, // The elbow angle is positive.
+    Negative, // The elbow angle is negative.
+}
+
+impl ElbowConfiguration {
+    /// Get the configuration the given state is in.
+    pub fn of(state: &KinematicState) -> Self {
+        if state.theta_2 >= 0_f64 {
+            Self::Positive
+        } else {
+            Self::Negative
+        }
+    }
+}
+
+/// Analytical inverse kinematic approach, which decouples the position of the arm from the
+///  orientation of the wrist. The wrist center is found by moving back from the target along the
+///  approach vector by the length of the last two links, the base, shoulder and elbow are solved
+///  in closed form to reach it, after which the wrist pitch makes up the rest of the approach
+///  angle and the wrist roll is set directly.
+#[derive(Default)]
+pub struct AnalyticalIKAlgorithm {}
+
+/// Get the distance between the given angles, taking the wrap around into account.
+fn angle_distance(a: f64, b: f64) -> f64 {
+    ((a - b + PI).rem_euclid(TAU) - PI).abs()
+}
+
+impl AnalyticalIKAlgorithm {
+    /// The margin by which a wrist center may lie outside the reach of the shoulder and elbow,
+    ///  so rounding errors at full extension don't make a target unreachable.
+    const REACH_EPSILON: f64 = 1e-9_f64;
+
+    /// The horizontal distance (and the sine of the approach angle) below which the target is
+    ///  considered to be on the vertical axis of the base.
+    const AXIS_EPSILON: f64 = 1e-9_f64;
+
+    /// Solve the joint angles that put the end-effector of the fourth link at the given position
+    ///  (in world coordinates), with the last links at the given approach angle (from the vertical
+    ///  axis of the base, in radians) and with the given wrist roll. The base can face the target
+    ///  in two ways (reaching forward or backward over the top), the one closest to the given base
+    ///  angle is used, which is also the one used when the target is right above the base.
+    pub fn solve(
+        &self,
+        params @ &KinematicParameters {
+            l_0,
+            l_1,
+            l_2,
+            l_3,
+            l_4,
+            ..
+        }: &KinematicParameters,
+        position: &Vector3<f64>,
+        approach: f64,
+        roll: f64,
+        elbow: ElbowConfiguration,
+        base: f64,
+    ) -> Result<KinematicState, KinematicError> {
+        let position = params.world_to_base(position);
+
+        // Face the base towards the target, the reach is negative when reaching backward.
+        let horizontal = position.x.hypot(position.z);
+        let forward = position.x.atan2(-position.z);
+        let backward = if forward > 0_f64 {
+            forward - PI
+        } else {
+            forward + PI
+        };
+
+        let (theta_0, reach) = if horizontal < Self::AXIS_EPSILON {
+            (base, 0_f64)
+        } else if angle_distance(forward, base) <= angle_distance(backward, base) {
+            (forward, horizontal)
+        } else {
+            (backward, -horizontal)
+        };
+
+        // Move back from the target along the approach vector to find the wrist center, relative
+        //  to the shoulder.
+        let wrist_reach = reach - (l_3 + l_4) * approach.sin();
+        let wrist_height = position.y - l_0 - (l_3 + l_4) * approach.cos();
+
+        // Solve the shoulder and elbow as a planar two-link arm, using the law of cosines.
+        let cos_theta_2 = (wrist_reach.powi(2) + wrist_height.powi(2) - l_1.powi(2) - l_2.powi(2))
+            / (2_f64 * l_1 * l_2);
+        if cos_theta_2.is_nan() || cos_theta_2.abs() > 1_f64 + Self::REACH_EPSILON {
+            return Err(KinematicError::Unreachable);
+        }
+
+        let theta_2 = match elbow {
+            ElbowConfiguration::Positive => cos_theta_2.clamp(-1_f64, 1_f64).acos(),
+            ElbowConfiguration::Negative => -cos_theta_2.clamp(-1_f64, 1_f64).acos(),
+        };
+        let theta_1 = wrist_reach.atan2(wrist_height)
+            - (l_2 * theta_2.sin()).atan2(l_1 + l_2 * theta_2.cos());
+
+        // The wrist pitch makes up the rest of the approach angle.
+        let mut state = KinematicState {
+            theta_0,
+            theta_1,
+            theta_2,
+            theta_3: approach - theta_1 - theta_2,
+            theta_4: roll,
+        };
+        state.normalize_angles();
+
+        Ok(state)
+    }
+}
+
+impl InverseKinematicAlgorithm for AnalyticalIKAlgorithm {
+    /// Translate the end-effector, keeping the approach angle, the wrist roll and the elbow
+    ///  configuration of the given state.
+    fn translate_limb4_end_effector(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        delta: &Vector3<f64>,
+    ) -> Result<KinematicState, KinematicError> {
+        let fk_solver = AnalyticalFKAlgorithm::default();
+        let target = fk_solver.limb4_position_vector(params, state) + delta;
+
+        self.solve(
+            params,
+            &target,
+            state.theta_1 + state.theta_2 + state.theta_3,
+            state.theta_4,
+            ElbowConfiguration::of(state),
+            state.theta_0,
+        )
+    }
+
+    /// Rotate the end-effector by the given rotation vector (in world coordinates), keeping its
+    ///  position. Since the base has to keep facing the end-effector, only the part of the
+    ///  rotation that pitches the last links within the plane of the arm and rolls the wrist can
+    ///  be followed, the closest reachable orientation is used.
+    fn rotate_limb4_end_effector(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        delta: &Vector3<f64>,
+    ) -> Result<KinematicState, KinematicError> {
+        let fk_solver = AnalyticalFKAlgorithm::default();
+        let position = fk_solver.limb4_position_vector(params, state);
+
+        // Compute the target orientation in base coordinates.
+        let base_rotation = params.base_transform.rotation.to_rotation_matrix();
+        let orientation = base_rotation.inverse()
+            * Rotation3::new(*delta)
+            * Rotation3::from_matrix_unchecked(fk_solver.limb4_orientation_matrix(params, state));
+        let matrix = orientation.matrix();
+
+        // The second column is the direction of the last links, it's projected onto the plane of
+        //  the arm to get the approach angle.
+        let plane = Vector3::<f64>::new(state.theta_0.sin(), 0_f64, -state.theta_0.cos());
+        let approach = matrix
+            .column(1_usize)
+            .dot(&plane)
+            .atan2(matrix[(1_usize, 1_usize)]);
+
+        // While the last links are vertical the wrist roll can't be told apart from the base.
+        let sin_approach = approach.sin();
+        if sin_approach.abs() < Self::AXIS_EPSILON {
+            return Err(KinematicError::NearSingularity);
+        }
+
+        let roll = (matrix[(1_usize, 0_usize)] * sin_approach.signum())
+            .atan2(matrix[(1_usize, 2_usize)] * sin_approach.signum());
+
+        self.solve(
+            params,
+            &position,
+            approach,
+            roll,
+            ElbowConfiguration::of(state),
+            state.theta_0,
+        )
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    use nalgebra::{Rotation3, Vector3};
+
+    use crate::error::KinematicError;
+    use crate::forward::algorithms::analytical::AnalyticalFKAlgorithm;
+    use crate::forward::algorithms::ForwardKinematicAlgorithm;
+    use crate::inverse::algorithms::analytical::{AnalyticalIKAlgorithm, ElbowConfiguration};
+    use crate::inverse::algorithms::InverseKinematicAlgorithm;
+    use crate::model::{KinematicParameters, KinematicState};
+
+    #[test]
+    pub fn known_poses() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let ik_solver: AnalyticalIKAlgorithm = AnalyticalIKAlgorithm::default();
+
+        // All links pointing straight up, the arm is fully extended.
+        let state = ik_solver
+            .solve(
+                &params,
+                &Vector3::<f64>::new(0_f64, 50_f64, 0_f64),
+                0_f64,
+                0_f64,
+                ElbowConfiguration::Positive,
+                0_f64,
+            )
+            .unwrap();
+        let expected = KinematicState {
+            theta_0: 0_f64,
+            theta_1: 0_f64,
+            theta_2: 0_f64,
+            theta_3: 0_f64,
+            theta_4: 0_f64,
+        };
+        assert!(state.approx_eq(&expected, 1e-9_f64));
+
+        // The first link points forward and the others up again, the wrist center lies at a
+        //  right angle from the shoulder so the elbow is at a right angle too.
+        let state = ik_solver
+            .solve(
+                &params,
+                &Vector3::<f64>::new(0_f64, 40_f64, -10_f64),
+                0_f64,
+                0.5_f64,
+                ElbowConfiguration::Negative,
+                0_f64,
+            )
+            .unwrap();
+        let expected = KinematicState {
+            theta_0: 0_f64,
+            theta_1: FRAC_PI_2,
+            theta_2: -FRAC_PI_2,
+            theta_3: 0_f64,
+            theta_4: 0.5_f64,
+        };
+        assert!(state.approx_eq(&expected, 1e-9_f64));
+
+        // The same pose, turned to the side by the base.
+        let state = ik_solver
+            .solve(
+                &params,
+                &Vector3::<f64>::new(10_f64, 40_f64, 0_f64),
+                0_f64,
+                0.5_f64,
+                ElbowConfiguration::Negative,
+                0_f64,
+            )
+            .unwrap();
+        let expected = KinematicState {
+            theta_0: FRAC_PI_2,
+            ..expected
+        };
+        assert!(state.approx_eq(&expected, 1e-9_f64));
+
+        // Beyond the reach of the arm.
+        assert!(matches!(
+            ik_solver.solve(
+                &params,
+                &Vector3::<f64>::new(0_f64, 51_f64, 0_f64),
+                0_f64,
+                0_f64,
+                ElbowConfiguration::Positive,
+                0_f64,
+            ),
+            Err(KinematicError::Unreachable)
+        ));
+    }
+
+    #[test]
+    pub fn round_trip_through_forward_kinematics() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let fk_solver: AnalyticalFKAlgorithm = AnalyticalFKAlgorithm::default();
+        let ik_solver: AnalyticalIKAlgorithm = AnalyticalIKAlgorithm::default();
+
+        // Generate pseudo-random angles in the range [-pi, pi) using a linear congruential
+        //  generator, so the test is reproducible.
+        let mut seed: u64 = 0x2545F4914F6CDD1D_u64;
+        let mut next_angle = || {
+            seed = seed
+                .wrapping_mul(6364136223846793005_u64)
+                .wrapping_add(1442695040888963407_u64);
+
+            ((seed >> 11) as f64 / (1_u64 << 53) as f64 - 0.5_f64) * 2_f64 * PI
+        };
+
+        for _ in 0..1000 {
+            let state: KinematicState = KinematicState {
+                theta_0: next_angle(),
+                theta_1: next_angle(),
+                theta_2: next_angle(),
+                theta_3: next_angle(),
+                theta_4: next_angle(),
+            };
+
+            let solved = ik_solver
+                .solve(
+                    &params,
+                    &fk_solver.limb4_position_vector(&params, &state),
+                    state.theta_1 + state.theta_2 + state.theta_3,
+                    state.theta_4,
+                    ElbowConfiguration::of(&state),
+                    state.theta_0,
+                )
+                .unwrap();
+
+            // The approach angle can be reached in one way only, so it's the very same state.
+            assert!(solved.approx_eq(&state, 1e-6_f64));
+        }
+
+        // Translating is exact, so the target is reached in a single step.
+        let state = KinematicState::ready();
+        let delta = Vector3::<f64>::new(1_f64, -2_f64, 3_f64);
+        let translated = ik_solver
+            .translate_limb4_end_effector(&params, &state, &delta)
+            .unwrap();
+        assert!(
+            (fk_solver.limb4_position_vector(&params, &translated)
+                - fk_solver.limb4_position_vector(&params, &state)
+                - delta)
+                .magnitude()
+                < 1e-9_f64
+        );
+    }
+
+    #[test]
+    pub fn rotate_keeps_position() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let fk_solver: AnalyticalFKAlgorithm = AnalyticalFKAlgorithm::default();
+        let ik_solver: AnalyticalIKAlgorithm = AnalyticalIKAlgorithm::default();
+
+        let state: KinematicState = KinematicState {
+            theta_0: 0.3_f64,
+            theta_1: 0.4_f64,
+            theta_2: 0.5_f64,
+            theta_3: 0.2_f64,
+            theta_4: 0.1_f64,
+        };
+        let position = fk_solver.limb4_position_vector(&params, &state);
+        let orientation = fk_solver.limb4_orientation_matrix(&params, &state);
+
+        // Rolling around the last links, and pitching them within the plane of the arm.
+        let approach = orientation.column(1_usize).into_owned();
+        let normal = Vector3::<f64>::new(state.theta_0.cos(), 0_f64, state.theta_0.sin());
+
+        for delta in [approach * 0.3_f64, normal * 0.2_f64] {
+            let rotated = ik_solver
+                .rotate_limb4_end_effector(&params, &state, &delta)
+                .unwrap();
+
+            assert!(
+                (fk_solver.limb4_position_vector(&params, &rotated) - position).magnitude()
+                    < 1e-9_f64
+            );
+            assert!(
+                (fk_solver.limb4_orientation_matrix(&params, &rotated)
+                    - Rotation3::new(delta).matrix() * orientation)
+                    .norm()
+                    < 1e-9_f64
+            );
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use nalgebra::{Matrix3x5, Matrix5x3, Vector3, Vector5};
+use nalgebra::{Matrix3x5, Matrix5, Matrix5x3, Rotation3, Vector3, Vector5};
 use thiserror::Error;
 
 use crate::error::KinematicError;
@@ -11,19 +11,95 @@ pub enum HeuristicInverseKinematicsAlgorithmError {
     PseudoInvertFailure(&'static str),
 }
 
+/// This struct represents a secondary objective that is resolved in the nullspace of the
+///  jacobian, it pulls the joints toward a preferred posture without moving the end-effector so
+///  the redundant joints don't drift arbitrarily between solves.
+#[derive(Debug, Clone)]
+pub struct PostureObjective {
+    pub posture: KinematicState, // The preferred angles of the joints.
+    pub weights: Vector5<f64>,   // How strongly each joint is pulled toward its preferred angle.
+    pub gain: f64,               // The part of the posture error that's corrected per step.
+}
+
+impl PostureObjective {
+    /// The gain used when none is configured.
+    pub const DEFAULT_GAIN: f64 = 0.5_f64;
+
+    /// Create a new objective that pulls the joints toward the given posture, weighted per joint.
+    pub fn new(posture: KinematicState, weights: Vector5<f64>) -> Self {
+        Self {
+            posture,
+            weights,
+            gain: Self::DEFAULT_GAIN,
+        }
+    }
+
+    /// Create a new objective that keeps every joint near the middle of its range, joints without
+    ///  (finite) limits have no middle and are left alone.
+    pub fn mid_range(params: &KinematicParameters) -> Self {
+        let mut posture: Vector5<f64> = Vector5::<f64>::zeros();
+        let mut weights: Vector5<f64> = Vector5::<f64>::zeros();
+        for (i, [min, max]) in params.joint_limits.iter().enumerate() {
+            if min.is_finite() && max.is_finite() {
+                posture[i] = (min + max) / 2_f64;
+                weights[i] = 1_f64;
+            }
+        }
+
+        Self::new(KinematicState::from(posture), weights)
+    }
+
+    pub fn with_gain(mut self, gain: f64) -> Self {
+        assert!(gain > 0_f64 && gain <= 1_f64);
+
+        self.gain = gain;
+
+        self
+    }
+
+    /// Get the joint velocities that move the given state toward the preferred posture.
+    pub fn joint_velocities(&self, state: &KinematicState) -> Vector5<f64> {
+        (Vector5::<f64>::from(&self.posture) - Vector5::<f64>::from(state))
+            .component_mul(&self.weights)
+            * self.gain
+    }
+}
+
 pub struct HeuristicIKAlgorithm {
     pseudo_inverse_eps: f64,
+    singularity_threshold: Option<f64>,
+    posture_objective: Option<PostureObjective>,
 }
 
 impl Default for HeuristicIKAlgorithm {
     fn default() -> Self {
         Self {
             pseudo_inverse_eps: 0.0000000000001,
+            singularity_threshold: None,
+            posture_objective: None,
         }
     }
 }
 
 impl HeuristicIKAlgorithm {
+    /// Refuse to translate the end-effector when the smallest singular value of the jacobian
+    ///  drops below the given threshold, since the arm can't move in some direction near a
+    ///  singularity and the pseudo-inverse will demand huge joint velocities to do so anyway.
+    pub fn with_singularity_threshold(mut self, singularity_threshold: f64) -> Self {
+        self.singularity_threshold = Some(singularity_threshold);
+
+        self
+    }
+
+    /// Resolve the given posture objective in the nullspace of the jacobian on every translation,
+    ///  so repeated solves for the same target end up in the same posture regardless of the
+    ///  state they started from.
+    pub fn with_posture_objective(mut self, posture_objective: PostureObjective) -> Self {
+        self.posture_objective = Some(posture_objective);
+
+        self
+    }
+
     fn limb4_end_effector_position_jacobian(
         &self,
         &KinematicParameters {
@@ -82,6 +158,47 @@ impl HeuristicIKAlgorithm {
             0_f64,
         )
     }
+
+    /// Compute the jacobian of the position of the tool tip relative to the base. The tool offset
+    ///  turns along with the flange, so on top of moving the flange every joint also swings the
+    ///  offset around its own axis.
+    fn limb4_tool_position_jacobian(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+    ) -> Matrix3x5<f64> {
+        let mut jacobian: Matrix3x5<f64> = self.limb4_end_effector_position_jacobian(params, state);
+
+        let base: Rotation3<f64> =
+            Rotation3::<f64>::from_axis_angle(&Vector3::y_axis(), -state.theta_0);
+        let pitch: Rotation3<f64> = Rotation3::<f64>::from_axis_angle(
+            &Vector3::x_axis(),
+            -(state.theta_1 + state.theta_2 + state.theta_3),
+        );
+        let roll: Rotation3<f64> =
+            Rotation3::<f64>::from_axis_angle(&Vector3::y_axis(), -state.theta_4);
+
+        // The tool offset in the orientation of the flange, relative to the base.
+        let offset: Vector3<f64> = base * pitch * roll * params.tool_offset.translation.vector;
+
+        // The axes the joints turn around relative to the base, all of them turn in the negative
+        //  direction.
+        let pitch_axis: Vector3<f64> = -(base * Vector3::<f64>::x());
+        let axes: [Vector3<f64>; 5] = [
+            -Vector3::<f64>::y(),
+            pitch_axis,
+            pitch_axis,
+            pitch_axis,
+            -(base * pitch * Vector3::<f64>::y()),
+        ];
+
+        for (i, axis) in axes.iter().enumerate() {
+            let column: Vector3<f64> = jacobian.column(i) + axis.cross(&offset);
+            jacobian.set_column(i, &column);
+        }
+
+        jacobian
+    }
 }
 
 impl InverseKinematicAlgorithm for HeuristicIKAlgorithm {
@@ -91,22 +208,34 @@ impl InverseKinematicAlgorithm for HeuristicIKAlgorithm {
         state: &KinematicState,
         delta: &Vector3<f64>,
     ) -> Result<KinematicState, KinematicError> {
-        // Compute the jacobian matrix for the end-effector position.
-        let jacobian: Matrix3x5<f64> = self.limb4_end_effector_position_jacobian(params, state);
+        // Compute the jacobian matrix for the position of the tool tip.
+        let jacobian: Matrix3x5<f64> = self.limb4_tool_position_jacobian(params, state);
+
+        // Make sure we're not (too close to) a singularity, if requested.
+        if let Some(singularity_threshold) = self.singularity_threshold {
+            if jacobian.singular_values().min() < singularity_threshold {
+                return Err(KinematicError::NearSingularity);
+            }
+        }
 
         // Invert the jacobian matrix.
-        let jacobian_inverse: Matrix5x3<f64> =
-            match jacobian.pseudo_inverse(self.pseudo_inverse_eps) {
-                Ok(x) => x,
-                Err(_) => {
-                    return Err(KinematicError::InversionFailure);
-                }
-            };
+        let jacobian_inverse: Matrix5x3<f64> = jacobian
+            .pseudo_inverse(self.pseudo_inverse_eps)
+            .map_err(HeuristicInverseKinematicsAlgorithmError::PseudoInvertFailure)?;
+
+        // Move the joints toward the preferred posture (if any), projected onto the nullspace of
+        //  the jacobian so it doesn't affect the position of the end-effector.
+        let posture_delta: Vector5<f64> = match &self.posture_objective {
+            Some(posture_objective) => {
+                (Matrix5::<f64>::identity() - jacobian_inverse * jacobian)
+                    * posture_objective.joint_velocities(state)
+            }
+            None => Vector5::<f64>::zeros(),
+        };
 
-        println!("{:?}", (jacobian));
         // Compute the new kinematic state and return it.
         Ok(KinematicState::from(
-            (jacobian_inverse * delta) + Vector5::<f64>::from(state),
+            (jacobian_inverse * delta) + posture_delta + Vector5::<f64>::from(state),
         ))
     }
 
@@ -122,12 +251,13 @@ impl InverseKinematicAlgorithm for HeuristicIKAlgorithm {
 
 #[cfg(test)]
 pub mod tests {
+    use crate::error::KinematicError;
     use crate::forward::algorithms::analytical::AnalyticalFKAlgorithm;
     use crate::forward::algorithms::ForwardKinematicAlgorithm;
-    use crate::inverse::algorithms::heuristic::HeuristicIKAlgorithm;
+    use crate::inverse::algorithms::heuristic::{HeuristicIKAlgorithm, PostureObjective};
     use crate::inverse::algorithms::InverseKinematicAlgorithm;
     use crate::model::{KinematicParameters, KinematicState};
-    use nalgebra::Vector3;
+    use nalgebra::{Isometry3, Matrix3x5, Vector3, Vector5};
 
     #[test]
     pub fn solve() {
@@ -158,7 +288,6 @@ pub mod tests {
             // Compute the current end effector position, and the difference between it and the
             //  target.
             let delta: Vector3<f64> = target - fk_solver.limb4_position_vector(&params, &state);
-            println!("{}", delta.magnitude());
 
             // If the target is really close, just break.
             if delta.magnitude() < thresh {
@@ -174,4 +303,183 @@ pub mod tests {
         // Make sure that the algorithm reached the destinaton.
         assert!((fk_solver.limb4_position_vector(&params, &state) - target).magnitude() < thresh);
     }
+
+    #[test]
+    pub fn posture_objective_consistent_between_seeds() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let fk_solver: AnalyticalFKAlgorithm = AnalyticalFKAlgorithm::default();
+        let ik_solver: HeuristicIKAlgorithm = HeuristicIKAlgorithm::default()
+            .with_posture_objective(PostureObjective::new(
+                KinematicState::ready(),
+                Vector5::<f64>::repeat(1_f64),
+            ));
+
+        let target: Vector3<f64> = fk_solver.limb4_position_vector(
+            &params,
+            &KinematicState {
+                theta_0: 0.3_f64,
+                theta_1: 0.4_f64,
+                theta_2: 0.6_f64,
+                theta_3: 0.5_f64,
+                theta_4: 0_f64,
+            },
+        );
+
+        let seeds: [KinematicState; 2] = [
+            KinematicState {
+                theta_0: 0.2_f64,
+                theta_1: 0.2_f64,
+                theta_2: 0.9_f64,
+                theta_3: 0.1_f64,
+                theta_4: 0.5_f64,
+            },
+            KinematicState {
+                theta_0: 0.4_f64,
+                theta_1: 0.6_f64,
+                theta_2: 0.4_f64,
+                theta_3: 0.7_f64,
+                theta_4: -0.3_f64,
+            },
+        ];
+
+        let solutions: Vec<KinematicState> = seeds
+            .iter()
+            .map(|seed| {
+                let mut state: KinematicState = seed.clone();
+                for _ in 0_usize..500_usize {
+                    let delta: Vector3<f64> =
+                        target - fk_solver.limb4_position_vector(&params, &state);
+                    state = ik_solver
+                        .translate_limb4_end_effector(&params, &state, &delta)
+                        .unwrap();
+                }
+
+                state
+            })
+            .collect();
+
+        for solution in solutions.iter() {
+            // The target is reached, and the posture settled where the objective wants it.
+            assert!(
+                (fk_solver.limb4_position_vector(&params, solution) - target).magnitude()
+                    < 1e-6_f64
+            );
+            assert!(ik_solver
+                .translate_limb4_end_effector(&params, solution, &Vector3::<f64>::zeros())
+                .unwrap()
+                .approx_eq(solution, 1e-6_f64));
+            assert!((solution.theta_4 - KinematicState::ready().theta_4).abs() < 1e-6_f64);
+        }
+
+        // Both seeds end up in the same posture.
+        assert!(solutions[0_usize].approx_eq(&solutions[1_usize], 1e-4_f64));
+    }
+
+    #[test]
+    pub fn near_singularity() {
+        // With all links pointing straight up the arm can't move horizontally.
+        let state: KinematicState = KinematicState {
+            theta_0: 0_f64,
+            theta_1: 0_f64,
+            theta_2: 0_f64,
+            theta_3: 0_f64,
+            theta_4: 0_f64,
+        };
+
+        let params: KinematicParameters = KinematicParameters::default();
+        let ik_solver: HeuristicIKAlgorithm =
+            HeuristicIKAlgorithm::default().with_singularity_threshold(1e-3_f64);
+
+        assert!(matches!(
+            ik_solver.translate_limb4_end_effector(
+                &params,
+                &state,
+                &Vector3::<f64>::new(1_f64, 0_f64, 0_f64)
+            ),
+            Err(KinematicError::NearSingularity)
+        ));
+
+        // Away from the singularity it should just work.
+        assert!(ik_solver
+            .translate_limb4_end_effector(
+                &params,
+                &KinematicState::default(),
+                &Vector3::<f64>::new(1_f64, 0_f64, 0_f64)
+            )
+            .is_ok());
+    }
+
+    #[test]
+    pub fn tool_position_jacobian_matches_forward_kinematics() {
+        // A tool that sticks out far sideways, so turning the wrist swings the tip around.
+        let params: KinematicParameters = KinematicParameters {
+            tool_offset: Isometry3::<f64>::translation(15_f64, 5_f64, -10_f64),
+            ..KinematicParameters::default()
+        };
+        let state: KinematicState = KinematicState {
+            theta_0: 0.3_f64,
+            theta_1: 0.4_f64,
+            theta_2: 0.6_f64,
+            theta_3: -0.5_f64,
+            theta_4: 0.8_f64,
+        };
+
+        let fk_solver: AnalyticalFKAlgorithm = AnalyticalFKAlgorithm::default();
+        let ik_solver: HeuristicIKAlgorithm = HeuristicIKAlgorithm::default();
+
+        // Differentiate the forward kinematics numerically, one joint at a time.
+        let h: f64 = 1e-6_f64;
+        let mut expected: Matrix3x5<f64> = Matrix3x5::<f64>::zeros();
+        for i in 0_usize..5_usize {
+            let mut angles: Vector5<f64> = Vector5::<f64>::from(&state);
+            angles[i] += h;
+            let forward: Vector3<f64> =
+                fk_solver.limb4_tool_position_vector(&params, &KinematicState::from(angles));
+            angles[i] -= 2_f64 * h;
+            let backward: Vector3<f64> =
+                fk_solver.limb4_tool_position_vector(&params, &KinematicState::from(angles));
+            expected.set_column(i, &((forward - backward) / (2_f64 * h)));
+        }
+
+        let jacobian: Matrix3x5<f64> = ik_solver.limb4_tool_position_jacobian(&params, &state);
+
+        assert!((jacobian - expected).abs().max() < 1e-6_f64);
+    }
+
+    #[test]
+    pub fn solve_large_tool_offset() {
+        let params: KinematicParameters = KinematicParameters {
+            tool_offset: Isometry3::<f64>::translation(15_f64, 5_f64, -10_f64),
+            ..KinematicParameters::default()
+        };
+
+        let fk_solver: AnalyticalFKAlgorithm = AnalyticalFKAlgorithm::default();
+        let ik_solver: HeuristicIKAlgorithm = HeuristicIKAlgorithm::default();
+
+        let target: Vector3<f64> = fk_solver.limb4_tool_position_vector(
+            &params,
+            &KinematicState {
+                theta_0: 0.3_f64,
+                theta_1: 0.4_f64,
+                theta_2: 0.6_f64,
+                theta_3: 0.5_f64,
+                theta_4: 0.2_f64,
+            },
+        );
+
+        let mut state: KinematicState = KinematicState::default();
+        for _ in 0_usize..10_usize {
+            let delta: Vector3<f64> =
+                target - fk_solver.limb4_tool_position_vector(&params, &state);
+            state = ik_solver
+                .translate_limb4_end_effector(&params, &state, &delta)
+                .unwrap();
+        }
+
+        // Full steps converge within a few iterations, the jacobian of the flange would still be
+        //  meters away from the target by now.
+        assert!(
+            (fk_solver.limb4_tool_position_vector(&params, &state) - target).magnitude() < 1e-6_f64
+        );
+    }
 }
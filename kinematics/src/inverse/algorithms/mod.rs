@@ -2,6 +2,7 @@ use nalgebra::Vector3;
 
 use crate::{error::KinematicError, model::{KinematicParameters, KinematicState}};
 
+pub mod analytical;
 pub mod heuristic;
 
 pub trait InverseKinematicAlgorithm: Send + Sync {
@@ -6,13 +6,15 @@ use crate::{
     error::KinematicError, forward::algorithms::ForwardKinematicAlgorithm, inverse::algorithms::InverseKinematicAlgorithm, model::{KinematicParameters, KinematicState}
 };
 
-use super::{IKSolverResult, KinematicSolver};
+use super::{IKSolverResult, KinematicSolver, SolveOptions, SolverCapabilities};
 
 pub struct HeuristicSolverBuilder {
     inverse_algorithm: Arc<dyn InverseKinematicAlgorithm>,
     forward_algorithm: Arc<dyn ForwardKinematicAlgorithm>,
     threshold: f64,
     max_iterations: usize,
+    step_size: f64,
+    proportional_approach: bool,
 }
 
 impl HeuristicSolverBuilder {
@@ -22,12 +24,15 @@ impl HeuristicSolverBuilder {
     ) -> Self {
         let threshold: f64 = 0.01;
         let max_iterations: usize = 200_usize;
+        let step_size: f64 = 1_f64;
 
         Self {
             inverse_algorithm,
             forward_algorithm,
             threshold,
             max_iterations,
+            step_size,
+            proportional_approach: false,
         }
     }
 
@@ -43,12 +48,33 @@ impl HeuristicSolverBuilder {
         self
     }
 
+    /// Set the factor the delta position is scaled with before it's fed to the inverse algorithm,
+    ///  a smaller step size takes more iterations but prevents overshooting the target.
+    pub fn with_step_size(mut self, step_size: f64) -> Self {
+        assert!(step_size > 0_f64);
+
+        self.step_size = step_size;
+
+        self
+    }
+
+    /// Scale the step down as the end-effector approaches the target, to half of it at the
+    ///  threshold, instead of always applying the full step. This prevents chattering around the
+    ///  threshold when the inverse algorithm overshoots.
+    pub fn with_proportional_approach(mut self, proportional_approach: bool) -> Self {
+        self.proportional_approach = proportional_approach;
+
+        self
+    }
+
     pub fn build(self) -> HeuristicSolver {
         HeuristicSolver::new(
             self.inverse_algorithm,
             self.forward_algorithm,
             self.threshold,
             self.max_iterations,
+            self.step_size,
+            self.proportional_approach,
         )
     }
 }
@@ -58,20 +84,30 @@ pub struct HeuristicSolver {
     forward_algorithm: Arc<dyn ForwardKinematicAlgorithm>,
     threshold: f64,
     max_iterations: usize,
+    step_size: f64,
+    proportional_approach: bool, // Whether the step is scaled down near the target.
 }
 
 impl HeuristicSolver {
+    /// The relative decrease of the delta position magnitude below which an iteration isn't
+    ///  considered progress (so rounding errors of an oscillation don't count).
+    pub const PROGRESS_TOLERANCE: f64 = 1e-6_f64;
+
     pub fn new(
         inverse_algorithm: Arc<dyn InverseKinematicAlgorithm>,
         forward_algorithm: Arc<dyn ForwardKinematicAlgorithm>,
         threshold: f64,
         max_iterations: usize,
+        step_size: f64,
+        proportional_approach: bool,
     ) -> Self {
         Self {
             inverse_algorithm,
             forward_algorithm,
             threshold,
             max_iterations,
+            step_size,
+            proportional_approach,
         }
     }
 
@@ -81,34 +117,98 @@ impl HeuristicSolver {
     ) -> HeuristicSolverBuilder {
         HeuristicSolverBuilder::new(inverse_algorithm, forward_algorithm)
     }
-}
 
-impl KinematicSolver for HeuristicSolver {
-    fn translate_limb4_end_effector(
+    /// Translate the end-effector like `translate_limb4_end_effector` does, but also return the
+    ///  magnitude of the delta position of every iteration, so it can be seen if the solver
+    ///  diverged, plateaued or simply ran out of iterations.
+    pub fn solve_with_trace(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        target_position: &Vector3<f64>,
+    ) -> Result<(IKSolverResult, Vec<f64>), KinematicError> {
+        let mut convergence_history: Vec<f64> = Vec::new();
+
+        let result = self.solve(
+            params,
+            state,
+            target_position,
+            &SolveOptions::default(),
+            Some(&mut convergence_history),
+        )?;
+
+        Ok((result, convergence_history))
+    }
+
+    /// Solve the translation of the end-effector, pushing the magnitude of the delta position of
+    ///  every iteration into the convergence history (if given). The given options override the
+    ///  threshold, maximum number of iterations and step size of the solver.
+    ///
+    /// The target is the position of the tool tip in world coordinates. Targets further away from
+    ///  the shoulder than the arm (and tool) is long are unreachable, a solution outside the joint
+    ///  limits results in `KinematicError::JointLimitViolated`. Running out of iterations results
+    ///  in `IKSolverResult::NotConverged` if the last iteration still got closer to the target, and
+    ///  in `IKSolverResult::Unreachable` if it didn't.
+    fn solve(
         &self,
         params: &KinematicParameters,
         state: &KinematicState,
         target_position: &Vector3<f64>,
+        options: &SolveOptions,
+        mut convergence_history: Option<&mut Vec<f64>>,
     ) -> Result<IKSolverResult, KinematicError> {
+        params.validate()?;
+
+        // The target is in meters, so the lengths of the arm must be as well.
+        let params: &KinematicParameters = &params.to_meters();
+
+        let threshold: f64 = options.threshold.unwrap_or(self.threshold);
+        let max_iterations: usize = options.max_iterations.unwrap_or(self.max_iterations);
+        let step_size: f64 = options.step_size.unwrap_or(self.step_size);
+
+        // Don't even bother iterating if the target is out of reach of the links after the base
+        //  (and the tool).
+        let shoulder_position: Vector3<f64> =
+            self.forward_algorithm.limb0_position_vector(params, state);
+        if (target_position - shoulder_position).magnitude()
+            > params.sum_of_link_lengths() - params.l_0
+                + params.tool_offset.translation.vector.magnitude()
+        {
+            return Ok(IKSolverResult::Unreachable);
+        }
+
         let mut iterations: usize = 0_usize;
 
+        // The magnitude of the delta position of the previous iteration.
+        let mut previous_delta_position_magnitude: f64 = f64::INFINITY;
+
         // We need a new kinematic state, since it will be modified during
         //  the solving process.
         let mut new_state: KinematicState = state.clone();
 
-        while iterations < self.max_iterations {
-            // Compute the current position using the forward kinematic algorithm.
+        while iterations < max_iterations {
+            // Compute the current position of the tool tip using the forward kinematic algorithm.
             let current_position: Vector3<f64> =
-                self.forward_algorithm.limb4_position_vector(params, &new_state);
+                self.forward_algorithm.limb4_tool_position_vector(params, &new_state);
 
             // Compute the difference between the current and target position, to
             //  know where we should move.
             let delta_position: Vector3<f64> = target_position - current_position;
 
+            // Record the magnitude of the delta position if we're tracing.
+            let delta_position_magnitude = delta_position.magnitude();
+            if let Some(convergence_history) = convergence_history.as_deref_mut() {
+                convergence_history.push(delta_position_magnitude);
+            }
+
             // If the magnitude of the delta position is lower than the threshold,
             //  the simply just exit, we've reached the target.
-            let delta_position_magnitude = delta_position.magnitude();
-            if delta_position_magnitude < self.threshold {
+            if delta_position_magnitude < threshold {
+                // Drop the whole turns the iterations might have accumulated, without wrapping the
+                //  joints away from where they started.
+                new_state.normalize_angles_near(state, params);
+                new_state.check_joint_limits(params)?;
+
                 return Ok(IKSolverResult::Reached {
                     iterations,
                     delta_position_magnitude,
@@ -116,19 +216,73 @@ impl KinematicSolver for HeuristicSolver {
                 });
             }
 
-            // Adjust the new state.
+            previous_delta_position_magnitude = delta_position_magnitude;
+
+            // Scale the step down when getting close to the target (if requested), the full step
+            //  is taken far away from it and half of it at the threshold.
+            let step_size: f64 = if self.proportional_approach {
+                step_size * delta_position_magnitude / (delta_position_magnitude + threshold)
+            } else {
+                step_size
+            };
+
+            // Adjust the new state, scaling the delta by the step size to prevent overshooting. The
+            //  target is in world coordinates, while the inverse algorithm works relative to the
+            //  base.
             new_state = self.inverse_algorithm.translate_limb4_end_effector(
                 params,
                 &new_state,
-                &delta_position,
+                &params.world_to_base_vector(&(delta_position * step_size)),
             )?;
 
             // Increase the iter variable.
             iterations += 1_usize;
         }
 
+        // Check if the last iteration still got us closer to the target, in which case more
+        //  iterations might help.
+        let current_position: Vector3<f64> =
+            self.forward_algorithm.limb4_tool_position_vector(params, &new_state);
+        let delta_position_magnitude = (target_position - current_position).magnitude();
+
+        if delta_position_magnitude
+            < previous_delta_position_magnitude * (1_f64 - Self::PROGRESS_TOLERANCE)
+        {
+            return Ok(IKSolverResult::NotConverged {
+                iterations,
+                delta_position_magnitude,
+            });
+        }
+
         Ok(IKSolverResult::Unreachable)
     }
+}
+
+impl KinematicSolver for HeuristicSolver {
+    fn translate_limb4_end_effector(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        target_position: &Vector3<f64>,
+    ) -> Result<IKSolverResult, KinematicError> {
+        self.solve(
+            params,
+            state,
+            target_position,
+            &SolveOptions::default(),
+            None,
+        )
+    }
+
+    fn translate_limb4_end_effector_with(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        target_position: &Vector3<f64>,
+        options: &SolveOptions,
+    ) -> Result<IKSolverResult, KinematicError> {
+        self.solve(params, state, target_position, options, None)
+    }
 
     fn rotate_limb4_end_effector(
         &self,
@@ -146,4 +300,415 @@ impl KinematicSolver for HeuristicSolver {
     fn forward_algorithm(&self) -> &Arc<dyn ForwardKinematicAlgorithm> {
         &self.forward_algorithm
     }
+
+    /// The heuristic solver iterates towards a single solution close to the given state, and
+    ///  can't rotate the end-effector.
+    fn capabilities(&self) -> SolverCapabilities {
+        SolverCapabilities {
+            supports_orientation: false,
+            supports_multiple_solutions: false,
+            is_exact: false,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Arc;
+
+    use nalgebra::{Isometry3, Matrix3, Vector3};
+
+    use crate::error::KinematicError;
+    use crate::forward::algorithms::{
+        analytical::AnalyticalFKAlgorithm, ForwardKinematicAlgorithm,
+    };
+    use crate::inverse::algorithms::{heuristic::HeuristicIKAlgorithm, InverseKinematicAlgorithm};
+    use crate::inverse::solvers::heuristic::HeuristicSolver;
+    use crate::inverse::solvers::{
+        IKSolverResult, KinematicSolver, SolveOptions, SolverCapabilities,
+    };
+    use crate::model::{KinematicParameters, KinematicState};
+
+    /// Forward algorithm that maps the first three joint angles directly onto the end-effector
+    ///  position.
+    struct LinearFKAlgorithm;
+
+    impl ForwardKinematicAlgorithm for LinearFKAlgorithm {
+        fn limb0_position_vector(
+            &self,
+            _: &KinematicParameters,
+            _: &KinematicState,
+        ) -> Vector3<f64> {
+            Vector3::<f64>::zeros()
+        }
+
+        fn limb1_position_vector(
+            &self,
+            _: &KinematicParameters,
+            _: &KinematicState,
+        ) -> Vector3<f64> {
+            Vector3::<f64>::zeros()
+        }
+
+        fn limb2_position_vector(
+            &self,
+            _: &KinematicParameters,
+            _: &KinematicState,
+        ) -> Vector3<f64> {
+            Vector3::<f64>::zeros()
+        }
+
+        fn limb3_position_vector(
+            &self,
+            _: &KinematicParameters,
+            _: &KinematicState,
+        ) -> Vector3<f64> {
+            Vector3::<f64>::zeros()
+        }
+
+        fn limb4_position_vector(
+            &self,
+            _: &KinematicParameters,
+            state: &KinematicState,
+        ) -> Vector3<f64> {
+            Vector3::<f64>::new(state.theta_0, state.theta_1, state.theta_2)
+        }
+
+        fn limb4_euler_angles(&self, _: &KinematicParameters, _: &KinematicState) -> Vector3<f64> {
+            Vector3::<f64>::zeros()
+        }
+
+        fn limb4_orientation_matrix(
+            &self,
+            _: &KinematicParameters,
+            _: &KinematicState,
+        ) -> Matrix3<f64> {
+            Matrix3::<f64>::identity()
+        }
+    }
+
+    /// Inverse algorithm that always moves twice as far as requested, so applying the full delta
+    ///  jumps back and forth around the target.
+    struct OvershootingIKAlgorithm;
+
+    impl InverseKinematicAlgorithm for OvershootingIKAlgorithm {
+        fn translate_limb4_end_effector(
+            &self,
+            _: &KinematicParameters,
+            state: &KinematicState,
+            delta: &Vector3<f64>,
+        ) -> Result<KinematicState, KinematicError> {
+            let mut new_state = state.clone();
+            new_state.theta_0 += 2_f64 * delta.x;
+            new_state.theta_1 += 2_f64 * delta.y;
+            new_state.theta_2 += 2_f64 * delta.z;
+
+            Ok(new_state)
+        }
+
+        fn rotate_limb4_end_effector(
+            &self,
+            _: &KinematicParameters,
+            state: &KinematicState,
+            _: &Vector3<f64>,
+        ) -> Result<KinematicState, KinematicError> {
+            Ok(state.clone())
+        }
+    }
+
+    fn solve(step_size: f64) -> Result<IKSolverResult, KinematicError> {
+        let solver = HeuristicSolver::builder(
+            Arc::new(OvershootingIKAlgorithm),
+            Arc::new(LinearFKAlgorithm),
+        )
+        .with_step_size(step_size)
+        .build();
+
+        solver.translate_limb4_end_effector(
+            &KinematicParameters::default(),
+            &KinematicState::default(),
+            &Vector3::<f64>::new(1_f64, 2_f64, 3_f64),
+        )
+    }
+
+    #[test]
+    pub fn convergence_history_non_increasing() {
+        // The overshooting algorithm doubles the delta, so this halves the error every iteration.
+        let solver = HeuristicSolver::builder(
+            Arc::new(OvershootingIKAlgorithm),
+            Arc::new(LinearFKAlgorithm),
+        )
+        .with_step_size(0.25_f64)
+        .build();
+
+        let (result, convergence_history) = solver
+            .solve_with_trace(
+                &KinematicParameters::default(),
+                &KinematicState::default(),
+                &Vector3::<f64>::new(1_f64, 2_f64, 3_f64),
+            )
+            .unwrap();
+
+        assert!(result.is_reached());
+        assert!(convergence_history.len() > 1_usize);
+        assert!(convergence_history.windows(2).all(|x| x[1] <= x[0]));
+    }
+
+    #[test]
+    pub fn capabilities() {
+        let solver = HeuristicSolver::builder(
+            Arc::new(HeuristicIKAlgorithm::default()),
+            Arc::new(AnalyticalFKAlgorithm::default()),
+        )
+        .build();
+
+        assert_eq!(solver.capabilities(), SolverCapabilities::default());
+        assert!(!solver.capabilities().supports_orientation);
+        assert!(!solver.capabilities().is_exact);
+    }
+
+    #[test]
+    pub fn proportional_approach_settles_borderline_target() {
+        let solve = |proportional_approach: bool| {
+            let solver = HeuristicSolver::builder(
+                Arc::new(OvershootingIKAlgorithm),
+                Arc::new(LinearFKAlgorithm),
+            )
+            .with_threshold(0.01_f64)
+            .with_max_iterations(50_usize)
+            .with_proportional_approach(proportional_approach)
+            .build();
+
+            let state = KinematicState {
+                theta_0: 0_f64,
+                theta_1: 0_f64,
+                theta_2: 0_f64,
+                theta_3: 0_f64,
+                theta_4: 0_f64,
+            };
+
+            solver.translate_limb4_end_effector(
+                &KinematicParameters::default(),
+                &state,
+                &Vector3::<f64>::new(0.015_f64, 0_f64, 0_f64),
+            )
+        };
+
+        // Just outside the threshold, the full step jumps back and forth around the target.
+        assert!(matches!(solve(false), Ok(IKSolverResult::Unreachable)));
+
+        // The smaller step near the target settles it right away.
+        let Ok(IKSolverResult::Reached {
+            iterations,
+            delta_position_magnitude,
+            ..
+        }) = solve(true)
+        else {
+            panic!("Expected the target to be reached");
+        };
+        assert_eq!(iterations, 1_usize);
+        assert!(delta_position_magnitude < 0.005_f64);
+    }
+
+    #[test]
+    pub fn full_step_oscillates() {
+        assert!(matches!(solve(1_f64), Ok(IKSolverResult::Unreachable)));
+    }
+
+    #[test]
+    pub fn slow_convergence_not_converged() {
+        // A small step size gets closer every iteration, but needs more than five of them.
+        let solver = HeuristicSolver::builder(
+            Arc::new(OvershootingIKAlgorithm),
+            Arc::new(LinearFKAlgorithm),
+        )
+        .with_step_size(0.05_f64)
+        .with_max_iterations(5_usize)
+        .build();
+
+        let result = solver
+            .translate_limb4_end_effector(
+                &KinematicParameters::default(),
+                &KinematicState::default(),
+                &Vector3::<f64>::new(1_f64, 2_f64, 3_f64),
+            )
+            .unwrap();
+
+        assert!(matches!(
+            result,
+            IKSolverResult::NotConverged { iterations: 5, delta_position_magnitude }
+                if delta_position_magnitude > 0.01_f64
+        ));
+    }
+
+    #[test]
+    pub fn finer_threshold_takes_more_iterations() {
+        let solver = HeuristicSolver::builder(
+            Arc::new(OvershootingIKAlgorithm),
+            Arc::new(LinearFKAlgorithm),
+        )
+        .with_step_size(0.25_f64)
+        .build();
+
+        let iterations = |threshold: f64| {
+            let result = solver
+                .translate_limb4_end_effector_with(
+                    &KinematicParameters::default(),
+                    &KinematicState::default(),
+                    &Vector3::<f64>::new(1_f64, 2_f64, 3_f64),
+                    &SolveOptions::default().with_threshold(threshold),
+                )
+                .unwrap();
+
+            match result {
+                IKSolverResult::Reached { iterations, .. } => iterations,
+                _ => panic!("Expected the target to be reached"),
+            }
+        };
+
+        assert!(iterations(1e-6_f64) > iterations(0.1_f64));
+    }
+
+    #[test]
+    pub fn half_step_converges() {
+        assert!(solve(0.5_f64).unwrap().is_reached());
+    }
+
+    #[test]
+    pub fn target_out_of_reach() {
+        let solver = HeuristicSolver::builder(
+            Arc::new(OvershootingIKAlgorithm),
+            Arc::new(LinearFKAlgorithm),
+        )
+        .build();
+
+        // The links after the base are 40 long with the default parameters.
+        let result = solver
+            .translate_limb4_end_effector(
+                &KinematicParameters::default(),
+                &KinematicState::default(),
+                &Vector3::<f64>::new(0_f64, 41_f64, 0_f64),
+            )
+            .unwrap();
+
+        assert!(!result.is_reached());
+    }
+
+    #[test]
+    pub fn solution_outside_joint_limits() {
+        let solver = HeuristicSolver::builder(
+            Arc::new(OvershootingIKAlgorithm),
+            Arc::new(LinearFKAlgorithm),
+        )
+        .with_step_size(0.5_f64)
+        .build();
+
+        // Limit the base joint, so it can't rotate half a turn either way.
+        let mut params: KinematicParameters = KinematicParameters::default();
+        params.joint_limits[0] = [-1.5_f64, 1.5_f64];
+
+        // The linear algorithm needs theta 0 to be 2, which is beyond the limit (and stays there
+        //  when the angles are normalized).
+        assert!(matches!(
+            solver.translate_limb4_end_effector(
+                &params,
+                &KinematicState::default(),
+                &Vector3::<f64>::new(2_f64, 0_f64, 0_f64),
+            ),
+            Err(KinematicError::JointLimitViolated { joint: 0, .. })
+        ));
+    }
+
+    #[test]
+    pub fn invalid_parameters() {
+        let solver = HeuristicSolver::builder(
+            Arc::new(OvershootingIKAlgorithm),
+            Arc::new(LinearFKAlgorithm),
+        )
+        .build();
+
+        let params = KinematicParameters {
+            l_1: 0_f64,
+            ..KinematicParameters::default()
+        };
+
+        assert!(matches!(
+            solver.translate_limb4_end_effector(
+                &params,
+                &KinematicState::default(),
+                &Vector3::<f64>::zeros(),
+            ),
+            Err(KinematicError::InvalidParameters(_))
+        ));
+    }
+
+    #[test]
+    pub fn target_in_world_coordinates() {
+        let forward_algorithm: Arc<dyn ForwardKinematicAlgorithm> =
+            Arc::new(AnalyticalFKAlgorithm::default());
+        let solver = HeuristicSolver::builder(
+            Arc::new(HeuristicIKAlgorithm::default()),
+            forward_algorithm.clone(),
+        )
+        .build();
+
+        // Turn the base a quarter turn around the vertical axis, and move it aside.
+        let params: KinematicParameters = KinematicParameters {
+            base_transform: Isometry3::<f64>::new(
+                Vector3::<f64>::new(5_f64, 0_f64, 0_f64),
+                Vector3::<f64>::y() * std::f64::consts::FRAC_PI_2,
+            ),
+            ..KinematicParameters::default()
+        };
+
+        // Aim for where the ready pose puts the end-effector, which is in world coordinates.
+        let target_position =
+            forward_algorithm.limb4_position_vector(&params, &KinematicState::ready());
+
+        let result = solver
+            .translate_limb4_end_effector(&params, &KinematicState::default(), &target_position)
+            .unwrap();
+        let new_state = result.new_state().unwrap();
+
+        assert!(
+            (forward_algorithm.limb4_position_vector(&params, new_state) - target_position).norm()
+                < 0.01_f64
+        );
+    }
+
+    #[test]
+    pub fn targets_tool_tip() {
+        let forward_algorithm: Arc<dyn ForwardKinematicAlgorithm> =
+            Arc::new(AnalyticalFKAlgorithm::default());
+        let solver = HeuristicSolver::builder(
+            Arc::new(HeuristicIKAlgorithm::default()),
+            forward_algorithm.clone(),
+        )
+        .build();
+
+        // Mount a gripper that sticks out a meter from the flange.
+        let params: KinematicParameters = KinematicParameters {
+            tool_offset: Isometry3::<f64>::translation(0_f64, 1_f64, 0_f64),
+            ..KinematicParameters::default()
+        };
+
+        let target_position =
+            forward_algorithm.limb4_tool_position_vector(&params, &KinematicState::ready());
+
+        let result = solver
+            .translate_limb4_end_effector(&params, &KinematicState::default(), &target_position)
+            .unwrap();
+        let new_state = result.new_state().unwrap();
+
+        // The tool tip ends up at the target, so the flange doesn't.
+        assert!(
+            (forward_algorithm.limb4_tool_position_vector(&params, new_state) - target_position)
+                .norm()
+                < 0.01_f64
+        );
+        assert!(
+            (forward_algorithm.limb4_position_vector(&params, new_state) - target_position).norm()
+                > 0.5_f64
+        );
+    }
 }
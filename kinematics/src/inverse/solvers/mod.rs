@@ -1,11 +1,11 @@
 use std::sync::Arc;
 
-use nalgebra::Vector3;
+use nalgebra::{Vector3, Vector5};
 use serde::Serialize;
 
 use crate::{
     error::KinematicError,
-    forward::algorithms::ForwardKinematicAlgorithm,
+    forward::algorithms::{compute_center_of_mass, ForwardKinematicAlgorithm},
     model::{KinematicParameters, KinematicState},
 };
 
@@ -16,6 +16,12 @@ pub mod heuristic;
 #[derive(Serialize)]
 pub enum IKSolverResult {
     Unreachable,
+    /// The iterations ran out while the solver was still getting closer to the target, so more
+    ///  iterations might still reach it.
+    NotConverged {
+        iterations: usize,
+        delta_position_magnitude: f64,
+    },
     Reached {
         iterations: usize,
         delta_position_magnitude: f64,
@@ -23,6 +29,79 @@ pub enum IKSolverResult {
     },
 }
 
+impl IKSolverResult {
+    /// Check if the target has been reached.
+    #[inline]
+    pub fn is_reached(&self) -> bool {
+        matches!(self, IKSolverResult::Reached { .. })
+    }
+
+    /// Get the new kinematic state if the target has been reached.
+    #[inline]
+    pub fn new_state(&self) -> Option<&KinematicState> {
+        match self {
+            IKSolverResult::Reached { new_state, .. } => Some(new_state),
+            IKSolverResult::Unreachable | IKSolverResult::NotConverged { .. } => None,
+        }
+    }
+
+    /// Convert the result into the new kinematic state, or `KinematicError::Unreachable` if the
+    ///  target has not been reached (`KinematicError::MaxIterationsExceeded` if it didn't converge).
+    pub fn into_result(self) -> Result<KinematicState, KinematicError> {
+        match self {
+            IKSolverResult::Reached { new_state, .. } => Ok(new_state),
+            IKSolverResult::Unreachable => Err(KinematicError::Unreachable),
+            IKSolverResult::NotConverged { iterations, .. } => {
+                Err(KinematicError::MaxIterationsExceeded { iterations })
+            }
+        }
+    }
+}
+
+/// This struct holds the options that override the defaults of a solver for a single call, so
+///  coarse jogs and fine placements can share the same solver.
+#[derive(Debug, Clone, Default)]
+pub struct SolveOptions {
+    pub threshold: Option<f64>, // The distance to the target that counts as reached.
+    pub max_iterations: Option<usize>, // The maximum number of iterations.
+    pub step_size: Option<f64>, // The factor the delta position is scaled with.
+}
+
+impl SolveOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = Some(threshold);
+
+        self
+    }
+
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = Some(max_iterations);
+
+        self
+    }
+
+    pub fn with_step_size(mut self, step_size: f64) -> Self {
+        assert!(step_size > 0_f64);
+
+        self.step_size = Some(step_size);
+
+        self
+    }
+}
+
+/// This struct describes what a solver is capable of, so callers (e.g. the frontend) can tell
+///  which operations make sense for the solver that's in use.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SolverCapabilities {
+    pub supports_orientation: bool, // Whether the end-effector can be rotated.
+    pub supports_multiple_solutions: bool, // Whether all solutions for a target are found.
+    pub is_exact: bool,             // Whether the solutions are exact, not iterated.
+}
+
 pub trait KinematicSolver: Send + Sync {
     /// Translate the end-effector position of the fourth link.
     fn translate_limb4_end_effector(
@@ -32,6 +111,32 @@ pub trait KinematicSolver: Send + Sync {
         target_position: &Vector3<f64>,
     ) -> Result<IKSolverResult, KinematicError>;
 
+    /// Translate the end-effector position of the fourth link, overriding the defaults of the
+    ///  solver with the given options. Solvers without any of these settings ignore them.
+    fn translate_limb4_end_effector_with(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        target_position: &Vector3<f64>,
+        _options: &SolveOptions,
+    ) -> Result<IKSolverResult, KinematicError> {
+        self.translate_limb4_end_effector(params, state, target_position)
+    }
+
+    /// Find the solutions that translate the end-effector of the fourth link to the given target
+    ///  position, none if it can't be reached. Solvers that iterate towards a single solution
+    ///  return just that one, or the error explaining why it wasn't reached.
+    fn translate_limb4_end_effector_solutions(
+        &self,
+        params: &KinematicParameters,
+        state: &KinematicState,
+        target_position: &Vector3<f64>,
+    ) -> Result<Vec<KinematicState>, KinematicError> {
+        Ok(vec![self
+            .translate_limb4_end_effector(params, state, target_position)?
+            .into_result()?])
+    }
+
     /// Rotate the end-effector of the fourth-link.
     fn rotate_limb4_end_effector(
         &self,
@@ -43,4 +148,135 @@ pub trait KinematicSolver: Send + Sync {
     fn inverse_algorithm(&self) -> &Arc<dyn InverseKinematicAlgorithm>;
 
     fn forward_algorithm(&self) -> &Arc<dyn ForwardKinematicAlgorithm>;
+
+    /// Get the capabilities of the solver.
+    fn capabilities(&self) -> SolverCapabilities;
+}
+
+/// The joint-space distance (in radians) within which solutions are considered equally close.
+pub const SOLUTION_DISTANCE_TOLERANCE: f64 = 1e-6_f64;
+
+/// Select the solution closest (in joint-space) to the current state out of the given candidate
+///  solutions for the same target.
+///
+/// When `prefer_gravity_assisted` is set, the solution with the lowest center of mass is chosen
+///  among the ones that are equally close, so the arm moves with gravity rather than against it.
+pub fn select_solution<'a>(
+    algorithm: &Arc<dyn ForwardKinematicAlgorithm>,
+    params: &KinematicParameters,
+    state: &KinematicState,
+    candidates: &'a [KinematicState],
+    prefer_gravity_assisted: bool,
+) -> Option<&'a KinematicState> {
+    let distance = |candidate: &KinematicState| {
+        (Vector5::<f64>::from(candidate) - Vector5::<f64>::from(state)).norm()
+    };
+
+    let min_distance = candidates
+        .iter()
+        .map(distance)
+        .min_by(|a, b| a.total_cmp(b))?;
+
+    let closest = candidates
+        .iter()
+        .filter(|candidate| distance(candidate) - min_distance <= SOLUTION_DISTANCE_TOLERANCE);
+
+    match prefer_gravity_assisted {
+        true => closest.min_by(|a, b| {
+            let a = compute_center_of_mass(algorithm, params, a).y;
+            let b = compute_center_of_mass(algorithm, params, b).y;
+
+            a.total_cmp(&b)
+        }),
+        false => closest.min_by(|a, b| distance(a).total_cmp(&distance(b))),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Arc;
+
+    use crate::error::KinematicError;
+    use crate::forward::algorithms::analytical::AnalyticalFKAlgorithm;
+    use crate::forward::algorithms::ForwardKinematicAlgorithm;
+    use crate::inverse::solvers::{select_solution, IKSolverResult};
+    use crate::model::{KinematicParameters, KinematicState};
+
+    fn reached() -> IKSolverResult {
+        IKSolverResult::Reached {
+            iterations: 1_usize,
+            delta_position_magnitude: 0_f64,
+            new_state: KinematicState::default(),
+        }
+    }
+
+    #[test]
+    pub fn is_reached() {
+        assert!(reached().is_reached());
+        assert!(!IKSolverResult::Unreachable.is_reached());
+    }
+
+    #[test]
+    pub fn new_state() {
+        assert_eq!(
+            reached().new_state().map(|x| x.theta_0),
+            Some(KinematicState::default().theta_0)
+        );
+        assert!(IKSolverResult::Unreachable.new_state().is_none());
+    }
+
+    #[test]
+    pub fn into_result() {
+        assert!(reached().into_result().is_ok());
+        assert!(matches!(
+            IKSolverResult::Unreachable.into_result(),
+            Err(KinematicError::Unreachable)
+        ));
+        assert!(matches!(
+            IKSolverResult::NotConverged {
+                iterations: 5_usize,
+                delta_position_magnitude: 1_f64,
+            }
+            .into_result(),
+            Err(KinematicError::MaxIterationsExceeded { iterations: 5 })
+        ));
+    }
+
+    #[test]
+    pub fn select_gravity_assisted_solution() {
+        let params = KinematicParameters::default();
+        let algorithm: Arc<dyn ForwardKinematicAlgorithm> =
+            Arc::new(AnalyticalFKAlgorithm::default());
+
+        // The current state has the arm straight up, both candidates lean the shoulder forwards
+        //  and bend the elbow equally far, either back up or further down.
+        let state = KinematicState {
+            theta_0: 0_f64,
+            theta_1: 0_f64,
+            theta_2: 0_f64,
+            theta_3: 0_f64,
+            theta_4: 0_f64,
+        };
+        let raised = KinematicState {
+            theta_1: 0.5_f64,
+            theta_2: -0.5_f64,
+            ..state.clone()
+        };
+        let lowered = KinematicState {
+            theta_1: 0.5_f64,
+            theta_2: 0.5_f64,
+            ..state.clone()
+        };
+        let candidates = [raised, lowered];
+
+        // Without the preference the first of the equally close solutions is chosen.
+        let selected = select_solution(&algorithm, &params, &state, &candidates, false).unwrap();
+        assert!(selected.theta_2 < 0_f64);
+
+        // With the preference the solution lowering the center of mass is chosen.
+        let selected = select_solution(&algorithm, &params, &state, &candidates, true).unwrap();
+        assert!(selected.theta_2 > 0_f64);
+
+        assert!(select_solution(&algorithm, &params, &state, &[], true).is_none());
+    }
 }
@@ -1,19 +1,263 @@
-use nalgebra::Vector5;
+use std::{
+    f64::consts::{PI, TAU},
+    fmt::{self, Display},
+    ops::{Index, IndexMut},
+};
+
+use nalgebra::{Isometry3, Point3, Vector3, Vector5};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+use crate::error::KinematicError;
+
+/// This enum represents the unit the lengths of the kinematic parameters are expressed in.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LengthUnit {
+    #[default]
+    Meters,
+    Millimeters,
+}
+
+impl LengthUnit {
+    /// Get the number of meters in one unit.
+    #[inline]
+    pub const fn meters_per_unit(self) -> f64 {
+        match self {
+            LengthUnit::Meters => 1_f64,
+            LengthUnit::Millimeters => 1e-3_f64,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct KinematicParameters {
     pub l_0: f64,
     pub l_1: f64,
     pub l_2: f64,
     pub l_3: f64,
     pub l_4: f64,
+    /// The maximum velocity of each joint (in radians/second).
+    #[serde(default = "KinematicParameters::default_joint_velocity_limits")]
+    pub joint_velocity_limits: [f64; 5],
+    /// The approximate mass of each link (in kilograms).
+    #[serde(default = "KinematicParameters::default_link_masses")]
+    pub link_masses: [f64; 5],
+    /// The minimum and maximum angle of each joint (in radians).
+    #[serde(default = "KinematicParameters::default_joint_limits")]
+    pub joint_limits: [[f64; 2]; 5],
+    /// The minimum and maximum angle of each joint before a warning is given (in radians), these
+    ///  should lie within the joint limits.
+    #[serde(default)]
+    pub soft_joint_limits: Option<[[f64; 2]; 5]>,
+    /// The pose of the base in world coordinates, for an arm that's mounted tilted or moved
+    ///  away from the origin.
+    #[serde(default = "Isometry3::identity")]
+    pub base_transform: Isometry3<f64>,
+    /// The pose of the tool tip relative to the flange at the end of the last limb, this is the
+    ///  identity if no tool is mounted.
+    #[serde(default = "Isometry3::identity")]
+    pub tool_offset: Isometry3<f64>,
+    /// The unit of the link lengths and of the translations of the base transform and tool
+    ///  offset, the solvers normalize these to meters since that's what targets are given in.
+    #[serde(default)]
+    pub length_unit: LengthUnit,
 }
 
 impl KinematicParameters {
+    /// Get the lengths of the links, ordered like `Link::ALL`.
+    #[inline]
+    pub fn link_lengths(&self) -> [f64; 5] {
+        [self.l_0, self.l_1, self.l_2, self.l_3, self.l_4]
+    }
+
+    /// Get the number of links of the arm.
+    #[inline]
+    pub fn link_count(&self) -> usize {
+        self.link_lengths().len()
+    }
+
+    /// The joint velocity limits used when none are configured.
+    fn default_joint_velocity_limits() -> [f64; 5] {
+        [2_f64 * PI; 5]
+    }
+
+    /// The link masses used when none are configured.
+    fn default_link_masses() -> [f64; 5] {
+        [0.1_f64; 5]
+    }
+
+    /// The joint limits used when none are configured, which is unbounded so arms configured
+    ///  before joint limits existed behave like they did.
+    fn default_joint_limits() -> [[f64; 2]; 5] {
+        [[f64::NEG_INFINITY, f64::INFINITY]; 5]
+    }
+
+    /// Check that the parameters describe a physically possible arm, so the link lengths, joint
+    ///  velocity limits must be finite and positive, the link masses finite and not negative (and not
+    ///  all zero), and the joint limits ordered.
+    pub fn validate(&self) -> Result<(), KinematicError> {
+        let link_lengths = self.link_lengths();
+
+        if let Some(i) = link_lengths
+            .iter()
+            .position(|x| !(x.is_finite() && *x > 0_f64))
+        {
+            return Err(KinematicError::InvalidParameters(format!(
+                "Length of link {} must be finite and positive, got {}",
+                i, link_lengths[i]
+            )));
+        }
+
+        if let Some(i) = self
+            .joint_velocity_limits
+            .iter()
+            .position(|x| !(x.is_finite() && *x > 0_f64))
+        {
+            return Err(KinematicError::InvalidParameters(format!(
+                "Velocity limit of joint {} must be finite and positive, got {}",
+                i, self.joint_velocity_limits[i]
+            )));
+        }
+
+        if let Some(i) = self
+            .link_masses
+            .iter()
+            .position(|x| !(x.is_finite() && *x >= 0_f64))
+        {
+            return Err(KinematicError::InvalidParameters(format!(
+                "Mass of link {} must be finite and not negative, got {}",
+                i, self.link_masses[i]
+            )));
+        }
+
+        // Links without mass are fine, but the center of mass of an arm without any is undefined.
+        if self.link_masses.iter().sum::<f64>() <= 0_f64 {
+            return Err(KinematicError::InvalidParameters(
+                "At least one link must have a mass".to_string(),
+            ));
+        }
+
+        if let Some(i) = self
+            .joint_limits
+            .iter()
+            .position(|[min, max]| min.is_nan() || max.is_nan() || min > max)
+        {
+            return Err(KinematicError::InvalidParameters(format!(
+                "Limits of joint {} must be ordered, got {:?}",
+                i, self.joint_limits[i]
+            )));
+        }
+
+        if let Some(soft_joint_limits) = &self.soft_joint_limits {
+            if let Some(i) = soft_joint_limits
+                .iter()
+                .zip(self.joint_limits.iter())
+                .position(|([soft_min, soft_max], [min, max])| {
+                    !(min <= soft_min && soft_min <= soft_max && soft_max <= max)
+                })
+            {
+                return Err(KinematicError::InvalidParameters(format!(
+                    "Soft limits of joint {} must be ordered and within its limits, got {:?}",
+                    i, soft_joint_limits[i]
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the same parameters with all the lengths converted into meters.
+    pub fn to_meters(&self) -> KinematicParameters {
+        let scale = self.length_unit.meters_per_unit();
+
+        let mut params = self.clone();
+        params.l_0 *= scale;
+        params.l_1 *= scale;
+        params.l_2 *= scale;
+        params.l_3 *= scale;
+        params.l_4 *= scale;
+        params.base_transform.translation.vector *= scale;
+        params.tool_offset.translation.vector *= scale;
+        params.length_unit = LengthUnit::Meters;
+
+        params
+    }
+
+    /// Check if the given joint is continuous, meaning it has no limits either way so it can turn
+    ///  around any number of times.
+    pub fn is_continuous(&self, joint: Joint) -> bool {
+        self.joint_limits[joint.index()] == [f64::NEG_INFINITY, f64::INFINITY]
+    }
+
     /// Compute the sum of all the link lengths.
     pub fn sum_of_link_lengths(&self) -> f64 {
-        self.l_0 + self.l_1 + self.l_2 + self.l_3 + self.l_4
+        self.link_lengths().iter().sum()
+    }
+
+    /// Transform the given position from base coordinates into world coordinates.
+    pub fn base_to_world(&self, position: &Vector3<f64>) -> Vector3<f64> {
+        self.base_transform
+            .transform_point(&Point3::from(*position))
+            .coords
+    }
+
+    /// Transform the given position from world coordinates into base coordinates.
+    pub fn world_to_base(&self, position: &Vector3<f64>) -> Vector3<f64> {
+        self.base_transform
+            .inverse_transform_point(&Point3::from(*position))
+            .coords
+    }
+
+    /// Transform the given vector (a displacement, not a position) from world coordinates into
+    ///  base coordinates.
+    pub fn world_to_base_vector(&self, vector: &Vector3<f64>) -> Vector3<f64> {
+        self.base_transform.inverse_transform_vector(vector)
+    }
+
+    /// Check if all the values are within `epsilon` of the ones of the other parameters, the
+    ///  rotations of the transforms are compared by the angle between them (in radians). A NaN
+    ///  value is never considered equal, and neither are soft limits that only one of them has or
+    ///  parameters in different length units.
+    pub fn approx_eq(&self, other: &KinematicParameters, epsilon: f64) -> bool {
+        // Equal infinities (of unbounded joint limits) are close, even though their difference
+        //  isn't.
+        let close = |a: &f64, b: &f64| a == b || (a - b).abs() <= epsilon;
+        let close_isometry = |a: &Isometry3<f64>, b: &Isometry3<f64>| {
+            (a.translation.vector - b.translation.vector)
+                .iter()
+                .all(|x| x.abs() <= epsilon)
+                && a.rotation.angle_to(&b.rotation) <= epsilon
+        };
+
+        let soft_joint_limits_close = match (&self.soft_joint_limits, &other.soft_joint_limits) {
+            (Some(a), Some(b)) => a
+                .iter()
+                .flatten()
+                .zip(b.iter().flatten())
+                .all(|(a, b)| close(a, b)),
+            (None, None) => true,
+            _ => false,
+        };
+
+        [self.l_0, self.l_1, self.l_2, self.l_3, self.l_4]
+            .iter()
+            .zip([other.l_0, other.l_1, other.l_2, other.l_3, other.l_4].iter())
+            .chain(
+                self.joint_velocity_limits
+                    .iter()
+                    .zip(other.joint_velocity_limits.iter()),
+            )
+            .chain(self.link_masses.iter().zip(other.link_masses.iter()))
+            .chain(
+                self.joint_limits
+                    .iter()
+                    .flatten()
+                    .zip(other.joint_limits.iter().flatten()),
+            )
+            .all(|(a, b)| close(a, b))
+            && soft_joint_limits_close
+            && close_isometry(&self.base_transform, &other.base_transform)
+            && close_isometry(&self.tool_offset, &other.tool_offset)
+            && self.length_unit == other.length_unit
     }
 }
 
@@ -25,11 +269,99 @@ impl Default for KinematicParameters {
             l_2: 10_f64,
             l_3: 10_f64,
             l_4: 10_f64,
+            joint_velocity_limits: Self::default_joint_velocity_limits(),
+            link_masses: Self::default_link_masses(),
+            joint_limits: Self::default_joint_limits(),
+            soft_joint_limits: None,
+            base_transform: Isometry3::identity(),
+            tool_offset: Isometry3::identity(),
+            length_unit: LengthUnit::default(),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// This enum represents the joints of the arm, ordered from the base up to the end-effector, so
+///  the angles of a state can be indexed by name instead of by a raw integer.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Joint {
+    Base,       // Rotates the whole arm around the vertical axis.
+    Shoulder,   // Pitches the first link.
+    Elbow,      // Pitches the second link.
+    WristPitch, // Pitches the third link.
+    WristRoll,  // Rolls the end-effector.
+}
+
+impl Joint {
+    /// All the joints, ordered from the base up to the end-effector.
+    pub const ALL: [Joint; 5] = [
+        Joint::Base,
+        Joint::Shoulder,
+        Joint::Elbow,
+        Joint::WristPitch,
+        Joint::WristRoll,
+    ];
+
+    /// Get the index of the joint in the angle arrays.
+    #[inline]
+    pub const fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Get the joint with the given index, `None` if there's no such joint.
+    pub fn from_index(index: usize) -> Option<Self> {
+        Self::ALL.get(index).copied()
+    }
+}
+
+impl Display for Joint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Joint::Base => "base",
+            Joint::Shoulder => "shoulder",
+            Joint::Elbow => "elbow",
+            Joint::WristPitch => "wrist pitch",
+            Joint::WristRoll => "wrist roll",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// This enum represents the links of the arm, ordered from the base up to the end-effector. Every
+///  link is moved by the joint with the same index.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Link {
+    Column,   // Stands on the base, the shoulder sits on top of it.
+    UpperArm, // Runs from the shoulder to the elbow.
+    Forearm,  // Runs from the elbow to the wrist.
+    Wrist,    // Runs from the wrist pitch joint to the wrist roll joint.
+    Hand,     // Ends in the flange the tool is mounted on.
+}
+
+impl Link {
+    /// All the links, ordered from the base up to the end-effector.
+    pub const ALL: [Link; 5] = [
+        Link::Column,
+        Link::UpperArm,
+        Link::Forearm,
+        Link::Wrist,
+        Link::Hand,
+    ];
+
+    /// Get the index of the link in the link arrays.
+    #[inline]
+    pub const fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Get the length of the link.
+    #[inline]
+    pub fn length(self, params: &KinematicParameters) -> f64 {
+        params.link_lengths()[self.index()]
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct KinematicState {
     pub theta_0: f64,
     pub theta_1: f64,
@@ -50,6 +382,255 @@ impl Default for KinematicState {
     }
 }
 
+impl KinematicState {
+    /// Create a state with the given angles (in radians), ordered like `Joint::ALL`.
+    pub fn from_angles(angles: [f64; 5]) -> Self {
+        Self {
+            theta_0: angles[0_usize],
+            theta_1: angles[1_usize],
+            theta_2: angles[2_usize],
+            theta_3: angles[3_usize],
+            theta_4: angles[4_usize],
+        }
+    }
+
+    /// Get a neutral pose that's safe to start from, the shoulder is leaning slightly forward and
+    ///  the elbow and wrist are bent, so the arm is far away from the (singular) straight-up pose
+    ///  and the end-effector can move in every direction.
+    pub fn ready() -> Self {
+        Self {
+            theta_0: 0_f64,
+            theta_1: 0.3_f64,
+            theta_2: 0.8_f64,
+            theta_3: 0.4_f64,
+            theta_4: 0_f64,
+        }
+    }
+
+    /// Get the angle of the base joint (in radians).
+    #[inline]
+    pub fn base(&self) -> f64 {
+        self.theta_0
+    }
+
+    /// Get the angle of the shoulder joint (in radians).
+    #[inline]
+    pub fn shoulder(&self) -> f64 {
+        self.theta_1
+    }
+
+    /// Get the angle of the elbow joint (in radians).
+    #[inline]
+    pub fn elbow(&self) -> f64 {
+        self.theta_2
+    }
+
+    /// Get the angle of the wrist pitch joint (in radians).
+    #[inline]
+    pub fn wrist_pitch(&self) -> f64 {
+        self.theta_3
+    }
+
+    /// Get the angle of the wrist roll joint (in radians).
+    #[inline]
+    pub fn wrist_roll(&self) -> f64 {
+        self.theta_4
+    }
+
+    #[inline]
+    pub fn set_base(&mut self, angle: f64) {
+        self.theta_0 = angle;
+    }
+
+    #[inline]
+    pub fn set_shoulder(&mut self, angle: f64) {
+        self.theta_1 = angle;
+    }
+
+    #[inline]
+    pub fn set_elbow(&mut self, angle: f64) {
+        self.theta_2 = angle;
+    }
+
+    #[inline]
+    pub fn set_wrist_pitch(&mut self, angle: f64) {
+        self.theta_3 = angle;
+    }
+
+    #[inline]
+    pub fn set_wrist_roll(&mut self, angle: f64) {
+        self.theta_4 = angle;
+    }
+
+    /// Set the angle of the given joint (in radians).
+    #[inline]
+    pub fn set_angle(&mut self, joint: Joint, angle: f64) {
+        self[joint] = angle;
+    }
+
+    /// Get the angles of all the joints, ordered like `Joint::ALL`.
+    pub fn angles(&self) -> [f64; 5] {
+        Joint::ALL.map(|joint| self[joint])
+    }
+
+    /// Wrap the angle of each joint into $(-\pi, \pi]$. All the joints are revolute, so adding or
+    ///  removing whole turns doesn't change the pose, it only keeps the angles comparable.
+    pub fn normalize_angles(&mut self) {
+        for joint in Joint::ALL {
+            let angle = self[joint];
+
+            self[joint] = angle - TAU * ((angle - PI) / TAU).ceil();
+        }
+    }
+
+    /// Replace the angle of each joint by the equivalent one (differing by whole turns) that's
+    ///  nearest to the angle of the given seed, while staying within the joint limits if any
+    ///  equivalent does. This drops the turns an iterative solver might have accumulated without
+    ///  making the joint jump across the $\pm\pi$ boundary the way wrapping would.
+    ///
+    /// Continuous joints are left alone, any number of turns is a position of their own.
+    pub fn normalize_angles_near(&mut self, seed: &KinematicState, params: &KinematicParameters) {
+        for joint in Joint::ALL {
+            if params.is_continuous(joint) {
+                continue;
+            }
+
+            let angle = self[joint];
+            let [min, max] = params.joint_limits[joint.index()];
+
+            let nearest = angle - TAU * ((angle - seed[joint]) / TAU).round();
+            if (min..=max).contains(&nearest) {
+                self[joint] = nearest;
+                continue;
+            }
+
+            // The lowest and highest equivalents within the limits (if the limits leave room for
+            //  any), one of which is the nearest to the seed.
+            let lowest = angle - TAU * ((angle - min) / TAU).floor();
+            let highest = angle - TAU * ((angle - max) / TAU).ceil();
+
+            self[joint] = [lowest, highest]
+                .into_iter()
+                .filter(|x| (min..=max).contains(x))
+                .min_by(|a, b| (a - seed[joint]).abs().total_cmp(&(b - seed[joint]).abs()))
+                .unwrap_or(nearest);
+        }
+    }
+
+    /// Check if all the joint angles are within `epsilon` of the ones of the other state, a NaN
+    ///  angle is never considered equal.
+    pub fn approx_eq(&self, other: &KinematicState, epsilon: f64) -> bool {
+        Vector5::<f64>::from(self)
+            .iter()
+            .zip(Vector5::<f64>::from(other).iter())
+            .all(|(a, b)| (a - b).abs() <= epsilon)
+    }
+
+    /// Get the joints that are outside of their soft limits but still within their hard limits,
+    ///  this is always empty if no soft limits are configured.
+    pub fn soft_limit_warnings(&self, params: &KinematicParameters) -> Vec<JointLimitWarning> {
+        let Some(soft_joint_limits) = &params.soft_joint_limits else {
+            return Vec::new();
+        };
+
+        Vector5::<f64>::from(self)
+            .iter()
+            .zip(soft_joint_limits.iter().zip(params.joint_limits.iter()))
+            .enumerate()
+            .filter(|(_, (value, ([soft_min, soft_max], [min, max])))| {
+                (min..=max).contains(value) && !(soft_min..=soft_max).contains(value)
+            })
+            .map(|(joint, (value, (_, [min, max])))| JointLimitWarning {
+                joint,
+                value: *value,
+                margin: (value - min).min(max - value),
+            })
+            .collect()
+    }
+
+    /// Check that all the joint angles are within the joint limits of the given parameters,
+    ///  returning the first joint that isn't.
+    pub fn check_joint_limits(&self, params: &KinematicParameters) -> Result<(), KinematicError> {
+        for (joint, (value, [min, max])) in Vector5::<f64>::from(self)
+            .iter()
+            .zip(params.joint_limits.iter())
+            .enumerate()
+        {
+            if !(min..=max).contains(&value) {
+                return Err(KinematicError::JointLimitViolated {
+                    joint,
+                    value: *value,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that the state can be safely sent to the servos, so all the angles must be finite
+    ///  and within the joint limits of the given parameters.
+    pub fn validate(&self, params: &KinematicParameters) -> Result<(), KinematicError> {
+        if let Some((joint, value)) = self
+            .angles()
+            .into_iter()
+            .enumerate()
+            .find(|(_, value)| !value.is_finite())
+        {
+            return Err(KinematicError::NonFiniteAngle { joint, value });
+        }
+
+        self.check_joint_limits(params)
+    }
+}
+
+impl Index<Joint> for KinematicState {
+    type Output = f64;
+
+    fn index(&self, joint: Joint) -> &Self::Output {
+        match joint {
+            Joint::Base => &self.theta_0,
+            Joint::Shoulder => &self.theta_1,
+            Joint::Elbow => &self.theta_2,
+            Joint::WristPitch => &self.theta_3,
+            Joint::WristRoll => &self.theta_4,
+        }
+    }
+}
+
+impl IndexMut<Joint> for KinematicState {
+    fn index_mut(&mut self, joint: Joint) -> &mut Self::Output {
+        match joint {
+            Joint::Base => &mut self.theta_0,
+            Joint::Shoulder => &mut self.theta_1,
+            Joint::Elbow => &mut self.theta_2,
+            Joint::WristPitch => &mut self.theta_3,
+            Joint::WristRoll => &mut self.theta_4,
+        }
+    }
+}
+
+impl Display for KinematicState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let angles = Vector5::<f64>::from(self);
+
+        for (i, theta) in angles.iter().enumerate() {
+            if i != 0 {
+                writeln!(f)?;
+            }
+
+            write!(
+                f,
+                "theta {}: {:.4} rad ({:.2} deg)",
+                i,
+                theta,
+                theta.to_degrees()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
 impl From<Vector5<f64>> for KinematicState {
     fn from(value: Vector5<f64>) -> Self {
         Self {
@@ -73,3 +654,409 @@ impl From<&KinematicState> for Vector5<f64> {
         )
     }
 }
+
+/// This struct represents a joint that entered the warning band between its soft and hard limits.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JointLimitWarning {
+    pub joint: usize, // The index of the joint.
+    pub value: f64,   // The angle of the joint (in radians).
+    pub margin: f64,  // The distance left to the nearest hard limit (in radians).
+}
+
+/// This struct represents the geometry of the arm, being the positions of all its vertices (from
+///  the base up to the end-effector).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArmGeometry(pub [Vector3<f64>; 6]);
+
+impl ArmGeometry {
+    /// Get the vertices of the arm.
+    #[inline]
+    pub fn vertices(&self) -> &[Vector3<f64>; 6] {
+        &self.0
+    }
+
+    /// Compute the total length of the arm by summing the lengths of all the segments.
+    pub fn total_length(&self) -> f64 {
+        self.0
+            .windows(2)
+            .map(|segment| (segment[1] - segment[0]).magnitude())
+            .sum()
+    }
+}
+
+impl From<[Vector3<f64>; 6]> for ArmGeometry {
+    fn from(value: [Vector3<f64>; 6]) -> Self {
+        Self(value)
+    }
+}
+
+impl Display for ArmGeometry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, vertex) in self.0.iter().enumerate() {
+            writeln!(
+                f,
+                "vertex {}: ({:.4}, {:.4}, {:.4})",
+                i, vertex.x, vertex.y, vertex.z
+            )?;
+        }
+
+        write!(f, "total length: {:.4}", self.total_length())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::f64::consts::{PI, TAU};
+
+    use nalgebra::{Isometry3, Vector3};
+
+    use crate::error::KinematicError;
+    use crate::forward::algorithms::{
+        analytical::AnalyticalFKAlgorithm, ForwardKinematicAlgorithm,
+    };
+    use crate::model::{Joint, JointLimitWarning, KinematicParameters, KinematicState, LengthUnit};
+
+    #[test]
+    pub fn approx_eq_within_epsilon() {
+        let a = KinematicState::default();
+        let mut b = a.clone();
+        b.theta_3 += 0.001_f64;
+
+        assert!(a.approx_eq(&a, 0_f64));
+        assert!(a.approx_eq(&b, 0.01_f64));
+        assert!(!a.approx_eq(&b, 0.0001_f64));
+    }
+
+    #[test]
+    pub fn approx_eq_nan() {
+        let a = KinematicState {
+            theta_0: f64::NAN,
+            ..KinematicState::default()
+        };
+
+        assert!(!a.approx_eq(&a, f64::INFINITY));
+        assert!(!a.approx_eq(&KinematicState::default(), f64::INFINITY));
+    }
+
+    #[test]
+    pub fn display_radians_and_degrees() {
+        let state = KinematicState {
+            theta_2: std::f64::consts::PI,
+            ..KinematicState::default()
+        };
+
+        let display = state.to_string();
+
+        assert_eq!(display.lines().count(), 5_usize);
+        assert!(display.contains("theta 2: 3.1416 rad (180.00 deg)"));
+    }
+
+    #[test]
+    pub fn invalid_parameters() {
+        assert!(KinematicParameters::default().validate().is_ok());
+
+        let params = KinematicParameters {
+            l_2: -1_f64,
+            ..KinematicParameters::default()
+        };
+        assert!(matches!(
+            params.validate(),
+            Err(KinematicError::InvalidParameters(_))
+        ));
+
+        let params = KinematicParameters {
+            joint_limits: [[1_f64, -1_f64]; 5],
+            ..KinematicParameters::default()
+        };
+        assert!(matches!(
+            params.validate(),
+            Err(KinematicError::InvalidParameters(_))
+        ));
+
+        let params = KinematicParameters {
+            link_masses: [0_f64; 5],
+            ..KinematicParameters::default()
+        };
+        assert!(matches!(
+            params.validate(),
+            Err(KinematicError::InvalidParameters(_))
+        ));
+    }
+
+    #[test]
+    pub fn joint_limit_violated() {
+        let params = KinematicParameters {
+            joint_limits: [[-PI, PI]; 5],
+            ..KinematicParameters::default()
+        };
+        let state = KinematicState {
+            theta_3: 4_f64,
+            ..KinematicState::default()
+        };
+
+        assert!(KinematicState::default()
+            .check_joint_limits(&params)
+            .is_ok());
+        assert!(matches!(
+            state.check_joint_limits(&params),
+            Err(KinematicError::JointLimitViolated { joint: 3, value }) if value == 4_f64
+        ));
+
+        // The joints are unbounded unless limits are configured.
+        assert!(state
+            .check_joint_limits(&KinematicParameters::default())
+            .is_ok());
+        assert!(KinematicParameters::default().approx_eq(&KinematicParameters::default(), 0_f64));
+    }
+
+    #[test]
+    pub fn validate_rejects_invalid_angles() {
+        let params = KinematicParameters {
+            joint_limits: [[-PI, PI]; 5],
+            ..KinematicParameters::default()
+        };
+
+        assert!(KinematicState::default().validate(&params).is_ok());
+
+        let state = KinematicState {
+            theta_1: f64::NAN,
+            ..KinematicState::default()
+        };
+        assert!(matches!(
+            state.validate(&params),
+            Err(KinematicError::NonFiniteAngle { joint: 1, value }) if value.is_nan()
+        ));
+
+        let state = KinematicState {
+            theta_4: f64::INFINITY,
+            ..KinematicState::default()
+        };
+        assert!(matches!(
+            state.validate(&params),
+            Err(KinematicError::NonFiniteAngle { joint: 4, value }) if value == f64::INFINITY
+        ));
+
+        let state = KinematicState {
+            theta_2: -4_f64,
+            ..KinematicState::default()
+        };
+        assert!(matches!(
+            state.validate(&params),
+            Err(KinematicError::JointLimitViolated { joint: 2, value }) if value == -4_f64
+        ));
+    }
+
+    #[test]
+    pub fn soft_limit_warnings() {
+        let params = KinematicParameters {
+            joint_limits: [[-PI, PI]; 5],
+            soft_joint_limits: Some([[-3_f64, 3_f64]; 5]),
+            ..KinematicParameters::default()
+        };
+        assert!(params.validate().is_ok());
+
+        // Only the joint in the band between the soft and hard limits gets a warning.
+        let state = KinematicState {
+            theta_1: 3.1_f64,
+            theta_2: 4_f64,
+            ..KinematicState::default()
+        };
+        let warnings = state.soft_limit_warnings(&params);
+
+        assert_eq!(warnings.len(), 1_usize);
+        assert_eq!(warnings[0].joint, 1_usize);
+        assert_eq!(warnings[0].value, 3.1_f64);
+        assert!((warnings[0].margin - (PI - 3.1_f64)).abs() < 1e-9_f64);
+
+        // Without soft limits there are never any warnings.
+        assert_eq!(
+            state.soft_limit_warnings(&KinematicParameters::default()),
+            Vec::<JointLimitWarning>::new()
+        );
+    }
+
+    #[test]
+    pub fn named_joint_accessors() {
+        let mut state = KinematicState::default();
+
+        state.set_base(0_f64);
+        state.set_shoulder(1_f64);
+        state.set_elbow(2_f64);
+        state.set_wrist_pitch(3_f64);
+        state[Joint::WristRoll] = 4_f64;
+
+        assert_eq!(state.angles(), [0_f64, 1_f64, 2_f64, 3_f64, 4_f64]);
+        assert_eq!(state[Joint::Elbow], state.elbow());
+        assert_eq!(state.wrist_roll(), 4_f64);
+
+        for (i, joint) in Joint::ALL.iter().enumerate() {
+            assert_eq!(joint.index(), i);
+            assert_eq!(Joint::from_index(i), Some(*joint));
+            assert_eq!(state[*joint], i as f64);
+        }
+        assert_eq!(Joint::from_index(5_usize), None);
+    }
+
+    #[test]
+    pub fn parameters_approx_eq() {
+        let params = KinematicParameters::default();
+        assert_eq!(params, params.clone());
+        assert!(params.approx_eq(&params, 0_f64));
+
+        // A slightly longer link is only equal within a large enough epsilon.
+        let mut perturbed = params.clone();
+        perturbed.l_2 += 1e-6_f64;
+        assert_ne!(params, perturbed);
+        assert!(params.approx_eq(&perturbed, 1e-5_f64));
+        assert!(!params.approx_eq(&perturbed, 1e-7_f64));
+
+        // So is a slightly rotated base.
+        let mut perturbed = params.clone();
+        perturbed.base_transform = Isometry3::rotation(Vector3::<f64>::new(0_f64, 0_f64, 1e-6_f64));
+        assert!(params.approx_eq(&perturbed, 1e-5_f64));
+        assert!(!params.approx_eq(&perturbed, 1e-7_f64));
+
+        // Soft limits on only one of them are never equal.
+        let mut perturbed = params.clone();
+        perturbed.soft_joint_limits = Some(params.joint_limits);
+        assert!(!params.approx_eq(&perturbed, 1_f64));
+    }
+
+    #[test]
+    pub fn millimeter_parameters_normalized() {
+        let meters = KinematicParameters {
+            l_0: 0.1_f64,
+            l_1: 0.2_f64,
+            l_2: 0.15_f64,
+            l_3: 0.05_f64,
+            l_4: 0.03_f64,
+            tool_offset: Isometry3::translation(0_f64, 0.02_f64, 0_f64),
+            ..KinematicParameters::default()
+        };
+        let millimeters = KinematicParameters {
+            l_0: 100_f64,
+            l_1: 200_f64,
+            l_2: 150_f64,
+            l_3: 50_f64,
+            l_4: 30_f64,
+            tool_offset: Isometry3::translation(0_f64, 20_f64, 0_f64),
+            length_unit: LengthUnit::Millimeters,
+            ..KinematicParameters::default()
+        };
+
+        assert!(!millimeters.approx_eq(&meters, 1e-12_f64));
+        assert!(millimeters.to_meters().approx_eq(&meters, 1e-12_f64));
+
+        let algorithm: AnalyticalFKAlgorithm = AnalyticalFKAlgorithm::default();
+        for state in [KinematicState::default(), KinematicState::ready()] {
+            let a = algorithm.limb4_tool_position_vector(&meters.to_meters(), &state);
+            let b = algorithm.limb4_tool_position_vector(&millimeters.to_meters(), &state);
+
+            assert!((a - b).norm() < 1e-12_f64);
+        }
+    }
+
+    #[test]
+    pub fn angles_round_trip() {
+        let angles = [0.1_f64, -0.2_f64, 0.3_f64, -0.4_f64, 0.5_f64];
+
+        let mut state = KinematicState::from_angles(angles);
+        assert_eq!(state.angles(), angles);
+        assert_eq!(state.wrist_pitch(), -0.4_f64);
+
+        state.set_angle(Joint::Elbow, 1_f64);
+        assert_eq!(state.elbow(), 1_f64);
+        assert_eq!(
+            state,
+            KinematicState::from_angles([0.1_f64, -0.2_f64, 1_f64, -0.4_f64, 0.5_f64])
+        );
+    }
+
+    #[test]
+    pub fn normalize_angles() {
+        let params: KinematicParameters = KinematicParameters::default();
+        let algorithm: AnalyticalFKAlgorithm = AnalyticalFKAlgorithm::default();
+
+        let state: KinematicState = KinematicState {
+            theta_0: -2.5_f64 * PI,
+            theta_1: 3_f64 * PI,
+            theta_2: 0.5_f64,
+            theta_3: -PI,
+            theta_4: 4_f64 * PI + 0.1_f64,
+        };
+
+        let mut normalized: KinematicState = state.clone();
+        normalized.normalize_angles();
+
+        // Every angle ends up in the half open range, so -pi itself wraps around to pi.
+        assert!(normalized
+            .angles()
+            .iter()
+            .all(|x| *x > -PI - 1e-9_f64 && *x <= PI + 1e-9_f64));
+        assert!((normalized.theta_0 + 0.5_f64 * PI).abs() < 1e-9_f64);
+        assert!((normalized.theta_1.abs() - PI).abs() < 1e-9_f64);
+        assert_eq!(normalized.theta_2, 0.5_f64);
+        assert!((normalized.theta_3 - PI).abs() < 1e-9_f64);
+        assert!((normalized.theta_4 - 0.1_f64).abs() < 1e-9_f64);
+
+        // Whole turns don't change the pose.
+        assert!(
+            (algorithm.limb4_position_vector(&params, &state)
+                - algorithm.limb4_position_vector(&params, &normalized))
+            .norm()
+                < 1e-9_f64
+        );
+    }
+
+    #[test]
+    pub fn normalize_angles_near_seed() {
+        let params: KinematicParameters = KinematicParameters {
+            joint_limits: [
+                [-TAU, TAU],
+                [-PI, PI],
+                [-PI, PI],
+                [-PI, PI],
+                [f64::NEG_INFINITY, f64::INFINITY],
+            ],
+            ..KinematicParameters::default()
+        };
+        let algorithm: AnalyticalFKAlgorithm = AnalyticalFKAlgorithm::default();
+
+        // The seed has the base and shoulder just below pi.
+        let seed: KinematicState = KinematicState {
+            theta_0: 3_f64,
+            theta_1: 3_f64,
+            theta_2: 0_f64,
+            theta_3: 0_f64,
+            theta_4: 0_f64,
+        };
+        let state: KinematicState = KinematicState {
+            theta_0: 3.2_f64 - TAU,
+            theta_1: 3.2_f64 - TAU,
+            theta_2: 0.5_f64 + 2_f64 * TAU,
+            theta_3: 0.2_f64,
+            theta_4: 3_f64 * PI,
+        };
+
+        let mut normalized: KinematicState = state.clone();
+        normalized.normalize_angles_near(&seed, &params);
+
+        // The base has room to go past pi, so it stays next to the seed instead of wrapping.
+        assert!((normalized.theta_0 - 3.2_f64).abs() < 1e-9_f64);
+        // The shoulder doesn't, so it ends up at the nearest equivalent within its limits.
+        assert!((normalized.theta_1 - (3.2_f64 - TAU)).abs() < 1e-9_f64);
+        // The accumulated turns are dropped.
+        assert!((normalized.theta_2 - 0.5_f64).abs() < 1e-9_f64);
+        assert_eq!(normalized.theta_3, 0.2_f64);
+        // The continuous wrist roll is left alone.
+        assert_eq!(normalized.theta_4, 3_f64 * PI);
+
+        // Whole turns don't change the pose.
+        assert!(
+            (algorithm.limb4_position_vector(&params, &state)
+                - algorithm.limb4_position_vector(&params, &normalized))
+            .norm()
+                < 1e-9_f64
+        );
+    }
+}